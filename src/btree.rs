@@ -0,0 +1,343 @@
+use crate::pager::Page;
+use crate::varint::read_varint;
+use crate::{Error, Result};
+
+/// SQLite won't descend more than this many levels into a b-tree, so a deeper path means a
+/// child pointer loops back up the tree
+pub const MAX_DEPTH: usize = 20;
+
+/// The error for a walk that went past [`MAX_DEPTH`]
+pub fn too_deep(page: u32) -> Error {
+    Error::Corrupt(format!(
+        "b-tree is more than {} levels deep at page {}; a child pointer may loop",
+        MAX_DEPTH, page
+    ))
+}
+
+/// The error for a walk that reached the same page twice. Each page of a sound b-tree has
+/// one parent, so a second visit means child pointers loop or are shared, and following
+/// them could take exponentially many steps without ever going too deep.
+pub fn revisited(page: u32) -> Error {
+    Error::Corrupt(format!(
+        "b-tree page {} is reached more than once; child pointers loop or are shared",
+        page
+    ))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageType {
+    InteriorIndex,
+    InteriorTable,
+    LeafIndex,
+    LeafTable,
+}
+
+impl PageType {
+    pub fn from_byte(b: u8) -> Option<PageType> {
+        match b {
+            2 => Some(PageType::InteriorIndex),
+            5 => Some(PageType::InteriorTable),
+            10 => Some(PageType::LeafIndex),
+            13 => Some(PageType::LeafTable),
+            _ => None,
+        }
+    }
+
+    pub fn is_leaf(self) -> bool {
+        matches!(self, PageType::LeafIndex | PageType::LeafTable)
+    }
+
+    pub fn is_table(self) -> bool {
+        matches!(self, PageType::InteriorTable | PageType::LeafTable)
+    }
+
+    /// Interior pages carry a 4-byte right-most child pointer in their header
+    pub fn header_len(self) -> usize {
+        if self.is_leaf() {
+            8
+        } else {
+            12
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BTreeHeader {
+    pub page_type: PageType,
+    pub first_freeblock: u16,
+    pub cell_count: u16,
+    pub cell_content_start: u32,
+    pub fragmented_free_bytes: u8,
+    pub right_child: Option<u32>,
+}
+
+/// The portion of a record stored in a cell, plus where the rest of it lives
+#[derive(Debug, Clone)]
+pub struct Payload {
+    /// Total size of the record, including any overflow
+    pub size: u64,
+    pub local: Vec<u8>,
+    pub first_overflow: Option<u32>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Cell {
+    TableLeaf { rowid: i64, payload: Payload },
+    TableInterior { left_child: u32, rowid: i64 },
+    IndexLeaf { payload: Payload },
+    IndexInterior { left_child: u32, payload: Payload },
+}
+
+impl Cell {
+    pub fn left_child(&self) -> Option<u32> {
+        match self {
+            Cell::TableInterior { left_child, .. } | Cell::IndexInterior { left_child, .. } => {
+                Some(*left_child)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn payload(&self) -> Option<&Payload> {
+        match self {
+            Cell::TableLeaf { payload, .. }
+            | Cell::IndexLeaf { payload }
+            | Cell::IndexInterior { payload, .. } => Some(payload),
+            Cell::TableInterior { .. } => None,
+        }
+    }
+}
+
+/// A page interpreted as a b-tree node
+#[derive(Debug, Clone)]
+pub struct BTreePage {
+    page: Page,
+    header: BTreeHeader,
+    usable_size: usize,
+}
+
+impl BTreePage {
+    pub fn parse(page: Page, usable_size: u32) -> Result<BTreePage> {
+        let usable_size = usable_size as usize;
+        let offset = page.btree_header_offset();
+        let data = page.data();
+        let corrupt = |msg: String| Error::Corrupt(format!("page {}: {}", page.number(), msg));
+
+        let page_type = PageType::from_byte(data[offset])
+            .ok_or_else(|| corrupt(format!("invalid b-tree page type {}", data[offset])))?;
+        let u16_at = |o: usize| u16::from_be_bytes([data[offset + o], data[offset + o + 1]]);
+
+        // a zero cell content offset means 65536
+        let cell_content_start = match u16_at(5) {
+            0 => 65536,
+            n => n as u32,
+        };
+        let right_child = (!page_type.is_leaf()).then(|| {
+            u32::from_be_bytes([
+                data[offset + 8],
+                data[offset + 9],
+                data[offset + 10],
+                data[offset + 11],
+            ])
+        });
+        let header = BTreeHeader {
+            page_type,
+            first_freeblock: u16_at(1),
+            cell_count: u16_at(3),
+            cell_content_start,
+            fragmented_free_bytes: data[offset + 7],
+            right_child,
+        };
+
+        let pointers_end = offset + page_type.header_len() + 2 * header.cell_count as usize;
+        if pointers_end > usable_size {
+            return Err(corrupt(format!(
+                "{} cells do not fit in the page",
+                header.cell_count
+            )));
+        }
+
+        Ok(BTreePage {
+            page,
+            header,
+            usable_size,
+        })
+    }
+
+    pub fn number(&self) -> u32 {
+        self.page.number()
+    }
+
+    pub fn page(&self) -> &Page {
+        &self.page
+    }
+
+    pub fn header(&self) -> &BTreeHeader {
+        &self.header
+    }
+
+    pub fn page_type(&self) -> PageType {
+        self.header.page_type
+    }
+
+    pub fn cell_count(&self) -> usize {
+        self.header.cell_count as usize
+    }
+
+    /// The `(offset, size)` of every freeblock, in chain order. SQLite keeps the chain sorted
+    /// by offset, so a chain that doesn't move forward is corrupt rather than followed forever.
+    pub fn freeblocks(&self) -> Result<Vec<(usize, usize)>> {
+        let data = &self.page.data()[..self.usable_size];
+        let mut blocks = Vec::new();
+        let mut offset = self.header.first_freeblock as usize;
+        while offset != 0 {
+            let min = blocks.last().map_or(0, |&(o, size)| o + size);
+            if offset < min || offset + 4 > data.len() {
+                return Err(Error::Corrupt(format!(
+                    "page {}: freeblock at offset {} is out of order or past the end of the page",
+                    self.number(),
+                    offset
+                )));
+            }
+            let size = u16::from_be_bytes([data[offset + 2], data[offset + 3]]) as usize;
+            if size < 4 || offset + size > data.len() {
+                return Err(Error::Corrupt(format!(
+                    "page {}: freeblock at offset {} has invalid size {}",
+                    self.number(),
+                    offset,
+                    size
+                )));
+            }
+            blocks.push((offset, size));
+            offset = u16::from_be_bytes([data[offset], data[offset + 1]]) as usize;
+        }
+        Ok(blocks)
+    }
+
+    /// Offset of the cell pointer array, right after the b-tree page header
+    pub fn cell_pointers_offset(&self) -> usize {
+        self.page.btree_header_offset() + self.header.page_type.header_len()
+    }
+
+    /// Offset of the `index`th cell from the start of the page
+    pub fn cell_pointer(&self, index: usize) -> usize {
+        let at = self.cell_pointers_offset() + 2 * index;
+        let data = self.page.data();
+        u16::from_be_bytes([data[at], data[at + 1]]) as usize
+    }
+
+    pub fn cell(&self, index: usize) -> Result<Cell> {
+        self.cell_at(self.cell_pointer(index)).map(|(cell, _)| cell)
+    }
+
+    /// Parses the cell starting at `offset`, returning it along with its size on the page
+    pub fn cell_at(&self, offset: usize) -> Result<(Cell, usize)> {
+        let data = &self.page.data()[..self.usable_size];
+        let corrupt = |msg: &str| {
+            Error::Corrupt(format!(
+                "page {}: cell at offset {} {}",
+                self.number(),
+                offset,
+                msg
+            ))
+        };
+        if offset >= data.len() {
+            return Err(corrupt("starts past the end of the page"));
+        }
+
+        let mut p = offset;
+        let varint = |p: &mut usize| {
+            let (v, n) =
+                read_varint(&data[*p..]).ok_or_else(|| corrupt("has a truncated varint"))?;
+            *p += n;
+            Ok::<u64, Error>(v)
+        };
+        let child = |p: &mut usize| {
+            let b = data
+                .get(*p..*p + 4)
+                .ok_or_else(|| corrupt("has a truncated child pointer"))?;
+            *p += 4;
+            Ok::<u32, Error>(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+        };
+
+        let cell = match self.header.page_type {
+            PageType::LeafTable => {
+                let size = varint(&mut p)?;
+                let rowid = varint(&mut p)? as i64;
+                let payload = self.payload_at(&mut p, size, true).map_err(&corrupt)?;
+                Cell::TableLeaf { rowid, payload }
+            }
+            PageType::InteriorTable => {
+                let left_child = child(&mut p)?;
+                let rowid = varint(&mut p)? as i64;
+                Cell::TableInterior { left_child, rowid }
+            }
+            PageType::LeafIndex => {
+                let size = varint(&mut p)?;
+                let payload = self.payload_at(&mut p, size, false).map_err(&corrupt)?;
+                Cell::IndexLeaf { payload }
+            }
+            PageType::InteriorIndex => {
+                let left_child = child(&mut p)?;
+                let size = varint(&mut p)?;
+                let payload = self.payload_at(&mut p, size, false).map_err(&corrupt)?;
+                Cell::IndexInterior {
+                    left_child,
+                    payload,
+                }
+            }
+        };
+        Ok((cell, p - offset))
+    }
+
+    fn payload_at(
+        &self,
+        p: &mut usize,
+        size: u64,
+        table_leaf: bool,
+    ) -> std::result::Result<Payload, &'static str> {
+        let data = &self.page.data()[..self.usable_size];
+        let local_len = local_payload_len(size, self.usable_size as u64, table_leaf);
+        let local = data
+            .get(*p..*p + local_len)
+            .ok_or("has a payload extending past the end of the page")?
+            .to_vec();
+        *p += local_len;
+
+        let first_overflow = if (local_len as u64) < size {
+            let b = data
+                .get(*p..*p + 4)
+                .ok_or("has a truncated overflow pointer")?;
+            *p += 4;
+            Some(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+        } else {
+            None
+        };
+
+        Ok(Payload {
+            size,
+            local,
+            first_overflow,
+        })
+    }
+}
+
+/// How many bytes of a payload of `size` bytes are stored on the b-tree page itself, per the
+/// spill rules in the file format documentation
+pub fn local_payload_len(size: u64, usable_size: u64, table_leaf: bool) -> usize {
+    let max_local = if table_leaf {
+        usable_size - 35
+    } else {
+        ((usable_size - 12) * 64 / 255) - 23
+    };
+    if size <= max_local {
+        return size as usize;
+    }
+    let min_local = ((usable_size - 12) * 32 / 255) - 23;
+    let k = min_local + ((size - min_local) % (usable_size - 4));
+    if k <= max_local {
+        k as usize
+    } else {
+        min_local as usize
+    }
+}
@@ -0,0 +1,310 @@
+use crate::btree::{local_payload_len, PageType};
+use crate::db::{Database, SCHEMA_ROOT_PAGE};
+use crate::pages::LOCK_BYTE_OFFSET;
+use crate::schema::ObjectType;
+use crate::Error;
+use std::fmt;
+
+/// Same cap as sqlite3's integrity checks, so a thoroughly broken file doesn't produce
+/// millions of lines
+pub const MAX_PROBLEMS: usize = 100;
+
+#[derive(Debug, Clone)]
+pub struct Problem {
+    pub page: Option<u32>,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.page {
+            Some(page) => write!(f, "page {}: {}", page, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// Structural validation in the spirit of `PRAGMA quick_check`: every b-tree page header,
+/// cell pointer, cell varint and overflow chain is checked for sanity, and every page must be
+/// accounted for exactly once. Index contents are not compared against their tables.
+pub fn quick_check(db: &Database) -> Vec<Problem> {
+    let mut checker = Checker {
+        db,
+        seen: vec![false; db.page_count() as usize + 1],
+        problems: Vec::new(),
+    };
+
+    checker.check_tree(SCHEMA_ROOT_PAGE, "sqlite_schema");
+    match db.schema() {
+        Ok(schema) => {
+            for entry in schema {
+                if matches!(entry.kind, ObjectType::Table | ObjectType::Index)
+                    && entry.root_page != 0
+                {
+                    checker.check_tree(entry.root_page, &entry.name);
+                }
+            }
+        }
+        Err(e) => checker.report(None, format!("unable to read sqlite_schema: {}", e)),
+    }
+    checker.check_freelist();
+    checker.check_unused();
+
+    checker.problems.truncate(MAX_PROBLEMS);
+    checker.problems
+}
+
+struct Checker<'a> {
+    db: &'a Database,
+    seen: Vec<bool>,
+    problems: Vec<Problem>,
+}
+
+impl Checker<'_> {
+    fn report(&mut self, page: Option<u32>, message: String) {
+        self.problems.push(Problem { page, message });
+    }
+
+    /// Records a reference to `page`, returning whether it is safe to descend into it
+    fn mark(&mut self, page: u32, parent: Option<u32>, what: &str) -> bool {
+        if page == 0 || page > self.db.page_count() {
+            self.report(
+                parent,
+                format!(
+                    "{} page {} is out of range 1..{}",
+                    what,
+                    page,
+                    self.db.page_count()
+                ),
+            );
+            return false;
+        }
+        if self.seen[page as usize] {
+            self.report(
+                Some(page),
+                format!("{} page referenced more than once", what),
+            );
+            return false;
+        }
+        self.seen[page as usize] = true;
+        true
+    }
+
+    fn check_tree(&mut self, root: u32, name: &str) {
+        let usable = self.db.usable_size() as usize;
+        let mut table_tree: Option<bool> = None;
+        let mut leaf_depth: Option<usize> = None;
+        let mut stack = vec![(root, None, 0usize)];
+
+        while let Some((number, parent, depth)) = stack.pop() {
+            if self.problems.len() >= MAX_PROBLEMS {
+                return;
+            }
+            if !self.mark(number, parent, &format!("{} b-tree", name)) {
+                continue;
+            }
+            let page = match self.db.btree_page(number) {
+                Ok(page) => page,
+                Err(e) => {
+                    self.report(None, format!("{} (in {})", describe(&e), name));
+                    continue;
+                }
+            };
+
+            let page_type = page.page_type();
+            match table_tree {
+                None => table_tree = Some(page_type.is_table()),
+                Some(is_table) if is_table != page_type.is_table() => {
+                    self.report(
+                        Some(number),
+                        format!("mixes table and index pages in the {} b-tree", name),
+                    );
+                    continue;
+                }
+                _ => {}
+            }
+            if page_type.is_leaf() {
+                match leaf_depth {
+                    None => leaf_depth = Some(depth),
+                    Some(d) if d != depth => self.report(
+                        Some(number),
+                        format!(
+                            "leaf at depth {} but other leaves of {} are at depth {}",
+                            depth, name, d
+                        ),
+                    ),
+                    _ => {}
+                }
+            }
+
+            let header = page.header();
+            let pointers_end = page.cell_pointers_offset() + 2 * page.cell_count();
+            let content_start = header.cell_content_start as usize;
+            if content_start < pointers_end || content_start > usable {
+                self.report(
+                    Some(number),
+                    format!(
+                        "cell content area starts at {}, outside {}..{}",
+                        content_start, pointers_end, usable
+                    ),
+                );
+                continue;
+            }
+
+            for i in 0..page.cell_count() {
+                let offset = page.cell_pointer(i);
+                if offset < content_start || offset >= usable {
+                    self.report(
+                        Some(number),
+                        format!(
+                            "cell {} offset {} is out of range {}..{}",
+                            i, offset, content_start, usable
+                        ),
+                    );
+                    continue;
+                }
+                let cell = match page.cell_at(offset) {
+                    Ok((cell, _)) => cell,
+                    Err(e) => {
+                        self.report(None, describe(&e));
+                        continue;
+                    }
+                };
+                if let Some(payload) = cell.payload() {
+                    if let Some(first) = payload.first_overflow {
+                        let local = local_payload_len(
+                            payload.size,
+                            usable as u64,
+                            page_type == PageType::LeafTable,
+                        );
+                        self.check_overflow(number, first, payload.size - local as u64);
+                    }
+                }
+                if let Some(child) = cell.left_child() {
+                    stack.push((child, Some(number), depth + 1));
+                }
+            }
+            if let Some(child) = header.right_child {
+                stack.push((child, Some(number), depth + 1));
+            }
+        }
+    }
+
+    fn check_overflow(&mut self, owner: u32, first: u32, overflow_bytes: u64) {
+        let per_page = self.db.usable_size() as u64 - 4;
+        let expected = overflow_bytes.div_ceil(per_page);
+        let mut next = first;
+        let mut count = 0u64;
+        while next != 0 && count < expected {
+            if !self.mark(next, Some(owner), "overflow") {
+                return;
+            }
+            count += 1;
+            match self.db.read_page(next) {
+                Ok(page) => {
+                    let d = page.data();
+                    next = u32::from_be_bytes([d[0], d[1], d[2], d[3]]);
+                }
+                Err(e) => {
+                    self.report(Some(next), describe(&e));
+                    return;
+                }
+            }
+        }
+        if count < expected {
+            self.report(
+                Some(owner),
+                format!("overflow chain has {} pages, expected {}", count, expected),
+            );
+        } else if next != 0 {
+            self.report(
+                Some(owner),
+                format!(
+                    "overflow chain continues past its last page to page {}",
+                    next
+                ),
+            );
+        }
+    }
+
+    fn check_freelist(&mut self) {
+        let header = self.db.header();
+        let expected = header.freelist_count;
+        let max_leaves = self.db.usable_size() / 4 - 2;
+        let mut next = header.first_freelist_trunk;
+        let mut count = 0u32;
+        let mut parent = None;
+
+        while next != 0 && count <= expected {
+            if !self.mark(next, parent, "freelist trunk") {
+                return;
+            }
+            count += 1;
+            let page = match self.db.read_page(next) {
+                Ok(page) => page,
+                Err(e) => {
+                    self.report(Some(next), describe(&e));
+                    return;
+                }
+            };
+            let d = page.data();
+            let u32_at = |o: usize| u32::from_be_bytes([d[o], d[o + 1], d[o + 2], d[o + 3]]);
+            let leaves = u32_at(4);
+            if leaves > max_leaves {
+                self.report(
+                    Some(next),
+                    format!(
+                        "freelist trunk claims {} leaves, at most {} fit",
+                        leaves, max_leaves
+                    ),
+                );
+            } else {
+                for i in 0..leaves as usize {
+                    let leaf = u32_at(8 + 4 * i);
+                    if self.mark(leaf, Some(next), "freelist leaf") {
+                        count += 1;
+                    }
+                }
+            }
+            parent = Some(next);
+            next = u32_at(0);
+        }
+
+        if count != expected {
+            self.report(
+                None,
+                format!(
+                    "freelist has {} pages but the header says {}",
+                    count, expected
+                ),
+            );
+        }
+    }
+
+    fn check_unused(&mut self) {
+        let page_size = self.db.page_size() as u64;
+        let lock_byte_page = (LOCK_BYTE_OFFSET / page_size + 1) as u32;
+
+        // Auto-vacuum databases interleave pointer-map pages, which nothing references
+        let ptrmap_interval = self.db.usable_size() / 5 + 1;
+        let auto_vacuum = self.db.header().largest_root_page != 0;
+        let is_ptrmap = |page: u32| auto_vacuum && page >= 2 && (page - 2) % ptrmap_interval == 0;
+
+        for page in 1..=self.db.page_count() {
+            if self.problems.len() >= MAX_PROBLEMS {
+                return;
+            }
+            if !self.seen[page as usize] && page != lock_byte_page && !is_ptrmap(page) {
+                self.report(Some(page), "never used".to_string());
+            }
+        }
+    }
+}
+
+/// Corruption errors already say where they happened, so drop the generic prefix
+fn describe(e: &Error) -> String {
+    match e {
+        Error::Corrupt(message) => message.clone(),
+        e => e.to_string(),
+    }
+}
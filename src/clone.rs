@@ -0,0 +1,184 @@
+//! Copying a database page by page, resumably: an incremental copy records each run of pages
+//! it has written in a state file next to the copy, so an interrupted copy carries on from
+//! where it stopped
+
+use anyhow::{bail, Context, Result};
+use sqliter::inflate::crc32;
+use sqliter::sha256::{to_hex, Sha256};
+use sqliter::Database;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Pages copied between checkpoints of the state file
+const CHUNK_PAGES: u32 = 256;
+
+const STATE_HEADER: &str = "sqliter clone 1";
+
+/// What a copy did
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CloneSummary {
+    pub pages: u32,
+    /// Pages written this run
+    pub copied: u32,
+    /// Pages an earlier run wrote whose checksums still matched, so they were kept
+    pub verified: u32,
+    /// Of the pages copied, those an earlier run wrote that no longer matched their checksums
+    pub recopied: u32,
+}
+
+/// The state file of an incremental copy to `dest`: `<dest>-clone`
+pub fn state_path(dest: &Path) -> PathBuf {
+    let mut path = dest.as_os_str().to_owned();
+    path.push("-clone");
+    PathBuf::from(path)
+}
+
+/// Copies every page of `db`, as of its last commit, to a new file `dest`. The copy has no
+/// WAL, so one taken from a WAL-mode database is marked as a rollback-journal database.
+///
+/// With `incremental`, each run of [`CHUNK_PAGES`] pages is synced and then recorded with
+/// its CRC-32 in the state file. A later incremental copy to the same `dest` checks the
+/// pages already written against their checksums, copies only those that are missing or
+/// damaged, and removes the state file once the copy is complete. It starts over if the
+/// source's size or first page changed in between.
+pub fn clone_database(db: &Database, dest: &Path, incremental: bool) -> Result<CloneSummary> {
+    let page_size = db.page_size();
+    let pages = db.page_count();
+    let state_path = state_path(dest);
+    let source = fingerprint(db)?;
+
+    let resume = incremental && state_path.exists();
+    if dest.exists() && !resume {
+        bail!(
+            "{} already exists; remove it first, or copy with --incremental to resume an interrupted incremental copy",
+            dest.display()
+        );
+    }
+    let mut out = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(dest)
+        .with_context(|| format!("Unable to open {}", dest.display()))?;
+
+    let chunks = pages.div_ceil(CHUNK_PAGES) as usize;
+    let mut done = vec![false; chunks];
+    let mut summary = CloneSummary {
+        pages,
+        ..CloneSummary::default()
+    };
+    let mut state = if incremental {
+        let recorded = if resume {
+            read_state(&state_path, &source)?
+        } else {
+            None
+        };
+        let mut state = File::create(&state_path)
+            .with_context(|| format!("Unable to write {}", state_path.display()))?;
+        writeln!(state, "{}", STATE_HEADER)?;
+        writeln!(state, "{}", source)?;
+        // chunks that still match are recorded again, so the rewritten file is complete
+        for (chunk, checksum) in recorded.into_iter().flatten() {
+            let Some(slot) = done.get_mut(chunk as usize) else {
+                continue;
+            };
+            let (first, count) = chunk_pages(chunk, pages);
+            let mut bytes = vec![0u8; count as usize * page_size as usize];
+            out.seek(SeekFrom::Start(u64::from(first - 1) * u64::from(page_size)))?;
+            let intact = out.read_exact(&mut bytes).is_ok() && crc32(&bytes) == checksum;
+            if intact {
+                *slot = true;
+                summary.verified += count;
+                writeln!(state, "{} {:08x}", chunk, checksum)?;
+            } else {
+                summary.recopied += count;
+            }
+        }
+        state.sync_data()?;
+        Some(state)
+    } else {
+        None
+    };
+
+    out.set_len(u64::from(pages) * u64::from(page_size))?;
+    for chunk in (0..chunks).filter(|&c| !done[c]) {
+        let (first, count) = chunk_pages(chunk as u32, pages);
+        let mut bytes = Vec::with_capacity(count as usize * page_size as usize);
+        for number in first..first + count {
+            let page = db.read_page(number)?;
+            let start = bytes.len();
+            bytes.extend_from_slice(page.data());
+            if number == 1 {
+                // the file format versions say whether a WAL is used; the copy has none
+                let header = &mut bytes[start..];
+                if header[18] == 2 {
+                    header[18] = 1;
+                }
+                if header[19] == 2 {
+                    header[19] = 1;
+                }
+            }
+        }
+        out.seek(SeekFrom::Start(u64::from(first - 1) * u64::from(page_size)))?;
+        out.write_all(&bytes)?;
+        if let Some(state) = &mut state {
+            // the pages must be on disk before the state file says they are
+            out.sync_data()?;
+            writeln!(state, "{} {:08x}", chunk, crc32(&bytes))?;
+            state.sync_data()?;
+        }
+        summary.copied += count;
+    }
+    out.sync_all()?;
+    if state.is_some() {
+        std::fs::remove_file(&state_path)
+            .with_context(|| format!("Unable to remove {}", state_path.display()))?;
+    }
+    Ok(summary)
+}
+
+/// The first page and number of pages of a chunk
+fn chunk_pages(chunk: u32, pages: u32) -> (u32, u32) {
+    let first = chunk * CHUNK_PAGES + 1;
+    (first, CHUNK_PAGES.min(pages + 1 - first))
+}
+
+/// Identifies the source as copied: its page size and count, and a digest of its first
+/// page, whose header changes with most writes
+fn fingerprint(db: &Database) -> Result<String> {
+    let mut hasher = Sha256::new();
+    hasher.update(db.read_page(1)?.data());
+    Ok(format!(
+        "source {} {} {}",
+        db.page_size(),
+        db.page_count(),
+        to_hex(&hasher.finish())
+    ))
+}
+
+/// The chunks an earlier run recorded with their checksums, or `None` when it copied a
+/// different source. A line cut short by an interruption fails its check and is copied again.
+fn read_state(path: &Path, source: &str) -> Result<Option<Vec<(u32, u32)>>> {
+    let file = File::open(path).with_context(|| format!("Unable to read {}", path.display()))?;
+    let mut lines = BufReader::new(file).lines();
+    if lines.next().transpose()?.as_deref() != Some(STATE_HEADER) {
+        bail!("{} is not a clone state file", path.display());
+    }
+    if lines.next().transpose()?.as_deref() != Some(source) {
+        return Ok(None);
+    }
+    let mut chunks = Vec::new();
+    for line in lines {
+        let line = line?;
+        let parsed = line.split_once(' ').and_then(|(chunk, checksum)| {
+            Some((chunk.parse().ok()?, u32::from_str_radix(checksum, 16).ok()?))
+        });
+        match parsed {
+            Some(entry) => chunks.push(entry),
+            None => break,
+        }
+    }
+    Ok(Some(chunks))
+}
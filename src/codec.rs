@@ -0,0 +1,293 @@
+//! Decompressing BLOBs that an application compressed before storing them, so values such as
+//! gzipped JSON read as what was compressed
+
+use crate::inflate::{gzip_decompress, zlib_decompress};
+use crate::table::TableInfo;
+use crate::{Error, Result, Value};
+
+/// Most bytes one value may decompress to, so a hostile value can't exhaust memory
+pub const MAX_DECOMPRESSED_LEN: usize = 256 << 20;
+
+const GZIP_MAGIC: [u8; 3] = [0x1f, 0x8b, 0x08];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+/// The stream identifier chunk every framed snappy stream starts with
+const SNAPPY_STREAM: [u8; 10] = [0xff, 0x06, 0x00, 0x00, b's', b'N', b'a', b'P', b'p', b'Y'];
+
+/// How a column's values are compressed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    /// Whichever of the others a value's leading bytes identify; values that start with no
+    /// known magic are left alone. Raw snappy has no magic, so only the framed format is
+    /// recognized.
+    Auto,
+    Gzip,
+    Zlib,
+    /// Framed or raw snappy
+    Snappy,
+    /// Recognized so it can be reported, but not decoded
+    Zstd,
+}
+
+impl Codec {
+    pub fn from_name(name: &str) -> Option<Codec> {
+        match name.to_ascii_lowercase().as_str() {
+            "auto" => Some(Codec::Auto),
+            "gzip" => Some(Codec::Gzip),
+            "zlib" => Some(Codec::Zlib),
+            "snappy" => Some(Codec::Snappy),
+            "zstd" => Some(Codec::Zstd),
+            _ => None,
+        }
+    }
+
+    /// The codec whose magic bytes `data` starts with
+    pub fn detect(data: &[u8]) -> Option<Codec> {
+        if data.starts_with(&GZIP_MAGIC) {
+            Some(Codec::Gzip)
+        } else if data.starts_with(&ZSTD_MAGIC) {
+            Some(Codec::Zstd)
+        } else if data.starts_with(&SNAPPY_STREAM) {
+            Some(Codec::Snappy)
+        } else if let [cmf @ 0x78, flg, ..] = data {
+            // a 32K-window deflate stream, with the header check that zlib requires
+            ((u16::from(*cmf) << 8 | u16::from(*flg)) % 31 == 0).then_some(Codec::Zlib)
+        } else {
+            None
+        }
+    }
+
+    /// Decompresses `data`, or returns `None` for [`Codec::Auto`] when it isn't recognized
+    pub fn decompress(self, data: &[u8]) -> Result<Option<Vec<u8>>> {
+        let codec = match self {
+            Codec::Auto => match Codec::detect(data) {
+                Some(codec) => codec,
+                None => return Ok(None),
+            },
+            codec => codec,
+        };
+        let out = match codec {
+            Codec::Auto => unreachable!("detect names a codec"),
+            Codec::Gzip => gzip_decompress(data, MAX_DECOMPRESSED_LEN)?,
+            Codec::Zlib => zlib_decompress(data, MAX_DECOMPRESSED_LEN)?,
+            Codec::Snappy if data.starts_with(&SNAPPY_STREAM) => snappy_framed(data)?,
+            Codec::Snappy => snappy_raw(data, MAX_DECOMPRESSED_LEN)?,
+            Codec::Zstd => {
+                return Err(Error::Decompress(
+                    "zstd frames are not supported".to_string(),
+                ))
+            }
+        };
+        Ok(Some(out))
+    }
+
+    /// Decodes a BLOB, giving TEXT when what was compressed is valid UTF-8. Other values,
+    /// and BLOBs [`Codec::Auto`] doesn't recognize, are returned unchanged.
+    pub fn decode(self, value: Value) -> Result<Value> {
+        let Value::Blob(data) = &value else {
+            return Ok(value);
+        };
+        Ok(match self.decompress(data)? {
+            None => value,
+            Some(out) => match String::from_utf8(out) {
+                Ok(text) => Value::Text(text),
+                Err(e) => Value::Blob(e.into_bytes()),
+            },
+        })
+    }
+}
+
+/// The codecs configured for columns, each given as `[table.]column=codec`. A column named
+/// without a table matches in every table; when several entries match, the last wins.
+#[derive(Debug, Clone, Default)]
+pub struct ColumnDecoders {
+    rules: Vec<DecodeRule>,
+}
+
+#[derive(Debug, Clone)]
+struct DecodeRule {
+    table: Option<String>,
+    column: String,
+    codec: Codec,
+}
+
+impl ColumnDecoders {
+    /// Adds an entry such as `events.payload=gzip` or `payload=auto`
+    pub fn add(&mut self, spec: &str) -> Result<()> {
+        let (target, codec) = spec
+            .rsplit_once('=')
+            .ok_or_else(|| Error::Syntax(format!("expected [table.]column=codec: {}", spec)))?;
+        let codec = Codec::from_name(codec.trim()).ok_or_else(|| {
+            Error::Syntax(format!(
+                "unknown codec {} (expected auto, gzip, zlib, snappy or zstd)",
+                codec
+            ))
+        })?;
+        let (table, column) = match target.split_once('.') {
+            Some((table, column)) => (Some(table.trim().to_string()), column.trim()),
+            None => (None, target.trim()),
+        };
+        if column.is_empty() {
+            return Err(Error::Syntax(format!("no column named in {}", spec)));
+        }
+        self.rules.push(DecodeRule {
+            table,
+            column: column.to_string(),
+            codec,
+        });
+        Ok(())
+    }
+
+    /// The codec for each of `table`'s columns, by position
+    pub fn for_table(&self, table: &TableInfo) -> Vec<Option<Codec>> {
+        table
+            .columns()
+            .iter()
+            .map(|column| {
+                self.rules
+                    .iter()
+                    .rev()
+                    .find(|rule| {
+                        rule.column.eq_ignore_ascii_case(&column.name)
+                            && rule
+                                .table
+                                .as_deref()
+                                .map_or(true, |name| name.eq_ignore_ascii_case(&table.name))
+                    })
+                    .map(|rule| rule.codec)
+            })
+            .collect()
+    }
+}
+
+fn bad(msg: &str) -> Error {
+    Error::Decompress(msg.to_string())
+}
+
+/// Decompresses a snappy framing format stream, verifying each chunk's masked CRC-32C
+fn snappy_framed(data: &[u8]) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    let mut rest = data;
+    while !rest.is_empty() {
+        let [kind, a, b, c, body @ ..] = rest else {
+            return Err(bad("snappy chunk header is truncated"));
+        };
+        let len = usize::from(*a) | usize::from(*b) << 8 | usize::from(*c) << 16;
+        let chunk = body
+            .get(..len)
+            .ok_or_else(|| bad("snappy chunk is truncated"))?;
+        rest = &body[len..];
+        let contents = match kind {
+            // compressed and uncompressed data, each after a checksum of what they hold
+            0x00 | 0x01 => {
+                let (checksum, payload) = chunk
+                    .split_first_chunk::<4>()
+                    .ok_or_else(|| bad("snappy chunk is missing its checksum"))?;
+                let limit = MAX_DECOMPRESSED_LEN - out.len();
+                let contents = if *kind == 0x00 {
+                    snappy_raw(payload, limit)?
+                } else if payload.len() <= limit {
+                    payload.to_vec()
+                } else {
+                    return Err(bad("snappy stream decompresses past the size limit"));
+                };
+                if masked_crc32c(&contents) != u32::from_le_bytes(*checksum) {
+                    return Err(bad("snappy checksum mismatch"));
+                }
+                contents
+            }
+            // the stream identifier may repeat where streams were concatenated
+            0xff if chunk == &SNAPPY_STREAM[4..] => continue,
+            // padding and reserved skippable chunks
+            0x80..=0xfe => continue,
+            _ => return Err(bad("unknown snappy chunk type")),
+        };
+        out.extend_from_slice(&contents);
+    }
+    Ok(out)
+}
+
+/// Decompresses a raw snappy block: its length as a varint, then literals and back-references
+fn snappy_raw(data: &[u8], max_len: usize) -> Result<Vec<u8>> {
+    let mut pos = 0;
+    let mut len = 0usize;
+    for shift in (0..35).step_by(7) {
+        let byte = *data
+            .get(pos)
+            .ok_or_else(|| bad("snappy length is truncated"))?;
+        pos += 1;
+        len |= usize::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            break;
+        }
+    }
+    if len > max_len {
+        return Err(bad("snappy block decompresses past the size limit"));
+    }
+
+    let mut out = Vec::with_capacity(len);
+    let take = |pos: &mut usize, n: usize| -> Result<usize> {
+        let bytes = data
+            .get(*pos..*pos + n)
+            .ok_or_else(|| bad("snappy block is truncated"))?;
+        *pos += n;
+        Ok(bytes
+            .iter()
+            .rev()
+            .fold(0, |acc, &b| acc << 8 | usize::from(b)))
+    };
+    while pos < data.len() {
+        let tag = data[pos];
+        pos += 1;
+        let (length, offset) = match tag & 0x03 {
+            0 => {
+                let length = match usize::from(tag >> 2) {
+                    n @ 0..=59 => n + 1,
+                    n => take(&mut pos, n - 59)? + 1,
+                };
+                let literal = data
+                    .get(pos..pos + length)
+                    .ok_or_else(|| bad("snappy literal is truncated"))?;
+                if out.len() + length > len {
+                    return Err(bad("snappy block is longer than it says"));
+                }
+                out.extend_from_slice(literal);
+                pos += length;
+                continue;
+            }
+            1 => (
+                4 + usize::from(tag >> 2 & 0x07),
+                usize::from(tag >> 5) << 8 | take(&mut pos, 1)?,
+            ),
+            2 => (1 + usize::from(tag >> 2), take(&mut pos, 2)?),
+            _ => (1 + usize::from(tag >> 2), take(&mut pos, 4)?),
+        };
+        if offset == 0 || offset > out.len() {
+            return Err(bad("snappy copy reaches before the start"));
+        }
+        if out.len() + length > len {
+            return Err(bad("snappy block is longer than it says"));
+        }
+        // copies may overlap what they produce, so they go a byte at a time
+        let start = out.len() - offset;
+        for i in 0..length {
+            out.push(out[start + i]);
+        }
+    }
+    if out.len() != len {
+        return Err(bad("snappy block is shorter than it says"));
+    }
+    Ok(out)
+}
+
+/// CRC-32C (Castagnoli), rotated and offset as the snappy framing format stores it
+fn masked_crc32c(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0x82f6_3b78 & (crc & 1).wrapping_neg());
+        }
+    }
+    let crc = !crc;
+    (crc.rotate_right(15)).wrapping_add(0xa282_ead8)
+}
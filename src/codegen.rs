@@ -0,0 +1,280 @@
+use crate::json;
+use anyhow::{bail, Result};
+use sqliter::sql::create::Affinity;
+use sqliter::table::{ColumnInfo, Field, TableInfo};
+use sqliter::{Database, Value};
+use std::fmt::Write;
+use std::str::FromStr;
+
+/// What `.codegen` writes for each table
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lang {
+    Rust,
+    JsonSchema,
+}
+
+impl FromStr for Lang {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Lang> {
+        match s {
+            "rust" => Ok(Lang::Rust),
+            "json-schema" => Ok(Lang::JsonSchema),
+            _ => bail!(
+                "Unknown .codegen language: {} (expected rust or json-schema)",
+                s
+            ),
+        }
+    }
+}
+
+/// The storage classes a column's values were found in
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Observed {
+    null: bool,
+    integer: bool,
+    real: bool,
+    text: bool,
+    blob: bool,
+}
+
+/// The type a column's values map to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Integer,
+    Real,
+    Text,
+    Blob,
+    /// Values of more than one class that don't widen to one of the others
+    Mixed,
+}
+
+/// Scans a table for the storage classes each column holds. Values too big to load are
+/// classified from their record header alone.
+pub fn observe(db: &Database, table: &TableInfo) -> Result<Vec<Observed>> {
+    let mut observed = vec![Observed::default(); table.columns().len()];
+    for row in table.stream_rows(db) {
+        for (seen, field) in observed.iter_mut().zip(row?.fields) {
+            match field {
+                Field::Value(Value::Null) => seen.null = true,
+                Field::Value(Value::Integer(_)) => seen.integer = true,
+                Field::Value(Value::Real(_)) => seen.real = true,
+                Field::Value(Value::Text(_) | Value::RawText(_)) => seen.text = true,
+                Field::Value(Value::Blob(_)) => seen.blob = true,
+                Field::Stored(stored) if stored.is_text() => seen.text = true,
+                Field::Stored(_) => seen.blob = true,
+            }
+        }
+    }
+    Ok(observed)
+}
+
+/// The type for a column: from the values seen in it, or from its declared affinity when it
+/// held nothing but NULLs
+fn kind(column: &ColumnInfo, affinity: Affinity, seen: &Observed) -> Kind {
+    if column.rowid_alias {
+        return Kind::Integer;
+    }
+    match (seen.integer, seen.real, seen.text, seen.blob) {
+        (false, false, false, false) => match affinity {
+            Affinity::Integer => Kind::Integer,
+            Affinity::Real | Affinity::Numeric => Kind::Real,
+            Affinity::Text => Kind::Text,
+            Affinity::Blob => Kind::Blob,
+        },
+        (true, false, false, false) => Kind::Integer,
+        (_, true, false, false) => Kind::Real,
+        (false, false, true, false) => Kind::Text,
+        (false, false, false, true) => Kind::Blob,
+        _ => Kind::Mixed,
+    }
+}
+
+/// Each column with the type its values map to
+fn columns(table: &TableInfo, observed: &[Observed]) -> Vec<(ColumnInfo, Kind, Observed)> {
+    table
+        .column_info()
+        .into_iter()
+        .zip(table.columns())
+        .zip(observed)
+        .map(|((info, definition), seen)| {
+            let kind = kind(&info, definition.affinity(), seen);
+            (info, kind, *seen)
+        })
+        .collect()
+}
+
+/// Whether a column can hold NULL: it isn't declared NOT NULL or is the rowid, or a NULL was
+/// found in it anyway
+fn nullable(column: &ColumnInfo, seen: &Observed) -> bool {
+    seen.null || !(column.not_null || column.rowid_alias)
+}
+
+/// A Rust struct with a field per column. Columns holding values of several classes use a
+/// `SqlValue` enum, defined once before the first struct that needs it.
+pub fn rust(tables: &[(TableInfo, Vec<Observed>)]) -> Result<String> {
+    let mut out = String::new();
+    let mut enum_written = false;
+    for (table, observed) in tables {
+        let columns = columns(table, observed);
+        if !enum_written && columns.iter().any(|(_, kind, _)| *kind == Kind::Mixed) {
+            out.push_str(SQL_VALUE_ENUM);
+            enum_written = true;
+        }
+        writeln!(out, "/// A row of `{}`", table.name)?;
+        out.push_str("#[derive(Debug, Clone, PartialEq)]\n");
+        writeln!(out, "pub struct {} {{", type_name(&table.name))?;
+        for (column, kind, seen) in &columns {
+            let rust_type = match kind {
+                Kind::Integer => "i64",
+                Kind::Real => "f64",
+                Kind::Text => "String",
+                Kind::Blob => "Vec<u8>",
+                Kind::Mixed => "SqlValue",
+            };
+            let field = field_name(&column.name);
+            let declared = if column.type_name.is_empty() {
+                "no type".to_string()
+            } else {
+                column.type_name.clone()
+            };
+            if field == column.name || field == format!("r#{}", column.name) {
+                writeln!(out, "    /// {}", declared)?;
+            } else {
+                writeln!(out, "    /// `{}`, {}", column.name, declared)?;
+            }
+            if nullable(column, seen) {
+                writeln!(out, "    pub {}: Option<{}>,", field, rust_type)?;
+            } else {
+                writeln!(out, "    pub {}: {},", field, rust_type)?;
+            }
+        }
+        out.push_str("}\n\n");
+    }
+    out.pop();
+    Ok(out)
+}
+
+/// A JSON Schema (draft 2020-12) object per table, as a JSON array when there are several.
+/// BLOBs are described as base64 strings.
+pub fn json_schema(tables: &[(TableInfo, Vec<Observed>)]) -> Result<String> {
+    let schemas: Vec<String> = tables
+        .iter()
+        .map(|(table, observed)| {
+            let mut properties = Vec::new();
+            let mut required = Vec::new();
+            for (column, kind, seen) in columns(table, observed) {
+                let mut types: Vec<&str> = match kind {
+                    Kind::Integer => vec!["integer"],
+                    Kind::Real => vec!["number"],
+                    Kind::Text | Kind::Blob => vec!["string"],
+                    Kind::Mixed => {
+                        let mut types = Vec::new();
+                        if seen.integer && !seen.real {
+                            types.push("integer");
+                        }
+                        if seen.real {
+                            types.push("number");
+                        }
+                        if seen.text || seen.blob {
+                            types.push("string");
+                        }
+                        types
+                    }
+                };
+                if nullable(&column, &seen) {
+                    types.push("null");
+                } else {
+                    required.push(json::string(&column.name));
+                }
+                let types: Vec<String> = types.iter().map(|t| json::string(t)).collect();
+                let mut property = match types.as_slice() {
+                    [single] => format!("\"type\":{}", single),
+                    _ => format!("\"type\":[{}]", types.join(",")),
+                };
+                if kind == Kind::Blob || (kind == Kind::Mixed && seen.blob) {
+                    property.push_str(",\"contentEncoding\":\"base64\"");
+                }
+                if !column.type_name.is_empty() {
+                    write!(
+                        property,
+                        ",\"description\":{}",
+                        json::string(&column.type_name)
+                    )?;
+                }
+                properties.push(format!("{}:{{{}}}", json::string(&column.name), property));
+            }
+            Ok(format!(
+                "{{\"$schema\":\"https://json-schema.org/draft/2020-12/schema\",\"title\":{},\"type\":\"object\",\"properties\":{{{}}},\"required\":[{}],\"additionalProperties\":false}}",
+                json::string(&table.name),
+                properties.join(","),
+                required.join(",")
+            ))
+        })
+        .collect::<Result<_>>()?;
+    Ok(match schemas.as_slice() {
+        [single] => single.clone(),
+        _ => format!("[{}]", schemas.join(",")),
+    })
+}
+
+const SQL_VALUE_ENUM: &str = "/// A value of whichever storage class SQLite kept it in
+#[derive(Debug, Clone, PartialEq)]
+pub enum SqlValue {
+    Integer(i64),
+    Real(f64),
+    Text(String),
+    Blob(Vec<u8>),
+}
+
+";
+
+/// A table name as an UpperCamelCase type name
+fn type_name(name: &str) -> String {
+    let mut out = String::new();
+    for word in name.split(|c: char| !c.is_ascii_alphanumeric()) {
+        let mut chars = word.chars();
+        if let Some(first) = chars.next() {
+            out.push(first.to_ascii_uppercase());
+            out.extend(chars);
+        }
+    }
+    if !out.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        out.insert(0, 'T');
+    }
+    out
+}
+
+/// A column name as a snake_case field name, raw when it is a keyword
+fn field_name(name: &str) -> String {
+    let mut out = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_ascii_uppercase() {
+            if i > 0 && !out.ends_with('_') {
+                out.push('_');
+            }
+            out.push(c.to_ascii_lowercase());
+        } else if c.is_ascii_alphanumeric() {
+            out.push(c);
+        } else if !out.ends_with('_') {
+            out.push('_');
+        }
+    }
+    if !out.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') || out == "_" {
+        out.insert_str(0, "c_");
+    }
+    match out.as_str() {
+        // keywords that can't be raw identifiers
+        "self" | "super" | "crate" => format!("{}_", out),
+        _ if KEYWORDS.contains(&out.as_str()) => format!("r#{}", out),
+        _ => out,
+    }
+}
+
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "dyn", "else", "enum", "extern", "false",
+    "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref",
+    "return", "static", "struct", "trait", "true", "type", "unsafe", "use", "where", "while",
+    "abstract", "become", "box", "do", "final", "gen", "macro", "override", "priv", "try",
+    "typeof", "unsized", "virtual", "yield",
+];
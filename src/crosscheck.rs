@@ -0,0 +1,247 @@
+//! Comparing the tables two databases share, to confirm that a copy or a migrated database
+//! holds what the original does
+
+use crate::db::Database;
+use crate::schema::{ObjectType, SchemaEntry};
+use crate::sql::create::CreateTable;
+use crate::sql::normalize;
+use crate::table::{TableHash, TableInfo};
+use crate::Result;
+
+/// How one table compares between the two databases
+#[derive(Debug, Clone)]
+pub struct TableCheck {
+    pub table: String,
+    /// Rows on each side; `None` where the table is missing or can't be read
+    pub main_rows: Option<u64>,
+    pub aux_rows: Option<u64>,
+    /// Everything that differs; empty when the two copies match
+    pub problems: Vec<String>,
+}
+
+impl TableCheck {
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// Checks every table of `main` against the table of the same name in `aux`: that their
+/// columns, keys and foreign keys agree, and that they hold the same rows. Tables found on
+/// one side only are reported too, after the rest.
+pub fn crosscheck(main: &Database, aux: &Database) -> Result<Vec<TableCheck>> {
+    let main_tables = user_tables(main)?;
+    let aux_tables = user_tables(aux)?;
+    let find = |tables: &[SchemaEntry], name: &str| -> Option<SchemaEntry> {
+        tables
+            .iter()
+            .find(|e| e.name.eq_ignore_ascii_case(name))
+            .cloned()
+    };
+
+    let mut checks = Vec::new();
+    let mut missing = Vec::new();
+    for entry in &main_tables {
+        match find(&aux_tables, &entry.name) {
+            Some(other) => checks.push(check_table(main, entry, aux, &other)?),
+            None => missing.push(TableCheck {
+                table: entry.name.clone(),
+                main_rows: count_rows(main, entry),
+                aux_rows: None,
+                problems: vec!["missing from aux".to_string()],
+            }),
+        }
+    }
+    for entry in &aux_tables {
+        if find(&main_tables, &entry.name).is_none() {
+            missing.push(TableCheck {
+                table: entry.name.clone(),
+                main_rows: None,
+                aux_rows: count_rows(aux, entry),
+                problems: vec!["missing from main".to_string()],
+            });
+        }
+    }
+    checks.extend(missing);
+    Ok(checks)
+}
+
+/// Tables that hold rows, leaving out SQLite's own
+fn user_tables(db: &Database) -> Result<Vec<SchemaEntry>> {
+    Ok(db
+        .schema()?
+        .into_iter()
+        .filter(|e| e.kind == ObjectType::Table && e.root_page != 0 && !e.is_internal())
+        .collect())
+}
+
+fn count_rows(db: &Database, entry: &SchemaEntry) -> Option<u64> {
+    let table = TableInfo::load(db, entry).ok()?;
+    let mut rows = 0;
+    for row in table.stream_rows(db) {
+        row.ok()?;
+        rows += 1;
+    }
+    Some(rows)
+}
+
+fn check_table(
+    main: &Database,
+    main_entry: &SchemaEntry,
+    aux: &Database,
+    aux_entry: &SchemaEntry,
+) -> Result<TableCheck> {
+    let mut check = TableCheck {
+        table: main_entry.name.clone(),
+        main_rows: None,
+        aux_rows: None,
+        problems: Vec::new(),
+    };
+    let (main_table, aux_table) = match (
+        TableInfo::load(main, main_entry),
+        TableInfo::load(aux, aux_entry),
+    ) {
+        (Ok(a), Ok(b)) => (a, b),
+        (Err(e), _) | (_, Err(e)) => {
+            check.problems.push(format!("unreadable definition: {}", e));
+            return Ok(check);
+        }
+    };
+    check.problems = schema_problems(&main_table.definition, &aux_table.definition)?;
+
+    let hash =
+        |db: &Database, table: &TableInfo, side: &str, problems: &mut Vec<String>| match table
+            .content_hash(db)
+        {
+            Ok(hash) => Some(hash),
+            Err(e) => {
+                problems.push(format!("unable to read {} rows: {}", side, e));
+                None
+            }
+        };
+    let main_hash = hash(main, &main_table, "main", &mut check.problems);
+    let aux_hash = hash(aux, &aux_table, "aux", &mut check.problems);
+    check.main_rows = main_hash.as_ref().map(|h| h.rows);
+    check.aux_rows = aux_hash.as_ref().map(|h| h.rows);
+    if let (Some(TableHash { rows: a, digest: x }), Some(TableHash { rows: b, digest: y })) =
+        (main_hash, aux_hash)
+    {
+        if a != b {
+            check.problems.push("row counts differ".to_string());
+        } else if x != y && check.problems.is_empty() {
+            // with different columns the rows can't match, which says nothing new
+            check.problems.push("row contents differ".to_string());
+        }
+    }
+    Ok(check)
+}
+
+/// Differences between two definitions of a table that would make one copy's rows mean
+/// something else in the other
+fn schema_problems(main: &CreateTable, aux: &CreateTable) -> Result<Vec<String>> {
+    let mut problems = Vec::new();
+    let names = |t: &CreateTable| -> Vec<String> {
+        t.columns.iter().map(|c| c.name.to_lowercase()).collect()
+    };
+    if names(main) != names(aux) {
+        problems.push(format!(
+            "columns differ: ({}) in main, ({}) in aux",
+            names(main).join(", "),
+            names(aux).join(", ")
+        ));
+    } else {
+        for (a, b) in main.columns.iter().zip(&aux.columns) {
+            if a.affinity() != b.affinity() {
+                problems.push(format!(
+                    "column {} has {} affinity in main, {} in aux",
+                    a.name,
+                    a.affinity().as_str(),
+                    b.affinity().as_str()
+                ));
+            }
+        }
+    }
+    if main.without_rowid != aux.without_rowid {
+        let side = if main.without_rowid { "main" } else { "aux" };
+        problems.push(format!("WITHOUT ROWID in {} only", side));
+    }
+    if primary_key(main) != primary_key(aux) {
+        problems.push(format!(
+            "primary keys differ: ({}) in main, ({}) in aux",
+            primary_key(main).join(", "),
+            primary_key(aux).join(", ")
+        ));
+    }
+    let (main_keys, aux_keys) = (foreign_keys(main)?, foreign_keys(aux)?);
+    for key in &main_keys {
+        if !aux_keys.contains(key) {
+            problems.push(format!("foreign key in main only: {}", key));
+        }
+    }
+    for key in &aux_keys {
+        if !main_keys.contains(key) {
+            problems.push(format!("foreign key in aux only: {}", key));
+        }
+    }
+    Ok(problems)
+}
+
+/// Lowercased names of the PRIMARY KEY columns, declared on a column or for the table
+fn primary_key(table: &CreateTable) -> Vec<String> {
+    table
+        .columns
+        .iter()
+        .filter(|c| c.primary_key)
+        .map(|c| c.name.to_lowercase())
+        .chain(table.primary_key.iter().map(|k| k.to_lowercase()))
+        .collect()
+}
+
+/// Words that end a column's REFERENCES clause by starting its next constraint
+const COLUMN_CONSTRAINT_WORDS: [&str; 7] = [
+    "constraint",
+    "primary",
+    "unique",
+    "check",
+    "collate",
+    "generated",
+    "as",
+];
+
+/// Every foreign key of the table in canonical form, as `(columns) references ...`
+fn foreign_keys(table: &CreateTable) -> Result<Vec<String>> {
+    let mut keys = Vec::new();
+    for column in &table.columns {
+        let constraints = normalize(&column.constraints)?;
+        let words: Vec<&str> = constraints.split(' ').collect();
+        let Some(start) = words.iter().position(|&w| w == "references") else {
+            continue;
+        };
+        // a clause ends at the next constraint. NULL and DEFAULT start one unless they follow
+        // SET (as in ON DELETE SET NULL), and NOT only as NOT NULL, not NOT DEFERRABLE.
+        let end = (start + 1..words.len())
+            .find(|&i| {
+                COLUMN_CONSTRAINT_WORDS.contains(&words[i])
+                    || (matches!(words[i], "null" | "default") && words[i - 1] != "set")
+                    || (words[i] == "not" && words.get(i + 1) == Some(&"null"))
+            })
+            .unwrap_or(words.len());
+        keys.push(format!(
+            "( {} ) {}",
+            column.name.to_lowercase(),
+            words[start..end].join(" ")
+        ));
+    }
+    for constraint in &table.constraints {
+        let constraint = normalize(constraint)?;
+        // a named constraint's name doesn't change what it enforces
+        let unnamed = match constraint.strip_prefix("constraint ") {
+            Some(rest) => rest.split_once(' ').map_or("", |(_, rest)| rest),
+            None => &constraint,
+        };
+        if let Some(key) = unnamed.strip_prefix("foreign key ") {
+            keys.push(key.to_string());
+        }
+    }
+    keys.sort();
+    Ok(keys)
+}
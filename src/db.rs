@@ -0,0 +1,869 @@
+use crate::btree::{revisited, too_deep, BTreePage, Cell, Payload, MAX_DEPTH};
+use crate::header::DbHeader;
+use crate::pager::{Page, Pager};
+use crate::pages::{FreelistTrunkPage, Pages};
+use crate::record::{decode_record_with, Utf8Policy};
+use crate::schema::SchemaEntry;
+use crate::settings::Settings;
+use crate::value::{compare_keys, SortOrder};
+use crate::{Error, Result, Value};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+
+/// The root page of `sqlite_schema` is always page 1
+pub const SCHEMA_ROOT_PAGE: u32 = 1;
+
+/// Most interior pages whose dividers a handle keeps decoded. Seeks reach the pages nearest
+/// the roots first, and those are the ones every later seek passes through, so once this is
+/// full no more are added.
+const DIVIDER_CACHE_PAGES: usize = 1024;
+
+/// A read-only handle on a database file. Cloning is cheap: clones share the file handle and
+/// page cache, and may be used from different threads concurrently.
+#[derive(Debug, Clone)]
+pub struct Database {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    path: PathBuf,
+    header: DbHeader,
+    pager: Pager,
+    freelist: OnceLock<HashSet<u32>>,
+    /// `sqlite_schema`, read on first use. A handle reads one snapshot of the file (its header,
+    /// change counter included, is read once on opening), so the schema can't change under it.
+    schema: OnceLock<Vec<SchemaEntry>>,
+    settings: RwLock<Settings>,
+    dividers: DividerCache,
+}
+
+/// The children of an interior page and the keys separating them, decoded once so that
+/// repeated seeks through the page compare against them without re-reading its cells
+#[derive(Debug)]
+struct Dividers {
+    keys: DividerKeys,
+    /// Each child's page number, the right child last
+    children: Vec<u32>,
+}
+
+#[derive(Debug)]
+enum DividerKeys {
+    /// The largest rowid in each child but the rightmost
+    Table(Vec<i64>),
+    /// The entry sorting between each child and the next
+    Index(Vec<Vec<Value>>),
+}
+
+#[derive(Debug, Default)]
+struct DividerCache {
+    pages: Mutex<HashMap<u32, Arc<Dividers>>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+/// How often seeks found an interior page's dividers already decoded
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DividerCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl DividerCacheStats {
+    /// The share of lookups that were hits, from 0 to 1; 0 when there were none
+    pub fn hit_rate(&self) -> f64 {
+        match self.hits + self.misses {
+            0 => 0.0,
+            total => self.hits as f64 / total as f64,
+        }
+    }
+}
+
+impl Database {
+    pub fn open(path: impl AsRef<Path>) -> Result<Database> {
+        Self::open_with_settings(path, Settings::default())
+    }
+
+    pub fn open_with_settings(path: impl AsRef<Path>, settings: Settings) -> Result<Database> {
+        Self::open_with(path.as_ref(), None, settings)
+    }
+
+    /// Opens a database that starts `offset` bytes into the file, as when it is embedded in an
+    /// executable or archive. Without an offset, databases appended by SQLite's append VFS are
+    /// found from their trailer.
+    pub fn open_at_offset(
+        path: impl AsRef<Path>,
+        offset: u64,
+        settings: Settings,
+    ) -> Result<Database> {
+        Self::open_with(path.as_ref(), Some(offset), settings)
+    }
+
+    fn open_with(path: &Path, offset: Option<u64>, settings: Settings) -> Result<Database> {
+        let (pager, header) = Pager::open_at(path, offset)?;
+        pager.resize_cache(settings.cache_pages(header.page_size));
+        pager.set_readahead(settings.readahead);
+        Ok(Database {
+            inner: Arc::new(Inner {
+                path: path.to_path_buf(),
+                header,
+                pager,
+                freelist: OnceLock::new(),
+                schema: OnceLock::new(),
+                settings: RwLock::new(settings),
+                dividers: DividerCache::default(),
+            }),
+        })
+    }
+
+    /// Opens a file that may have been crafted to attack the reader, such as an upload. Parsing
+    /// never panics and never allocates more than the file's size justifies, whichever way a
+    /// database is opened: counts and sizes are checked against the file, every b-tree walk
+    /// stops at SQLite's depth limit, and damage is reported as [`Error::Corrupt`]. On top of
+    /// that this refuses headers SQLite itself would reject, and a first page that isn't the
+    /// schema table, before any of the file is used.
+    pub fn open_untrusted(path: impl AsRef<Path>) -> Result<Database> {
+        Self::untrusted(Self::open(path)?)
+    }
+
+    /// [`open_untrusted`](Self::open_untrusted) for a database `offset` bytes into the file
+    pub fn open_untrusted_at_offset(path: impl AsRef<Path>, offset: u64) -> Result<Database> {
+        Self::untrusted(Self::open_at_offset(path, offset, Settings::default())?)
+    }
+
+    fn untrusted(db: Database) -> Result<Database> {
+        db.header().validate(db.inner.pager.file_pages())?;
+        let schema = db.btree_page(SCHEMA_ROOT_PAGE)?;
+        if !schema.page_type().is_table() {
+            return Err(Error::Corrupt(
+                "page 1 is not the root of the schema table".to_string(),
+            ));
+        }
+        Ok(db)
+    }
+
+    pub fn settings(&self) -> Settings {
+        *self
+            .inner
+            .settings
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Replaces the settings for this handle and all of its clones. A smaller cache size
+    /// evicts pages as they are next cached, and decoded index keys are dropped since the
+    /// UTF-8 policy may have changed.
+    pub fn set_settings(&self, settings: Settings) {
+        self.inner
+            .dividers
+            .pages
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clear();
+        self.inner
+            .pager
+            .resize_cache(settings.cache_pages(self.page_size()));
+        self.inner.pager.set_readahead(settings.readahead);
+        *self
+            .inner
+            .settings
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = settings;
+    }
+
+    /// Applies a PRAGMA-style assignment, see [`Settings::apply_pragma`]
+    pub fn pragma(&self, pragma: &str) -> Result<()> {
+        let mut settings = self.settings();
+        settings.apply_pragma(pragma)?;
+        self.set_settings(settings);
+        Ok(())
+    }
+
+    /// How TEXT values that aren't valid UTF-8 are decoded
+    pub fn utf8_policy(&self) -> Utf8Policy {
+        self.settings().utf8_policy
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.inner.path
+    }
+
+    /// Hits and misses of the interior-page divider cache, across this handle and its clones
+    pub fn divider_cache_stats(&self) -> DividerCacheStats {
+        DividerCacheStats {
+            hits: self.inner.dividers.hits.load(Ordering::Relaxed),
+            misses: self.inner.dividers.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    /// The dividers of an interior page, decoded on first use
+    fn dividers(&self, page: &BTreePage) -> Result<Arc<Dividers>> {
+        let cache = &self.inner.dividers;
+        let lock = || {
+            cache
+                .pages
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+        };
+        if let Some(found) = lock().get(&page.number()) {
+            cache.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(found.clone());
+        }
+        cache.misses.fetch_add(1, Ordering::Relaxed);
+        let cells = page.cell_count();
+        let mut children = Vec::with_capacity(cells + 1);
+        let mut rowids = Vec::new();
+        let mut keys = Vec::new();
+        for i in 0..cells {
+            let cell = page.cell(i)?;
+            children.push(cell.left_child().unwrap_or(0));
+            match cell {
+                Cell::TableInterior { rowid, .. } => rowids.push(rowid),
+                Cell::IndexInterior { payload, .. } => {
+                    let record = self.read_payload(&payload)?;
+                    keys.push(decode_record_with(&record, self.utf8_policy())?);
+                }
+                _ => {}
+            }
+        }
+        children.push(page.header().right_child.unwrap_or(0));
+        let dividers = Arc::new(Dividers {
+            keys: if page.page_type().is_table() {
+                DividerKeys::Table(rowids)
+            } else {
+                DividerKeys::Index(keys)
+            },
+            children,
+        });
+        let mut pages = lock();
+        if pages.len() < DIVIDER_CACHE_PAGES {
+            pages.insert(page.number(), dividers.clone());
+        }
+        Ok(dividers)
+    }
+
+    /// Byte offset of the database within its file, which is 0 unless it is embedded
+    pub fn offset(&self) -> u64 {
+        self.inner.pager.base_offset()
+    }
+
+    pub fn header(&self) -> &DbHeader {
+        &self.inner.header
+    }
+
+    pub fn page_size(&self) -> u32 {
+        self.inner.pager.page_size()
+    }
+
+    pub fn usable_size(&self) -> u32 {
+        self.inner.header.usable_size()
+    }
+
+    pub fn page_count(&self) -> u32 {
+        self.inner.pager.page_count()
+    }
+
+    pub fn read_page(&self, number: u32) -> Result<Page> {
+        self.inner.pager.read_page(number)
+    }
+
+    pub fn btree_page(&self, number: u32) -> Result<BTreePage> {
+        BTreePage::parse(self.read_page(number)?, self.usable_size())
+    }
+
+    /// Reassembles a full payload, following its overflow chain if it spilled off the page.
+    /// Damaged chains are reported rather than followed blindly: the declared size is checked
+    /// against what the file could possibly hold before anything is allocated, and a chain
+    /// that revisits a page, leaves the file or runs onto the freelist is an error.
+    pub fn read_payload(&self, payload: &Payload) -> Result<Vec<u8>> {
+        if payload.first_overflow.is_none() {
+            return Ok(payload.local.clone());
+        }
+        let mut reader = self.payload_reader(payload)?;
+        // grow as pages actually arrive rather than trusting the declared size up front
+        let mut data = Vec::with_capacity(payload.size.min(1 << 20) as usize);
+        let mut buf = vec![0u8; self.usable_size() as usize];
+        loop {
+            let n = reader.read_chunk(&mut buf)?;
+            if n == 0 {
+                return Ok(data);
+            }
+            data.extend_from_slice(&buf[..n]);
+        }
+    }
+
+    /// Reads a payload incrementally, one overflow page at a time, so values far larger than
+    /// memory can be copied out. The chain is checked as it is followed, like [`read_payload`].
+    ///
+    /// [`read_payload`]: Database::read_payload
+    pub fn payload_reader<'a>(&'a self, payload: &'a Payload) -> Result<PayloadReader<'a>> {
+        let chunk = self.usable_size() as u64 - 4;
+        let overflow_bytes = payload.size.saturating_sub(payload.local.len() as u64);
+        let max_bytes = self.page_count() as u64 * chunk;
+        if overflow_bytes > max_bytes {
+            return Err(Error::Corrupt(format!(
+                "payload declares {} bytes, more than the {}-page file can hold",
+                payload.size,
+                self.page_count()
+            )));
+        }
+        Ok(PayloadReader {
+            db: self,
+            payload,
+            position: 0,
+            page: None,
+            next: payload.first_overflow.unwrap_or(0),
+            visited: HashSet::new(),
+        })
+    }
+
+    /// Pages on the freelist, gathered once per handle. A damaged freelist yields whatever
+    /// could be read before the damage.
+    pub fn freelist_pages(&self) -> &HashSet<u32> {
+        self.inner.freelist.get_or_init(|| {
+            let mut pages = HashSet::new();
+            let mut next = self.header().first_freelist_trunk;
+            while next != 0 && pages.insert(next) {
+                let Ok(page) = self.read_page(next) else {
+                    break;
+                };
+                let trunk = FreelistTrunkPage::new(page, self.usable_size());
+                pages.extend(trunk.leaves());
+                next = trunk.next_trunk().unwrap_or(0);
+            }
+            pages
+        })
+    }
+
+    /// Every page in the file, classified by what it is used for
+    pub fn pages(&self) -> Pages<'_> {
+        Pages::new(self)
+    }
+
+    /// Every row of `sqlite_schema`. It is read and parsed once, then shared by every clone of
+    /// the handle; a failed read is tried again on the next call.
+    pub fn schema(&self) -> Result<Vec<SchemaEntry>> {
+        if let Some(schema) = self.inner.schema.get() {
+            return Ok(schema.clone());
+        }
+        let schema: Vec<SchemaEntry> = self
+            .table_rows(SCHEMA_ROOT_PAGE)
+            .map(|row| SchemaEntry::from_record(&row?.1))
+            .collect::<Result<_>>()?;
+        Ok(self.inner.schema.get_or_init(|| schema).clone())
+    }
+
+    /// Iterates a table b-tree in rowid order, decoding each record
+    pub fn table_rows(&self, root_page: u32) -> TableRows<'_> {
+        TableRows {
+            db: self,
+            stack: Vec::new(),
+            visited: HashSet::new(),
+            root: Some(root_page),
+        }
+    }
+
+    /// Iterates every entry-holding cell of a table or index b-tree in key order, along with
+    /// where it was found. For indexes that includes the keys stored on interior pages.
+    pub fn tree_cells(&self, root_page: u32) -> TreeCells<'_> {
+        TreeCells {
+            db: self,
+            stack: Vec::new(),
+            visited: HashSet::new(),
+            root: Some(root_page),
+            seek: None,
+        }
+    }
+
+    /// Like [`tree_cells`](Self::tree_cells) on a table b-tree, but starting at the first row
+    /// with a rowid greater than `rowid`. Only the pages on the path down to it are read.
+    pub fn table_cells_after(&self, root_page: u32, rowid: i64) -> TreeCells<'_> {
+        TreeCells {
+            db: self,
+            stack: Vec::new(),
+            visited: HashSet::new(),
+            root: Some(root_page),
+            seek: Some(Seek::Rowid(rowid)),
+        }
+    }
+
+    /// Like [`tree_cells`](Self::tree_cells) on an index b-tree, but starting at the first
+    /// entry that sorts after `key` by `order` (see [`compare_keys`]). Only the pages on the
+    /// path down to it are read.
+    pub fn index_cells_after(
+        &self,
+        root_page: u32,
+        key: Vec<Value>,
+        order: Vec<SortOrder>,
+    ) -> TreeCells<'_> {
+        TreeCells {
+            db: self,
+            stack: Vec::new(),
+            visited: HashSet::new(),
+            root: Some(root_page),
+            seek: Some(Seek::Key(key, order)),
+        }
+    }
+
+    /// Like [`index_cells_after`](Self::index_cells_after), but starting at the first entry
+    /// that doesn't sort before `key`, so entries that begin with it are included
+    pub fn index_cells_from(
+        &self,
+        root_page: u32,
+        key: Vec<Value>,
+        order: Vec<SortOrder>,
+    ) -> TreeCells<'_> {
+        TreeCells {
+            db: self,
+            stack: Vec::new(),
+            visited: HashSet::new(),
+            root: Some(root_page),
+            seek: Some(Seek::KeyFrom(key, order)),
+        }
+    }
+}
+
+/// In-order walk over the leaves of a table b-tree, keeping the path from the root on an
+/// explicit stack
+pub struct TableRows<'a> {
+    db: &'a Database,
+    stack: Vec<(BTreePage, usize)>,
+    /// Pages entered so far, which a sound tree reaches once each
+    visited: HashSet<u32>,
+    root: Option<u32>,
+}
+
+impl TableRows<'_> {
+    fn push(&mut self, number: u32) -> Result<()> {
+        if self.stack.len() >= MAX_DEPTH {
+            return Err(too_deep(number));
+        }
+        if !self.visited.insert(number) {
+            return Err(revisited(number));
+        }
+        let page = self.db.btree_page(number)?;
+        if !page.page_type().is_table() {
+            return Err(Error::Corrupt(format!(
+                "page {} is not a table b-tree page",
+                number
+            )));
+        }
+        self.stack.push((page, 0));
+        Ok(())
+    }
+
+    fn next_row(&mut self) -> Result<Option<(i64, Vec<Value>)>> {
+        if let Some(root) = self.root.take() {
+            self.push(root)?;
+        }
+        loop {
+            let Some((page, index)) = self.stack.last_mut() else {
+                return Ok(None);
+            };
+            let i = *index;
+            *index += 1;
+
+            if page.page_type().is_leaf() {
+                if i >= page.cell_count() {
+                    self.stack.pop();
+                    continue;
+                }
+                if let Cell::TableLeaf { rowid, payload } = page.cell(i)? {
+                    let record = self.db.read_payload(&payload)?;
+                    return Ok(Some((
+                        rowid,
+                        decode_record_with(&record, self.db.utf8_policy())?,
+                    )));
+                }
+            } else if i < page.cell_count() {
+                let child = page.cell(i)?.left_child().unwrap_or(0);
+                self.push(child)?;
+            } else if i == page.cell_count() {
+                let child = page.header().right_child.unwrap_or(0);
+                self.push(child)?;
+            } else {
+                self.stack.pop();
+            }
+        }
+    }
+}
+
+impl Iterator for TableRows<'_> {
+    type Item = Result<(i64, Vec<Value>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_row() {
+            Ok(row) => row.map(Ok),
+            Err(e) => {
+                // stop after the first error rather than looping on a broken page
+                self.stack.clear();
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+/// Sequential access to a payload; see [`Database::payload_reader`]
+pub struct PayloadReader<'a> {
+    db: &'a Database,
+    payload: &'a Payload,
+    /// Bytes of the payload already returned
+    position: u64,
+    /// The overflow page being read and the offset of its unread content
+    page: Option<(Page, usize)>,
+    next: u32,
+    visited: HashSet<u32>,
+}
+
+impl PayloadReader<'_> {
+    /// Total size of the payload
+    pub fn size(&self) -> u64 {
+        self.payload.size
+    }
+
+    /// Bytes not read yet
+    pub fn remaining(&self) -> u64 {
+        self.payload.size - self.position
+    }
+
+    /// Reads up to `buf.len()` bytes, at most one page's worth, returning 0 at the end
+    pub fn read_chunk(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let remaining = self.remaining();
+        if remaining == 0 || buf.is_empty() {
+            return Ok(0);
+        }
+        let local = &self.payload.local;
+        let want = buf.len().min(remaining.min(usize::MAX as u64) as usize);
+        if (self.position as usize) < local.len() {
+            let source = &local[self.position as usize..];
+            let n = source.len().min(want);
+            buf[..n].copy_from_slice(&source[..n]);
+            self.position += n as u64;
+            return Ok(n);
+        }
+
+        // overflow pages hold a 4-byte next pointer followed by content up to the usable size
+        let usable = self.db.usable_size() as usize;
+        if self.page.as_ref().map_or(true, |(_, at)| *at >= usable) {
+            self.page = Some((self.next_overflow_page()?, 4));
+        }
+        let (page, at) = self.page.as_mut().expect("page was just loaded");
+        let n = (usable - *at).min(want);
+        buf[..n].copy_from_slice(&page.data()[*at..*at + n]);
+        *at += n;
+        self.position += n as u64;
+        Ok(n)
+    }
+
+    /// Moves `n` bytes forward without keeping them
+    pub fn skip(&mut self, mut n: u64) -> Result<()> {
+        let mut buf = vec![0u8; self.db.usable_size() as usize];
+        while n > 0 {
+            let want = n.min(buf.len() as u64) as usize;
+            let read = self.read_chunk(&mut buf[..want])?;
+            if read == 0 {
+                return Err(Error::Corrupt(format!(
+                    "skipped past the end of a {}-byte payload",
+                    self.payload.size
+                )));
+            }
+            n -= read as u64;
+        }
+        Ok(())
+    }
+
+    fn next_overflow_page(&mut self) -> Result<Page> {
+        let next = self.next;
+        let chain_error = |msg: String| {
+            Error::Corrupt(format!(
+                "overflow chain starting at page {} {} after {} of {} payload bytes",
+                self.payload.first_overflow.unwrap_or(0),
+                msg,
+                self.position,
+                self.payload.size
+            ))
+        };
+        if next == 0 {
+            return Err(chain_error("ends early".to_string()));
+        }
+        if next > self.db.page_count() {
+            return Err(chain_error(format!(
+                "points past the end of the file to page {}",
+                next
+            )));
+        }
+        if self.visited.contains(&next) {
+            return Err(chain_error(format!("loops back to page {}", next)));
+        }
+        if self.db.freelist_pages().contains(&next) {
+            return Err(chain_error(format!("runs into freelist page {}", next)));
+        }
+        self.visited.insert(next);
+        let page = self.db.read_page(next)?;
+        let bytes = page.data();
+        self.next = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        Ok(page)
+    }
+}
+
+impl std::io::Read for PayloadReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.read_chunk(buf).map_err(|e| match e {
+            Error::Io(e) => e,
+            e => std::io::Error::new(std::io::ErrorKind::InvalidData, e),
+        })
+    }
+}
+
+/// A b-tree cell together with its location in the file
+#[derive(Debug, Clone)]
+pub struct TreeCell {
+    pub page: u32,
+    /// Position in the page's cell pointer array
+    pub index: usize,
+    /// Byte offset of the cell within the page
+    pub offset: usize,
+    pub cell: Cell,
+}
+
+/// In-order walk over a b-tree. Each stack entry counts steps through its page: on interior
+/// pages even steps descend into child `step / 2` and odd steps visit cell `step / 2`, on leaves
+/// every step visits the next cell.
+pub struct TreeCells<'a> {
+    db: &'a Database,
+    stack: Vec<(BTreePage, usize)>,
+    /// Pages entered so far, which a sound tree reaches once each
+    visited: HashSet<u32>,
+    root: Option<u32>,
+    /// Entry to seek past before the first step
+    seek: Option<Seek>,
+}
+
+/// Where a [`TreeCells`] walk starts: after a rowid in a table b-tree, or after or at a key
+/// in an index b-tree
+enum Seek {
+    Rowid(i64),
+    Key(Vec<Value>, Vec<SortOrder>),
+    KeyFrom(Vec<Value>, Vec<SortOrder>),
+}
+
+impl TreeCells<'_> {
+    fn next_cell(&mut self) -> Result<Option<TreeCell>> {
+        if let Some(root) = self.root.take() {
+            self.visited.insert(root);
+            self.stack.push((self.db.btree_page(root)?, 0));
+            if let Some(seek) = self.seek.take() {
+                self.seek(&seek)?;
+            }
+        }
+        loop {
+            let depth = self.stack.len();
+            let Some((page, step)) = self.stack.last_mut() else {
+                return Ok(None);
+            };
+            let current = *step;
+            *step += 1;
+
+            let leaf = page.page_type().is_leaf();
+            let (i, visit) = if leaf {
+                (current, true)
+            } else {
+                (current / 2, current % 2 == 1)
+            };
+            if i > page.cell_count() || (i == page.cell_count() && visit) {
+                self.stack.pop();
+            } else if visit {
+                // table interior cells only separate children; they hold no entry
+                if leaf || !page.page_type().is_table() {
+                    return Ok(Some(TreeCell {
+                        page: page.number(),
+                        index: i,
+                        offset: page.cell_pointer(i),
+                        cell: page.cell(i)?,
+                    }));
+                }
+            } else {
+                let child = if i < page.cell_count() {
+                    page.cell(i)?.left_child()
+                } else {
+                    page.header().right_child
+                };
+                let child = child.unwrap_or(0);
+                if depth >= MAX_DEPTH {
+                    return Err(too_deep(child));
+                }
+                if !self.visited.insert(child) {
+                    return Err(revisited(child));
+                }
+                let child = self.db.btree_page(child)?;
+                if child.page_type().is_table() != page.page_type().is_table() {
+                    return Err(Error::Corrupt(format!(
+                        "page {} mixes table and index b-tree pages",
+                        page.number()
+                    )));
+                }
+                self.stack.push((child, 0));
+            }
+        }
+    }
+}
+
+impl TreeCells<'_> {
+    /// Descends from the root towards the first entry past `seek`, leaving each page's step
+    /// where the in-order walk would be on reaching it
+    fn seek(&mut self, seek: &Seek) -> Result<()> {
+        loop {
+            let depth = self.stack.len();
+            let Some((page, step)) = self.stack.last_mut() else {
+                return Ok(());
+            };
+            let table = matches!(seek, Seek::Rowid(_));
+            if page.page_type().is_table() != table {
+                return Err(Error::Corrupt(format!(
+                    "page {} is not {} b-tree page",
+                    page.number(),
+                    if table { "a table" } else { "an index" }
+                )));
+            }
+            // the first cell whose key is past the target: on table interior pages each key is
+            // the largest rowid in the child to its left, and on index interior pages each
+            // entry sorts between the children either side of it. Cells are in key order, so
+            // a binary search finds it; interior pages are searched through their cached
+            // dividers, as every seek into the tree passes through the same few of them.
+            let db = self.db;
+            let key_past = |key: &[Value], target: &[Value], order: &[SortOrder]| {
+                let ordering = compare_keys(key, target, order);
+                ordering.is_gt() || (ordering.is_eq() && matches!(seek, Seek::KeyFrom(..)))
+            };
+            if page.page_type().is_leaf() {
+                let past = |i: usize| -> Result<bool> {
+                    Ok(match (page.cell(i)?, seek) {
+                        (Cell::TableLeaf { rowid, .. }, Seek::Rowid(target)) => rowid > *target,
+                        (
+                            Cell::IndexLeaf { payload },
+                            Seek::Key(target, order) | Seek::KeyFrom(target, order),
+                        ) => {
+                            let record = db.read_payload(&payload)?;
+                            key_past(
+                                &decode_record_with(&record, db.utf8_policy())?,
+                                target,
+                                order,
+                            )
+                        }
+                        _ => unreachable!("the page type was checked above"),
+                    })
+                };
+                let (mut i, mut end) = (0, page.cell_count());
+                while i < end {
+                    let mid = i + (end - i) / 2;
+                    if past(mid)? {
+                        end = mid;
+                    } else {
+                        i = mid + 1;
+                    }
+                }
+                *step = i;
+                return Ok(());
+            }
+            let dividers = db.dividers(page)?;
+            let i = match (&dividers.keys, seek) {
+                (DividerKeys::Table(rowids), Seek::Rowid(target)) => {
+                    rowids.partition_point(|rowid| rowid <= target)
+                }
+                (
+                    DividerKeys::Index(keys),
+                    Seek::Key(target, order) | Seek::KeyFrom(target, order),
+                ) => keys.partition_point(|key| !key_past(key, target, order)),
+                _ => unreachable!("the page type was checked above"),
+            };
+            // step 2i descends into child i; the walk continues with the step after it
+            *step = 2 * i + 1;
+            let child = dividers.children[i];
+            if depth >= MAX_DEPTH {
+                return Err(too_deep(child));
+            }
+            if !self.visited.insert(child) {
+                return Err(revisited(child));
+            }
+            let child = self.db.btree_page(child)?;
+            self.stack.push((child, 0));
+        }
+    }
+}
+
+impl Iterator for TreeCells<'_> {
+    type Item = Result<TreeCell>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_cell() {
+            Ok(cell) => cell.map(Ok),
+            Err(e) => {
+                self.stack.clear();
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+// Embedding servers share one handle across request threads
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Database>();
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A copy of `shop.db` with the first four bytes of each `(page, next)` pair overwritten,
+    /// so those pages read as links of an overflow chain
+    fn with_links(name: &str, links: &[(u32, u32)]) -> Database {
+        let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/shop.db");
+        let mut bytes = std::fs::read(fixture).unwrap();
+        for &(page, next) in links {
+            let at = (page as usize - 1) * 1024;
+            bytes[at..at + 4].copy_from_slice(&next.to_be_bytes());
+        }
+        let path = std::env::temp_dir().join(format!("sqliter-{}-{}.db", name, std::process::id()));
+        std::fs::write(&path, bytes).unwrap();
+        let db = Database::open(&path);
+        std::fs::remove_file(&path).unwrap();
+        db.unwrap()
+    }
+
+    fn spilled(size: u64, first: u32) -> Payload {
+        Payload {
+            size,
+            local: vec![0; 100],
+            first_overflow: Some(first),
+        }
+    }
+
+    #[test]
+    fn two_page_overflow_cycle() {
+        let db = with_links("cycle", &[(7, 8), (8, 7)]);
+        let err = db.read_payload(&spilled(5000, 7)).unwrap_err().to_string();
+        assert!(err.contains("loops back to page 7"), "{}", err);
+    }
+
+    #[test]
+    fn chains_that_leave_the_file_or_end_early() {
+        let db = with_links("leave", &[(7, 8), (8, 9999)]);
+        let err = db.read_payload(&spilled(5000, 7)).unwrap_err().to_string();
+        assert!(
+            err.contains("past the end of the file to page 9999"),
+            "{}",
+            err
+        );
+
+        let db = with_links("early", &[(7, 0)]);
+        let err = db.read_payload(&spilled(5000, 7)).unwrap_err().to_string();
+        assert!(err.contains("ends early after 1120 of 5000"), "{}", err);
+
+        let huge = spilled(u64::MAX, 7);
+        let err = db.read_payload(&huge).unwrap_err().to_string();
+        assert!(err.contains("more than the"), "{}", err);
+    }
+}
@@ -0,0 +1,210 @@
+use crate::btree::{local_payload_len, revisited, too_deep, BTreePage, Cell, PageType, MAX_DEPTH};
+use crate::db::{Database, SCHEMA_ROOT_PAGE};
+use crate::pages::OverflowPage;
+use crate::{Result, Value};
+use std::collections::HashSet;
+
+/// Column names of SQLite's `dbstat` virtual table, in order
+pub const DBSTAT_COLUMNS: [&str; 10] = [
+    "name",
+    "path",
+    "pageno",
+    "pagetype",
+    "ncell",
+    "payload",
+    "unused",
+    "mx_payload",
+    "pgoffset",
+    "pgsize",
+];
+
+/// One row of `dbstat`: space accounting for a single page
+#[derive(Debug, Clone)]
+pub struct DbStatRow {
+    pub name: String,
+    /// Position in the tree: `/` for the root, `/00a/` for its eleventh child and
+    /// `/00a/003+000001` for the second overflow page of the fourth cell on that child
+    pub path: String,
+    pub pageno: u32,
+    /// `internal`, `leaf` or `overflow`
+    pub pagetype: &'static str,
+    pub ncell: u32,
+    /// Payload bytes stored on this page
+    pub payload: u64,
+    /// Free bytes on the page: the gap, freeblocks and fragments
+    pub unused: u64,
+    /// Largest payload of any cell on the page, including the part that overflows
+    pub mx_payload: u64,
+    pub pgoffset: u64,
+    pub pgsize: u32,
+}
+
+impl DbStatRow {
+    pub fn values(&self) -> Vec<Value> {
+        vec![
+            self.name.as_str().into(),
+            self.path.as_str().into(),
+            self.pageno.into(),
+            self.pagetype.into(),
+            self.ncell.into(),
+            Value::Integer(self.payload as i64),
+            Value::Integer(self.unused as i64),
+            Value::Integer(self.mx_payload as i64),
+            Value::Integer(self.pgoffset as i64),
+            self.pgsize.into(),
+        ]
+    }
+}
+
+/// Produces the rows `SELECT * FROM dbstat` would return, in the same order: trees by root
+/// page, each walked depth-first with a cell's overflow pages listed before its child
+pub fn dbstat(db: &Database) -> Result<Vec<DbStatRow>> {
+    let mut trees = vec![(SCHEMA_ROOT_PAGE, "sqlite_schema".to_string())];
+    for entry in db.schema()? {
+        if entry.root_page != 0 {
+            trees.push((entry.root_page, entry.name));
+        }
+    }
+    trees.sort();
+
+    let mut rows = Vec::new();
+    for (root, name) in trees {
+        walk(db, &name, root, &mut rows)?;
+    }
+    Ok(rows)
+}
+
+/// A b-tree page on the path of a [`walk`], with the step to take next: step `i` lists the
+/// overflow pages of cell `i` and then descends into its child, and the step after the last
+/// cell descends into the right child
+struct Frame {
+    path: String,
+    page_type: PageType,
+    cells: Vec<Cell>,
+    right_child: Option<u32>,
+    step: usize,
+}
+
+/// Walks one tree depth-first, keeping the path from the root on an explicit stack so a
+/// damaged tree can't exhaust the call stack
+fn walk(db: &Database, name: &str, root: u32, rows: &mut Vec<DbStatRow>) -> Result<()> {
+    let usable = db.usable_size() as u64;
+    let mut visited = HashSet::from([root]);
+    let mut stack = vec![visit(db, name, root, "/".to_string(), rows)?];
+    loop {
+        let depth = stack.len();
+        let Some(frame) = stack.last_mut() else {
+            return Ok(());
+        };
+        let i = frame.step;
+        frame.step += 1;
+        let child = match frame.cells.get(i) {
+            Some(cell) => {
+                if let Some(payload) = cell.payload() {
+                    let local = local_payload_len(
+                        payload.size,
+                        usable,
+                        frame.page_type == PageType::LeafTable,
+                    ) as u64;
+                    let mut remaining = payload.size - local;
+                    let mut next = payload.first_overflow;
+                    let mut seq = 0;
+                    while let Some(overflow) = next.filter(|_| remaining > 0) {
+                        let on_page = remaining.min(usable - 4);
+                        rows.push(DbStatRow {
+                            name: name.to_string(),
+                            path: format!("{}{:03x}+{:06x}", frame.path, i, seq),
+                            pageno: overflow,
+                            pagetype: "overflow",
+                            ncell: 0,
+                            payload: on_page,
+                            unused: usable - 4 - on_page,
+                            mx_payload: 0,
+                            pgoffset: (overflow as u64 - 1) * db.page_size() as u64,
+                            pgsize: db.page_size(),
+                        });
+                        remaining -= on_page;
+                        seq += 1;
+                        next = OverflowPage::new(db.read_page(overflow)?, db.usable_size()).next();
+                    }
+                }
+                cell.left_child()
+            }
+            None if i == frame.cells.len() => frame.right_child,
+            None => {
+                stack.pop();
+                continue;
+            }
+        };
+        if let Some(child) = child {
+            if depth >= MAX_DEPTH {
+                return Err(too_deep(child));
+            }
+            if !visited.insert(child) {
+                return Err(revisited(child));
+            }
+            let path = format!("{}{:03x}/", frame.path, i);
+            stack.push(visit(db, name, child, path, rows)?);
+        }
+    }
+}
+
+/// Reads a b-tree page and adds its row, returning it to walk from
+fn visit(
+    db: &Database,
+    name: &str,
+    number: u32,
+    path: String,
+    rows: &mut Vec<DbStatRow>,
+) -> Result<Frame> {
+    let page = db.btree_page(number)?;
+    let usable = db.usable_size() as u64;
+    let page_type = page.page_type();
+
+    let mut cells = Vec::with_capacity(page.cell_count());
+    for i in 0..page.cell_count() {
+        cells.push(page.cell(i)?);
+    }
+    let local_len = |cell: &Cell| match cell.payload() {
+        Some(p) => local_payload_len(p.size, usable, page_type == PageType::LeafTable) as u64,
+        None => 0,
+    };
+
+    rows.push(DbStatRow {
+        name: name.to_string(),
+        path: path.clone(),
+        pageno: number,
+        pagetype: if page_type.is_leaf() {
+            "leaf"
+        } else {
+            "internal"
+        },
+        ncell: cells.len() as u32,
+        payload: cells.iter().map(local_len).sum(),
+        unused: unused_bytes(&page)?,
+        mx_payload: cells
+            .iter()
+            .filter_map(|c| c.payload().map(|p| p.size))
+            .max()
+            .unwrap_or(0),
+        pgoffset: (number as u64 - 1) * db.page_size() as u64,
+        pgsize: db.page_size(),
+    });
+    Ok(Frame {
+        path,
+        page_type,
+        cells,
+        right_child: page.header().right_child,
+        step: 0,
+    })
+}
+
+/// Free space as dbstat counts it: the gap between the cell pointers and the cell content
+/// area, plus fragmented bytes and every freeblock
+fn unused_bytes(page: &BTreePage) -> Result<u64> {
+    let header = page.header();
+    let pointers_end = page.cell_pointers_offset() + 2 * page.cell_count();
+    let gap = (header.cell_content_start as u64).saturating_sub(pointers_end as u64);
+    let freeblocks: usize = page.freeblocks()?.iter().map(|&(_, size)| size).sum();
+    Ok(gap + header.fragmented_free_bytes as u64 + freeblocks as u64)
+}
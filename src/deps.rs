@@ -0,0 +1,201 @@
+//! What else in the schema depends on a table or one of its columns, found by reading the
+//! stored SQL of each index, view, trigger and table, so the effect of dropping or renaming
+//! something can be seen before it is done
+
+use crate::db::Database;
+use crate::schema::{ObjectType, SchemaEntry};
+use crate::sql::create::parse_create_table;
+use crate::sql::tokenizer::{tokenize, Token, TokenKind};
+use crate::Result;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reason {
+    /// An index on the table, or with the column in its key or WHERE clause
+    Indexes,
+    /// A trigger that fires on changes to the table
+    FiresOn,
+    /// A view, trigger or virtual table whose SQL names the table or column
+    RefersTo,
+    /// A view or trigger that names the table and selects `*`, and so every column
+    SelectsAll,
+    /// A table with a foreign key to the table or column
+    ForeignKey,
+    /// A view or trigger that depends on a view that depends on the object
+    Through,
+}
+
+impl Reason {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Reason::Indexes => "indexes",
+            Reason::FiresOn => "fires-on",
+            Reason::RefersTo => "refers-to",
+            Reason::SelectsAll => "selects-all",
+            Reason::ForeignKey => "foreign-key",
+            Reason::Through => "through",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Dependent {
+    pub kind: ObjectType,
+    pub name: String,
+    pub reason: Reason,
+    pub detail: String,
+}
+
+/// The objects that depend on `table`, or with `column` given on that column of it, directly
+/// and then through the views that depend on it; nothing when there is no such table or
+/// view. Names are matched as whole tokens outside strings, so a name that only appears in a
+/// literal doesn't count, but an unrelated column of the same name in another table does:
+/// the list errs on the side of including too much.
+pub fn dependents(db: &Database, table: &str, column: Option<&str>) -> Result<Vec<Dependent>> {
+    let schema = db.schema()?;
+    let Some(target) = schema.iter().find(|e| {
+        matches!(e.kind, ObjectType::Table | ObjectType::View) && e.name.eq_ignore_ascii_case(table)
+    }) else {
+        return Ok(Vec::new());
+    };
+    let definition = match (target.kind, &target.sql) {
+        (ObjectType::Table, Some(sql)) => parse_create_table(sql).ok(),
+        _ => None,
+    };
+
+    let mut found: Vec<Dependent> = Vec::new();
+    for entry in &schema {
+        if entry.name.eq_ignore_ascii_case(&target.name) {
+            continue;
+        }
+        let on_target = entry.tbl_name.eq_ignore_ascii_case(&target.name);
+        let Some(sql) = &entry.sql else {
+            // the indexes made for UNIQUE and PRIMARY KEY constraints have no SQL of their own
+            if entry.kind == ObjectType::Index && on_target && column.is_none() {
+                found.push(dependent(entry, Reason::Indexes, "made for a constraint"));
+            }
+            continue;
+        };
+        let tokens = body(sql, &entry.name)?;
+        let names_table = mentions(&tokens, &target.name);
+        let names_column = column.map_or(true, |c| mentions(&tokens, c));
+        match entry.kind {
+            ObjectType::Index if on_target && names_column => {
+                found.push(dependent(entry, Reason::Indexes, ""));
+            }
+            ObjectType::Trigger if on_target && names_column => {
+                found.push(dependent(entry, Reason::FiresOn, ""));
+            }
+            ObjectType::Table if entry.root_page != 0 => {
+                let Ok(child) = parse_create_table(sql) else {
+                    continue;
+                };
+                for key in child.foreign_keys()? {
+                    if !key.table.eq_ignore_ascii_case(&target.name) {
+                        continue;
+                    }
+                    let refers = match column {
+                        None => true,
+                        // a key naming no columns refers to the parent's primary key
+                        Some(column) if key.parent_columns.is_empty() => {
+                            definition.as_ref().is_some_and(|parent| {
+                                parent.primary_key_terms().is_ok_and(|terms| {
+                                    terms.iter().any(|term| {
+                                        term.name
+                                            .as_deref()
+                                            .is_some_and(|n| n.eq_ignore_ascii_case(column))
+                                    })
+                                })
+                            })
+                        }
+                        Some(column) => key
+                            .parent_columns
+                            .iter()
+                            .any(|c| c.eq_ignore_ascii_case(column)),
+                    };
+                    if refers {
+                        found.push(dependent(
+                            entry,
+                            Reason::ForeignKey,
+                            &format!("({})", key.columns.join(", ")),
+                        ));
+                    }
+                }
+            }
+            ObjectType::View | ObjectType::Trigger | ObjectType::Table if names_table => {
+                if names_column {
+                    found.push(dependent(entry, Reason::RefersTo, ""));
+                } else if selects_all(&tokens) {
+                    found.push(dependent(entry, Reason::SelectsAll, ""));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // whatever depends on a dependent view breaks along with it
+    let mut next = 0;
+    while next < found.len() {
+        if found[next].kind != ObjectType::View {
+            next += 1;
+            continue;
+        }
+        let view = found[next].name.clone();
+        next += 1;
+        for entry in &schema {
+            let seen = entry.name.eq_ignore_ascii_case(&target.name)
+                || found
+                    .iter()
+                    .any(|d| d.name.eq_ignore_ascii_case(&entry.name));
+            if seen || !matches!(entry.kind, ObjectType::View | ObjectType::Trigger) {
+                continue;
+            }
+            let Some(sql) = &entry.sql else {
+                continue;
+            };
+            if entry.tbl_name.eq_ignore_ascii_case(&view)
+                || mentions(&body(sql, &entry.name)?, &view)
+            {
+                found.push(dependent(entry, Reason::Through, &view));
+            }
+        }
+    }
+    Ok(found)
+}
+
+fn dependent(entry: &SchemaEntry, reason: Reason, detail: &str) -> Dependent {
+    Dependent {
+        kind: entry.kind,
+        name: entry.name.clone(),
+        reason,
+        detail: detail.to_string(),
+    }
+}
+
+/// The tokens of a CREATE statement after the name of the object it creates, so the name
+/// itself isn't taken for a reference
+fn body<'a>(sql: &'a str, name: &str) -> Result<Vec<Token<'a>>> {
+    let tokens = tokenize(sql)?;
+    let start = tokens
+        .iter()
+        .position(|t| t.is_name() && t.value().eq_ignore_ascii_case(name))
+        .map_or(0, |i| i + 1);
+    Ok(tokens[start..].to_vec())
+}
+
+/// Whether a name appears among the tokens as an identifier, quoted or not, but not as a
+/// string literal
+fn mentions(tokens: &[Token], name: &str) -> bool {
+    tokens
+        .iter()
+        .any(|t| t.is_name() && t.kind != TokenKind::String && t.value().eq_ignore_ascii_case(name))
+}
+
+/// Whether the tokens select `*` or `table.*`, rather than multiply
+fn selects_all(tokens: &[Token]) -> bool {
+    tokens.windows(2).any(|w| {
+        w[1].is_punct("*")
+            && (w[0].is_keyword("SELECT") || w[0].is_keyword("DISTINCT") || w[0].is_punct(","))
+    }) || tokens
+        .windows(2)
+        .any(|w| w[0].is_punct(".") && w[1].is_punct("*"))
+}
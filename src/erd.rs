@@ -0,0 +1,165 @@
+//! The schema as an entity-relationship diagram in Graphviz's DOT language: a node per table
+//! listing its columns, and an edge per foreign key from the referring columns to the
+//! columns of the parent table they refer to
+
+use anyhow::Result;
+use sqliter::schema::ObjectType;
+use sqliter::sql::create::{parse_create_table, CreateTable};
+use sqliter::Database;
+use std::fmt::Write;
+
+pub fn render_dot(db: &Database) -> Result<String> {
+    let mut tables: Vec<CreateTable> = Vec::new();
+    for entry in db.schema()? {
+        if entry.kind != ObjectType::Table || entry.is_internal() {
+            continue;
+        }
+        let Some(sql) = &entry.sql else {
+            continue;
+        };
+        // virtual tables have no column definitions to read, only their module's arguments
+        if let Ok(table) = parse_create_table(sql) {
+            tables.push(table);
+        }
+    }
+    let position = |name: &str| {
+        tables
+            .iter()
+            .position(|t| t.name.eq_ignore_ascii_case(name))
+    };
+
+    let mut dot = String::from(
+        "digraph erd {\n  rankdir=LR;\n  node [shape=plain fontname=monospace fontsize=10];\n",
+    );
+    let mut edges = Vec::new();
+    let mut missing: Vec<String> = Vec::new();
+    for (i, table) in tables.iter().enumerate() {
+        let keys = table.foreign_keys()?;
+        let primary_key: Vec<String> = table
+            .primary_key_terms()?
+            .into_iter()
+            .filter_map(|term| term.name)
+            .collect();
+        writeln!(
+            dot,
+            "  t{} [label=<<table border=\"0\" cellborder=\"1\" cellspacing=\"0\" cellpadding=\"4\">",
+            i
+        )?;
+        writeln!(
+            dot,
+            "    <tr><td bgcolor=\"lightgrey\"><b>{}</b></td></tr>",
+            escape_html(&table.name)
+        )?;
+        for (c, column) in table.columns.iter().enumerate() {
+            let mut text = column.name.clone();
+            if !column.type_name.is_empty() {
+                text.push(' ');
+                text.push_str(&column.type_name);
+            }
+            let mut marks = Vec::new();
+            if primary_key
+                .iter()
+                .any(|k| k.eq_ignore_ascii_case(&column.name))
+            {
+                marks.push("PK");
+            }
+            if keys.iter().any(|key| {
+                key.columns
+                    .iter()
+                    .any(|k| k.eq_ignore_ascii_case(&column.name))
+            }) {
+                marks.push("FK");
+            }
+            if !marks.is_empty() {
+                write!(text, " ({})", marks.join(", "))?;
+            }
+            writeln!(
+                dot,
+                "    <tr><td port=\"c{}\" align=\"left\">{}</td></tr>",
+                c,
+                escape_html(&text)
+            )?;
+        }
+        dot.push_str("  </table>>];\n");
+
+        for key in keys {
+            let from = match key.columns.first().and_then(|k| column_position(table, k)) {
+                Some(c) => format!("t{}:c{}", i, c),
+                None => format!("t{}", i),
+            };
+            let to = match position(&key.table) {
+                Some(parent) => {
+                    // a key naming no columns refers to the parent's primary key
+                    let parent_table = &tables[parent];
+                    let first = match key.parent_columns.first() {
+                        Some(name) => Some(name.clone()),
+                        None => parent_table
+                            .primary_key_terms()?
+                            .into_iter()
+                            .find_map(|term| term.name),
+                    };
+                    match first.and_then(|k| column_position(parent_table, &k)) {
+                        Some(c) => format!("t{}:c{}", parent, c),
+                        None => format!("t{}", parent),
+                    }
+                }
+                None => {
+                    let m = match missing
+                        .iter()
+                        .position(|name| name.eq_ignore_ascii_case(&key.table))
+                    {
+                        Some(m) => m,
+                        None => {
+                            missing.push(key.table.clone());
+                            missing.len() - 1
+                        }
+                    };
+                    format!("m{}", m)
+                }
+            };
+            // an edge joins only the first columns of a composite key, so the label names all
+            let label = if key.columns.len() > 1 {
+                let mut text = key.columns.join(", ");
+                if !key.parent_columns.is_empty() {
+                    write!(text, " -> {}", key.parent_columns.join(", "))?;
+                }
+                format!(" [label=\"{}\"]", escape_dot(&text))
+            } else {
+                String::new()
+            };
+            edges.push(format!("{} -> {}{};", from, to, label));
+        }
+    }
+    // parents that a foreign key names but the schema lacks
+    for (m, name) in missing.iter().enumerate() {
+        writeln!(
+            dot,
+            "  m{} [label=\"{}\" shape=box style=dashed];",
+            m,
+            escape_dot(name)
+        )?;
+    }
+    for edge in edges {
+        writeln!(dot, "  {}", edge)?;
+    }
+    dot.push_str("}\n");
+    Ok(dot)
+}
+
+fn column_position(table: &CreateTable, name: &str) -> Option<usize> {
+    table
+        .columns
+        .iter()
+        .position(|c| c.name.eq_ignore_ascii_case(name))
+}
+
+fn escape_dot(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,27 @@
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    #[error("file is not a database")]
+    NotADatabase,
+
+    #[error("not a WAL file: {0}")]
+    NotAWal(String),
+
+    #[error("corrupt file: {0}")]
+    Corrupt(String),
+
+    #[error("TEXT value is not valid UTF-8 (invalid byte at offset {0})")]
+    InvalidUtf8(usize),
+
+    #[error("syntax error: {0}")]
+    Syntax(String),
+
+    #[error("unable to decompress: {0}")]
+    Decompress(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,132 @@
+//! Row counts estimated from a handful of random root-to-leaf descents instead of a full scan.
+//!
+//! Each descent picks a child uniformly at random at every interior page and weights the entries
+//! it passes by the product of the fanouts above them (Knuth's estimator). That is an unbiased
+//! estimate of the tree's entry count; averaging many descents narrows it, and their spread gives
+//! a confidence interval.
+
+use crate::btree::{too_deep, MAX_DEPTH};
+use crate::db::Database;
+use crate::Result;
+use std::collections::HashSet;
+
+/// Descents made when the caller has no preference
+pub const DEFAULT_SAMPLES: usize = 64;
+
+#[derive(Debug, Clone)]
+pub struct RowEstimate {
+    pub estimate: f64,
+    /// Bounds of the 95% confidence interval
+    pub low: f64,
+    pub high: f64,
+    /// Whether the tree was small enough that every page was read
+    pub exact: bool,
+    pub samples: usize,
+    /// Levels from the root to the leaves on the first descent
+    pub depth: usize,
+    /// Distinct pages read while sampling
+    pub pages_read: usize,
+}
+
+/// Estimates the number of entries in the b-tree rooted at `root`: rows for a table, keys for
+/// an index
+pub fn estimate_rows(db: &Database, root: u32, samples: usize, seed: u64) -> Result<RowEstimate> {
+    let root_page = db.btree_page(root)?;
+    if root_page.page_type().is_leaf() {
+        let count = root_page.cell_count() as f64;
+        return Ok(RowEstimate {
+            estimate: count,
+            low: count,
+            high: count,
+            exact: true,
+            samples: 0,
+            depth: 1,
+            pages_read: 1,
+        });
+    }
+
+    let mut rng = SplitMix64(seed);
+    let mut visited = HashSet::new();
+    let mut estimates = Vec::with_capacity(samples);
+    let mut depth = 0;
+    for _ in 0..samples.max(1) {
+        let (estimate, levels) = descend(db, root, &mut rng, &mut visited)?;
+        if depth == 0 {
+            depth = levels;
+        }
+        estimates.push(estimate);
+    }
+
+    let n = estimates.len() as f64;
+    let mean = estimates.iter().sum::<f64>() / n;
+    let variance = if estimates.len() > 1 {
+        estimates.iter().map(|e| (e - mean).powi(2)).sum::<f64>() / (n - 1.0)
+    } else {
+        0.0
+    };
+    let margin = 1.96 * (variance / n).sqrt();
+    Ok(RowEstimate {
+        estimate: mean,
+        low: (mean - margin).max(0.0),
+        high: mean + margin,
+        exact: false,
+        samples: estimates.len(),
+        depth,
+        pages_read: visited.len(),
+    })
+}
+
+/// One random descent, returning its estimate and the number of levels passed
+fn descend(
+    db: &Database,
+    root: u32,
+    rng: &mut SplitMix64,
+    visited: &mut HashSet<u32>,
+) -> Result<(f64, usize)> {
+    let mut weight = 1.0;
+    let mut estimate = 0.0;
+    let mut number = root;
+    let mut levels = 0;
+    loop {
+        levels += 1;
+        if levels > MAX_DEPTH {
+            return Err(too_deep(number));
+        }
+        visited.insert(number);
+        let page = db.btree_page(number)?;
+        let cells = page.cell_count();
+        // index interior cells hold keys too; table interior cells are only separators
+        if page.page_type().is_leaf() || !page.page_type().is_table() {
+            estimate += weight * cells as f64;
+        }
+        if page.page_type().is_leaf() {
+            return Ok((estimate, levels));
+        }
+
+        let fanout = cells + 1;
+        let child = rng.below(fanout);
+        number = if child == cells {
+            page.header().right_child.unwrap_or_default()
+        } else {
+            page.cell(child)?.left_child().unwrap_or_default()
+        };
+        weight *= fanout as f64;
+    }
+}
+
+/// A small, fast PRNG; sampling doesn't need anything stronger
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
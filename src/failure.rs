@@ -0,0 +1,223 @@
+//! How a run failed, told apart so scripts can branch on the exit code or on the JSON that
+//! `--errors json` writes instead of the message
+
+use crate::json;
+use sqliter::Error;
+use std::io::{ErrorKind, Write};
+use std::str::FromStr;
+
+/// A table, index, column or other named object that doesn't exist. A missing file is
+/// reported the same way through its [`ErrorKind::NotFound`].
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+pub struct NotFound(pub String);
+
+/// Context for an error that struck after some of the output was written, which is then
+/// incomplete
+#[derive(Debug, thiserror::Error)]
+#[error("output is incomplete")]
+pub struct Partial;
+
+/// A writer that notes whether any output went through it, so an error that stops the
+/// output is marked [`Partial`] only once there is some to be incomplete
+pub struct Tracked<W> {
+    inner: W,
+    written: bool,
+}
+
+impl<W: Write> Tracked<W> {
+    pub fn new(inner: W) -> Self {
+        Tracked {
+            inner,
+            written: false,
+        }
+    }
+
+    /// `err` with [`Partial`] context if anything was written
+    pub fn fail(&self, err: anyhow::Error) -> anyhow::Error {
+        if self.written {
+            err.context(Partial)
+        } else {
+            err
+        }
+    }
+}
+
+impl<W: Write> Write for Tracked<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.written |= n > 0;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// How errors are reported on stderr
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ErrorFormat {
+    /// The message and its causes, for people
+    #[default]
+    Text,
+    /// One JSON object on a line
+    Json,
+}
+
+impl FromStr for ErrorFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<ErrorFormat> {
+        match s {
+            "text" => Ok(ErrorFormat::Text),
+            "json" => Ok(ErrorFormat::Json),
+            _ => anyhow::bail!("Unknown --errors format: {} (expected text or json)", s),
+        }
+    }
+}
+
+/// The kind of failure, each with its own exit code
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Failure {
+    /// Anything not told apart below, such as bad arguments or an unreadable file
+    Other,
+    /// The database or WAL is damaged or isn't one, or a value in them can't be decoded
+    Corrupt,
+    /// SQL text, whether given or read from the schema, couldn't be parsed
+    Sql,
+    /// A file, table, index, column or other named object doesn't exist
+    NotFound,
+    /// Rows or a dump were partly written when the error struck
+    Partial,
+}
+
+impl Failure {
+    /// Classifies an error by the first cause in its chain that says what went wrong. Partial
+    /// output, marked by [`Partial`] context on the error, outranks the reason it stopped,
+    /// since that's what a consumer has to act on.
+    pub fn of(err: &anyhow::Error) -> Failure {
+        if err.downcast_ref::<Partial>().is_some() {
+            return Failure::Partial;
+        }
+        for cause in err.chain() {
+            if cause.is::<NotFound>() {
+                return Failure::NotFound;
+            }
+            let io = match cause.downcast_ref::<Error>() {
+                Some(Error::Io(io)) => io,
+                Some(Error::Syntax(_)) => return Failure::Sql,
+                Some(
+                    Error::NotADatabase
+                    | Error::NotAWal(_)
+                    | Error::Corrupt(_)
+                    | Error::InvalidUtf8(_)
+                    | Error::Decompress(_),
+                ) => return Failure::Corrupt,
+                None => match cause.downcast_ref::<std::io::Error>() {
+                    Some(io) => io,
+                    None => continue,
+                },
+            };
+            if io.kind() == ErrorKind::NotFound {
+                return Failure::NotFound;
+            }
+        }
+        Failure::Other
+    }
+
+    /// The process exit code. Ctrl-C isn't caught, so a run it stops ends by the signal,
+    /// which a shell reports as 130.
+    pub fn exit_code(self) -> u8 {
+        match self {
+            Failure::Other => 1,
+            Failure::Corrupt => 2,
+            Failure::Sql => 3,
+            Failure::NotFound => 4,
+            Failure::Partial => 5,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Failure::Other => "error",
+            Failure::Corrupt => "corrupt",
+            Failure::Sql => "sql",
+            Failure::NotFound => "not_found",
+            Failure::Partial => "partial",
+        }
+    }
+}
+
+/// Writes `err` to stderr in `format`, returning the exit code for it
+pub fn report(err: &anyhow::Error, format: ErrorFormat) -> u8 {
+    let failure = Failure::of(err);
+    match format {
+        ErrorFormat::Text => eprintln!("Error: {:?}", err),
+        ErrorFormat::Json => {
+            let causes: Vec<String> = err
+                .chain()
+                .skip(1)
+                .map(|cause| json::string(&cause.to_string()))
+                .collect();
+            eprintln!(
+                "{{\"error\":{{\"kind\":\"{}\",\"exit_code\":{},\"message\":{},\"causes\":[{}]}}}}",
+                failure.as_str(),
+                failure.exit_code(),
+                json::string(&err.to_string()),
+                causes.join(",")
+            );
+        }
+    }
+    failure.exit_code()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    fn of(err: impl Into<anyhow::Error>) -> Failure {
+        Failure::of(&err.into())
+    }
+
+    #[test]
+    fn classifies_every_error() {
+        assert_eq!(of(Error::NotADatabase), Failure::Corrupt);
+        assert_eq!(of(Error::NotAWal("bad magic".into())), Failure::Corrupt);
+        assert_eq!(of(Error::Corrupt("cycle".into())), Failure::Corrupt);
+        assert_eq!(of(Error::InvalidUtf8(3)), Failure::Corrupt);
+        assert_eq!(of(Error::Decompress("truncated".into())), Failure::Corrupt);
+        assert_eq!(of(Error::Syntax("near x".into())), Failure::Sql);
+        assert_eq!(of(NotFound("no such table: t".into())), Failure::NotFound);
+        let missing = || std::io::Error::from(ErrorKind::NotFound);
+        assert_eq!(of(Error::Io(missing())), Failure::NotFound);
+        assert_eq!(of(missing()), Failure::NotFound);
+        let denied = || std::io::Error::from(ErrorKind::PermissionDenied);
+        assert_eq!(of(Error::Io(denied())), Failure::Other);
+        assert_eq!(of(anyhow::anyhow!("Missing <command>")), Failure::Other);
+    }
+
+    #[test]
+    fn partial_once_something_was_written() {
+        let mut out = Tracked::new(Vec::new());
+        let failed = || anyhow::Error::new(Error::Corrupt("cycle".into()));
+        assert_eq!(Failure::of(&out.fail(failed())), Failure::Corrupt);
+        out.write_all(b"").unwrap();
+        assert_eq!(Failure::of(&out.fail(failed())), Failure::Corrupt);
+        out.write_all(b"BEGIN TRANSACTION;").unwrap();
+        assert_eq!(Failure::of(&out.fail(failed())), Failure::Partial);
+    }
+
+    #[test]
+    fn the_first_cause_that_says_decides() {
+        let err = Err::<(), _>(Error::Corrupt("cycle".into()))
+            .context("Unable to open x.db")
+            .unwrap_err();
+        assert_eq!(Failure::of(&err), Failure::Corrupt);
+        let err = Err::<(), _>(Error::Syntax("near x".into()))
+            .context(Partial)
+            .unwrap_err();
+        assert_eq!(Failure::of(&err), Failure::Partial);
+    }
+}
@@ -0,0 +1,70 @@
+//! Turning values into text the way SQLite does, shared by every output so a value reads the
+//! same whichever way it is printed
+
+/// A REAL as SQLite renders it for display and `CAST(x AS TEXT)`: `%!.15g`, so 15 significant
+/// digits and always a decimal point or exponent to tell it from an INTEGER (`3.0`, `0.3`,
+/// `1.0e+20`)
+pub fn format_real(f: f64) -> String {
+    format_g(f, 15)
+}
+
+/// A REAL in the fewest significant digits, at least 15, that read back as exactly the same
+/// number. Values that print the same for display do here too; the rest get the digits that
+/// tell them apart (`0.30000000000000004`).
+pub fn format_real_exact(f: f64) -> String {
+    (15..17)
+        .map(|precision| format_g(f, precision))
+        .find(|s| s.parse::<f64>() == Ok(f))
+        .unwrap_or_else(|| format_g(f, 17))
+}
+
+/// C's `%.{precision}g` with SQLite's `!` flag, which keeps a `.0` on whole numbers
+fn format_g(f: f64, precision: usize) -> String {
+    if f.is_nan() {
+        return "NaN".to_string();
+    }
+    if f.is_infinite() {
+        return if f > 0.0 { "Inf" } else { "-Inf" }.to_string();
+    }
+    if f == 0.0 {
+        // SQLite doesn't distinguish negative zero
+        return "0.0".to_string();
+    }
+
+    // rounding to the precision can carry into the exponent, so let the formatter do it
+    let scientific = format!("{:.*e}", precision - 1, f);
+    let (mantissa, exponent) = scientific
+        .split_once('e')
+        .expect("{:e} always has an exponent");
+    let exponent: i32 = exponent.parse().expect("{:e} exponents are integers");
+    let (sign, mantissa) = match mantissa.strip_prefix('-') {
+        Some(mantissa) => ("-", mantissa),
+        None => ("", mantissa),
+    };
+    let digits: String = mantissa.chars().filter(|&c| c != '.').collect();
+    let digits = digits.trim_end_matches('0');
+
+    if exponent < -4 || exponent >= precision as i32 {
+        let (first, rest) = digits.split_at(1);
+        let rest = if rest.is_empty() { "0" } else { rest };
+        let exponent_sign = if exponent < 0 { '-' } else { '+' };
+        format!(
+            "{}{}.{}e{}{:02}",
+            sign,
+            first,
+            rest,
+            exponent_sign,
+            exponent.unsigned_abs()
+        )
+    } else if exponent < 0 {
+        let zeros = "0".repeat(exponent.unsigned_abs() as usize - 1);
+        format!("{}0.{}{}", sign, zeros, digits)
+    } else {
+        let point = exponent as usize + 1;
+        if digits.len() > point {
+            format!("{}{}.{}", sign, &digits[..point], &digits[point..])
+        } else {
+            format!("{}{}{}.0", sign, digits, "0".repeat(point - digits.len()))
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use crate::db::Database;
+use crate::pages::PageKind;
+use crate::Result;
+
+/// Share of a page's usable space that has to be stranded in freeblocks and fragments before
+/// the page counts as churned
+pub const DEFAULT_THRESHOLD: f64 = 0.10;
+
+/// Free space on one b-tree page, split by whether it can be used without compaction
+#[derive(Debug, Clone)]
+pub struct PageFragmentation {
+    pub page: u32,
+    /// Root page of the b-tree the page belongs to
+    pub root: u32,
+    pub cells: usize,
+    /// Unallocated space between the cell pointers and the cell content area
+    pub gap: usize,
+    pub freeblocks: usize,
+    pub freeblock_bytes: usize,
+    /// Free fragments of one to three bytes, too small to be freeblocks
+    pub fragmented_bytes: usize,
+}
+
+impl PageFragmentation {
+    /// Free space that only compaction can join onto the gap: deleted or shrunk cells leave
+    /// it behind, so it measures churn
+    pub fn stranded(&self) -> usize {
+        self.freeblock_bytes + self.fragmented_bytes
+    }
+
+    pub fn is_churned(&self, usable_size: u32, threshold: f64) -> bool {
+        self.stranded() as f64 >= usable_size as f64 * threshold
+    }
+}
+
+/// Free space accounting for every b-tree page reachable from the schema, in page order
+pub fn fragmentation(db: &Database) -> Result<Vec<PageFragmentation>> {
+    let mut pages = Vec::new();
+    for page in db.pages() {
+        let page = page?;
+        let is_btree = matches!(
+            page.kind(),
+            PageKind::TableInterior
+                | PageKind::TableLeaf
+                | PageKind::IndexInterior
+                | PageKind::IndexLeaf
+        );
+        let Some(root) = page.root().filter(|_| is_btree) else {
+            continue;
+        };
+        let btree = db.btree_page(page.number())?;
+        let freeblocks = btree.freeblocks()?;
+        let pointers_end = btree.cell_pointers_offset() + 2 * btree.cell_count();
+        pages.push(PageFragmentation {
+            page: page.number(),
+            root,
+            cells: btree.cell_count(),
+            gap: (btree.header().cell_content_start as usize).saturating_sub(pointers_end),
+            freeblocks: freeblocks.len(),
+            freeblock_bytes: freeblocks.iter().map(|&(_, size)| size).sum(),
+            fragmented_bytes: btree.header().fragmented_free_bytes as usize,
+        });
+    }
+    Ok(pages)
+}
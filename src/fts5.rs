@@ -0,0 +1,514 @@
+//! Reading FTS5 full-text indexes straight from their `_data` shadow table, without the fts5
+//! module: listing the indexed terms and finding the rows that contain one
+
+use crate::db::Database;
+use crate::schema::ObjectType;
+use crate::sql::create::parse_create_virtual_table;
+use crate::sql::tokenizer::{tokenize, Token};
+use crate::table::TableInfo;
+use crate::varint::read_varint;
+use crate::{Error, Result, Value};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Rowid of the `_data` record listing the segments of the index
+const STRUCTURE_ROWID: i64 = 10;
+
+/// Marks a structure record in the format that adds per-segment origin counters
+const STRUCTURE_V2: [u8; 4] = [0xff, 0x00, 0x00, 0x01];
+
+/// Bits of a `_data` rowid below the segment id: the page number, b-tree height and doclist
+/// index flag
+const SEGMENT_ID_SHIFT: u32 = 31 + 5 + 1;
+
+/// First byte of the terms of the main index; prefix indexes use '1' and up
+const MAIN_INDEX: u8 = b'0';
+
+/// How much of each hit the index records
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Detail {
+    /// Column and offset of every hit
+    Full,
+    /// Only the columns hit
+    Column,
+    /// Only which rows hold the term
+    None,
+}
+
+/// Where the indexed text is kept
+#[derive(Debug, Clone)]
+enum Content {
+    /// In the `_content` shadow table, as columns `c0`, `c1`, ... after the id
+    Own(TableInfo),
+    /// In another table, looked up by rowid and matched by column name
+    External(TableInfo),
+    /// Nowhere: the index holds the only copy
+    None,
+}
+
+/// An FTS5 virtual table
+#[derive(Debug, Clone)]
+pub struct Fts5 {
+    pub name: String,
+    pub columns: Vec<String>,
+    pub detail: Detail,
+    content: Content,
+    data: TableInfo,
+    idx: TableInfo,
+}
+
+/// A term in the index and how many rows hold it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexedTerm {
+    pub term: Vec<u8>,
+    pub rows: usize,
+}
+
+/// One segment b-tree of the index, a run of leaf pages in term order
+#[derive(Debug, Clone, Copy)]
+struct Segment {
+    id: u64,
+    first_page: u64,
+    last_page: u64,
+}
+
+/// A row's entry in a term's doclist
+#[derive(Debug, Clone, Copy)]
+struct DocEntry {
+    rowid: i64,
+    /// Drops the row's entries from older segments
+    deleted: bool,
+    /// The row holds the term; a delete marker on its own has no hits
+    has_hits: bool,
+}
+
+impl Fts5 {
+    /// Looks up an FTS5 table by name, returning `None` if there is no table of that name or
+    /// it isn't an fts5 table
+    pub fn find(db: &Database, name: &str) -> Result<Option<Fts5>> {
+        let schema = db.schema()?;
+        let Some(entry) = schema
+            .iter()
+            .find(|e| e.kind == ObjectType::Table && e.name.eq_ignore_ascii_case(name))
+        else {
+            return Ok(None);
+        };
+        let Some(sql) = entry.sql.as_deref().filter(|_| entry.root_page == 0) else {
+            return Ok(None);
+        };
+        let Ok(table) = parse_create_virtual_table(sql) else {
+            return Ok(None);
+        };
+        if !table.module.eq_ignore_ascii_case("fts5") {
+            return Ok(None);
+        }
+
+        // arguments are column names, optionally followed by UNINDEXED, or key=value options
+        let mut columns = Vec::new();
+        let mut detail = Detail::Full;
+        let mut content_option = None;
+        let mut content_rowid = None;
+        for arg in &table.args {
+            let tokens = tokenize(arg)?;
+            match tokens.as_slice() {
+                [key, eq, value @ ..] if eq.is_punct("=") => {
+                    let value = option_value(value);
+                    match key.value().to_ascii_lowercase().as_str() {
+                        "detail" => {
+                            detail = match value.to_ascii_lowercase().as_str() {
+                                "full" => Detail::Full,
+                                "column" => Detail::Column,
+                                "none" => Detail::None,
+                                other => {
+                                    return Err(Error::Syntax(format!(
+                                        "unknown fts5 detail option {}",
+                                        other
+                                    )))
+                                }
+                            }
+                        }
+                        "content" => content_option = Some(value),
+                        "content_rowid" => content_rowid = Some(value),
+                        _ => {}
+                    }
+                }
+                [column, ..] => columns.push(column.value().into_owned()),
+                [] => {}
+            }
+        }
+
+        let shadow = |suffix: &str| -> Result<TableInfo> {
+            let name = format!("{}_{}", entry.name, suffix);
+            TableInfo::find(db, &name)?
+                .ok_or_else(|| Error::Corrupt(format!("fts5 table {} has no {}", entry.name, name)))
+        };
+        let content = match content_option.as_deref() {
+            None => Content::Own(shadow("content")?),
+            Some("") => Content::None,
+            Some(table) => match TableInfo::find(db, table)? {
+                Some(info) => Content::External(info),
+                None => Content::None,
+            },
+        };
+        // rows of an external table are looked up by rowid, so the key has to be the rowid
+        if let (Content::External(table), Some(column)) = (&content, &content_rowid) {
+            let alias = table
+                .definition
+                .rowid_alias()
+                .map(|i| table.columns()[i].name.as_str());
+            let is_rowid = ["rowid", "oid", "_rowid_"]
+                .iter()
+                .chain(&alias)
+                .any(|name| name.eq_ignore_ascii_case(column));
+            if !is_rowid {
+                return Err(Error::Syntax(format!(
+                    "fts5 table {} takes rowids from column {} of {}, which is not its rowid",
+                    entry.name, column, table.name
+                )));
+            }
+        }
+        Ok(Some(Fts5 {
+            name: entry.name.clone(),
+            columns,
+            detail,
+            content,
+            data: shadow("data")?,
+            idx: shadow("idx")?,
+        }))
+    }
+
+    /// Every term of the main index in byte order, with the number of rows holding it
+    pub fn terms(&self, db: &Database) -> Result<Vec<IndexedTerm>> {
+        let mut terms: BTreeMap<Vec<u8>, BTreeSet<i64>> = BTreeMap::new();
+        for segment in self.segments(db)? {
+            self.read_segment(db, segment, segment.first_page, &mut |term, entries| {
+                if term.first() == Some(&MAIN_INDEX) {
+                    apply(terms.entry(term[1..].to_vec()).or_default(), entries);
+                }
+                true
+            })?;
+        }
+        Ok(terms
+            .into_iter()
+            .filter(|(_, rows)| !rows.is_empty())
+            .map(|(term, rows)| IndexedTerm {
+                term,
+                rows: rows.len(),
+            })
+            .collect())
+    }
+
+    /// Rowids of the rows holding `term`, as `MATCH` would find them for a single bare term.
+    /// The term is lowercased as the default unicode61 tokenizer does, but accents are not
+    /// folded, so it should be given as the tokenizer left it.
+    pub fn match_term(&self, db: &Database, term: &str) -> Result<Vec<i64>> {
+        let mut key = vec![MAIN_INDEX];
+        key.extend(term.to_lowercase().into_bytes());
+        let mut rows = BTreeSet::new();
+        for segment in self.segments(db)? {
+            let start = self.start_page(db, segment, &key)?;
+            self.read_segment(db, segment, start, &mut |found, entries| {
+                if found == key.as_slice() {
+                    apply(&mut rows, entries);
+                }
+                // terms are in order, so the rest of the segment sorts after it
+                found <= key.as_slice()
+            })?;
+        }
+        Ok(rows.into_iter().collect())
+    }
+
+    /// The indexed columns of a row, or `None` if the table keeps no copy of its text or the
+    /// row is missing from it
+    pub fn document(&self, db: &Database, rowid: i64) -> Result<Option<Vec<Value>>> {
+        match &self.content {
+            Content::Own(table) => Ok(table.row(db, rowid)?.map(|row| {
+                let mut values = row.values;
+                values.remove(0);
+                values.resize(self.columns.len(), Value::Null);
+                values
+            })),
+            Content::External(table) => Ok(table.row(db, rowid)?.map(|row| {
+                self.columns
+                    .iter()
+                    .map(|name| {
+                        table
+                            .columns()
+                            .iter()
+                            .position(|c| c.name.eq_ignore_ascii_case(name))
+                            .map_or(Value::Null, |i| row.values[i].clone())
+                    })
+                    .collect()
+            })),
+            Content::None => Ok(None),
+        }
+    }
+
+    /// The segments of the index, oldest first: levels hold older data the higher they are,
+    /// and segments within a level are newer the later they come
+    fn segments(&self, db: &Database) -> Result<Vec<Segment>> {
+        let data = self.block(db, STRUCTURE_ROWID)?;
+        let corrupt = || Error::Corrupt(format!("fts5 table {} has a bad structure", self.name));
+        let mut p = 4;
+        let v2 = data.get(p..p + 4) == Some(&STRUCTURE_V2);
+        if v2 {
+            p += 4;
+        }
+        let mut varint = || -> Result<u64> {
+            let (value, n) = data.get(p..).and_then(read_varint).ok_or_else(corrupt)?;
+            p += n;
+            Ok(value)
+        };
+        let levels = varint()?;
+        let _segment_count = varint()?;
+        let _write_counter = varint()?;
+        if v2 {
+            let _origin_counter = varint()?;
+        }
+        let mut by_level = Vec::new();
+        for _ in 0..levels {
+            let _merge = varint()?;
+            let count = varint()?;
+            let mut level = Vec::new();
+            for _ in 0..count {
+                level.push(Segment {
+                    id: varint()?,
+                    first_page: varint()?,
+                    last_page: varint()?,
+                });
+                if v2 {
+                    // origin range, tombstone pages and entry counts
+                    for _ in 0..5 {
+                        varint()?;
+                    }
+                }
+            }
+            by_level.push(level);
+        }
+        Ok(by_level.into_iter().rev().flatten().collect())
+    }
+
+    /// The page of `segment` to start looking for `key` on: the `_idx` table records the
+    /// smallest prefix of the first term of each leaf after the first
+    fn start_page(&self, db: &Database, segment: Segment, key: &[u8]) -> Result<u64> {
+        let mut start = segment.first_page;
+        for row in self.idx.rows(db) {
+            let row = row?;
+            let (Some(Value::Integer(segid)), Some(term), Some(Value::Integer(page))) =
+                (row.values.first(), row.values.get(1), row.values.get(2))
+            else {
+                continue;
+            };
+            let term = match term {
+                Value::Blob(b) | Value::RawText(b) => b.as_slice(),
+                Value::Text(s) => s.as_bytes(),
+                _ => &[],
+            };
+            if *segid as u64 == segment.id && term <= key {
+                // the low bit flags a doclist index
+                start = start.max((*page as u64) >> 1);
+            }
+        }
+        Ok(start)
+    }
+
+    fn block(&self, db: &Database, rowid: i64) -> Result<Vec<u8>> {
+        match self
+            .data
+            .row(db, rowid)?
+            .and_then(|row| row.values.into_iter().nth(1))
+        {
+            Some(Value::Blob(data)) => Ok(data),
+            _ => Err(Error::Corrupt(format!(
+                "fts5 table {} has no data block {}",
+                self.name, rowid
+            ))),
+        }
+    }
+
+    /// Walks the leaves of `segment` from page `start`, handing each term and its doclist to
+    /// `visit` until it returns false. Doclists and position lists carry on across pages.
+    fn read_segment(
+        &self,
+        db: &Database,
+        segment: Segment,
+        start: u64,
+        visit: &mut dyn FnMut(&[u8], &[DocEntry]) -> bool,
+    ) -> Result<()> {
+        // the term read last, which the terms after it on a page share a prefix with, and
+        // whether its doclist is still being read
+        let mut term = Vec::new();
+        let mut open = false;
+        let mut entries = Vec::new();
+        // bytes of a position list still to skip at the start of the next page
+        let mut pending = 0;
+        for page in start..=segment.last_page {
+            let rowid = ((segment.id as i64) << SEGMENT_ID_SHIFT) + page as i64;
+            let leaf = Leaf::parse(self.block(db, rowid)?, &self.name)?;
+
+            let skip = pending.min(leaf.size - 4);
+            pending -= skip;
+            if pending > 0 {
+                continue;
+            }
+            let first_term = leaf.terms.first().copied().unwrap_or(leaf.size);
+            if open && leaf.rowid_offset != 0 && leaf.rowid_offset < first_term {
+                pending = self.read_doclist(&leaf, leaf.rowid_offset, first_term, &mut entries)?;
+            }
+            for (n, &offset) in leaf.terms.iter().enumerate() {
+                if open && !visit(&term, &entries) {
+                    return Ok(());
+                }
+                entries.clear();
+                // the first term of a page is whole; later ones share a prefix with the last
+                let mut p = offset;
+                let prefix = if n == 0 {
+                    0
+                } else {
+                    leaf.varint(&mut p)? as usize
+                };
+                let suffix = leaf.varint(&mut p)? as usize;
+                let bytes = leaf
+                    .data
+                    .get(p..p + suffix)
+                    .filter(|_| prefix <= term.len())
+                    .ok_or_else(|| leaf.corrupt())?;
+                term.truncate(prefix);
+                term.extend_from_slice(bytes);
+                p += suffix;
+                let end = leaf.terms.get(n + 1).copied().unwrap_or(leaf.size);
+                pending = self.read_doclist(&leaf, p, end, &mut entries)?;
+                open = true;
+            }
+        }
+        if open {
+            visit(&term, &entries);
+        }
+        Ok(())
+    }
+
+    /// Reads doclist entries between `p` and `end` on `leaf`, returning how many bytes of the
+    /// last position list run on into the next page. The first rowid is stored whole, later
+    /// ones as the difference from the one before.
+    fn read_doclist(
+        &self,
+        leaf: &Leaf,
+        mut p: usize,
+        end: usize,
+        entries: &mut Vec<DocEntry>,
+    ) -> Result<usize> {
+        let mut rowid: Option<i64> = None;
+        while p < end {
+            let value = leaf.varint(&mut p)? as i64;
+            let current = match rowid {
+                Some(previous) => previous.wrapping_add(value),
+                None => value,
+            };
+            rowid = Some(current);
+            let (deleted, has_hits, size) = if self.detail == Detail::None {
+                // a zero byte marks a delete, and a second one that the row holds the term
+                // anyway
+                match leaf.data[p..end] {
+                    [0, 0, ..] => {
+                        p += 2;
+                        (true, true, 0)
+                    }
+                    [0, ..] => {
+                        p += 1;
+                        (true, false, 0)
+                    }
+                    _ => (false, true, 0),
+                }
+            } else {
+                let header = leaf.varint(&mut p)?;
+                let size = (header >> 1) as usize;
+                (header & 1 == 1, size > 0, size)
+            };
+            entries.push(DocEntry {
+                rowid: current,
+                deleted,
+                has_hits,
+            });
+            if p + size > leaf.size {
+                return Ok(p + size - leaf.size);
+            }
+            p += size;
+        }
+        Ok(0)
+    }
+}
+
+/// Applies a segment's doclist for a term to the rows found in older segments
+fn apply(rows: &mut BTreeSet<i64>, entries: &[DocEntry]) {
+    for entry in entries {
+        if entry.deleted {
+            rows.remove(&entry.rowid);
+        }
+        if entry.has_hits {
+            rows.insert(entry.rowid);
+        }
+    }
+}
+
+/// The text of an option's value, without quotes
+fn option_value(tokens: &[Token]) -> String {
+    match tokens {
+        [value] => value.value().into_owned(),
+        _ => tokens.iter().map(|t| t.text).collect::<Vec<_>>().join(" "),
+    }
+}
+
+/// A leaf page of a segment: a 4-byte header holding the offset of the first rowid that
+/// doesn't follow a term and the size of the content, then the content, then the offsets of
+/// the terms on the page as varints, each after the first relative to the one before
+struct Leaf {
+    data: Vec<u8>,
+    rowid_offset: usize,
+    size: usize,
+    terms: Vec<usize>,
+}
+
+impl Leaf {
+    fn parse(data: Vec<u8>, table: &str) -> Result<Leaf> {
+        let corrupt = || Error::Corrupt(format!("fts5 table {} has a bad leaf page", table));
+        if data.len() < 4 {
+            return Err(corrupt());
+        }
+        let rowid_offset = u16::from_be_bytes([data[0], data[1]]) as usize;
+        let size = u16::from_be_bytes([data[2], data[3]]) as usize;
+        if size < 4 || size > data.len() {
+            return Err(corrupt());
+        }
+        let mut terms = Vec::new();
+        let mut p = size;
+        let mut offset = 0;
+        while p < data.len() {
+            let (delta, n) = read_varint(&data[p..]).ok_or_else(corrupt)?;
+            p += n;
+            offset += delta as usize;
+            if offset < 4 || offset >= size {
+                return Err(corrupt());
+            }
+            terms.push(offset);
+        }
+        Ok(Leaf {
+            data,
+            rowid_offset,
+            size,
+            terms,
+        })
+    }
+
+    fn varint(&self, p: &mut usize) -> Result<u64> {
+        let (value, n) = self
+            .data
+            .get(*p..self.size)
+            .and_then(read_varint)
+            .ok_or_else(|| self.corrupt())?;
+        *p += n;
+        Ok(value)
+    }
+
+    fn corrupt(&self) -> Error {
+        Error::Corrupt("fts5 leaf page is truncated".to_string())
+    }
+}
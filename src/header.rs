@@ -0,0 +1,255 @@
+use crate::{Error, Result};
+
+pub const HEADER_SIZE: usize = 100;
+const MAGIC: &[u8; 16] = b"SQLite format 3\0";
+
+/// File formats built on SQLite that mark their files with an application ID, as listed in
+/// SQLite's magic.txt
+const APPLICATIONS: &[(u32, &str)] = &[
+    (0x0f05_5111, "Fossil repository"),
+    (0x0f05_5112, "Fossil checkout"),
+    (0x0f05_5113, "Fossil global configuration"),
+    (0x4265_4462, "Bentley MicroStation"),
+    (0x4265_4c6e, "Bentley localization"),
+    (0x4750_3130, "GeoPackage 1.0"),
+    (0x4750_3131, "GeoPackage 1.1"),
+    (0x4750_4b47, "GeoPackage"),
+    (0x4d50_4258, "MBTiles"),
+];
+
+/// The 100-byte database header at the start of page 1
+#[derive(Debug, Clone)]
+pub struct DbHeader {
+    pub page_size: u32,
+    pub write_version: u8,
+    pub read_version: u8,
+    pub reserved_space: u8,
+    /// Fixed at 64, 32 and 32 in every database SQLite writes
+    pub max_payload_fraction: u8,
+    pub min_payload_fraction: u8,
+    pub leaf_payload_fraction: u8,
+    pub change_counter: u32,
+    /// Size of the database in pages; only trustworthy when `version_valid_for` matches
+    /// `change_counter`
+    pub page_count: u32,
+    pub first_freelist_trunk: u32,
+    pub freelist_count: u32,
+    pub schema_cookie: u32,
+    pub schema_format: u32,
+    pub default_cache_size: u32,
+    pub largest_root_page: u32,
+    pub text_encoding: u32,
+    pub user_version: u32,
+    pub incremental_vacuum: u32,
+    pub application_id: u32,
+    pub version_valid_for: u32,
+    pub sqlite_version: u32,
+}
+
+impl DbHeader {
+    pub fn parse(raw: &[u8]) -> Result<DbHeader> {
+        if raw.len() < HEADER_SIZE || &raw[..16] != MAGIC {
+            return Err(Error::NotADatabase);
+        }
+        let u32_at = |offset: usize| {
+            u32::from_be_bytes([
+                raw[offset],
+                raw[offset + 1],
+                raw[offset + 2],
+                raw[offset + 3],
+            ])
+        };
+
+        // The page size is stored at the 16th byte offset, using 2 bytes in big-endian order,
+        // with 1 standing in for 65536
+        let page_size = match u16::from_be_bytes([raw[16], raw[17]]) {
+            1 => 65536,
+            n => n as u32,
+        };
+        if !(512..=65536).contains(&page_size) || !page_size.is_power_of_two() {
+            return Err(Error::Corrupt(format!("invalid page size {}", page_size)));
+        }
+
+        let header = DbHeader {
+            page_size,
+            write_version: raw[18],
+            read_version: raw[19],
+            reserved_space: raw[20],
+            max_payload_fraction: raw[21],
+            min_payload_fraction: raw[22],
+            leaf_payload_fraction: raw[23],
+            change_counter: u32_at(24),
+            page_count: u32_at(28),
+            first_freelist_trunk: u32_at(32),
+            freelist_count: u32_at(36),
+            schema_cookie: u32_at(40),
+            schema_format: u32_at(44),
+            default_cache_size: u32_at(48),
+            largest_root_page: u32_at(52),
+            text_encoding: u32_at(56),
+            user_version: u32_at(60),
+            incremental_vacuum: u32_at(64),
+            application_id: u32_at(68),
+            version_valid_for: u32_at(92),
+            sqlite_version: u32_at(96),
+        };
+        if header.usable_size() < 480 {
+            return Err(Error::Corrupt(format!(
+                "reserved space of {} leaves too little usable space per page",
+                header.reserved_space
+            )));
+        }
+        Ok(header)
+    }
+
+    /// The name of the file format the application ID marks the database as, if it is one
+    /// of the well-known ones
+    pub fn application(&self) -> Option<&'static str> {
+        APPLICATIONS
+            .iter()
+            .find(|(id, _)| *id == self.application_id)
+            .map(|(_, name)| *name)
+    }
+
+    /// The in-header page count, if it was written by a version that keeps it up to date
+    pub fn valid_page_count(&self) -> Option<u32> {
+        (self.page_count != 0 && self.version_valid_for == self.change_counter)
+            .then_some(self.page_count)
+    }
+
+    /// The stricter checks SQLite makes before it trusts a header: known file format versions,
+    /// the fixed payload fractions, a known text encoding and schema format, and page and
+    /// freelist counts that fit in a file of `file_pages` pages
+    pub fn validate(&self, file_pages: u32) -> Result<()> {
+        let corrupt = |msg: String| Err(Error::Corrupt(msg));
+        if self.write_version > 2 || self.read_version > 2 {
+            return corrupt(format!(
+                "unknown file format version {}/{}",
+                self.write_version, self.read_version
+            ));
+        }
+        let fractions = (
+            self.max_payload_fraction,
+            self.min_payload_fraction,
+            self.leaf_payload_fraction,
+        );
+        if fractions != (64, 32, 32) {
+            return corrupt(format!(
+                "payload fractions are {}/{}/{} instead of 64/32/32",
+                fractions.0, fractions.1, fractions.2
+            ));
+        }
+        if self.text_encoding > 3 {
+            return corrupt(format!("unknown text encoding {}", self.text_encoding));
+        }
+        if self.schema_format > 4 {
+            return corrupt(format!("unknown schema format {}", self.schema_format));
+        }
+        if let Some(count) = self.valid_page_count().filter(|&count| count > file_pages) {
+            return corrupt(format!(
+                "header declares {} pages but the file holds {}",
+                count, file_pages
+            ));
+        }
+        let page_count = self.valid_page_count().unwrap_or(file_pages);
+        if self.first_freelist_trunk > page_count || self.freelist_count > page_count {
+            return corrupt(format!(
+                "freelist of {} pages starting at page {} doesn't fit in {} pages",
+                self.freelist_count, self.first_freelist_trunk, page_count
+            ));
+        }
+        Ok(())
+    }
+
+    /// Bytes of each page available to b-tree content, after the reserved region
+    pub fn usable_size(&self) -> u32 {
+        self.page_size - self.reserved_space as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The header of `shop.db`, a 17-page file with an empty freelist
+    fn shop() -> DbHeader {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/shop.db");
+        let bytes = std::fs::read(path).unwrap();
+        DbHeader::parse(&bytes[..HEADER_SIZE]).unwrap()
+    }
+
+    fn rejected(header: &DbHeader, file_pages: u32) -> String {
+        match header.validate(file_pages) {
+            Err(Error::Corrupt(msg)) => msg,
+            other => panic!("{:?} was accepted: {:?}", header, other),
+        }
+    }
+
+    #[test]
+    fn a_header_sqlite_wrote_is_valid() {
+        let header = shop();
+        assert_eq!(header.valid_page_count(), Some(17));
+        header.validate(17).unwrap();
+    }
+
+    #[test]
+    fn unknown_file_format_versions() {
+        let mut header = shop();
+        header.write_version = 3;
+        assert_eq!(rejected(&header, 17), "unknown file format version 3/1");
+        let mut header = shop();
+        header.read_version = 3;
+        assert_eq!(rejected(&header, 17), "unknown file format version 1/3");
+    }
+
+    #[test]
+    fn payload_fractions_are_fixed() {
+        for fractions in [(63, 32, 32), (64, 31, 32), (64, 32, 33)] {
+            let mut header = shop();
+            header.max_payload_fraction = fractions.0;
+            header.min_payload_fraction = fractions.1;
+            header.leaf_payload_fraction = fractions.2;
+            assert!(rejected(&header, 17).starts_with("payload fractions are"));
+        }
+    }
+
+    #[test]
+    fn unknown_text_encoding_and_schema_format() {
+        let mut header = shop();
+        header.text_encoding = 4;
+        assert_eq!(rejected(&header, 17), "unknown text encoding 4");
+        let mut header = shop();
+        header.schema_format = 5;
+        assert_eq!(rejected(&header, 17), "unknown schema format 5");
+    }
+
+    #[test]
+    fn page_count_must_fit_in_the_file() {
+        let header = shop();
+        assert_eq!(
+            rejected(&header, 16),
+            "header declares 17 pages but the file holds 16"
+        );
+        // a stale count isn't trusted, so it isn't held against the file either
+        let mut header = shop();
+        header.change_counter += 1;
+        header.validate(16).unwrap();
+    }
+
+    #[test]
+    fn freelist_must_fit_in_the_file() {
+        let mut header = shop();
+        header.first_freelist_trunk = 18;
+        assert_eq!(
+            rejected(&header, 17),
+            "freelist of 0 pages starting at page 18 doesn't fit in 17 pages"
+        );
+        let mut header = shop();
+        header.freelist_count = 18;
+        assert!(rejected(&header, 17).starts_with("freelist of 18 pages"));
+        // without a trusted page count the file size is the limit
+        header.change_counter += 1;
+        assert!(rejected(&header, 17).starts_with("freelist of 18 pages"));
+        header.validate(18).unwrap();
+    }
+}
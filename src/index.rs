@@ -0,0 +1,171 @@
+use crate::btree::Cell;
+use crate::db::{Database, TreeCells};
+use crate::record::decode_record_with;
+use crate::schema::{ObjectType, SchemaEntry};
+use crate::sql::create::{parse_create_index, CreateTable, IndexedColumn};
+use crate::table::{ResumeToken, TableInfo};
+use crate::value::{Collation, SortOrder};
+use crate::{Error, Result, Value};
+
+/// An index together with how its keys sort
+#[derive(Debug, Clone)]
+pub struct IndexInfo {
+    pub name: String,
+    pub table: String,
+    pub root_page: u32,
+    /// Sort order of each indexed term. The rowid that ends every key sorts ascending.
+    pub order: Vec<SortOrder>,
+}
+
+impl IndexInfo {
+    /// Reads an index's definition; `table` is the definition of the table it indexes, whose
+    /// column collations its terms inherit. Indexes SQLite creates for UNIQUE and PRIMARY KEY
+    /// constraints have no CREATE statement and are taken to sort by BINARY.
+    pub fn from_schema(entry: &SchemaEntry, table: &CreateTable) -> Result<IndexInfo> {
+        if entry.kind != ObjectType::Index || entry.root_page == 0 {
+            return Err(Error::Syntax(format!(
+                "{} is not a stored index",
+                entry.name
+            )));
+        }
+        let order = match &entry.sql {
+            Some(sql) => parse_create_index(sql)?
+                .indexed_columns()?
+                .iter()
+                .map(|term| sort_order(term, table))
+                .collect::<Result<_>>()?,
+            None => Vec::new(),
+        };
+        Ok(IndexInfo {
+            name: entry.name.clone(),
+            table: entry.tbl_name.clone(),
+            root_page: entry.root_page,
+            order,
+        })
+    }
+
+    /// Looks an index up by name, ignoring case as SQLite does
+    pub fn find(db: &Database, name: &str) -> Result<Option<IndexInfo>> {
+        let schema = db.schema()?;
+        let Some(entry) = schema
+            .iter()
+            .find(|e| e.kind == ObjectType::Index && e.name.eq_ignore_ascii_case(name))
+        else {
+            return Ok(None);
+        };
+        let table = schema
+            .iter()
+            .find(|e| e.kind == ObjectType::Table && e.name.eq_ignore_ascii_case(&entry.tbl_name))
+            .ok_or_else(|| Error::Corrupt(format!("index {} has no table", entry.name)))?;
+        IndexInfo::from_schema(entry, &TableInfo::load(db, table)?.definition).map(Some)
+    }
+
+    /// Scans the index in key order, yielding each key with the rowid at its end
+    pub fn entries<'a>(&'a self, db: &'a Database) -> IndexScan<'a> {
+        IndexScan {
+            db,
+            cells: db.tree_cells(self.root_page),
+            last: None,
+        }
+    }
+
+    /// Scans the index in key order from the first entry that starts with `key` or sorts after
+    /// it, reading only the pages on the path there. Entries that start with `key` come first,
+    /// so taking them while [`compare_keys`](crate::value::compare_keys) finds them equal looks
+    /// the key up.
+    pub fn entries_from_key<'a>(&'a self, db: &'a Database, key: &[Value]) -> IndexScan<'a> {
+        IndexScan {
+            db,
+            cells: db.index_cells_from(self.root_page, key.to_vec(), self.order.clone()),
+            last: None,
+        }
+    }
+
+    /// Scans the index in key order from just after the key of `token`, or from the start
+    /// without one. Only the pages on the path to the starting entry are read.
+    pub fn entries_from<'a>(
+        &'a self,
+        db: &'a Database,
+        token: Option<&ResumeToken>,
+    ) -> Result<IndexScan<'a>> {
+        let cells = match token {
+            None => db.tree_cells(self.root_page),
+            Some(ResumeToken::Key(key)) => {
+                db.index_cells_after(self.root_page, key.clone(), self.order.clone())
+            }
+            Some(ResumeToken::Rowid(_)) => {
+                return Err(Error::Syntax(format!(
+                    "index {} resumes from a key, not a rowid",
+                    self.name
+                )))
+            }
+        };
+        Ok(IndexScan {
+            db,
+            cells,
+            last: None,
+        })
+    }
+}
+
+/// How an index or key term sorts. Without a COLLATE of its own, a term takes its column's
+/// collation.
+pub(crate) fn sort_order(term: &IndexedColumn, table: &CreateTable) -> Result<SortOrder> {
+    let collate = term.collate.as_deref().or_else(|| {
+        term.name
+            .as_deref()
+            .and_then(|name| table.column(name))
+            .and_then(|column| column.collate.as_deref())
+    });
+    let collation = match collate {
+        Some(name) => Collation::from_name(name)
+            .ok_or_else(|| Error::Syntax(format!("unsupported collation {}", name)))?,
+        None => Collation::Binary,
+    };
+    Ok(SortOrder {
+        collation,
+        descending: term.descending,
+    })
+}
+
+pub struct IndexScan<'a> {
+    db: &'a Database,
+    cells: TreeCells<'a>,
+    /// Key of the entry returned last
+    last: Option<Vec<Value>>,
+}
+
+impl IndexScan<'_> {
+    /// A token for resuming after the entry returned last, or `None` before the first
+    pub fn resume_token(&self) -> Option<ResumeToken> {
+        self.last.clone().map(ResumeToken::Key)
+    }
+}
+
+impl Iterator for IndexScan<'_> {
+    type Item = Result<Vec<Value>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let found = match self.cells.next()? {
+            Ok(found) => found,
+            Err(e) => return Some(Err(e)),
+        };
+        let payload = match &found.cell {
+            Cell::IndexLeaf { payload } | Cell::IndexInterior { payload, .. } => payload,
+            _ => {
+                return Some(Err(Error::Corrupt(format!(
+                    "page {} is not an index b-tree page",
+                    found.page
+                ))))
+            }
+        };
+        let key = self
+            .db
+            .read_payload(payload)
+            .and_then(|record| decode_record_with(&record, self.db.utf8_policy()));
+        if let Ok(key) = &key {
+            self.last = Some(key.clone());
+        }
+        Some(key)
+    }
+}
@@ -0,0 +1,447 @@
+//! A small DEFLATE (RFC 1951) decoder with the zlib (RFC 1950) and gzip (RFC 1952) wrappers,
+//! enough to read the compressed content SQLite's own tools write and what applications
+//! commonly store in BLOBs. Output is always capped by the caller so a
+//! hostile stream can't balloon memory.
+
+use crate::{Error, Result};
+
+const MAX_BITS: usize = 15;
+
+// Base lengths and extra bits for length codes 257..285
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+// Base offsets and extra bits for distance codes 0..29
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+// Order in which code length code lengths are stored in a dynamic block header
+const CLEN_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+fn bad(msg: &str) -> Error {
+    Error::Decompress(msg.to_string())
+}
+
+/// Decompresses a zlib stream, verifying its Adler-32 trailer, producing at most `max_len`
+/// bytes
+pub fn zlib_decompress(data: &[u8], max_len: usize) -> Result<Vec<u8>> {
+    if data.len() < 6 {
+        return Err(bad("zlib stream is too short"));
+    }
+    let (cmf, flg) = (data[0], data[1]);
+    if cmf & 0x0f != 8 || (u16::from(cmf) << 8 | u16::from(flg)) % 31 != 0 {
+        return Err(bad("invalid zlib header"));
+    }
+    if flg & 0x20 != 0 {
+        return Err(bad("zlib preset dictionaries are not supported"));
+    }
+
+    let (out, used) = inflate_with_len(&data[2..], max_len)?;
+    let trailer = data
+        .get(2 + used..2 + used + 4)
+        .ok_or_else(|| bad("zlib stream is missing its checksum"))?;
+    let expected = u32::from_be_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+    if adler32(&out) != expected {
+        return Err(bad("zlib checksum mismatch"));
+    }
+    Ok(out)
+}
+
+/// Decompresses one gzip member, verifying its CRC-32 and length trailer, producing at most
+/// `max_len` bytes
+pub fn gzip_decompress(data: &[u8], max_len: usize) -> Result<Vec<u8>> {
+    const FHCRC: u8 = 0x02;
+    const FEXTRA: u8 = 0x04;
+    const FNAME: u8 = 0x08;
+    const FCOMMENT: u8 = 0x10;
+
+    if data.len() < 18 || data[0..2] != [0x1f, 0x8b] || data[2] != 8 {
+        return Err(bad("invalid gzip header"));
+    }
+    let flags = data[3];
+    // the fixed header is followed by whichever optional fields the flags announce
+    let mut pos = 10;
+    if flags & FEXTRA != 0 {
+        let len = data
+            .get(pos..pos + 2)
+            .ok_or_else(|| bad("gzip header is truncated"))?;
+        pos += 2 + usize::from(u16::from_le_bytes([len[0], len[1]]));
+    }
+    for flag in [FNAME, FCOMMENT] {
+        if flags & flag != 0 {
+            let end = data
+                .get(pos..)
+                .and_then(|rest| rest.iter().position(|&b| b == 0))
+                .ok_or_else(|| bad("gzip header is truncated"))?;
+            pos += end + 1;
+        }
+    }
+    if flags & FHCRC != 0 {
+        pos += 2;
+    }
+    let body = data
+        .get(pos..)
+        .ok_or_else(|| bad("gzip header is truncated"))?;
+
+    let (out, used) = inflate_with_len(body, max_len)?;
+    let trailer = body
+        .get(used..used + 8)
+        .ok_or_else(|| bad("gzip stream is missing its trailer"))?;
+    let expected_crc = u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+    let expected_len = u32::from_le_bytes([trailer[4], trailer[5], trailer[6], trailer[7]]);
+    if crc32(&out) != expected_crc || out.len() as u32 != expected_len {
+        return Err(bad("gzip checksum mismatch"));
+    }
+    Ok(out)
+}
+
+/// Decompresses a raw DEFLATE stream, producing at most `max_len` bytes
+pub fn inflate(data: &[u8], max_len: usize) -> Result<Vec<u8>> {
+    inflate_with_len(data, max_len).map(|(out, _)| out)
+}
+
+/// Returns the output along with the number of input bytes consumed
+fn inflate_with_len(data: &[u8], max_len: usize) -> Result<(Vec<u8>, usize)> {
+    let mut s = State {
+        input: data,
+        pos: 0,
+        bit_buf: 0,
+        bit_count: 0,
+        out: Vec::new(),
+        max_len,
+    };
+    loop {
+        let last = s.bits(1)? == 1;
+        match s.bits(2)? {
+            0 => s.stored()?,
+            1 => {
+                let (lengths, distances) = fixed_tables();
+                s.codes(&lengths, &distances)?;
+            }
+            2 => {
+                let (lengths, distances) = s.dynamic_tables()?;
+                s.codes(&lengths, &distances)?;
+            }
+            _ => return Err(bad("invalid deflate block type")),
+        }
+        if last {
+            break;
+        }
+    }
+    Ok((s.out, s.pos))
+}
+
+/// The CRC-32 of zlib and gzip (reflected polynomial 0xedb88320)
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xedb8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+pub fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += u32::from(byte);
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (b << 16) | a
+}
+
+/// Canonical Huffman code described by how many codes have each length and the symbols in
+/// code order
+struct Huffman {
+    counts: [u16; MAX_BITS + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Huffman> {
+        let mut counts = [0u16; MAX_BITS + 1];
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+        // reject over-subscribed codes; incomplete ones are allowed as zlib does
+        let mut left: i32 = 1;
+        for &count in &counts[1..] {
+            left = (left << 1) - i32::from(count);
+            if left < 0 {
+                return Err(bad("over-subscribed huffman code"));
+            }
+        }
+
+        let mut offsets = [0u16; MAX_BITS + 2];
+        for len in 1..=MAX_BITS {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+        let mut symbols = vec![0u16; lengths.len()];
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                symbols[offsets[len as usize] as usize] = symbol as u16;
+                offsets[len as usize] += 1;
+            }
+        }
+        Ok(Huffman { counts, symbols })
+    }
+}
+
+fn fixed_tables() -> (Huffman, Huffman) {
+    let mut lengths = [0u8; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+    // the fixed tables are valid by construction
+    let lengths = Huffman::new(&lengths).expect("fixed literal/length code");
+    let distances = Huffman::new(&[5u8; 30]).expect("fixed distance code");
+    (lengths, distances)
+}
+
+struct State<'a> {
+    input: &'a [u8],
+    pos: usize,
+    bit_buf: u64,
+    bit_count: u32,
+    out: Vec<u8>,
+    max_len: usize,
+}
+
+impl State<'_> {
+    fn bits(&mut self, need: u32) -> Result<u32> {
+        while self.bit_count < need {
+            let byte = *self
+                .input
+                .get(self.pos)
+                .ok_or_else(|| bad("deflate stream ended early"))?;
+            self.pos += 1;
+            self.bit_buf |= u64::from(byte) << self.bit_count;
+            self.bit_count += 8;
+        }
+        let value = (self.bit_buf & ((1u64 << need) - 1)) as u32;
+        self.bit_buf >>= need;
+        self.bit_count -= need;
+        Ok(value)
+    }
+
+    fn push(&mut self, byte: u8) -> Result<()> {
+        if self.out.len() >= self.max_len {
+            return Err(bad("decompressed data is larger than declared"));
+        }
+        self.out.push(byte);
+        Ok(())
+    }
+
+    fn stored(&mut self) -> Result<()> {
+        // stored blocks start on a byte boundary
+        self.bit_buf = 0;
+        self.bit_count = 0;
+        let header = self
+            .input
+            .get(self.pos..self.pos + 4)
+            .ok_or_else(|| bad("stored block header is truncated"))?;
+        let len = u16::from_le_bytes([header[0], header[1]]);
+        let nlen = u16::from_le_bytes([header[2], header[3]]);
+        if len != !nlen {
+            return Err(bad("stored block length check failed"));
+        }
+        self.pos += 4;
+        let bytes = self
+            .input
+            .get(self.pos..self.pos + len as usize)
+            .ok_or_else(|| bad("stored block is truncated"))?;
+        if self.out.len() + bytes.len() > self.max_len {
+            return Err(bad("decompressed data is larger than declared"));
+        }
+        self.out.extend_from_slice(bytes);
+        self.pos += len as usize;
+        Ok(())
+    }
+
+    fn decode(&mut self, h: &Huffman) -> Result<u16> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for len in 1..=MAX_BITS {
+            code |= self.bits(1)? as i32;
+            let count = i32::from(h.counts[len]);
+            if code - count < first {
+                return Ok(h.symbols[(index + (code - first)) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(bad("invalid huffman code"))
+    }
+
+    fn codes(&mut self, lengths: &Huffman, distances: &Huffman) -> Result<()> {
+        loop {
+            let symbol = self.decode(lengths)?;
+            match symbol {
+                0..=255 => self.push(symbol as u8)?,
+                256 => return Ok(()),
+                _ => {
+                    let i = (symbol - 257) as usize;
+                    if i >= LENGTH_BASE.len() {
+                        return Err(bad("invalid length code"));
+                    }
+                    let len = LENGTH_BASE[i] as usize + self.bits(LENGTH_EXTRA[i].into())? as usize;
+                    let d = self.decode(distances)? as usize;
+                    if d >= DIST_BASE.len() {
+                        return Err(bad("invalid distance code"));
+                    }
+                    let dist = DIST_BASE[d] as usize + self.bits(DIST_EXTRA[d].into())? as usize;
+                    if dist > self.out.len() {
+                        return Err(bad("distance reaches before the start of the output"));
+                    }
+                    for _ in 0..len {
+                        let byte = self.out[self.out.len() - dist];
+                        self.push(byte)?;
+                    }
+                }
+            }
+        }
+    }
+
+    fn dynamic_tables(&mut self) -> Result<(Huffman, Huffman)> {
+        let nlen = self.bits(5)? as usize + 257;
+        let ndist = self.bits(5)? as usize + 1;
+        let ncode = self.bits(4)? as usize + 4;
+        if nlen > 286 || ndist > 30 {
+            return Err(bad("too many length or distance codes"));
+        }
+
+        let mut clens = [0u8; 19];
+        for &i in &CLEN_ORDER[..ncode] {
+            clens[i] = self.bits(3)? as u8;
+        }
+        let clen_code = Huffman::new(&clens)?;
+
+        let mut lengths = vec![0u8; nlen + ndist];
+        let mut i = 0;
+        while i < lengths.len() {
+            let symbol = self.decode(&clen_code)?;
+            let (value, repeat) = match symbol {
+                0..=15 => (symbol as u8, 1),
+                16 => {
+                    let prev = *lengths[..i]
+                        .last()
+                        .ok_or_else(|| bad("repeat with no previous length"))?;
+                    (prev, 3 + self.bits(2)? as usize)
+                }
+                17 => (0, 3 + self.bits(3)? as usize),
+                _ => (0, 11 + self.bits(7)? as usize),
+            };
+            if i + repeat > lengths.len() {
+                return Err(bad("code lengths overrun"));
+            }
+            lengths[i..i + repeat].fill(value);
+            i += repeat;
+        }
+        if lengths[256] == 0 {
+            return Err(bad("missing end-of-block code"));
+        }
+        Ok((
+            Huffman::new(&lengths[..nlen])?,
+            Huffman::new(&lengths[nlen..])?,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HELLO_ZLIB: [u8; 16] = [
+        0x78, 0xda, 0xcb, 0x48, 0xcd, 0xc9, 0xc9, 0x57, 0xc8, 0x40, 0x90, 0x00, 0x3a, 0x2e, 0x06,
+        0x7d,
+    ];
+
+    fn fox() -> Vec<u8> {
+        (0..40)
+            .flat_map(|i| {
+                format!("the quick brown fox {} jumps over the lazy dog\n", i % 7).into_bytes()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn checksums() {
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+        assert_eq!(adler32(b""), 1);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn stored_block() {
+        let data = [
+            0x78, 0x01, 0x01, 0x03, 0x00, 0xfc, 0xff, 0x61, 0x62, 0x63, 0x02, 0x4d, 0x01, 0x27,
+        ];
+        assert_eq!(zlib_decompress(&data, 100).unwrap(), b"abc");
+    }
+
+    #[test]
+    fn fixed_huffman_block() {
+        assert_eq!(
+            zlib_decompress(&HELLO_ZLIB, 100).unwrap(),
+            b"hello hello hello"
+        );
+    }
+
+    #[test]
+    fn dynamic_huffman_block() {
+        let data = [
+            0xed, 0xd0, 0xb7, 0x15, 0x80, 0x30, 0x0c, 0x05, 0xc0, 0x9e, 0x29, 0xfe, 0x08, 0xe4,
+            0x7d, 0x08, 0xc6, 0x64, 0x81, 0x91, 0x49, 0xd3, 0xc3, 0x00, 0x34, 0x6a, 0x79, 0xea,
+            0xaf, 0x3a, 0x6e, 0x0d, 0x56, 0xdf, 0x55, 0x03, 0x4a, 0x47, 0xc7, 0x8c, 0x86, 0x4e,
+            0x84, 0xe8, 0xfd, 0xb4, 0x6c, 0xa0, 0xdd, 0x38, 0xf0, 0x0b, 0xc6, 0xe2, 0xbe, 0x50,
+            0x93, 0x0d, 0xf8, 0x43, 0x47, 0x22, 0x1d, 0x8b, 0x74, 0x22, 0xd2, 0xa9, 0x48, 0x67,
+            0x22, 0x9d, 0x8b, 0xb4, 0x0e, 0xea, 0xa0, 0x0e, 0xea, 0xa0, 0x0e, 0xfe, 0x6d, 0xf0,
+            0x01,
+        ];
+        assert_eq!(inflate(&data, 10_000).unwrap(), fox());
+    }
+
+    #[test]
+    fn gzip_member() {
+        let data = [
+            0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0xcb, 0x48, 0xcd, 0xc9,
+            0xc9, 0x57, 0x48, 0xaf, 0xca, 0x2c, 0x00, 0x00, 0x19, 0x6a, 0xd2, 0xdf, 0x0a, 0x00,
+            0x00, 0x00,
+        ];
+        assert_eq!(gzip_decompress(&data, 100).unwrap(), b"hello gzip");
+    }
+
+    #[test]
+    fn output_is_capped() {
+        assert!(zlib_decompress(&HELLO_ZLIB, 5).is_err());
+    }
+
+    #[test]
+    fn damaged_streams_are_errors() {
+        let mut data = HELLO_ZLIB;
+        data[15] ^= 1;
+        assert!(zlib_decompress(&data, 100).is_err());
+        assert!(zlib_decompress(&HELLO_ZLIB[..10], 100).is_err());
+        // block type 3 is reserved
+        assert!(inflate(&[0x07], 100).is_err());
+    }
+}
@@ -0,0 +1,47 @@
+use sqliter::format::format_real_exact;
+use sqliter::Value;
+use std::fmt::Write;
+
+/// A JSON string literal
+pub fn string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// A column value as JSON. BLOBs have no JSON equivalent, so they become `{"blob": "<hex>"}`.
+pub fn value(value: &Value) -> String {
+    match value {
+        Value::Null => "null".to_string(),
+        Value::Integer(i) => i.to_string(),
+        Value::Real(f) if f.is_finite() => format_real_exact(*f),
+        Value::Real(_) => "null".to_string(),
+        Value::Text(s) => string(s),
+        // JSON strings are Unicode, so invalid bytes can only be replaced
+        Value::RawText(b) => string(&String::from_utf8_lossy(b)),
+        Value::Blob(b) => {
+            let hex: String = b.iter().map(|b| format!("{:02x}", b)).collect();
+            format!("{{\"blob\":\"{}\"}}", hex)
+        }
+    }
+}
+
+/// A JSON array of values
+pub fn array(values: &[Value]) -> String {
+    let items: Vec<String> = values.iter().map(value).collect();
+    format!("[{}]", items.join(","))
+}
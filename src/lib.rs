@@ -0,0 +1,37 @@
+pub mod btree;
+pub mod check;
+pub mod codec;
+pub mod crosscheck;
+pub mod db;
+pub mod dbstat;
+pub mod deps;
+pub mod error;
+pub mod estimate;
+pub mod format;
+pub mod fragmentation;
+pub mod fts5;
+pub mod header;
+pub mod index;
+pub mod inflate;
+pub mod lint;
+pub mod pager;
+pub mod pages;
+pub mod pipeline;
+pub mod record;
+pub mod rtree;
+pub mod schema;
+pub mod schemadiff;
+pub mod settings;
+pub mod sha256;
+pub mod sql;
+pub mod sqlar;
+pub mod stats;
+pub mod table;
+pub mod tiles;
+pub mod value;
+pub mod varint;
+pub mod wal;
+
+pub use db::Database;
+pub use error::{Error, Result};
+pub use value::Value;
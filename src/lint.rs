@@ -0,0 +1,260 @@
+use crate::db::Database;
+use crate::schema::{ObjectType, SchemaEntry};
+use crate::sql::create::{parse_create_index, Affinity, CreateTable, IndexedColumn};
+use crate::table::{Field, TableInfo};
+use crate::{Result, Value};
+
+/// Tables with more columns than this are reported as wide
+pub const WIDE_TABLE_COLUMNS: usize = 64;
+
+/// Rows read from each table when looking at what its TEXT columns hold
+pub const SAMPLE_ROWS: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Issue {
+    /// A rowid table with no declared PRIMARY KEY
+    NoPrimaryKey,
+    /// An index whose key is a prefix of another index or key on the same table
+    RedundantIndex,
+    /// An index column collated differently from the column itself
+    CollationMismatch,
+    /// A TEXT column whose values are mostly numbers
+    NumericText,
+    WideTable,
+}
+
+impl Issue {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Issue::NoPrimaryKey => "no-primary-key",
+            Issue::RedundantIndex => "redundant-index",
+            Issue::CollationMismatch => "collation-mismatch",
+            Issue::NumericText => "numeric-text",
+            Issue::WideTable => "wide-table",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Finding {
+    /// The table or index the issue is about
+    pub object: String,
+    pub issue: Issue,
+    pub detail: String,
+}
+
+/// A key of a table: an index, or a PRIMARY KEY or UNIQUE constraint
+struct Key {
+    name: String,
+    unique: bool,
+    partial: Option<String>,
+    /// Lowercased column name and collation of each term; `None` for expressions
+    columns: Vec<Option<(String, String)>>,
+}
+
+/// Reviews the schema for common design problems. TEXT columns are judged from the first
+/// [`SAMPLE_ROWS`] rows of each table.
+pub fn lint(db: &Database) -> Result<Vec<Finding>> {
+    let schema = db.schema()?;
+    let mut findings = Vec::new();
+    for entry in &schema {
+        let user_table =
+            entry.kind == ObjectType::Table && entry.root_page != 0 && !entry.is_internal();
+        if !user_table {
+            continue;
+        }
+        let table = TableInfo::load(db, entry)?;
+        if table.inferred {
+            // without a definition there is no design to review
+            continue;
+        }
+        lint_table(db, &table, &mut findings)?;
+        lint_indexes(&schema, &table.definition, &mut findings)?;
+    }
+    Ok(findings)
+}
+
+fn lint_table(db: &Database, table: &TableInfo, findings: &mut Vec<Finding>) -> Result<()> {
+    let definition = &table.definition;
+    let mut finding = |issue, detail| {
+        findings.push(Finding {
+            object: table.name.clone(),
+            issue,
+            detail,
+        })
+    };
+    let has_key =
+        !definition.primary_key.is_empty() || definition.columns.iter().any(|c| c.primary_key);
+    if !has_key {
+        finding(
+            Issue::NoPrimaryKey,
+            "rows are identified only by a rowid that VACUUM may renumber".to_string(),
+        );
+    }
+    if definition.columns.len() > WIDE_TABLE_COLUMNS {
+        finding(
+            Issue::WideTable,
+            format!(
+                "{} columns, more than {}",
+                definition.columns.len(),
+                WIDE_TABLE_COLUMNS
+            ),
+        );
+    }
+
+    let text_columns: Vec<usize> = (0..definition.columns.len())
+        .filter(|&i| definition.columns[i].affinity() == Affinity::Text)
+        .collect();
+    if text_columns.is_empty() {
+        return Ok(());
+    }
+    // per TEXT column: non-NULL values seen, and how many of them read as numbers
+    let mut counts = vec![(0usize, 0usize); text_columns.len()];
+    for row in table.project(db, &text_columns)?.take(SAMPLE_ROWS) {
+        let row = row?;
+        for (count, field) in counts.iter_mut().zip(&row.fields) {
+            match field {
+                Field::Value(Value::Null) => {}
+                Field::Value(Value::Text(s)) => {
+                    count.0 += 1;
+                    count.1 += looks_numeric(s) as usize;
+                }
+                _ => count.0 += 1,
+            }
+        }
+    }
+    for ((seen, numeric), &i) in counts.into_iter().zip(&text_columns) {
+        if numeric > 0 && numeric * 2 >= seen {
+            finding(
+                Issue::NumericText,
+                format!(
+                    "column {} is {} but {} of {} sampled values are numbers",
+                    definition.columns[i].name, definition.columns[i].type_name, numeric, seen
+                ),
+            );
+        }
+    }
+    Ok(())
+}
+
+fn looks_numeric(s: &str) -> bool {
+    let s = s.trim();
+    !s.is_empty() && (s.parse::<i64>().is_ok() || s.parse::<f64>().is_ok_and(f64::is_finite))
+}
+
+fn lint_indexes(
+    schema: &[SchemaEntry],
+    table: &CreateTable,
+    findings: &mut Vec<Finding>,
+) -> Result<()> {
+    let collation_of = |column: &str| {
+        table
+            .column(column)
+            .and_then(|c| c.collate.clone())
+            .unwrap_or_else(|| "BINARY".to_string())
+    };
+
+    // keys the table's own constraints create, which explicit indexes may duplicate
+    let mut keys = Vec::new();
+    let implicit_pk = if table.primary_key.is_empty() {
+        table
+            .columns
+            .iter()
+            .filter(|c| c.primary_key)
+            .map(|c| c.name.clone())
+            .collect()
+    } else {
+        table.primary_key.clone()
+    };
+    if !implicit_pk.is_empty() {
+        let name = match table.rowid_alias() {
+            Some(_) => "the rowid",
+            None => "the PRIMARY KEY",
+        };
+        keys.push(Key {
+            name: name.to_string(),
+            unique: true,
+            partial: None,
+            columns: implicit_pk
+                .iter()
+                .map(|c| Some((c.to_lowercase(), collation_of(c).to_lowercase())))
+                .collect(),
+        });
+    }
+    for column in table.columns.iter().filter(|c| c.unique) {
+        keys.push(Key {
+            name: format!("the UNIQUE constraint on {}", column.name),
+            unique: true,
+            partial: None,
+            columns: vec![Some((
+                column.name.to_lowercase(),
+                collation_of(&column.name).to_lowercase(),
+            ))],
+        });
+    }
+    let implicit = keys.len();
+
+    for entry in schema {
+        let explicit_index = entry.kind == ObjectType::Index
+            && entry.tbl_name.eq_ignore_ascii_case(&table.name)
+            && entry.sql.is_some();
+        if !explicit_index {
+            continue;
+        }
+        let index = parse_create_index(entry.sql.as_deref().unwrap_or_default())?;
+        let mut columns = Vec::new();
+        for IndexedColumn { name, collate, .. } in index.indexed_columns()? {
+            let Some(name) = name else {
+                columns.push(None);
+                continue;
+            };
+            let declared = collation_of(&name);
+            let collation = collate.unwrap_or_else(|| declared.clone());
+            if !collation.eq_ignore_ascii_case(&declared) {
+                findings.push(Finding {
+                    object: index.name.clone(),
+                    issue: Issue::CollationMismatch,
+                    detail: format!(
+                        "{} is indexed with COLLATE {} but compares with {}, so only queries \
+                         that repeat the COLLATE can use it",
+                        name, collation, declared
+                    ),
+                });
+            }
+            columns.push(Some((name.to_lowercase(), collation.to_lowercase())));
+        }
+        keys.push(Key {
+            name: index.name,
+            unique: index.unique,
+            partial: index.where_clause,
+            columns,
+        });
+    }
+    for (i, key) in keys.iter().enumerate().skip(implicit) {
+        let covered_by = keys.iter().enumerate().find(|&(j, other)| {
+            if i == j
+                || other.partial != key.partial
+                || other.columns.len() < key.columns.len()
+                || !key.columns.iter().all(Option::is_some)
+                || other.columns[..key.columns.len()] != key.columns[..]
+            {
+                return false;
+            }
+            if other.columns.len() == key.columns.len() {
+                // of two identical keys, report the one that enforces less, or the later one
+                (other.unique && !key.unique) || (other.unique == key.unique && j < i)
+            } else {
+                // a UNIQUE index enforces something a longer key doesn't
+                !key.unique
+            }
+        });
+        if let Some((_, other)) = covered_by {
+            findings.push(Finding {
+                object: key.name.clone(),
+                issue: Issue::RedundantIndex,
+                detail: format!("its key is covered by {}", other.name),
+            });
+        }
+    }
+    Ok(())
+}
@@ -1,238 +1,2213 @@
-use anyhow::{bail, Result};
+mod clone;
+mod codegen;
+mod erd;
+mod failure;
+mod json;
+mod output;
+mod pagemap;
+mod script;
+
+use anyhow::{bail, Context, Result};
+use clone::clone_database;
+use codegen::Lang;
+use failure::{ErrorFormat, NotFound, Partial, Tracked};
+use output::{Format, OutputOptions, Redirect, RowStream, Sink, Table};
+use script::{parse_script, split_arguments, CommandKind};
+use sqliter::btree::Cell;
+use sqliter::check::quick_check;
+use sqliter::codec::{Codec, ColumnDecoders};
+use sqliter::crosscheck::crosscheck;
+use sqliter::db::DividerCacheStats;
+use sqliter::dbstat::{dbstat, DBSTAT_COLUMNS};
+use sqliter::deps::dependents;
+use sqliter::estimate::{estimate_rows, DEFAULT_SAMPLES};
+use sqliter::fragmentation::{fragmentation, DEFAULT_THRESHOLD};
+use sqliter::fts5::Fts5;
+use sqliter::lint::lint;
+use sqliter::pipeline::{pipelined, DEFAULT_DEPTH};
+use sqliter::record::{decode_record_with, Utf8Policy};
+use sqliter::rtree::{BoxMatch, RTree};
+use sqliter::schema::{ObjectType, SchemaEntry};
+use sqliter::schemadiff::{diff_schemas, Change};
+use sqliter::settings::Settings;
+use sqliter::sha256::to_hex;
+use sqliter::sql::create::Generated;
+use sqliter::sql::filter::{parse_filter, Filter};
+use sqliter::sql::select::{parse_select, ResultColumn, Select};
+use sqliter::sql::{literal, quote_identifier, HexWriter};
+use sqliter::sqlar::Sqlar;
+use sqliter::stats::{column_stats, DEFAULT_BUCKETS, DEFAULT_TOP};
+use sqliter::table::{Field, TableInfo};
+use sqliter::tiles::{self, Tileset};
+use sqliter::wal::Wal;
+use sqliter::{Database, Value};
+use std::ffi::{OsStr, OsString};
 use std::fs::File;
 use std::io::prelude::*;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Global flags, given before the database path
+#[derive(Debug, Clone, Default)]
+struct Options {
+    output: OutputOptions,
+    /// `--format` was given, so commands with a format of their own use it instead
+    format_given: bool,
+    settings: Settings,
+    /// Open with `Database::open_untrusted`
+    untrusted: bool,
+    /// Where the database starts in the file, for one embedded in another file
+    offset: Option<u64>,
+    /// How a failure is reported on stderr
+    errors: ErrorFormat,
+    /// Codecs for columns whose BLOBs the application compressed, applied as rows are output
+    decoders: ColumnDecoders,
+    /// With `--stats`, the cache statistics of every database opened, added up as each one is
+    /// closed and reported once the command finishes
+    stats: Option<CacheTotals>,
+}
+
+/// Divider cache hits and misses added up per database file
+type CacheTotals = Arc<Mutex<Vec<(PathBuf, u64, u64)>>>;
+
+/// A database a command opened. With `--stats` its cache statistics go into the totals when
+/// it is dropped, so handles that are replaced, as `.tail-follow` does on every change, don't
+/// stay open until the end.
+struct Opened {
+    db: Database,
+    totals: Option<CacheTotals>,
+}
+
+impl Deref for Opened {
+    type Target = Database;
+
+    fn deref(&self) -> &Database {
+        &self.db
+    }
+}
+
+impl Drop for Opened {
+    fn drop(&mut self) {
+        let Some(totals) = &self.totals else {
+            return;
+        };
+        let stats = self.db.divider_cache_stats();
+        let mut totals = totals
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        // a script may open the same file more than once
+        match totals.iter_mut().find(|(path, ..)| path == self.db.path()) {
+            Some((_, hits, misses)) => {
+                *hits += stats.hits;
+                *misses += stats.misses;
+            }
+            None => totals.push((self.db.path().to_path_buf(), stats.hits, stats.misses)),
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    // Parse arguments: [options] <database path> <command>
+    let (options, raw) = match parse_args(std::env::args_os().collect()) {
+        Ok(parsed) => parsed,
+        Err(e) => return ExitCode::from(failure::report(&e, ErrorFormat::Text)),
+    };
+    let errors = options.errors;
+    let stats = options.stats.clone();
+    let result = run(options, raw);
+    if let Some(totals) = stats {
+        report_stats(
+            &totals
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner()),
+        );
+    }
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => ExitCode::from(failure::report(&e, errors)),
+    }
+}
+
+/// Writes to stderr how often seeks in each database found interior pages already decoded
+fn report_stats(totals: &[(PathBuf, u64, u64)]) {
+    for &(ref path, hits, misses) in totals {
+        let rate = DividerCacheStats { hits, misses }.hit_rate();
+        eprintln!(
+            "{}: divider cache {} hits, {} misses ({:.1}% hit rate)",
+            path.display(),
+            hits,
+            misses,
+            rate * 100.0
+        );
+    }
+}
 
-fn main() -> Result<()> {
-    // Parse arguments
-    let args = std::env::args().collect::<Vec<_>>();
-    match args.len() {
+fn run(options: Options, raw: Vec<OsString>) -> Result<()> {
+    match raw.len() {
         0 | 1 => bail!("Missing <database path> and <command>"),
         2 => bail!("Missing <command>"),
         _ => {}
     }
 
-    // Parse command and act accordingly
+    // Paths are used exactly as given, so they name whatever the platform's file names hold;
+    // every other argument is text
+    let path = PathBuf::from(&raw[1]);
+    let args: Vec<String> = raw
+        .iter()
+        .map(|arg| arg.to_string_lossy().into_owned())
+        .collect();
     let command = &args[2];
+    for (i, arg) in raw.iter().enumerate().skip(2) {
+        let file_name = match command.as_str() {
+            ".schemadiff" | ".crosscheck" => i == 3,
+            // the one argument that isn't a flag is the file
+            ".clone" | ".read" => true,
+            ".dump" | ".rawdump" | ".pagemap" | ".erd" => raw[i - 1] == "--out",
+            ".ar" => raw[i - 1] == "--dir",
+            _ => false,
+        };
+        if arg.to_str().is_none() && !file_name {
+            bail!("Argument is not valid UTF-8: {}", args[i]);
+        }
+    }
+
+    // Parse command and act accordingly
     match command.as_str() {
         ".dbinfo" => {
-            let mut file = File::open(&args[1])?;
+            let db = open(&path, &options)?;
+            let base = db.offset();
+            let mut file = File::open(&path)?;
+            file.seek(std::io::SeekFrom::Start(base))?;
             let mut header = [0; 108];
             file.read_exact(&mut header)?;
 
             // The page size is stored at the 16th byte offset, using 2 bytes in big-endian order
-            #[allow(unused_variables)]
             let page_size = u16::from_be_bytes([header[16], header[17]]);
             let table_count = u16::from_be_bytes([header[103], header[104]]);
 
-            // You can use print statements as follows for debugging, they'll be visible when running tests.
-            eprintln!("Logs from your program will appear here!");
-
-            // Uncomment this block to pass the first stage
-            println!("database page size: {}", page_size);
-            println!("number of tables: {}", table_count);
+            let mut out = options.output.sink.lock();
+            writeln!(out, "database page size: {}", page_size)?;
+            writeln!(out, "number of tables: {}", table_count)?;
+            let application_id = db.header().application_id;
+            match db.header().application() {
+                Some(name) => writeln!(out, "application id: {} ({})", application_id, name)?,
+                None => writeln!(out, "application id: {}", application_id)?,
+            }
+            let commands = format_commands(application_id);
+            if !commands.is_empty() {
+                writeln!(out, "format commands: {}", commands.join(", "))?;
+            }
         }
         ".tables" => {
-            let mut file = File::open(&args[1])?;
-            let mut header = [0; 108];
-            file.read_exact(&mut header)?;
-            let table_count = u16::from_be_bytes([header[103], header[104]]);
+            // like sqlite3, internal tables only show up when asked for
+            let mut show_internal = false;
+            for arg in &args[3..] {
+                match arg.as_str() {
+                    "--show-internal" => show_internal = true,
+                    _ => bail!("Unknown .tables argument: {}", arg),
+                }
+            }
+
+            let db = open(&path, &options)?;
+            let mut out = options.output.sink.lock();
+            for entry in db.schema()? {
+                if entry.kind != ObjectType::Table || (entry.is_internal() && !show_internal) {
+                    continue;
+                }
+                writeln!(out, "{} ", entry.name)?;
+            }
+        }
+        ".walinfo" => {
+            let wal = Wal::open(&Wal::path_for(&path))?;
+            let header = &wal.header;
+            let mut out = options.output.sink.lock();
 
-            // the cells of the first page contain information on the tables
-            // each cell has a row
-            // each row has a tbl_name value which has the table's name
-            // the cell pointer array starts right after the header
-            let mut cell_pointer_array: Vec<u8> = vec![0; table_count as usize * 2];
-            file.read_exact(&mut cell_pointer_array)?;
-
-            for i in (0..table_count * 2).step_by(2) {
-                let cell_pointer = u16::from_be_bytes([
-                    cell_pointer_array[i as usize],
-                    cell_pointer_array[i as usize + 1],
+            writeln!(out, "wal file: {}", wal.path.display())?;
+            writeln!(out, "format version: {}", header.format_version)?;
+            writeln!(out, "page size: {}", header.page_size)?;
+            writeln!(out, "checkpoint sequence: {}", header.checkpoint_seq)?;
+            writeln!(out, "salt-1: 0x{:08x}", header.salt.0)?;
+            writeln!(out, "salt-2: 0x{:08x}", header.salt.1)?;
+            writeln!(
+                out,
+                "checksum byte order: {}",
+                if header.big_endian_checksums() {
+                    "big-endian"
+                } else {
+                    "little-endian"
+                }
+            )?;
+            writeln!(out, "header checksum: {}", ok_or_bad(header.checksum_valid))?;
+            writeln!(out, "frames: {}", wal.frames.len())?;
+            writeln!(out, "committed frames: {}", wal.committed_frames().count())?;
+            writeln!(out, "transactions: {}", wal.transaction_count())?;
+            match wal.last_commit() {
+                Some(frame) => {
+                    writeln!(out, "last commit frame: {}", frame.index)?;
+                    writeln!(
+                        out,
+                        "database size after last commit: {} pages",
+                        frame.db_size
+                    )?;
+                }
+                None => writeln!(out, "last commit frame: none")?,
+            }
+            let live_pages = wal.committed_frames().filter(|f| !f.superseded).count();
+            writeln!(out, "distinct pages in log: {}", live_pages)?;
+        }
+        ".walframes" => {
+            let wal = Wal::open(&Wal::path_for(&path))?;
+
+            let mut table = Table::new(&[
+                "frame",
+                "page",
+                "txn",
+                "commit",
+                "salt",
+                "checksum",
+                "status",
+                "superseded",
+            ]);
+            for frame in &wal.frames {
+                let status = if frame.committed {
+                    "committed"
+                } else if frame.salt_valid && frame.checksum_valid {
+                    "uncommitted"
+                } else {
+                    "invalid"
+                };
+                table.push(vec![
+                    frame.index.into(),
+                    frame.page_number.into(),
+                    frame.txn.into(),
+                    frame.is_commit().then_some(frame.db_size).into(),
+                    ok_or_bad(frame.salt_valid).into(),
+                    ok_or_bad(frame.checksum_valid).into(),
+                    status.into(),
+                    if frame.superseded { "yes" } else { "no" }.into(),
                 ]);
-                // On page 1, cell pointers are offsets from the start of the page (file offset 0)
-                // Do NOT add 100 here. The 100-byte database header is accounted for in the offsets.
-                file.seek(std::io::SeekFrom::Start(cell_pointer as u64))?;
+            }
+            table.print(&options.output)?;
+        }
+        ".quickcheck" => {
+            let db = open(&path, &options)?;
+            let problems = quick_check(&db);
+            let mut out = options.output.sink.lock();
+            if problems.is_empty() {
+                writeln!(out, "ok")?;
+            }
+            for problem in &problems {
+                writeln!(out, "{}", problem)?;
+            }
+        }
+        ".dbstat" => {
+            // optional tree name, as in `SELECT * FROM dbstat WHERE name = ?`
+            let name = args.get(3);
+            let db = open(&path, &options)?;
+            let mut table = Table::new(&DBSTAT_COLUMNS);
+            for row in dbstat(&db)? {
+                if name.map_or(true, |name| *name == row.name) {
+                    table.push(row.values());
+                }
+            }
+            table.print(&options.output)?;
+        }
+        ".estimate-rows" => {
+            let mut name: Option<&String> = None;
+            let mut samples = DEFAULT_SAMPLES;
+            let mut seed: Option<u64> = None;
+            let mut rest = args[3..].iter();
+            while let Some(arg) = rest.next() {
+                match arg.as_str() {
+                    "--samples" => {
+                        samples = rest
+                            .next()
+                            .context("Missing value for --samples")?
+                            .parse()
+                            .context("--samples expects a number")?
+                    }
+                    "--seed" => {
+                        seed = Some(
+                            rest.next()
+                                .context("Missing value for --seed")?
+                                .parse()
+                                .context("--seed expects a number")?,
+                        )
+                    }
+                    _ if name.is_none() => name = Some(arg),
+                    _ => bail!("Unknown .estimate-rows argument: {}", arg),
+                }
+            }
+            let name = name.context("Usage: .estimate-rows <table> [--samples N] [--seed N]")?;
+            // a different sample each run unless a seed is given
+            let seed = seed.unwrap_or_else(|| {
+                std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map_or(0, |d| d.as_nanos() as u64)
+            });
 
-                let mut payload_size: u64 = 0;
-                let mut row_id: u64 = 0;
-                let mut bytes_read = 0;
-                // read payload size
-                loop {
-                    let mut b = [0u8; 1];
-                    file.read_exact(&mut b)?;
-                    payload_size = (payload_size << 7) | u64::from(b[0] & 0x7F);
-                    bytes_read += 1;
-                    if (b[0] & 0x80) == 0 {
-                        break;
+            let db = open(&path, &options)?;
+            let entry = db
+                .schema()?
+                .into_iter()
+                .find(|e| e.root_page != 0 && e.name.eq_ignore_ascii_case(name))
+                .ok_or_else(|| NotFound(format!("No such table or index: {}", name)))?;
+            let estimate = estimate_rows(&db, entry.root_page, samples, seed)?;
+            let mut table = Table::new(&[
+                "name",
+                "estimate",
+                "low",
+                "high",
+                "exact",
+                "samples",
+                "depth",
+                "pages_read",
+            ]);
+            table.push(vec![
+                entry.name.as_str().into(),
+                Value::Integer(estimate.estimate.round() as i64),
+                Value::Integer(estimate.low.floor() as i64),
+                Value::Integer(estimate.high.ceil() as i64),
+                if estimate.exact { "yes" } else { "no" }.into(),
+                estimate.samples.into(),
+                estimate.depth.into(),
+                estimate.pages_read.into(),
+            ]);
+            table.print(&options.output)?;
+        }
+        ".stats" => {
+            const USAGE: &str = "Usage: .stats histogram <table> <column> [--buckets N] [--top N]";
+            if args.get(3).map(String::as_str) != Some("histogram") {
+                bail!(USAGE);
+            }
+            let mut names: Vec<&String> = Vec::new();
+            let mut buckets = DEFAULT_BUCKETS;
+            let mut top = DEFAULT_TOP;
+            let mut rest = args[4..].iter();
+            while let Some(arg) = rest.next() {
+                match arg.as_str() {
+                    "--buckets" => {
+                        buckets = rest
+                            .next()
+                            .context("Missing value for --buckets")?
+                            .parse()
+                            .context("--buckets expects a number")?
                     }
+                    "--top" => {
+                        top = rest
+                            .next()
+                            .context("Missing value for --top")?
+                            .parse()
+                            .context("--top expects a number")?
+                    }
+                    _ if names.len() < 2 => names.push(arg),
+                    _ => bail!("Unknown .stats argument: {}", arg),
+                }
+            }
+            let [name, column] = names[..] else {
+                bail!(USAGE);
+            };
 
-                    if bytes_read == 8 {
-                        file.read_exact(&mut b)?;
-                        payload_size = (payload_size << 8) | u64::from(b[0]);
-                        break;
+            let db = open(&path, &options)?;
+            let info = TableInfo::find(&db, name)?
+                .ok_or_else(|| NotFound(format!("No such table: {}", name)))?;
+            let index = info
+                .columns()
+                .iter()
+                .position(|c| c.name.eq_ignore_ascii_case(column))
+                .ok_or_else(|| NotFound(format!("No such column: {}.{}", info.name, column)))?;
+            let stats = column_stats(&db, &info, index, buckets, top)?;
+
+            let mut table = Table::new(&["rows", "nulls", "distinct", "min", "max"]);
+            table.push(vec![
+                Value::Integer(stats.rows as i64),
+                Value::Integer(stats.nulls as i64),
+                Value::Integer(stats.distinct as i64),
+                stats.min.unwrap_or(Value::Null),
+                stats.max.unwrap_or(Value::Null),
+            ]);
+            table.print(&options.output)?;
+            if !stats.buckets.is_empty() {
+                writeln!(options.output.sink.lock())?;
+                let mut table = Table::new(&["low", "high", "count"]);
+                for bucket in stats.buckets {
+                    table.push(vec![
+                        Value::Real(bucket.low),
+                        Value::Real(bucket.high),
+                        Value::Integer(bucket.count as i64),
+                    ]);
+                }
+                table.print(&options.output)?;
+            }
+            if !stats.top.is_empty() {
+                // counts of values that displaced others are upper bounds; at_least is exact
+                // for the rest
+                writeln!(options.output.sink.lock())?;
+                let mut table = Table::new(&["value", "count", "at_least"]);
+                for top in stats.top {
+                    table.push(vec![
+                        top.value,
+                        Value::Integer(top.count as i64),
+                        Value::Integer((top.count - top.error) as i64),
+                    ]);
+                }
+                table.print(&options.output)?;
+            }
+        }
+        ".tail-follow" => {
+            let mut name: Option<&String> = None;
+            let mut since = 0i64;
+            let mut interval = 1.0f64;
+            let mut rest = args[3..].iter();
+            while let Some(arg) = rest.next() {
+                match arg.as_str() {
+                    "--since-rowid" => {
+                        since = rest
+                            .next()
+                            .context("Missing value for --since-rowid")?
+                            .parse()
+                            .context("--since-rowid expects a rowid")?
+                    }
+                    "--interval" => {
+                        interval = rest
+                            .next()
+                            .context("Missing value for --interval")?
+                            .parse()
+                            .ok()
+                            .filter(|&secs: &f64| secs > 0.0 && secs.is_finite())
+                            .context("--interval expects a number of seconds")?
                     }
+                    _ if name.is_none() => name = Some(arg),
+                    _ => bail!("Unknown .tail-follow argument: {}", arg),
                 }
+            }
+            let name =
+                name.context("Usage: .tail-follow <table> [--since-rowid N] [--interval SECS]")?;
 
-                bytes_read = 0;
-                // read row id
-                loop {
-                    let mut b = [0u8; 1];
-                    file.read_exact(&mut b)?;
-                    row_id = (row_id << 7) | u64::from(b[0] & 0x7F);
-                    bytes_read += 1;
-                    if (b[0] & 0x80) == 0 {
-                        break;
+            // Every commit rewrites the database file or appends to its WAL, so the files are
+            // only re-read when one of them has changed since the last poll
+            let mut seen = None;
+            loop {
+                let marker = change_marker(&path)?;
+                if seen.as_ref() != Some(&marker) {
+                    seen = Some(marker);
+                    let db = open(&path, &options)?;
+                    let info = TableInfo::find(&db, name)?
+                        .ok_or_else(|| NotFound(format!("No such table: {}", name)))?;
+                    if info.definition.without_rowid {
+                        bail!(
+                            "{} is a WITHOUT ROWID table, so rows have no order of insertion",
+                            info.name
+                        );
                     }
+                    let columns: Vec<&str> =
+                        info.columns().iter().map(|c| c.name.as_str()).collect();
+                    let codecs = options.decoders.for_table(&info);
+                    let mut table = Table::new(&columns);
+                    for row in info.rows_after(&db, since) {
+                        let row = row?;
+                        since = row.rowid.unwrap_or(since);
+                        table.push(decode_row(&info, &codecs, row.rowid, row.values)?);
+                    }
+                    if !table.is_empty() {
+                        table.print(&options.output)?;
+                    }
+                }
+                std::thread::sleep(std::time::Duration::from_secs_f64(interval));
+            }
+        }
+        ".tablehash" => {
+            let mut name: Option<&String> = None;
+            let mut per_row = false;
+            for arg in &args[3..] {
+                match arg.as_str() {
+                    "--rows" => per_row = true,
+                    _ if name.is_none() => name = Some(arg),
+                    _ => bail!("Unknown .tablehash argument: {}", arg),
+                }
+            }
+            let name = name.context("Usage: .tablehash <table> [--rows]")?;
+
+            let db = open(&path, &options)?;
+            let info = TableInfo::find(&db, name)?
+                .ok_or_else(|| NotFound(format!("No such table: {}", name)))?;
+            // per-row digests narrow down where two copies drift apart
+            if per_row {
+                let mut stream = RowStream::new(&["rowid", "sha256"], &options.output);
+                let result = info.stream_rows(&db).try_for_each(|row| {
+                    let row = row?;
+                    let digest = row.content_hash(&db)?;
+                    stream.push(vec![
+                        row.rowid.map_or(Value::Null, Value::Integer),
+                        to_hex(&digest).into(),
+                    ])
+                });
+                stream.finish(result)?;
+            } else {
+                let hash = info.content_hash(&db)?;
+                let mut table = Table::new(&["name", "rows", "sha256"]);
+                table.push(vec![
+                    info.name.as_str().into(),
+                    Value::Integer(hash.rows as i64),
+                    to_hex(&hash.digest).into(),
+                ]);
+                table.print(&options.output)?;
+            }
+        }
+        ".dbpage" => {
+            // the rows of the sqlite3 shell's sqlite_dbpage table: every page, or those asked
+            // for by number or FIRST-LAST range
+            let db = open(&path, &options)?;
+            let mut pages = Vec::new();
+            for arg in &args[3..] {
+                let (first, last) = arg.split_once('-').unwrap_or((arg, arg));
+                let first: u32 = first
+                    .parse()
+                    .with_context(|| format!("Not a page number or range: {}", arg))?;
+                let last: u32 = last
+                    .parse()
+                    .with_context(|| format!("Not a page number or range: {}", arg))?;
+                if first == 0 || first > last || last > db.page_count() {
+                    bail!(
+                        "Page {} is out of range (database has {} pages)",
+                        arg,
+                        db.page_count()
+                    );
+                }
+                pages.push(first..=last);
+            }
+            if args.len() == 3 {
+                pages.push(1..=db.page_count());
+            }
 
-                    if bytes_read == 8 {
-                        file.read_exact(&mut b)?;
-                        row_id = (row_id << 8) | u64::from(b[0]);
-                        break;
+            // each page is read as its row is written, so only the table and markdown formats,
+            // which line rows up against each other, hold more than one at a time
+            let mut stream = RowStream::new(&["pgno", "data"], &options.output);
+            let result = pages.into_iter().flatten().try_for_each(|number| {
+                let page = db.read_page(number)?;
+                stream.push(vec![number.into(), Value::Blob(page.data().to_vec())])
+            });
+            stream.finish(result)?;
+        }
+        ".rawdump" => {
+            let mut target: Option<(ObjectType, &String)> = None;
+            let mut out: Option<&Path> = None;
+            let mut rest = args[3..].iter().zip(&raw[3..]);
+            while let Some((arg, _)) = rest.next() {
+                match arg.as_str() {
+                    "--table" => {
+                        target = Some((
+                            ObjectType::Table,
+                            rest.next().context("Missing value for --table")?.0,
+                        ))
                     }
+                    "--index" => {
+                        target = Some((
+                            ObjectType::Index,
+                            rest.next().context("Missing value for --index")?.0,
+                        ))
+                    }
+                    "--out" => {
+                        out = Some(rest.next().context("Missing value for --out")?.1.as_ref())
+                    }
+                    _ => bail!("Unknown .rawdump argument: {}", arg),
                 }
+            }
+            let usage = "Usage: .rawdump --table <name> | --index <name> --out <dir>";
+            let (kind, name) = target.context(usage)?;
+            let dir = out.context(usage)?;
 
-                let mut payload: Vec<u8> = vec![0; payload_size as usize];
-                file.read_exact(&mut payload)?;
+            let db = open(&path, &options)?;
+            let entry = db
+                .schema()?
+                .into_iter()
+                .find(|e| e.kind == kind && e.root_page != 0 && e.name.eq_ignore_ascii_case(name))
+                .ok_or_else(|| NotFound(format!("No such {}: {}", kind.as_str(), name)))?;
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Unable to create {}", dir.display()))?;
 
-                // this is our cursor to work in the payload buffer
-                let mut p = 0;
-                // decode header size
-                let mut header_size: u64 = 0;
-                bytes_read = 0;
-                loop {
-                    let b = payload[p];
-                    p += 1;
+            // one .bin file per cell with the record exactly as stored, and a manifest line
+            // per cell with its provenance and decoding
+            let mut manifest = std::io::BufWriter::new(File::create(dir.join("cells.jsonl"))?);
+            for (seq, found) in db.tree_cells(entry.root_page).enumerate() {
+                let found = found?;
+                let file = format!("{:08}.bin", seq + 1);
+                let rowid = match found.cell {
+                    Cell::TableLeaf { rowid, .. } => rowid.to_string(),
+                    _ => "null".to_string(),
+                };
+                let payload = found.cell.payload().context("Cell has no payload")?;
+                let (record, error) = match db.read_payload(payload) {
+                    Ok(record) => (record, None),
+                    Err(e) => (payload.local.clone(), Some(e.to_string())),
+                };
+                let decoded = match (&error, decode_record_with(&record, db.utf8_policy())) {
+                    (Some(e), _) => format!("\"error\":{}", json::string(e)),
+                    (None, Ok(values)) => format!("\"values\":{}", json::array(&values)),
+                    (None, Err(e)) => format!("\"error\":{}", json::string(&e.to_string())),
+                };
+                std::fs::write(dir.join(&file), &record)?;
+                writeln!(
+                    manifest,
+                    "{{\"file\":{},\"page\":{},\"cell\":{},\"offset\":{},\"rowid\":{},\"payload_size\":{},\"local_size\":{},\"first_overflow\":{},{}}}",
+                    json::string(&file),
+                    found.page,
+                    found.index,
+                    found.offset,
+                    rowid,
+                    payload.size,
+                    payload.local.len(),
+                    payload
+                        .first_overflow
+                        .map_or("null".to_string(), |p| p.to_string()),
+                    decoded,
+                )?;
+            }
+            manifest.flush()?;
+        }
+        ".dump" => {
+            let mut tables: Vec<&str> = Vec::new();
+            let mut filter: Option<&str> = None;
+            let mut dir: Option<&Path> = None;
+            let mut jobs = 1;
+            let mut rest = args[3..].iter().zip(&raw[3..]);
+            while let Some((arg, _)) = rest.next() {
+                match arg.as_str() {
+                    "--table" => tables.push(rest.next().context("Missing value for --table")?.0),
+                    "--out" => {
+                        dir = Some(rest.next().context("Missing value for --out")?.1.as_ref())
+                    }
+                    "--jobs" => {
+                        jobs = rest
+                            .next()
+                            .context("Missing value for --jobs")?
+                            .0
+                            .parse()
+                            .ok()
+                            .filter(|&jobs: &usize| jobs > 0)
+                            .context("--jobs expects a number of threads")?
+                    }
+                    "--where" => filter = Some(rest.next().context("Missing value for --where")?.0),
+                    _ => bail!("Unknown .dump argument: {}", arg),
+                }
+            }
+            if filter.is_some() && tables.is_empty() {
+                bail!("--where filters the rows of the --table tables, so it needs at least one");
+            }
 
-                    header_size = (header_size << 7) | u64::from(b & 0x7F);
-                    bytes_read += 1;
+            let db = open(&path, &options)?;
+            let schema = db.schema()?;
+            for name in &tables {
+                let entry = schema
+                    .iter()
+                    .find(|e| e.name.eq_ignore_ascii_case(name))
+                    .ok_or_else(|| NotFound(format!("No such table: {}", name)))?;
+                if entry.kind != ObjectType::Table {
+                    let article = if entry.kind == ObjectType::Index {
+                        "an"
+                    } else {
+                        "a"
+                    };
+                    bail!(
+                        "{} is {} {}, not a table",
+                        name,
+                        article,
+                        entry.kind.as_str()
+                    );
+                }
+                // a filter that doesn't fit a table is reported before anything is written
+                if let Some(filter) = filter {
+                    parse_filter(filter, &TableInfo::load(&db, entry)?)
+                        .with_context(|| format!("Invalid --where for {}", entry.name))?;
+                }
+            }
+            match dir {
+                Some(dir) => dump_to_dir(&db, &tables, filter, &options.decoders, dir, jobs)?,
+                None if jobs > 1 => bail!("--jobs writes a file per table, so it needs --out"),
+                None => {
+                    // counted above the buffer, as what reaches it is written when it drops
+                    let mut out = Tracked::new(std::io::BufWriter::new(options.output.sink.lock()));
+                    dump(&db, &tables, filter, &options.decoders, &mut out)
+                        .and_then(|()| Ok(out.flush()?))
+                        .map_err(|e| out.fail(e))?;
+                }
+            }
+        }
+        ".clone" => {
+            let mut dest: Option<&Path> = None;
+            let mut incremental = false;
+            for (arg, raw_arg) in args[3..].iter().zip(&raw[3..]) {
+                match arg.as_str() {
+                    "--incremental" => incremental = true,
+                    _ if dest.is_none() && !arg.starts_with("--") => dest = Some(raw_arg.as_ref()),
+                    _ => bail!("Unknown .clone argument: {}", arg),
+                }
+            }
+            let dest = dest.context("Usage: .clone <file> [--incremental]")?;
+            let db = open(&path, &options)?;
+            let summary = clone_database(&db, dest, incremental)?;
+            let mut table = Table::new(&["pages", "copied", "verified", "recopied"]);
+            table.push(vec![
+                summary.pages.into(),
+                summary.copied.into(),
+                summary.verified.into(),
+                summary.recopied.into(),
+            ]);
+            table.print(&options.output)?;
+        }
+        ".columns" => {
+            let mut name: Option<&String> = None;
+            let mut as_json = false;
+            for arg in &args[3..] {
+                match arg.as_str() {
+                    "--json" => as_json = true,
+                    _ if name.is_none() => name = Some(arg),
+                    _ => bail!("Unknown .columns argument: {}", arg),
+                }
+            }
+            let name = name.context("Usage: .columns <table> [--json]")?;
 
-                    // stop when MSB == 0
-                    if (b & 0x80) == 0 {
-                        break;
+            let db = open(&path, &options)?;
+            let info = TableInfo::find(&db, name)?
+                .ok_or_else(|| NotFound(format!("No such table: {}", name)))?;
+            let columns = info.column_info();
+            // one document per table, shaped for code generators rather than for reading
+            if as_json {
+                let items: Vec<String> = columns
+                    .iter()
+                    .enumerate()
+                    .map(|(cid, c)| {
+                        let generated = match c.generated {
+                            None => "null",
+                            Some(Generated::Virtual) => "\"virtual\"",
+                            Some(Generated::Stored) => "\"stored\"",
+                        };
+                        format!(
+                            "{{\"cid\":{},\"name\":{},\"type\":{},\"notnull\":{},\"dflt_value\":{},\"pk\":{},\"hidden\":{},\"generated\":{},\"rowid_alias\":{}}}",
+                            cid,
+                            json::string(&c.name),
+                            json::string(&c.type_name),
+                            c.not_null,
+                            c.default.as_deref().map_or("null".to_string(), json::string),
+                            c.pk,
+                            c.hidden(),
+                            generated,
+                            c.rowid_alias
+                        )
+                    })
+                    .collect();
+                writeln!(
+                    options.output.sink.lock(),
+                    "{{\"table\":{},\"without_rowid\":{},\"strict\":{},\"columns\":[{}]}}",
+                    json::string(&info.name),
+                    info.definition.without_rowid,
+                    info.definition.strict,
+                    items.join(",")
+                )?;
+            } else {
+                let mut table = Table::new(&[
+                    "cid",
+                    "name",
+                    "type",
+                    "notnull",
+                    "dflt_value",
+                    "pk",
+                    "hidden",
+                    "rowid_alias",
+                ]);
+                for (cid, c) in columns.into_iter().enumerate() {
+                    table.push(vec![
+                        cid.into(),
+                        Value::Text(c.name.clone()),
+                        Value::Text(c.type_name.clone()),
+                        Value::Integer(c.not_null as i64),
+                        c.default.clone().map_or(Value::Null, Value::Text),
+                        c.pk.into(),
+                        Value::Integer(c.hidden() as i64),
+                        Value::Integer(c.rowid_alias as i64),
+                    ]);
+                }
+                table.print(&options.output)?;
+            }
+        }
+        ".codegen" => {
+            let mut lang = None;
+            let mut names: Vec<&String> = Vec::new();
+            let mut rest = args[3..].iter();
+            while let Some(arg) = rest.next() {
+                match arg.as_str() {
+                    "--lang" => {
+                        lang = Some(
+                            rest.next()
+                                .context("Missing value for --lang")?
+                                .parse::<Lang>()?,
+                        )
                     }
+                    "--table" => names.push(rest.next().context("Missing value for --table")?),
+                    _ => bail!("Unknown .codegen argument: {}", arg),
+                }
+            }
+            let lang =
+                lang.context("Usage: .codegen --lang rust|json-schema [--table <name>]...")?;
 
-                    // 9th-byte special case
-                    if bytes_read == 8 {
-                        let b9 = payload[p];
-                        p += 1;
-                        header_size = (header_size << 8) | u64::from(b9);
-                        bytes_read += 1; // add this
-                        break;
+            // without names, every table with stored rows apart from SQLite's own
+            let db = open(&path, &options)?;
+            let tables = if names.is_empty() {
+                db.schema()?
+                    .iter()
+                    .filter(|e| e.kind == ObjectType::Table && e.root_page != 0 && !e.is_internal())
+                    .map(|entry| TableInfo::load(&db, entry))
+                    .collect::<sqliter::Result<Vec<_>>>()?
+            } else {
+                names
+                    .iter()
+                    .map(|name| {
+                        TableInfo::find(&db, name)?
+                            .ok_or_else(|| NotFound(format!("No such table: {}", name)).into())
+                    })
+                    .collect::<Result<Vec<_>>>()?
+            };
+            let observed = tables
+                .into_iter()
+                .map(|table| {
+                    let observed = codegen::observe(&db, &table)?;
+                    Ok((table, observed))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            match lang {
+                Lang::Rust => write!(options.output.sink.lock(), "{}", codegen::rust(&observed)?)?,
+                Lang::JsonSchema => writeln!(
+                    options.output.sink.lock(),
+                    "{}",
+                    codegen::json_schema(&observed)?
+                )?,
+            }
+        }
+        ".lint" => {
+            let db = open(&path, &options)?;
+            let mut table = Table::new(&["object", "issue", "detail"]);
+            for finding in lint(&db)? {
+                table.push(vec![
+                    Value::Text(finding.object),
+                    Value::Text(finding.issue.as_str().to_string()),
+                    Value::Text(finding.detail),
+                ]);
+            }
+            table.print(&options.output)?;
+        }
+        ".deps" => {
+            let object = match &args[3..] {
+                [object] => object,
+                _ => bail!("Usage: .deps <table>[.<column>]"),
+            };
+            let db = open(&path, &options)?;
+            let schema = db.schema()?;
+            let relation = |name: &str| {
+                schema.iter().any(|e| {
+                    matches!(e.kind, ObjectType::Table | ObjectType::View)
+                        && e.name.eq_ignore_ascii_case(name)
+                })
+            };
+            // a table whose name holds a dot is found whole before the name is split
+            let (name, column) = match object.rsplit_once('.') {
+                Some((name, column)) if !relation(object) => (name, Some(column)),
+                _ => (object.as_str(), None),
+            };
+            if !relation(name) {
+                return Err(NotFound(format!("No such table or view: {}", name)).into());
+            }
+            if let (Some(column), Some(table)) = (column, TableInfo::find(&db, name)?) {
+                if !table
+                    .columns()
+                    .iter()
+                    .any(|c| c.name.eq_ignore_ascii_case(column))
+                {
+                    return Err(NotFound(format!("No column {} in {}", column, name)).into());
+                }
+            }
+            let mut table = Table::new(&["type", "name", "reason", "detail"]);
+            for dependent in dependents(&db, name, column)? {
+                table.push(vec![
+                    Value::Text(dependent.kind.as_str().to_string()),
+                    Value::Text(dependent.name),
+                    Value::Text(dependent.reason.as_str().to_string()),
+                    Value::Text(dependent.detail),
+                ]);
+            }
+            table.print(&options.output)?;
+        }
+        ".schemadiff" => {
+            let target = raw.get(3).context("Usage: <old.db> .schemadiff <new.db>")?;
+            let old = open(&path, &options)?.schema()?;
+            let new = open(Path::new(target), &options)?.schema()?;
+            let mut out = options.output.sink.lock();
+            for change in diff_schemas(&old, &new)? {
+                match change {
+                    Change::Statement(sql) => writeln!(out, "{};", sql)?,
+                    Change::Rebuild { table, reasons } => {
+                        writeln!(out, "-- {} needs to be rebuilt:", table)?;
+                        for reason in reasons {
+                            writeln!(out, "--   {}", reason)?;
+                        }
                     }
                 }
-                // Sanity: header_size must fit within payload
-                if (header_size as usize) > payload.len() {
-                    // malformed row, skip
-                    continue;
+            }
+        }
+        ".crosscheck" => {
+            let target = raw
+                .get(3)
+                .context("Usage: <main.db> .crosscheck <aux.db>")?;
+            if let Some(arg) = args.get(4) {
+                bail!("Unknown .crosscheck argument: {}", arg);
+            }
+            let main = open(&path, &options)?;
+            let aux = open(Path::new(target), &options)?;
+            let mut table = Table::new(&["table", "main_rows", "aux_rows", "result"]);
+            for check in crosscheck(&main, &aux)? {
+                let rows =
+                    |rows: Option<u64>| rows.map_or(Value::Null, |n| Value::Integer(n as i64));
+                let result = if check.is_ok() {
+                    "ok".to_string()
+                } else {
+                    check.problems.join("; ")
+                };
+                table.push(vec![
+                    Value::Text(check.table),
+                    rows(check.main_rows),
+                    rows(check.aux_rows),
+                    Value::Text(result),
+                ]);
+            }
+            table.print(&options.output)?;
+        }
+        ".fragmentation" => {
+            let mut threshold = DEFAULT_THRESHOLD;
+            let mut list_pages = false;
+            let mut rest = args[3..].iter();
+            while let Some(arg) = rest.next() {
+                match arg.as_str() {
+                    "--threshold" => {
+                        let percent: f64 = rest
+                            .next()
+                            .context("Missing value for --threshold")?
+                            .parse()
+                            .context("--threshold expects a percentage")?;
+                        threshold = percent / 100.0;
+                    }
+                    "--pages" => list_pages = true,
+                    "--defrag" => {
+                        bail!("Rewriting pages is not supported: databases are opened read-only")
+                    }
+                    _ => bail!("Unknown .fragmentation argument: {}", arg),
                 }
+            }
 
-                // Mark the start of header bytes (immediately after header_size varint)
-                let mut header_bytes_remaining = (header_size as usize) - bytes_read;
+            let db = open(&path, &options)?;
+            let names = pagemap::tree_names(&db)?;
+            let name = |root: u32| names.get(&root).cloned().unwrap_or_default();
+            let usable = db.usable_size();
+            let pages = fragmentation(&db)?;
 
-                let mut serial_types: Vec<u64> = Vec::new();
-                // sqlite_schema has 5 columns. Decode exactly up to 5 serial types from header bytes
-                for _ in 0..5 {
-                    // start a new serial type varint
-                    let mut st: u64 = 0;
-                    let mut st_bytes: usize = 0;
+            if list_pages {
+                let mut table = Table::new(&[
+                    "page",
+                    "name",
+                    "cells",
+                    "gap",
+                    "freeblocks",
+                    "freeblock_bytes",
+                    "fragmented_bytes",
+                    "stranded",
+                ]);
+                for page in pages.iter().filter(|p| p.is_churned(usable, threshold)) {
+                    table.push(vec![
+                        page.page.into(),
+                        name(page.root).into(),
+                        page.cells.into(),
+                        page.gap.into(),
+                        page.freeblocks.into(),
+                        page.freeblock_bytes.into(),
+                        page.fragmented_bytes.into(),
+                        page.stranded().into(),
+                    ]);
+                }
+                table.print(&options.output)?;
+                return Ok(());
+            }
 
-                    // stop if we ran out of header bytes
-                    if header_bytes_remaining == 0 || p >= payload.len() {
-                        break;
+            // per tree: pages, churned pages, freeblocks, freeblock bytes, fragments, stranded
+            let mut trees: Vec<(u32, [usize; 6])> = Vec::new();
+            for page in &pages {
+                let i = match trees.iter().position(|(root, _)| *root == page.root) {
+                    Some(i) => i,
+                    None => {
+                        trees.push((page.root, [0; 6]));
+                        trees.len() - 1
                     }
+                };
+                let totals = &mut trees[i].1;
+                totals[0] += 1;
+                totals[1] += page.is_churned(usable, threshold) as usize;
+                totals[2] += page.freeblocks;
+                totals[3] += page.freeblock_bytes;
+                totals[4] += page.fragmented_bytes;
+                totals[5] += page.stranded();
+            }
+            trees.sort_by_key(|(root, totals)| (std::cmp::Reverse(totals[5]), *root));
 
-                    // read 1–9 bytes of this serial type
-                    loop {
-                        // bounds: don't read beyond header area or payload
-                        if st_bytes >= header_bytes_remaining || p >= payload.len() {
-                            break;
-                        }
+            let mut table = Table::new(&[
+                "name",
+                "pages",
+                "churned",
+                "freeblocks",
+                "freeblock_bytes",
+                "fragmented_bytes",
+                "stranded",
+                "stranded_pct",
+            ]);
+            for (root, totals) in trees {
+                let capacity = totals[0] as f64 * usable as f64;
+                let mut row = vec![name(root).into()];
+                row.extend(totals.iter().map(|&n| Value::from(n)));
+                row.push(format!("{:.1}", 100.0 * totals[5] as f64 / capacity).into());
+                table.push(row);
+            }
+            table.print(&options.output)?;
+        }
+        ".pagemap" => {
+            let mut out: Option<PathBuf> = None;
+            let mut format: Option<String> = None;
+            let mut rest = args[3..].iter().zip(&raw[3..]);
+            while let Some((arg, _)) = rest.next() {
+                match arg.as_str() {
+                    "--out" => out = Some(rest.next().context("Missing value for --out")?.1.into()),
+                    "--format" => {
+                        format = Some(rest.next().context("Missing value for --format")?.0.clone())
+                    }
+                    _ => bail!("Unknown .pagemap argument: {}", arg),
+                }
+            }
+            // the format follows the output file's extension unless given explicitly
+            let format =
+                format.unwrap_or_else(|| match out.as_ref().and_then(|path| path.extension()) {
+                    Some(ext) if ext == "dot" || ext == "gv" => "dot".to_string(),
+                    _ => "svg".to_string(),
+                });
 
-                        let b = payload[p];
-                        p += 1;
+            let db = open(&path, &options)?;
+            let rendered = match format.as_str() {
+                "svg" => pagemap::render_svg(&db)?,
+                "dot" => pagemap::render_dot(&db)?,
+                _ => bail!("Unknown .pagemap format: {} (expected svg or dot)", format),
+            };
+            match out {
+                Some(path) => std::fs::write(&path, rendered)
+                    .with_context(|| format!("Unable to write {}", path.display()))?,
+                None => write!(options.output.sink.lock(), "{}", rendered)?,
+            }
+        }
+        ".erd" => {
+            let mut out: Option<PathBuf> = None;
+            let mut rest = args[3..].iter().zip(&raw[3..]);
+            while let Some((arg, _)) = rest.next() {
+                match arg.as_str() {
+                    "--out" => out = Some(rest.next().context("Missing value for --out")?.1.into()),
+                    _ => bail!("Unknown .erd argument: {}", arg),
+                }
+            }
 
-                        st = (st << 7) | u64::from(b & 0x7F);
-                        st_bytes += 1;
+            let db = open(&path, &options)?;
+            let rendered = erd::render_dot(&db)?;
+            match out {
+                Some(path) => std::fs::write(&path, rendered)
+                    .with_context(|| format!("Unable to write {}", path.display()))?,
+                None => write!(options.output.sink.lock(), "{}", rendered)?,
+            }
+        }
+        ".fts-terms" => {
+            let name = match &args[3..] {
+                [name] => name,
+                _ => bail!("Usage: .fts-terms <table>"),
+            };
+            let db = open(&path, &options)?;
+            let fts = Fts5::find(&db, name)?
+                .ok_or_else(|| NotFound(format!("No such fts5 table: {}", name)))?;
+            let mut table = Table::new(&["term", "rows"]);
+            for term in fts.terms(&db)? {
+                let text = match String::from_utf8(term.term) {
+                    Ok(text) => Value::Text(text),
+                    Err(e) => Value::RawText(e.into_bytes()),
+                };
+                table.push(vec![text, term.rows.into()]);
+            }
+            table.print(&options.output)?;
+        }
+        ".fts-match" => {
+            let (name, term) = match &args[3..] {
+                [name, term] => (name, term),
+                _ => bail!("Usage: .fts-match <table> <term>"),
+            };
+            let db = open(&path, &options)?;
+            let fts = Fts5::find(&db, name)?
+                .ok_or_else(|| NotFound(format!("No such fts5 table: {}", name)))?;
+            // the text comes along when the table keeps a copy of it
+            let mut columns = vec!["rowid"];
+            columns.extend(fts.columns.iter().map(String::as_str));
+            let mut table = Table::new(&columns);
+            for rowid in fts.match_term(&db, term)? {
+                let mut row = vec![rowid.into()];
+                match fts.document(&db, rowid)? {
+                    Some(values) => row.extend(values),
+                    None => row.resize(columns.len(), Value::Null),
+                }
+                table.push(row);
+            }
+            table.print(&options.output)?;
+        }
+        ".tiles" => {
+            let usage = "Usage: .tiles info | get <z> <x> <y> [--table T] [--tms]";
+            let mut table_name: Option<&String> = None;
+            let mut tms = false;
+            let mut positional = Vec::new();
+            let mut rest = args[3..].iter();
+            while let Some(arg) = rest.next() {
+                match arg.as_str() {
+                    "--table" => {
+                        table_name = Some(rest.next().context("Missing value for --table")?)
+                    }
+                    "--tms" => tms = true,
+                    _ => positional.push(arg.as_str()),
+                }
+            }
 
-                        // stop when MSB == 0
-                        if (b & 0x80) == 0 {
-                            break;
+            let db = open(&path, &options)?;
+            let tileset = match table_name {
+                Some(name) => Tileset::open_table(&db, name)?
+                    .ok_or_else(|| NotFound(format!("No such table: {}", name)))?,
+                None => match Tileset::open(&db)? {
+                    Some(tileset) => tileset,
+                    // a GeoPackage with a single tile pyramid needs no --table
+                    None if db
+                        .header()
+                        .application()
+                        .is_some_and(|name| name.starts_with("GeoPackage")) =>
+                    {
+                        match tiles::pyramids(&db)?.as_slice() {
+                            [name] => Tileset::open_table(&db, name)?
+                                .ok_or_else(|| NotFound(format!("No such table: {}", name)))?,
+                            [] => {
+                                return Err(NotFound(
+                                    "No tile pyramids in gpkg_contents".to_string(),
+                                )
+                                .into())
+                            }
+                            names => bail!(
+                                "Several tile pyramids ({}); name one with --table",
+                                names.join(", ")
+                            ),
+                        }
+                    }
+                    None => {
+                        return Err(NotFound(
+                            "No tiles table; name a tile table with --table".to_string(),
+                        )
+                        .into())
+                    }
+                },
+            };
+            match positional.as_slice() {
+                ["info"] => {
+                    let mut table = Table::new(&["name", "value"]);
+                    if table_name.is_none() {
+                        for (name, value) in tiles::metadata(&db)? {
+                            table.push(vec![name, value]);
                         }
+                    }
+                    let levels = tileset.zoom_levels(&db)?;
+                    let total: usize = levels.iter().map(|level| level.tiles).sum();
+                    table.push(vec!["tiles".into(), total.into()]);
+                    for level in levels {
+                        table.push(vec![
+                            format!("zoom {}", level.zoom).into(),
+                            level.tiles.into(),
+                        ]);
+                    }
+                    table.print(&options.output)?;
+                }
+                ["get", z, x, y] => {
+                    let parse = |text: &str| {
+                        text.parse::<i64>()
+                            .with_context(|| format!("Invalid tile coordinate: {}", text))
+                    };
+                    let (z, x, y) = (parse(z)?, parse(x)?, parse(y)?);
+                    // y counts from the top, as in tile URLs, unless --tms counts it from the
+                    // bottom; a row outside the map is left for the lookup to miss
+                    let row = if tms {
+                        tiles::flip_row(z, y).unwrap_or(-1)
+                    } else {
+                        y
+                    };
+                    let data = tileset.get(&db, z, x, row)?.ok_or_else(|| {
+                        NotFound(format!("No tile {}/{}/{} in {}", z, x, y, tileset.name))
+                    })?;
+                    let mut out = options.output.sink.lock();
+                    out.write_all(&data)?;
+                    out.flush()?;
+                }
+                _ => bail!(usage),
+            }
+        }
+        ".rtree-dump" => {
+            let name = match &args[3..] {
+                [name] => name,
+                _ => bail!("Usage: .rtree-dump <table>"),
+            };
+            let db = open(&path, &options)?;
+            let rtree = RTree::find(&db, name)?
+                .ok_or_else(|| NotFound(format!("No such rtree table: {}", name)))?;
+            // interior cells hold the box of a child node, leaf cells the box of a row
+            let mut columns = vec!["node", "level", "id"];
+            columns.extend(rtree.columns[1..].iter().map(String::as_str));
+            let mut table = Table::new(&columns);
+            for node in rtree.nodes(&db)? {
+                for cell in &node.cells {
+                    let mut row = vec![
+                        node.number.into(),
+                        Value::Integer(node.level.into()),
+                        cell.id.into(),
+                    ];
+                    row.extend(cell.coords.iter().map(|&c| coordinate(&rtree, c)));
+                    table.push(row);
+                }
+            }
+            table.print(&options.output)?;
+        }
+        ".rtree-search" => {
+            let usage = "Usage: .rtree-search <table> --overlaps|--within <min,max,...>";
+            let mut name: Option<&String> = None;
+            let mut query: Option<(BoxMatch, &String)> = None;
+            let mut rest = args[3..].iter();
+            while let Some(arg) = rest.next() {
+                let mode = match arg.as_str() {
+                    "--overlaps" => BoxMatch::Overlaps,
+                    "--within" => BoxMatch::Within,
+                    _ if name.is_none() => {
+                        name = Some(arg);
+                        continue;
+                    }
+                    _ => bail!("Unknown .rtree-search argument: {}", arg),
+                };
+                let value = rest
+                    .next()
+                    .with_context(|| format!("Missing value for {}", arg))?;
+                query = Some((mode, value));
+            }
+            let (Some(name), Some((mode, query))) = (name, query) else {
+                bail!(usage);
+            };
+            let query = query
+                .split(',')
+                .map(|c| c.trim().parse::<f64>())
+                .collect::<std::result::Result<Vec<f64>, _>>()
+                .context("The search box expects numbers separated by commas")?;
 
-                        // 9th-byte special case
-                        if st_bytes == 8 {
-                            if header_bytes_remaining > st_bytes && p < payload.len() {
-                                let b9 = payload[p];
-                                p += 1;
-                                st = (st << 8) | u64::from(b9);
-                                st_bytes += 1;
+            let db = open(&path, &options)?;
+            let rtree = RTree::find(&db, name)?
+                .ok_or_else(|| NotFound(format!("No such rtree table: {}", name)))?;
+            let columns: Vec<&str> = rtree.columns.iter().map(String::as_str).collect();
+            let mut table = Table::new(&columns);
+            for cell in rtree.search(&db, &query, mode)? {
+                let mut row = vec![cell.id.into()];
+                row.extend(cell.coords.iter().map(|&c| coordinate(&rtree, c)));
+                table.push(row);
+            }
+            table.print(&options.output)?;
+        }
+        ".read" => {
+            let (file, flags) = read_arguments(&raw[3..])?;
+            let failed = read_script(&raw[..2], &options, &file, flags, 0)?;
+            if failed > 0 {
+                bail!("{} of the commands in {} failed", failed, file.display());
+            }
+        }
+        // the destination only lasts for the commands after it, which a script gives
+        ".output" | ".once" => bail!("{} only applies within a .read script", command),
+        ".ar" => {
+            let db = open(&path, &options)?;
+            let archive = Sqlar::open(&db)?.context("Database is not a SQLite Archive")?;
+            let subcommand = args.get(3).map(String::as_str);
+            match subcommand {
+                Some("list") => {
+                    let mut table = Table::new(&["mode", "mtime", "size", "stored", "name"]);
+                    for entry in archive.entries() {
+                        let entry = entry?;
+                        table.push(vec![
+                            format!("{:o}", entry.mode).into(),
+                            entry.mtime.into(),
+                            entry.size.into(),
+                            entry.stored_size().into(),
+                            entry.name.into(),
+                        ]);
+                    }
+                    table.print(&options.output)?;
+                }
+                Some("extract") => {
+                    let mut dir = std::path::PathBuf::from(".");
+                    let mut names = Vec::new();
+                    let mut rest = args[4..].iter().zip(&raw[4..]);
+                    while let Some((arg, _)) = rest.next() {
+                        match arg.as_str() {
+                            "--dir" => {
+                                dir = rest.next().context("Missing value for --dir")?.1.into()
                             }
-                            break;
+                            _ => names.push(arg.as_str()),
                         }
                     }
-
-                    if st_bytes == 0 {
-                        // No more header bytes available — avoid infinite loop
-                        break;
+                    for entry in archive.entries() {
+                        let entry = entry?;
+                        if !names.is_empty() && !names.contains(&entry.name.as_str()) {
+                            continue;
+                        }
+                        extract_entry(&dir, &entry)?;
+                        writeln!(options.output.sink.lock(), "{}", entry.name)?;
                     }
-
-                    // store this serial type and account for header bytes consumed
-                    serial_types.push(st);
-                    header_bytes_remaining = header_bytes_remaining.saturating_sub(st_bytes);
                 }
-                // Data area begins at offset `header_size` from the start of the record (payload buffer)
-                let mut q = header_size as usize;
-                if q > payload.len() {
-                    // malformed row, skip
-                    continue;
+                Some("create") => {
+                    bail!("Creating archives is not supported: databases are opened read-only")
                 }
-                let mut type_str: Option<String> = None;
-
-                for (i, &st) in serial_types.iter().enumerate() {
-                    // we’ll decode one column value based on `st`, advancing `q` as we read
-                    let col_len: usize = match st {
-                        0 => 0,                                                // NULL
-                        1 => 1,                                                // 1-byte int
-                        2 => 2,                                                // 2-byte int
-                        3 => 3,                                                // 3-byte int
-                        4 => 4,                                                // 4-byte int
-                        5 => 6,                                                // 6-byte int
-                        6 => 8,                                                // 8-byte int
-                        7 => 8,                                                // 8-byte float
-                        8 | 9 => 0, // integer 0 / 1 (encoded with no payload bytes)
-                        s if s >= 12 && s % 2 == 0 => ((s - 12) / 2) as usize, // BLOB
-                        s if s >= 13 && s % 2 == 1 => ((s - 13) / 2) as usize, // TEXT
-                        _ => 0,
-                    };
+                _ => bail!("Usage: .ar list | extract [--dir DIR] [NAME...]"),
+            }
+        }
+        sql if !sql.starts_with('.') => {
+            if let Some(extra) = args.get(3) {
+                bail!("Unexpected argument after the SQL statement: {}", extra);
+            }
+            let db = open(&path, &options)?;
+            select(&db, &options, &parse_select(sql)?)?;
+        }
+        _ => bail!("Missing or invalid command passed: {}", command),
+    }
+
+    Ok(())
+}
+
+/// How deep `.read` commands may nest, so a script that reads itself stops
+const MAX_READ_DEPTH: usize = 32;
+
+/// How a `.read` script runs
+#[derive(Debug, Clone, Copy)]
+struct ReadFlags {
+    /// Print each command before running it
+    echo: bool,
+    /// Stop at the first command that fails, rather than reporting it and going on
+    bail: bool,
+}
+
+fn read_arguments(args: &[impl AsRef<OsStr>]) -> Result<(PathBuf, ReadFlags)> {
+    let mut file = None;
+    let mut flags = ReadFlags {
+        echo: false,
+        bail: true,
+    };
+    for arg in args {
+        let arg = arg.as_ref();
+        match arg.to_str() {
+            Some("--echo") => flags.echo = true,
+            Some("--bail") => flags.bail = true,
+            Some("--continue") => flags.bail = false,
+            _ if file.is_none() => file = Some(PathBuf::from(arg)),
+            _ => bail!("Unknown .read argument: {}", arg.to_string_lossy()),
+        }
+    }
+    let file = file.context("Usage: .read <file.sql> [--echo] [--bail|--continue]")?;
+    Ok((file, flags))
+}
+
+/// Runs each command of a script as if it had been given on the command line after `base`,
+/// the program and database path. Returns how many commands failed, which is always none
+/// when `flags.bail` stops the script at the first failure.
+fn read_script(
+    base: &[OsString],
+    options: &Options,
+    file: &Path,
+    flags: ReadFlags,
+    depth: usize,
+) -> Result<usize> {
+    if depth >= MAX_READ_DEPTH {
+        bail!("Scripts are nested more than {} deep", MAX_READ_DEPTH);
+    }
+    let text = std::fs::read_to_string(file)
+        .with_context(|| format!("Unable to read {}", file.display()))?;
+    let mut options = options.clone();
+    // the file `.output` or `.once` sent output to, and whether only for the next command
+    let mut redirect: Option<(Redirect, bool)> = None;
+    let mut failed = 0;
+    for command in parse_script(&text)? {
+        if flags.echo {
+            writeln!(options.output.sink.lock(), "{}", command.text)?;
+        }
+        let redirects = match &command.kind {
+            CommandKind::Dot(args) => args[0] == ".output" || args[0] == ".once",
+            CommandKind::Sql => false,
+        };
+        // output goes back to stdout after the one command a `.once` applies to
+        let once = !redirects && matches!(redirect, Some((_, true)));
+        let result = match &command.kind {
+            CommandKind::Sql => {
+                let mut raw = base.to_vec();
+                raw.push(OsString::from(&command.text));
+                run(options.clone(), raw)
+            }
+            CommandKind::Dot(args) if redirects => {
+                let result = set_redirect(&mut redirect, args);
+                options.output.sink = match &redirect {
+                    Some((to, _)) => to.sink(),
+                    None => Sink::Stdout,
+                };
+                result
+            }
+            CommandKind::Dot(args) if args[0] == ".read" => read_arguments(&args[1..])
+                .and_then(|(nested, nested_flags)| {
+                    read_script(base, &options, &nested, nested_flags, depth + 1)
+                })
+                .map(|nested_failed| failed += nested_failed),
+            CommandKind::Dot(args) => {
+                let mut raw = base.to_vec();
+                raw.extend(args.iter().map(OsString::from));
+                run(options.clone(), raw)
+            }
+        };
+        if once {
+            if let Some((to, _)) = redirect.take() {
+                to.finish()?;
+            }
+            options.output.sink = Sink::Stdout;
+        }
+        if let Err(e) = result {
+            let e = e.context(format!(
+                "{}:{}: {}",
+                file.display(),
+                command.line,
+                command.text
+            ));
+            if flags.bail {
+                return Err(e);
+            }
+            failure::report(&e, options.errors);
+            failed += 1;
+        }
+    }
+    if let Some((to, _)) = redirect {
+        to.finish()?;
+    }
+    Ok(failed)
+}
 
-                    // bounds guard for data area
-                    if q + col_len > payload.len() {
-                        break; // or continue, but breaking this row is safest
+/// Handles `.output [file|stdout]` and `.once <file>`. Either ends the redirect before it,
+/// as in the sqlite3 shell, and `.once` lasts for the next command only.
+fn set_redirect(redirect: &mut Option<(Redirect, bool)>, args: &[String]) -> Result<()> {
+    if let Some((previous, _)) = redirect.take() {
+        previous.finish()?;
+    }
+    let once = args[0] == ".once";
+    match &args[1..] {
+        [] if !once => {}
+        [target] if target == "stdout" && !once => {}
+        [file] => *redirect = Some((Redirect::create(Path::new(file))?, once)),
+        _ if once => bail!("Usage: .once <file>"),
+        _ => bail!("Usage: .output [<file>|stdout]"),
+    }
+    Ok(())
+}
+
+/// Runs `SELECT <columns> FROM <table>`, writing the columns asked for from every row
+fn select(db: &Database, options: &Options, query: &Select) -> Result<()> {
+    let info = TableInfo::find(db, &query.table)?
+        .ok_or_else(|| NotFound(format!("No such table: {}", query.table)))?;
+    // the column each result comes from, with `None` standing for the rowid
+    let mut sources: Vec<Option<usize>> = Vec::new();
+    let mut names: Vec<String> = Vec::new();
+    for column in &query.columns {
+        match column {
+            ResultColumn::All => {
+                for (i, column) in info.columns().iter().enumerate() {
+                    sources.push(Some(i));
+                    names.push(column.name.clone());
+                }
+            }
+            ResultColumn::Column(name) => {
+                let position = info
+                    .columns()
+                    .iter()
+                    .position(|c| c.name.eq_ignore_ascii_case(name));
+                let rowid = ["rowid", "oid", "_rowid_"]
+                    .iter()
+                    .any(|alias| alias.eq_ignore_ascii_case(name));
+                match position {
+                    Some(i) => sources.push(Some(i)),
+                    None if rowid && !info.definition.without_rowid => sources.push(None),
+                    None => {
+                        return Err(
+                            NotFound(format!("No such column: {} in {}", name, info.name)).into(),
+                        )
                     }
+                }
+                names.push(name.clone());
+            }
+        }
+    }
 
-                    let col_bytes = &payload[q..q + col_len];
-                    q += col_len;
+    // their values come from an expression, and there is nothing here to evaluate one with
+    if let Some(column) = sources
+        .iter()
+        .flatten()
+        .map(|&i| &info.columns()[i])
+        .find(|c| c.generated == Some(Generated::Virtual))
+    {
+        bail!(
+            "Column {} is generated when read, so it can't be selected: no expressions are evaluated",
+            column.name
+        );
+    }
 
-                    // capture "type" column (i == 0) if TEXT
-                    if i == 0 && st >= 13 && st % 2 == 1 {
-                        type_str = Some(String::from_utf8_lossy(col_bytes).to_string());
+    let projected: Vec<usize> = sources.iter().flatten().copied().collect();
+    let codecs = options.decoders.for_table(&info);
+    // like the sqlite3 shell, query results are listed as they are unless a format is asked for
+    let mut output = options.output.clone();
+    if !options.format_given {
+        output.format = Format::List;
+    }
+    let columns: Vec<&str> = names.iter().map(String::as_str).collect();
+    let mut stream = RowStream::new(&columns, &output);
+    let result = info.project(db, &projected)?.try_for_each(|row| {
+        let row = row?;
+        let mut fields = row.fields.into_iter();
+        let mut values = Vec::with_capacity(sources.len());
+        for source in &sources {
+            let Some(i) = *source else {
+                values.push(row.rowid.map_or(Value::Null, Value::Integer));
+                continue;
+            };
+            let value = match fields.next() {
+                Some(Field::Value(value)) => value,
+                // a value too big to decode up front is read whole, as it is to be output
+                Some(Field::Stored(stored)) => {
+                    let mut data = Vec::new();
+                    stored.copy_to(db, &mut data)?;
+                    match (stored.is_text(), String::from_utf8(data)) {
+                        (true, Ok(text)) => Value::Text(text),
+                        (true, Err(e)) => Value::RawText(e.into_bytes()),
+                        (false, Err(e)) => Value::Blob(e.into_bytes()),
+                        (false, Ok(text)) => Value::Blob(text.into_bytes()),
                     }
+                }
+                None => Value::Null,
+            };
+            values.push(match codecs[i] {
+                Some(codec) => decode_value(&info, codec, row.rowid, i, value)?,
+                None => value,
+            });
+        }
+        stream.push(values)
+    });
+    stream.finish(result)
+}
 
-                    // print tbl_name (i == 2) only if type == "table"
-                    if i == 2 && st >= 13 && st % 2 == 1 {
-                        if matches!(type_str.as_deref(), Some("table")) {
-                            let tbl_name = String::from_utf8_lossy(col_bytes).to_string();
-                            if tbl_name == "sqlite_sequence" {
-                                continue;
-                            }
-                            println!("{} ", tbl_name);
+/// Applies the codecs configured for a table's columns to one of its rows
+fn decode_row(
+    table: &TableInfo,
+    codecs: &[Option<Codec>],
+    rowid: Option<i64>,
+    values: Vec<Value>,
+) -> Result<Vec<Value>> {
+    values
+        .into_iter()
+        .enumerate()
+        .map(|(i, value)| match codecs.get(i).copied().flatten() {
+            Some(codec) => decode_value(table, codec, rowid, i, value),
+            None => Ok(value),
+        })
+        .collect()
+}
+
+fn decode_value(
+    table: &TableInfo,
+    codec: Codec,
+    rowid: Option<i64>,
+    column: usize,
+    value: Value,
+) -> Result<Value> {
+    codec.decode(value).with_context(|| {
+        let column = &table.columns()[column].name;
+        match rowid {
+            Some(rowid) => format!(
+                "Unable to decompress {}.{} in row {}",
+                table.name, column, rowid
+            ),
+            None => format!("Unable to decompress {}.{}", table.name, column),
+        }
+    })
+}
+
+/// Opens a database with the settings given on the command line
+fn open(path: &Path, options: &Options) -> Result<Opened> {
+    let db = match (options.untrusted, options.offset) {
+        (true, offset) => {
+            let db = match offset {
+                Some(offset) => Database::open_untrusted_at_offset(path, offset),
+                None => Database::open_untrusted(path),
+            };
+            db.inspect(|db| db.set_settings(options.settings))
+        }
+        (false, Some(offset)) => Database::open_at_offset(path, offset, options.settings),
+        (false, None) => Database::open_with_settings(path, options.settings),
+    };
+    let db = db.with_context(|| format!("Unable to open {}", path.display()))?;
+    Ok(Opened {
+        db,
+        totals: options.stats.clone(),
+    })
+}
+
+/// The commands that read what a file format built on SQLite stores, for the format its
+/// application ID names
+fn format_commands(application_id: u32) -> &'static [&'static str] {
+    match application_id {
+        // MBTiles
+        0x4d50_4258 => &[".tiles info", ".tiles get <z> <x> <y>"],
+        // GeoPackage, whose tile pyramids are each in a table of their own
+        0x4750_3130 | 0x4750_3131 | 0x4750_4b47 => &[
+            ".tiles info [--table <tiles>]",
+            ".tiles get <z> <x> <y> [--table <tiles>]",
+        ],
+        _ => &[],
+    }
+}
+
+/// Size and modification time of the database file and its WAL, which change with every commit
+fn change_marker(path: &Path) -> Result<Vec<Option<(u64, std::time::SystemTime)>>> {
+    [path.to_path_buf(), Wal::path_for(path)]
+        .iter()
+        .map(|path| match std::fs::metadata(path) {
+            Ok(meta) => Ok(Some((meta.len(), meta.modified()?))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        })
+        .collect()
+}
+
+/// Writes the database as an SQL script that rebuilds it, laid out like the sqlite3 shell's
+/// .dump: tables with their rows first, then indexes, views and triggers. Given table names,
+/// only those tables are written, along with the indexes and triggers on them, and given a
+/// filter, only the rows it keeps.
+fn dump(
+    db: &Database,
+    tables: &[&str],
+    filter: Option<&str>,
+    decoders: &ColumnDecoders,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let schema = db.schema()?;
+    writeln!(out, "PRAGMA foreign_keys=OFF;")?;
+    writeln!(out, "BEGIN TRANSACTION;")?;
+    let mut writable_schema = false;
+    for entry in dumped_tables(&schema, tables) {
+        if entry.root_page == 0 {
+            if !writable_schema {
+                writeln!(out, "PRAGMA writable_schema=ON;")?;
+                writable_schema = true;
+            }
+            dump_virtual_table(entry, out)?;
+        } else {
+            dump_table(db, entry, filter, decoders, out)?;
+        }
+    }
+    dump_schema_objects(&schema, tables, out)?;
+    if writable_schema {
+        writeln!(out, "PRAGMA writable_schema=OFF;")?;
+    }
+    writeln!(out, "COMMIT;")?;
+    Ok(())
+}
+
+/// Writes the same dump as [`dump`] into `dir` as one script per table, written by `jobs`
+/// threads reading the same snapshot, then `schema.sql` with the virtual tables, indexes,
+/// views and triggers. `manifest.jsonl` lists the scripts in the order they are to be run.
+/// An error after the first script was created is marked [`Partial`].
+fn dump_to_dir(
+    db: &Database,
+    tables: &[&str],
+    filter: Option<&str>,
+    decoders: &ColumnDecoders,
+    dir: &Path,
+    jobs: usize,
+) -> Result<()> {
+    let created = AtomicBool::new(false);
+    write_dump_dir(db, tables, filter, decoders, dir, jobs, &created).map_err(|e| {
+        if created.load(Ordering::Relaxed) {
+            e.context(Partial)
+        } else {
+            e
+        }
+    })
+}
+
+/// [`dump_to_dir`], setting `created` once it has created a file in `dir`
+fn write_dump_dir(
+    db: &Database,
+    tables: &[&str],
+    filter: Option<&str>,
+    decoders: &ColumnDecoders,
+    dir: &Path,
+    jobs: usize,
+    created: &AtomicBool,
+) -> Result<()> {
+    let schema = db.schema()?;
+    let (stored, virtual_tables): (Vec<_>, Vec<_>) = dumped_tables(&schema, tables)
+        .into_iter()
+        .partition(|e| e.root_page != 0);
+    std::fs::create_dir_all(dir).with_context(|| format!("Unable to create {}", dir.display()))?;
+
+    // the numbering keeps the files in load order and apart when names differ only in case
+    let files: Vec<String> = stored
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| {
+            let name: String = entry
+                .name
+                .chars()
+                .map(|c| match c {
+                    'a'..='z' | 'A'..='Z' | '0'..='9' | '_' | '-' => c,
+                    _ => '_',
+                })
+                .collect();
+            format!("{:04}-{}.sql", i + 1, name)
+        })
+        .collect();
+    let dump_one = |i: usize| -> Result<u64> {
+        let path = dir.join(&files[i]);
+        let mut out = std::io::BufWriter::new(
+            File::create(&path).with_context(|| format!("Unable to create {}", path.display()))?,
+        );
+        created.store(true, Ordering::Relaxed);
+        writeln!(out, "PRAGMA foreign_keys=OFF;")?;
+        writeln!(out, "BEGIN TRANSACTION;")?;
+        let rows = dump_table(db, stored[i], filter, decoders, &mut out)
+            .with_context(|| format!("Unable to dump {}", stored[i].name))?;
+        writeln!(out, "COMMIT;")?;
+        out.flush()?;
+        Ok(rows)
+    };
+
+    // workers take the next table until none are left or one of them fails
+    let next = AtomicUsize::new(0);
+    let failed = AtomicBool::new(false);
+    let mut rows = vec![0; stored.len()];
+    std::thread::scope(|scope| -> Result<()> {
+        let workers: Vec<_> = (0..jobs.clamp(1, stored.len().max(1)))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut done = Vec::new();
+                    while !failed.load(Ordering::Relaxed) {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        if i >= stored.len() {
+                            break;
                         }
+                        let result = dump_one(i);
+                        failed.fetch_or(result.is_err(), Ordering::Relaxed);
+                        done.push((i, result));
+                    }
+                    done
+                })
+            })
+            .collect();
+        for worker in workers {
+            let done = worker
+                .join()
+                .map_err(|_| anyhow::anyhow!("A dump worker panicked"))?;
+            for (i, result) in done {
+                rows[i] = result?;
+            }
+        }
+        Ok(())
+    })?;
+
+    let mut out = std::io::BufWriter::new(File::create(dir.join("schema.sql"))?);
+    created.store(true, Ordering::Relaxed);
+    writeln!(out, "BEGIN TRANSACTION;")?;
+    if !virtual_tables.is_empty() {
+        writeln!(out, "PRAGMA writable_schema=ON;")?;
+        for entry in &virtual_tables {
+            dump_virtual_table(entry, &mut out)?;
+        }
+    }
+    dump_schema_objects(&schema, tables, &mut out)?;
+    if !virtual_tables.is_empty() {
+        writeln!(out, "PRAGMA writable_schema=OFF;")?;
+    }
+    writeln!(out, "COMMIT;")?;
+    out.flush()?;
+
+    let mut manifest = std::io::BufWriter::new(File::create(dir.join("manifest.jsonl"))?);
+    created.store(true, Ordering::Relaxed);
+    for ((file, entry), rows) in files.iter().zip(&stored).zip(&rows) {
+        writeln!(
+            manifest,
+            "{{\"file\":{},\"table\":{},\"rows\":{}}}",
+            json::string(file),
+            json::string(&entry.name),
+            rows
+        )?;
+    }
+    writeln!(manifest, "{{\"file\":\"schema.sql\"}}")?;
+    manifest.flush()?;
+    Ok(())
+}
+
+/// The tables a dump writes, in the order it writes them: sqlite_sequence goes last so the
+/// AUTOINCREMENT tables it refers to exist first, and SQLite's other internal tables are
+/// left for it to create, apart from the sqlite_stat tables ANALYZE makes
+fn dumped_tables<'a>(schema: &'a [SchemaEntry], tables: &[&str]) -> Vec<&'a SchemaEntry> {
+    let mut dumped: Vec<_> = schema
+        .iter()
+        .filter(|e| {
+            e.kind == ObjectType::Table
+                && (e.sql.is_some() || e.root_page != 0)
+                && dump_selects(tables, &e.name)
+                && (!e.is_internal() || e.carries_state())
+        })
+        .collect();
+    dumped.sort_by_key(|e| e.name.eq_ignore_ascii_case("sqlite_sequence"));
+    dumped
+}
+
+fn dump_selects(tables: &[&str], name: &str) -> bool {
+    tables.is_empty() || tables.iter().any(|t| t.eq_ignore_ascii_case(name))
+}
+
+/// Virtual tables can't be created before their module is loaded, so they are written
+/// straight into the schema, which needs `PRAGMA writable_schema=ON` first
+fn dump_virtual_table(entry: &SchemaEntry, out: &mut dyn Write) -> Result<()> {
+    writeln!(
+        out,
+        "INSERT INTO sqlite_schema(type,name,tbl_name,rootpage,sql)VALUES('table',{},{},0,{});",
+        literal(&Value::Text(entry.name.clone())),
+        literal(&Value::Text(entry.tbl_name.clone())),
+        literal(&Value::Text(entry.sql.clone().unwrap_or_default())),
+    )?;
+    Ok(())
+}
+
+/// The indexes, views and triggers of the dumped tables
+fn dump_schema_objects(schema: &[SchemaEntry], tables: &[&str], out: &mut dyn Write) -> Result<()> {
+    for entry in schema {
+        if entry.kind == ObjectType::Table || !dump_selects(tables, &entry.tbl_name) {
+            continue;
+        }
+        if let Some(sql) = &entry.sql {
+            writeln!(out, "{};", sql)?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes one stored table's CREATE statement and rows, returning how many rows there were.
+/// SQLite's own tables get the statement that prepares them instead of a CREATE.
+fn dump_table(
+    db: &Database,
+    entry: &SchemaEntry,
+    filter: Option<&str>,
+    decoders: &ColumnDecoders,
+    out: &mut dyn Write,
+) -> Result<u64> {
+    let name = entry.name.as_str();
+    let table = TableInfo::load(db, entry)?;
+    let filter = filter.map(|f| parse_filter(f, &table)).transpose()?;
+    let filtered = filter.as_ref().map_or_else(Vec::new, Filter::columns);
+    if name.eq_ignore_ascii_case("sqlite_sequence") {
+        writeln!(out, "DELETE FROM sqlite_sequence;")?;
+    } else if entry.carries_state() {
+        writeln!(out, "ANALYZE sqlite_schema;")?;
+    } else if table.inferred {
+        // a table whose CREATE statement is missing or unreadable is recreated with the
+        // columns its records hold
+        let names: Vec<String> = table
+            .columns()
+            .iter()
+            .map(|c| quote_identifier(&c.name))
+            .collect();
+        writeln!(
+            out,
+            "CREATE TABLE {}({});",
+            quote_identifier(name),
+            names.join(",")
+        )?;
+    } else {
+        writeln!(out, "{};", entry.sql.as_deref().unwrap_or_default())?;
+    }
+    // generated columns can't be given values, so name the rest when there are any
+    let stored: Vec<usize> = (0..table.columns().len())
+        .filter(|&i| table.columns()[i].generated.is_none())
+        .collect();
+    let target = if stored.len() == table.columns().len() {
+        quote_identifier(name)
+    } else {
+        let names: Vec<String> = stored
+            .iter()
+            .map(|&i| quote_identifier(&table.columns()[i].name))
+            .collect();
+        format!("{}({})", quote_identifier(name), names.join(","))
+    };
+    // huge values go straight from the overflow chain to the output as hex, unless they
+    // are decompressed on the way
+    let codecs = decoders.for_table(&table);
+    // rows are read and decoded on a thread of their own while earlier ones are written
+    pipelined(table.stream_rows(db), DEFAULT_DEPTH, |rows| {
+        let mut count = 0;
+        for row in rows {
+            let row = row?;
+            if let Some(filter) = &filter {
+                // only the columns the filter reads are loaded for it
+                let mut values = vec![Value::Null; row.fields.len()];
+                for &i in &filtered {
+                    values[i] = field_value(db, &row.fields[i])?;
+                }
+                if !filter.matches(row.rowid, &values) {
+                    continue;
+                }
+            }
+            write!(out, "INSERT INTO {} VALUES(", target)?;
+            for (n, &i) in stored.iter().enumerate() {
+                if n > 0 {
+                    write!(out, ",")?;
+                }
+                match (&row.fields[i], codecs[i]) {
+                    (field, Some(codec)) => {
+                        let value = field_value(db, field)?;
+                        let value = decode_value(&table, codec, row.rowid, i, value)?;
+                        write!(out, "{}", literal(&value))?
+                    }
+                    (Field::Value(value), None) => write!(out, "{}", literal(value))?,
+                    (Field::Stored(stored), None) => {
+                        let (open, close) = if stored.is_text() {
+                            ("CAST(X'", "' AS TEXT)")
+                        } else {
+                            ("X'", "'")
+                        };
+                        write!(out, "{}", open)?;
+                        stored.copy_to(db, &mut HexWriter(&mut *out))?;
+                        write!(out, "{}", close)?;
                     }
                 }
             }
+            writeln!(out, ");")?;
+            count += 1;
         }
-        _ => bail!("Missing or invalid command passed: {}", command),
+        Ok(count)
+    })
+}
+
+/// A streamed field's value, reading it out of the file if it was left there
+fn field_value(db: &Database, field: &Field) -> Result<Value> {
+    Ok(match field {
+        Field::Value(value) => value.clone(),
+        Field::Stored(stored) => {
+            let mut data = Vec::new();
+            stored.copy_to(db, &mut data)?;
+            if stored.is_text() {
+                Value::RawText(data)
+            } else {
+                Value::Blob(data)
+            }
+        }
+    })
+}
+
+fn parse_args(raw: Vec<OsString>) -> Result<(Options, Vec<OsString>)> {
+    parse_options(Options::default(), raw)
+}
+
+/// Applies the options at the start of `raw` on top of `options`, returning the arguments
+/// that follow them after the first
+fn parse_options(mut options: Options, raw: Vec<OsString>) -> Result<(Options, Vec<OsString>)> {
+    let mut raw = raw.into_iter();
+    let mut args: Vec<OsString> = raw.next().into_iter().collect();
+
+    while let Some(arg) = raw.next() {
+        let option = match arg.to_str() {
+            Some(option) if args[1..].is_empty() && option.starts_with("--") => option,
+            _ => {
+                args.push(arg);
+                continue;
+            }
+        };
+        let mut value = |name: &str| {
+            raw.next()
+                .with_context(|| format!("Missing value for {}", name))?
+                .into_string()
+                .map_err(|value| {
+                    anyhow::anyhow!(
+                        "Value for {} is not valid UTF-8: {}",
+                        name,
+                        value.to_string_lossy()
+                    )
+                })
+        };
+        match option {
+            "--format" => {
+                options.output.format = value("--format")?.parse()?;
+                options.format_given = true;
+            }
+            "--max-col-width" => {
+                options.output.max_col_width = value("--max-col-width")?
+                    .parse()
+                    .context("--max-col-width expects a number of characters")?
+            }
+            "--raw" => options.output.raw = true,
+            "--utf8" => {
+                options.settings.utf8_policy = match value("--utf8")?.as_str() {
+                    "lossy" => Utf8Policy::Lossy,
+                    "strict" => Utf8Policy::Strict,
+                    "raw" => Utf8Policy::Raw,
+                    other => bail!(
+                        "Unknown --utf8 policy: {} (expected lossy, strict or raw)",
+                        other
+                    ),
+                }
+            }
+            "--insert-table" => options.output.insert_table = value("--insert-table")?,
+            "--nullvalue" => options.output.null_value = value("--nullvalue")?,
+            "--cache-size" => {
+                options.settings.cache_size = value("--cache-size")?
+                    .parse()
+                    .context("--cache-size expects pages, or KiB when negative")?
+            }
+            "--untrusted" => options.untrusted = true,
+            "--offset" => {
+                options.offset = Some(
+                    value("--offset")?
+                        .parse()
+                        .context("--offset expects a byte offset")?,
+                )
+            }
+            "--pragma" => options.settings.apply_pragma(&value("--pragma")?)?,
+            "--errors" => options.errors = value("--errors")?.parse()?,
+            "--decode" => options.decoders.add(&value("--decode")?)?,
+            "--stats" => options.stats = Some(Arc::default()),
+            "--profile" => {
+                let file = value("--profile")?;
+                let (loaded, rest) = parse_options(options, profile_args(&file)?)?;
+                if let Some(extra) = rest.get(1) {
+                    bail!(
+                        "Profile {} holds something other than options: {}",
+                        file,
+                        extra.to_string_lossy()
+                    );
+                }
+                options = loaded;
+            }
+            _ => bail!("Unknown option: {}", option),
+        }
+    }
+    Ok((options, args))
+}
+
+/// The options in a profile file, led by the file's name in place of the program's. Each
+/// line holds options as they are given on the command line, quoted as in `.read` scripts;
+/// blank lines and lines starting with `#` are skipped.
+fn profile_args(file: &str) -> Result<Vec<OsString>> {
+    let text = std::fs::read_to_string(file)
+        .with_context(|| format!("Failed to read profile {}", file))?;
+    let mut args = vec![OsString::from(file)];
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let words = split_arguments(line).with_context(|| format!("{}:{}", file, n + 1))?;
+        // a profile loading itself would never finish
+        if words.iter().any(|w| w == "--profile") {
+            bail!("{}:{}: profiles can't load other profiles", file, n + 1);
+        }
+        args.extend(words.into_iter().map(OsString::from));
     }
+    Ok(args)
+}
 
+/// A coordinate as the rtree module returns it: an INTEGER for rtree_i32 tables, else a REAL
+fn coordinate(rtree: &RTree, c: f64) -> Value {
+    if rtree.integer {
+        Value::Integer(c as i64)
+    } else {
+        Value::Real(c)
+    }
+}
+
+fn extract_entry(dir: &Path, entry: &sqliter::sqlar::ArchiveEntry) -> Result<()> {
+    // archive names are relative paths; refuse anything that would escape the target directory
+    let relative = Path::new(&entry.name);
+    if relative
+        .components()
+        .any(|c| !matches!(c, std::path::Component::Normal(_)))
+    {
+        bail!("Refusing to extract unsafe path: {}", entry.name);
+    }
+    // nor through a symlink, whether an earlier entry made it or it was already there: the
+    // link could point anywhere
+    let mut path = dir.to_path_buf();
+    for component in relative.components() {
+        path.push(component);
+        match std::fs::symlink_metadata(&path) {
+            Ok(metadata) if metadata.file_type().is_symlink() => {
+                bail!("Refusing to extract through a symlink: {}", entry.name)
+            }
+            Ok(_) => {}
+            Err(_) => break,
+        }
+    }
+    let path = dir.join(relative);
+
+    if entry.is_dir() {
+        std::fs::create_dir_all(&path)?;
+    } else {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let contents = entry.contents()?;
+        if entry.is_symlink() {
+            #[cfg(unix)]
+            std::os::unix::fs::symlink(String::from_utf8_lossy(&contents).as_ref(), &path)?;
+            #[cfg(not(unix))]
+            bail!("Symlinks can only be extracted on unix: {}", entry.name);
+            #[cfg(unix)]
+            return Ok(());
+        }
+        std::fs::write(&path, contents)?;
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(entry.mode & 0o7777))?;
+    }
+    if !entry.is_dir() {
+        let mtime =
+            std::time::UNIX_EPOCH + std::time::Duration::from_secs(entry.mtime.max(0) as u64);
+        File::options()
+            .write(true)
+            .open(&path)?
+            .set_modified(mtime)?;
+    }
     Ok(())
 }
+
+fn ok_or_bad(valid: bool) -> &'static str {
+    if valid {
+        "ok"
+    } else {
+        "bad"
+    }
+}
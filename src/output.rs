@@ -0,0 +1,859 @@
+use crate::failure::Partial;
+use crate::json;
+use anyhow::{bail, Context, Result};
+use sqliter::format::format_real;
+use sqliter::sql::{literal, quote_identifier};
+use sqliter::Value;
+use std::fs::File;
+use std::io::{BufWriter, StdoutLock, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// How tabular command output is rendered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Format {
+    /// Space-aligned columns for reading in a terminal
+    #[default]
+    Table,
+    /// Values separated by `|`, without a header and as stored, like the sqlite3 shell's
+    /// default `.mode list`
+    List,
+    /// Tab-separated values that paste straight into spreadsheets
+    Tsv,
+    /// GitHub-flavoured pipe table
+    Markdown,
+    /// Comma-separated SQL literals, like the sqlite3 shell's `.mode quote`
+    Quote,
+    /// One INSERT statement per row, like `.mode insert`
+    Insert,
+    /// One `column = value` line per column, rows separated by a blank line
+    Line,
+    /// One JSON object per row, then a `{"summary": ...}` object with the row count and any
+    /// notes about output cut short
+    Ndjson,
+}
+
+impl FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "table" => Ok(Format::Table),
+            "list" => Ok(Format::List),
+            "tsv" => Ok(Format::Tsv),
+            "markdown" | "md" => Ok(Format::Markdown),
+            "quote" => Ok(Format::Quote),
+            "insert" | "sql" => Ok(Format::Insert),
+            "line" => Ok(Format::Line),
+            "ndjson" | "jsonl" => Ok(Format::Ndjson),
+            _ => bail!(
+                "Unknown output format: {} (expected table, list, tsv, markdown, quote, insert (or sql), line or ndjson)",
+                s
+            ),
+        }
+    }
+}
+
+impl Format {
+    fn writer(self, options: &OutputOptions) -> Box<dyn OutputWriter + '_> {
+        if let Some(rows) = self.row_writer(options) {
+            return Box::new(EachRow(rows));
+        }
+        match self {
+            Format::Markdown => Box::new(MarkdownWriter {
+                null: &options.null_value,
+            }),
+            _ => Box::new(TableWriter { options }),
+        }
+    }
+
+    /// The writer for formats that lay out each row on its own, or `None` for those that
+    /// need every row first
+    fn row_writer(self, options: &OutputOptions) -> Option<Box<dyn RowWriter + '_>> {
+        Some(match self {
+            Format::Table | Format::Markdown => return None,
+            Format::List => Box::new(ListWriter {
+                null: &options.null_value,
+            }),
+            Format::Tsv => Box::new(TsvWriter {
+                null: &options.null_value,
+            }),
+            Format::Quote => Box::new(QuoteWriter),
+            Format::Insert => Box::new(InsertWriter {
+                table: &options.insert_table,
+            }),
+            Format::Line => Box::new(LineWriter {
+                null: &options.null_value,
+            }),
+            Format::Ndjson => Box::new(NdjsonWriter),
+        })
+    }
+}
+
+/// Renders a collected table; each output format is one implementation
+pub trait OutputWriter {
+    fn write(&self, out: &mut dyn Write, table: &Table) -> Result<()>;
+}
+
+/// Renders rows one at a time, for the formats where a row's output doesn't depend on the
+/// others, so [`RowStream`] can write them as they come
+trait RowWriter {
+    /// What comes before the first row, such as a header
+    fn begin(&self, _out: &mut dyn Write, _columns: &[String]) -> Result<()> {
+        Ok(())
+    }
+
+    /// Row `index`, counting from 0
+    fn row(&self, out: &mut dyn Write, columns: &[String], index: u64, row: &[Value])
+        -> Result<()>;
+
+    /// What comes after the last of `rows` rows, with `notes` on output cut short
+    fn end(&self, _out: &mut dyn Write, _rows: u64, _notes: &[String]) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// A [`RowWriter`] rendering a collected table
+struct EachRow<'a>(Box<dyn RowWriter + 'a>);
+
+impl OutputWriter for EachRow<'_> {
+    fn write(&self, out: &mut dyn Write, table: &Table) -> Result<()> {
+        self.0.begin(out, &table.columns)?;
+        for (i, row) in table.rows.iter().enumerate() {
+            self.0.row(out, &table.columns, i as u64, row)?;
+        }
+        self.0.end(out, table.rows.len() as u64, &[])
+    }
+}
+
+/// Longest cell the default writer prints before eliding the rest
+pub const DEFAULT_MAX_COL_WIDTH: usize = 80;
+
+#[derive(Debug, Clone)]
+pub struct OutputOptions {
+    pub format: Format,
+    /// Cells in the default writer are cut to this many characters, with an ellipsis
+    pub max_col_width: usize,
+    /// Print values byte-for-byte, without escaping or truncation
+    pub raw: bool,
+    /// Table named by the INSERT statements of the insert format
+    pub insert_table: String,
+    /// How NULL is shown by the formats that don't print SQL literals, like the sqlite3
+    /// shell's `.nullvalue`
+    pub null_value: String,
+    /// Where output goes
+    pub sink: Sink,
+}
+
+impl Default for OutputOptions {
+    fn default() -> Self {
+        OutputOptions {
+            format: Format::default(),
+            max_col_width: DEFAULT_MAX_COL_WIDTH,
+            raw: false,
+            insert_table: "table".to_string(),
+            null_value: String::new(),
+            sink: Sink::default(),
+        }
+    }
+}
+
+/// Where command output is written: stdout, or a file a script redirected it to with
+/// `.output` or `.once`
+#[derive(Debug, Clone, Default)]
+pub enum Sink {
+    #[default]
+    Stdout,
+    File(Arc<Mutex<BufWriter<File>>>),
+}
+
+impl Sink {
+    /// The destination, held until the returned writer is dropped
+    pub fn lock(&self) -> SinkWriter<'_> {
+        match self {
+            Sink::Stdout => SinkWriter::Stdout(std::io::stdout().lock()),
+            // a panic while writing leaves nothing worse than a partial line
+            Sink::File(file) => SinkWriter::File(file.lock().unwrap_or_else(|e| e.into_inner())),
+        }
+    }
+}
+
+pub enum SinkWriter<'a> {
+    Stdout(StdoutLock<'static>),
+    File(MutexGuard<'a, BufWriter<File>>),
+}
+
+impl Write for SinkWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            SinkWriter::Stdout(out) => out.write(buf),
+            SinkWriter::File(out) => out.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            SinkWriter::Stdout(out) => out.flush(),
+            SinkWriter::File(out) => out.flush(),
+        }
+    }
+}
+
+/// Output redirected to a file. It is written to a temporary file beside it and renamed
+/// into place by [`finish`](Redirect::finish), so the file is never left holding part of
+/// the output; a redirect dropped without finishing discards what was written.
+#[derive(Debug)]
+pub struct Redirect {
+    path: PathBuf,
+    temp: PathBuf,
+    file: Arc<Mutex<BufWriter<File>>>,
+}
+
+impl Redirect {
+    pub fn create(path: &Path) -> Result<Redirect> {
+        let mut name = path
+            .file_name()
+            .with_context(|| format!("Not a file name: {}", path.display()))?
+            .to_owned();
+        name.push(format!(".{}.tmp", std::process::id()));
+        let temp = path.with_file_name(name);
+        let file =
+            File::create(&temp).with_context(|| format!("Unable to write {}", path.display()))?;
+        Ok(Redirect {
+            path: path.to_path_buf(),
+            temp,
+            file: Arc::new(Mutex::new(BufWriter::new(file))),
+        })
+    }
+
+    pub fn sink(&self) -> Sink {
+        Sink::File(self.file.clone())
+    }
+
+    /// Writes out everything sent to the file and moves it into place
+    pub fn finish(self) -> Result<()> {
+        {
+            let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+            file.flush()?;
+            file.get_ref().sync_all()?;
+        }
+        std::fs::rename(&self.temp, &self.path)
+            .with_context(|| format!("Unable to write {}", self.path.display()))
+    }
+}
+
+impl Drop for Redirect {
+    fn drop(&mut self) {
+        // gone already when finished
+        let _ = std::fs::remove_file(&self.temp);
+    }
+}
+
+/// Rows collected by a command before being written out in the selected format
+pub struct Table {
+    columns: Vec<String>,
+    rows: Vec<Vec<Value>>,
+}
+
+impl Table {
+    pub fn new(columns: &[&str]) -> Self {
+        Table {
+            columns: columns.iter().map(|c| c.to_string()).collect(),
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, row: Vec<Value>) {
+        debug_assert_eq!(row.len(), self.columns.len());
+        self.rows.push(row);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    pub fn print(&self, options: &OutputOptions) -> Result<()> {
+        let mut out = options.sink.lock();
+        options.format.writer(options).write(&mut out, self)?;
+        out.flush()?;
+        Ok(())
+    }
+
+    /// A column is right-aligned when every non-NULL value in it is a number
+    fn numeric_columns(&self) -> Vec<bool> {
+        (0..self.columns.len())
+            .map(|i| {
+                let mut values = self
+                    .rows
+                    .iter()
+                    .map(|row| &row[i])
+                    .filter(|v| **v != Value::Null);
+                let first = values.next();
+                first.is_some_and(Value::is_numeric) && values.all(Value::is_numeric)
+            })
+            .collect()
+    }
+}
+
+struct TableWriter<'a> {
+    options: &'a OutputOptions,
+}
+
+impl OutputWriter for TableWriter<'_> {
+    fn write(&self, out: &mut dyn Write, table: &Table) -> Result<()> {
+        let header: Vec<Vec<u8>> = table
+            .columns
+            .iter()
+            .map(|c| c.clone().into_bytes())
+            .collect();
+        let rendered: Vec<Vec<Vec<u8>>> = table
+            .rows
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|v| render_display(v, self.options))
+                    .collect()
+            })
+            .collect();
+        let width = |cell: &[u8]| String::from_utf8_lossy(cell).chars().count();
+        let mut widths: Vec<usize> = header.iter().map(|c| width(c)).collect();
+        for row in &rendered {
+            for (w, cell) in widths.iter_mut().zip(row) {
+                *w = (*w).max(width(cell));
+            }
+        }
+
+        let numeric = table.numeric_columns();
+        for row in std::iter::once(&header).chain(&rendered) {
+            let mut line = Vec::new();
+            for (i, cell) in row.iter().enumerate() {
+                if i > 0 {
+                    line.push(b' ');
+                }
+                let pad = vec![b' '; widths[i] - width(cell)];
+                if numeric[i] {
+                    line.extend_from_slice(&pad);
+                    line.extend_from_slice(cell);
+                } else {
+                    line.extend_from_slice(cell);
+                    line.extend_from_slice(&pad);
+                }
+            }
+            while line.last() == Some(&b' ') {
+                line.pop();
+            }
+            line.push(b'\n');
+            out.write_all(&line)?;
+        }
+        Ok(())
+    }
+}
+
+struct ListWriter<'a> {
+    null: &'a str,
+}
+
+impl RowWriter for ListWriter<'_> {
+    fn row(
+        &self,
+        out: &mut dyn Write,
+        _columns: &[String],
+        _index: u64,
+        row: &[Value],
+    ) -> Result<()> {
+        for (i, value) in row.iter().enumerate() {
+            if i > 0 {
+                out.write_all(b"|")?;
+            }
+            match value {
+                Value::Text(s) => out.write_all(s.as_bytes())?,
+                Value::RawText(b) | Value::Blob(b) => out.write_all(b)?,
+                v => out.write_all(render(v, self.null).as_bytes())?,
+            }
+        }
+        out.write_all(b"\n")?;
+        Ok(())
+    }
+}
+
+struct TsvWriter<'a> {
+    null: &'a str,
+}
+
+impl RowWriter for TsvWriter<'_> {
+    fn begin(&self, out: &mut dyn Write, columns: &[String]) -> Result<()> {
+        let header: Vec<String> = columns.iter().map(|c| tsv_escape(c)).collect();
+        writeln!(out, "{}", header.join("\t"))?;
+        Ok(())
+    }
+
+    fn row(
+        &self,
+        out: &mut dyn Write,
+        _columns: &[String],
+        _index: u64,
+        row: &[Value],
+    ) -> Result<()> {
+        // invalid UTF-8 kept by the raw policy goes out as stored
+        let cells: Vec<Vec<u8>> = row
+            .iter()
+            .map(|v| match v {
+                Value::RawText(bytes) => bytes
+                    .utf8_chunks()
+                    .flat_map(|chunk| {
+                        let mut cell = tsv_escape(chunk.valid()).into_bytes();
+                        cell.extend_from_slice(chunk.invalid());
+                        cell
+                    })
+                    .collect(),
+                v => tsv_escape(&render(v, self.null)).into_bytes(),
+            })
+            .collect();
+        out.write_all(&cells.join(&b'\t'))?;
+        out.write_all(b"\n")?;
+        Ok(())
+    }
+}
+
+fn tsv_escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+struct MarkdownWriter<'a> {
+    null: &'a str,
+}
+
+impl OutputWriter for MarkdownWriter<'_> {
+    fn write(&self, out: &mut dyn Write, table: &Table) -> Result<()> {
+        let escape = |s: &str| {
+            s.replace('|', "\\|")
+                .replace("\r\n", "<br>")
+                .replace('\n', "<br>")
+        };
+        let line = |cells: Vec<String>| format!("| {} |", cells.join(" | "));
+
+        writeln!(
+            out,
+            "{}",
+            line(table.columns.iter().map(|c| escape(c)).collect())
+        )?;
+        let separator = table
+            .numeric_columns()
+            .iter()
+            .map(|&numeric| if numeric { "---:" } else { ":---" }.to_string())
+            .collect();
+        writeln!(out, "{}", line(separator))?;
+        for row in &table.rows {
+            writeln!(
+                out,
+                "{}",
+                line(row.iter().map(|v| escape(&render(v, self.null))).collect())
+            )?;
+        }
+        Ok(())
+    }
+}
+
+struct QuoteWriter;
+
+impl RowWriter for QuoteWriter {
+    fn row(
+        &self,
+        out: &mut dyn Write,
+        _columns: &[String],
+        _index: u64,
+        row: &[Value],
+    ) -> Result<()> {
+        let cells: Vec<String> = row.iter().map(literal).collect();
+        writeln!(out, "{}", cells.join(","))?;
+        Ok(())
+    }
+}
+
+struct InsertWriter<'a> {
+    table: &'a str,
+}
+
+impl RowWriter for InsertWriter<'_> {
+    fn row(
+        &self,
+        out: &mut dyn Write,
+        _columns: &[String],
+        _index: u64,
+        row: &[Value],
+    ) -> Result<()> {
+        let cells: Vec<String> = row.iter().map(literal).collect();
+        writeln!(
+            out,
+            "INSERT INTO {} VALUES({});",
+            quote_identifier(self.table),
+            cells.join(",")
+        )?;
+        Ok(())
+    }
+}
+
+struct LineWriter<'a> {
+    null: &'a str,
+}
+
+impl RowWriter for LineWriter<'_> {
+    fn row(
+        &self,
+        out: &mut dyn Write,
+        columns: &[String],
+        index: u64,
+        row: &[Value],
+    ) -> Result<()> {
+        let width = columns.iter().map(|c| c.chars().count()).max().unwrap_or(0);
+        if index > 0 {
+            writeln!(out)?;
+        }
+        for (column, value) in columns.iter().zip(row) {
+            let value = render(value, self.null);
+            writeln!(out, "{:>width$} = {}", column, value, width = width)?;
+        }
+        Ok(())
+    }
+}
+
+struct NdjsonWriter;
+
+impl RowWriter for NdjsonWriter {
+    fn row(
+        &self,
+        out: &mut dyn Write,
+        columns: &[String],
+        _index: u64,
+        row: &[Value],
+    ) -> Result<()> {
+        let fields: Vec<String> = columns
+            .iter()
+            .zip(row)
+            .map(|(column, value)| format!("{}:{}", json::string(column), json::value(value)))
+            .collect();
+        writeln!(out, "{{{}}}", fields.join(","))?;
+        Ok(())
+    }
+
+    /// The last line, so a consumer can tell a complete result from one cut off
+    fn end(&self, out: &mut dyn Write, rows: u64, notes: &[String]) -> Result<()> {
+        let notes: Vec<String> = notes.iter().map(|n| json::string(n)).collect();
+        writeln!(
+            out,
+            "{{\"summary\":{{\"rows\":{},\"notes\":[{}]}}}}",
+            rows,
+            notes.join(",")
+        )?;
+        Ok(())
+    }
+}
+
+/// Output for commands whose rows may not fit in memory. Formats that lay out each row on
+/// its own write and flush rows as they are pushed, so a slow reader holds the command back
+/// rather than letting rows pile up; table and markdown line rows up against each other and
+/// collect them as [`Table`] does.
+pub struct RowStream<'a> {
+    options: &'a OutputOptions,
+    writer: Option<Box<dyn RowWriter + 'a>>,
+    table: Table,
+    rows: u64,
+}
+
+impl<'a> RowStream<'a> {
+    pub fn new(columns: &[&str], options: &'a OutputOptions) -> Self {
+        RowStream {
+            options,
+            writer: options.format.row_writer(options),
+            table: Table::new(columns),
+            rows: 0,
+        }
+    }
+
+    pub fn push(&mut self, row: Vec<Value>) -> Result<()> {
+        let Some(writer) = &self.writer else {
+            self.rows += 1;
+            self.table.push(row);
+            return Ok(());
+        };
+        let mut out = self.options.sink.lock();
+        if self.rows == 0 {
+            writer.begin(&mut out, &self.table.columns)?;
+        }
+        writer.row(&mut out, &self.table.columns, self.rows, &row)?;
+        out.flush()?;
+        self.rows += 1;
+        Ok(())
+    }
+
+    /// Ends the output with the outcome of producing the rows, which is passed on. Streamed
+    /// rows are out already, so an error that cut them short is marked [`Partial`] if there
+    /// were any, and NDJSON's summary notes where it struck.
+    pub fn finish(self, result: Result<()>) -> Result<()> {
+        let Some(writer) = &self.writer else {
+            result?;
+            return self.table.print(self.options);
+        };
+        let mut out = self.options.sink.lock();
+        let notes: Vec<String> = match &result {
+            Ok(()) => {
+                if self.rows == 0 {
+                    writer.begin(&mut out, &self.table.columns)?;
+                }
+                Vec::new()
+            }
+            Err(e) => vec![format!("stopped early: {:#}", e)],
+        };
+        writer.end(&mut out, self.rows, &notes)?;
+        out.flush()?;
+        if self.rows == 0 {
+            return result;
+        }
+        result.context(Partial)
+    }
+}
+
+/// A value as plain text: REALs as SQLite displays them, NULL as the chosen token
+fn render(value: &Value, null: &str) -> String {
+    match value {
+        Value::Null => null.to_string(),
+        Value::Integer(i) => i.to_string(),
+        Value::Real(f) => format_real(*f),
+        Value::Text(s) => s.clone(),
+        Value::RawText(b) => String::from_utf8_lossy(b).into_owned(),
+        Value::Blob(b) => format!("x'{}'", hex(b)),
+    }
+}
+
+/// Rendering for the terminal: control characters are escaped, BLOBs shown as hex and long
+/// values elided, unless `--raw` asks for the bytes exactly as stored
+fn render_display(value: &Value, options: &OutputOptions) -> Vec<u8> {
+    if options.raw {
+        return match value {
+            Value::Text(s) => s.clone().into_bytes(),
+            Value::RawText(b) | Value::Blob(b) => b.clone(),
+            v => render(v, &options.null_value).into_bytes(),
+        };
+    }
+
+    let max = options.max_col_width.max(1);
+    let text = match value {
+        Value::Text(s) => escape_control(s),
+        // show the bytes that aren't UTF-8 rather than replacement characters
+        Value::RawText(b) => b
+            .utf8_chunks()
+            .map(|chunk| {
+                let invalid: String = chunk
+                    .invalid()
+                    .iter()
+                    .map(|b| format!("\\x{:02x}", b))
+                    .collect();
+                escape_control(chunk.valid()) + &invalid
+            })
+            .collect(),
+        // no point hex-encoding more of the blob than can be shown
+        Value::Blob(b) => format!("x'{}'", hex(&b[..b.len().min(max / 2 + 1)])),
+        v => render(v, &options.null_value),
+    };
+    let elided = match value {
+        Value::Blob(b) => text.len() > max || b.len() > max / 2 + 1,
+        _ => text.chars().count() > max,
+    };
+    if elided {
+        let mut cut: String = text.chars().take(max - 1).collect();
+        cut.push('…');
+        cut.into_bytes()
+    } else {
+        text.into_bytes()
+    }
+}
+
+fn escape_control(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => out.push_str(&format!("\\x{:02x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn display(value: Value, max_col_width: usize) -> String {
+        let options = OutputOptions {
+            max_col_width,
+            ..OutputOptions::default()
+        };
+        String::from_utf8(render_display(&value, &options)).unwrap()
+    }
+
+    #[test]
+    fn long_cells_are_cut_with_an_ellipsis() {
+        assert_eq!(display(Value::Text("abcdef".to_string()), 6), "abcdef");
+        assert_eq!(display(Value::Text("abcdefg".to_string()), 6), "abcde…");
+        // the width is counted in characters, not bytes
+        assert_eq!(display(Value::Text("ééééééé".to_string()), 6), "ééééé…");
+        assert_eq!(display(Value::Blob(vec![0xab; 100]), 10), "x'abababa…");
+    }
+
+    #[test]
+    fn control_characters_and_blobs_are_escaped() {
+        assert_eq!(
+            display(Value::Text("a\tb\nc\r\u{7}".to_string()), 80),
+            "a\\tb\\nc\\r\\x07"
+        );
+        assert_eq!(display(Value::Blob(vec![0, 0xff, b'a']), 80), "x'00ff61'");
+    }
+
+    #[test]
+    fn raw_values_are_written_as_stored() {
+        let options = OutputOptions {
+            raw: true,
+            max_col_width: 2,
+            ..OutputOptions::default()
+        };
+        let text = Value::Text("a\nbcd".to_string());
+        assert_eq!(render_display(&text, &options), b"a\nbcd");
+        let blob = Value::Blob(vec![0, 0xff]);
+        assert_eq!(render_display(&blob, &options), [0, 0xff]);
+    }
+
+    #[test]
+    fn columns_are_as_wide_as_their_cut_cells() {
+        let mut table = Table::new(&["n", "text"]);
+        table.push(vec![Value::Integer(1), Value::Text("x".repeat(10))]);
+        table.push(vec![Value::Integer(20), Value::Text("y\n".to_string())]);
+        let options = OutputOptions {
+            max_col_width: 4,
+            ..OutputOptions::default()
+        };
+        let mut out = Vec::new();
+        Format::Table
+            .writer(&options)
+            .write(&mut out, &table)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            " n text\n 1 xxx…\n20 y\\n\n"
+        );
+    }
+
+    fn written(format: Format, table: &Table) -> String {
+        let options = OutputOptions {
+            insert_table: "my table".to_string(),
+            ..OutputOptions::default()
+        };
+        let mut out = Vec::new();
+        format.writer(&options).write(&mut out, table).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn sql_literal_formats() {
+        let mut table = Table::new(&["id", "name", "score", "data"]);
+        table.push(vec![
+            Value::Integer(1),
+            Value::Text("O'Brien".to_string()),
+            Value::Real(2.5),
+            Value::Blob(vec![0, 0xff]),
+        ]);
+        table.push(vec![
+            Value::Integer(2),
+            Value::Null,
+            Value::Null,
+            Value::Null,
+        ]);
+        assert_eq!(
+            written(Format::Quote, &table),
+            "1,'O''Brien',2.5,X'00FF'\n2,NULL,NULL,NULL\n"
+        );
+        assert_eq!(
+            written(Format::Insert, &table),
+            "INSERT INTO \"my table\" VALUES(1,'O''Brien',2.5,X'00FF');\n\
+             INSERT INTO \"my table\" VALUES(2,NULL,NULL,NULL);\n"
+        );
+    }
+
+    #[test]
+    fn rows_stream_unless_the_format_lines_them_up() {
+        let dir = std::env::temp_dir().join(format!("sqliter-stream-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for (format, first, total) in [
+            (Format::List, "1|a\n", "1|a\n2|b\n"),
+            (Format::Tsv, "n\ts\n1\ta\n", "n\ts\n1\ta\n2\tb\n"),
+            (
+                Format::Line,
+                "n = 1\ns = a\n",
+                "n = 1\ns = a\n\nn = 2\ns = b\n",
+            ),
+            (Format::Table, "", "n s\n1 a\n2 b\n"),
+            (
+                Format::Markdown,
+                "",
+                "| n | s |\n| ---: | :--- |\n| 1 | a |\n| 2 | b |\n",
+            ),
+        ] {
+            let redirect = Redirect::create(&dir.join("out")).unwrap();
+            let options = OutputOptions {
+                format,
+                sink: redirect.sink(),
+                ..OutputOptions::default()
+            };
+            let mut stream = RowStream::new(&["n", "s"], &options);
+            stream
+                .push(vec![Value::Integer(1), Value::Text("a".to_string())])
+                .unwrap();
+            assert_eq!(std::fs::read_to_string(&redirect.temp).unwrap(), first);
+            stream
+                .push(vec![Value::Integer(2), Value::Text("b".to_string())])
+                .unwrap();
+            stream.finish(Ok(())).unwrap();
+            redirect.finish().unwrap();
+            assert_eq!(std::fs::read_to_string(dir.join("out")).unwrap(), total);
+        }
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn streams_cut_short_are_partial_once_rows_are_out() {
+        let dir = std::env::temp_dir().join(format!("sqliter-partial-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let redirect = Redirect::create(&dir.join("out")).unwrap();
+        let options = OutputOptions {
+            format: Format::Tsv,
+            sink: redirect.sink(),
+            ..OutputOptions::default()
+        };
+        let failed = || Err(anyhow::anyhow!("corrupt file"));
+        let stream = RowStream::new(&["n"], &options);
+        assert!(stream
+            .finish(failed())
+            .unwrap_err()
+            .downcast_ref::<Partial>()
+            .is_none());
+        let mut stream = RowStream::new(&["n"], &options);
+        stream.push(vec![Value::Integer(1)]).unwrap();
+        assert!(stream
+            .finish(failed())
+            .unwrap_err()
+            .downcast_ref::<Partial>()
+            .is_some());
+        drop(redirect);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,231 @@
+use anyhow::Result;
+use sqliter::pages::{PageKind, PageRef};
+use sqliter::Database;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// Pages per row in the SVG grid
+const GRID_COLUMNS: u32 = 64;
+const CELL_SIZE: u32 = 12;
+
+/// Distinct hues handed out to b-trees in root-page order
+const PALETTE: &[&str] = &[
+    "#4e79a7", "#f28e2b", "#59a14f", "#b07aa1", "#76b7b2", "#edc948", "#ff9da7", "#9c755f",
+    "#e15759", "#86bcb6", "#8cd17d", "#d4a6c8",
+];
+
+/// Names of the b-trees in the file, keyed by root page
+pub fn tree_names(db: &Database) -> Result<HashMap<u32, String>> {
+    let mut names = HashMap::from([(1, "sqlite_schema".to_string())]);
+    for entry in db.schema()? {
+        if entry.root_page != 0 {
+            names.insert(entry.root_page, entry.name);
+        }
+    }
+    Ok(names)
+}
+
+fn color(kind: PageKind, root: Option<u32>, roots: &[u32]) -> &'static str {
+    match (kind, root) {
+        (PageKind::FreelistTrunk | PageKind::FreelistLeaf, _) => "#d9d9d9",
+        (PageKind::PointerMap | PageKind::LockByte, _) => "#595959",
+        (PageKind::Orphan, _) => "#ff0000",
+        (_, Some(root)) => {
+            let i = roots.iter().position(|&r| r == root).unwrap_or(0);
+            PALETTE[i % PALETTE.len()]
+        }
+        (_, None) => "#000000",
+    }
+}
+
+fn describe(page: &PageRef, names: &HashMap<u32, String>) -> String {
+    match page.root().and_then(|r| names.get(&r)) {
+        Some(name) => format!(
+            "page {}: {} ({})",
+            page.number(),
+            page.kind().as_str(),
+            name
+        ),
+        None => format!("page {}: {}", page.number(), page.kind().as_str()),
+    }
+}
+
+/// A grid with one square per page, coloured by owning b-tree. Overflow pages share their
+/// tree's colour at reduced opacity so long chains stand out from the tree itself.
+pub fn render_svg(db: &Database) -> Result<String> {
+    let names = tree_names(db)?;
+    let mut roots: Vec<u32> = names.keys().copied().collect();
+    roots.sort_unstable();
+
+    let rows = db.page_count().div_ceil(GRID_COLUMNS);
+    let legend_height = 16 * (roots.len() as u32 + 4);
+    let width = GRID_COLUMNS * CELL_SIZE;
+    let height = rows * CELL_SIZE + legend_height + 8;
+
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" font-family="monospace" font-size="12">"#,
+        width, height
+    )?;
+    for page in db.pages() {
+        let page = page?;
+        let i = page.number() - 1;
+        let opacity = if page.kind() == PageKind::Overflow {
+            0.45
+        } else {
+            1.0
+        };
+        writeln!(
+            svg,
+            r#"<rect x="{}" y="{}" width="{}" height="{}" fill="{}" fill-opacity="{}" stroke="white" stroke-width="1"><title>{}</title></rect>"#,
+            (i % GRID_COLUMNS) * CELL_SIZE,
+            (i / GRID_COLUMNS) * CELL_SIZE,
+            CELL_SIZE,
+            CELL_SIZE,
+            color(page.kind(), page.root(), &roots),
+            opacity,
+            escape_xml(&describe(&page, &names)),
+        )?;
+    }
+
+    let mut legend: Vec<(&str, String)> = roots
+        .iter()
+        .map(|r| {
+            (
+                color(PageKind::TableLeaf, Some(*r), &roots),
+                names[r].clone(),
+            )
+        })
+        .collect();
+    legend.push((
+        color(PageKind::FreelistLeaf, None, &roots),
+        "freelist".to_string(),
+    ));
+    legend.push((
+        color(PageKind::PointerMap, None, &roots),
+        "pointer map / lock byte".to_string(),
+    ));
+    legend.push((color(PageKind::Orphan, None, &roots), "orphan".to_string()));
+    let top = rows * CELL_SIZE + 8;
+    for (i, (fill, label)) in legend.iter().enumerate() {
+        let y = top + 16 * i as u32;
+        writeln!(
+            svg,
+            r#"<rect x="0" y="{}" width="{}" height="{}" fill="{}"/><text x="{}" y="{}">{}</text>"#,
+            y,
+            CELL_SIZE,
+            CELL_SIZE,
+            fill,
+            CELL_SIZE + 6,
+            y + CELL_SIZE - 2,
+            escape_xml(label),
+        )?;
+    }
+    writeln!(svg, "</svg>")?;
+    Ok(svg)
+}
+
+/// The b-tree structure as a Graphviz digraph: one cluster per tree, edges from parents to
+/// children and along overflow chains
+pub fn render_dot(db: &Database) -> Result<String> {
+    let names = tree_names(db)?;
+    let mut clusters: HashMap<Option<u32>, Vec<String>> = HashMap::new();
+    let mut edges = Vec::new();
+
+    for page in db.pages() {
+        let page = page?;
+        let n = page.number();
+        let shape = match page.kind() {
+            PageKind::TableInterior | PageKind::IndexInterior => "box",
+            PageKind::Overflow => "note",
+            PageKind::Orphan => "octagon",
+            _ => "ellipse",
+        };
+        clusters.entry(page.root()).or_default().push(format!(
+            r#"p{} [label="{}\n{}" shape={}];"#,
+            n,
+            n,
+            page.kind().as_str(),
+            shape
+        ));
+
+        let mut children = Vec::new();
+        let mut overflow = Vec::new();
+        if let Some(interior) = page.as_table_interior() {
+            children.extend(
+                interior
+                    .cells()
+                    .filter_map(|c| c.ok())
+                    .map(|(child, _)| child),
+            );
+            children.push(interior.right_child());
+        } else if let Some(interior) = page.as_index_interior() {
+            for (child, payload) in interior.cells().filter_map(|c| c.ok()) {
+                children.push(child);
+                overflow.extend(payload.first_overflow);
+            }
+            children.push(interior.right_child());
+        } else if let Some(leaf) = page.as_table_leaf() {
+            overflow.extend(
+                leaf.cells()
+                    .filter_map(|c| c.ok())
+                    .filter_map(|(_, p)| p.first_overflow),
+            );
+        } else if let Some(leaf) = page.as_index_leaf() {
+            overflow.extend(
+                leaf.cells()
+                    .filter_map(|c| c.ok())
+                    .filter_map(|p| p.first_overflow),
+            );
+        } else if let Some(next) = page.as_overflow().and_then(|o| o.next()) {
+            overflow.push(next);
+        } else if let Some(trunk) = page.as_freelist_trunk() {
+            edges.extend(
+                trunk
+                    .next_trunk()
+                    .map(|t| format!("p{} -> p{} [style=dashed];", n, t)),
+            );
+        }
+        edges.extend(children.iter().map(|c| format!("p{} -> p{};", n, c)));
+        edges.extend(
+            overflow
+                .iter()
+                .map(|o| format!("p{} -> p{} [style=dotted];", n, o)),
+        );
+    }
+
+    let mut keys: Vec<Option<u32>> = clusters.keys().copied().collect();
+    keys.sort_unstable();
+    let mut dot = String::from("digraph pagemap {\n  node [fontname=monospace fontsize=10];\n");
+    for key in keys {
+        let nodes = &clusters[&key];
+        match key.and_then(|r| names.get(&r).map(|name| (r, name))) {
+            Some((root, name)) => {
+                writeln!(dot, "  subgraph cluster_{} {{", root)?;
+                writeln!(dot, "    label=\"{}\";", name.replace('"', "\\\""))?;
+                for node in nodes {
+                    writeln!(dot, "    {}", node)?;
+                }
+                writeln!(dot, "  }}")?;
+            }
+            None => {
+                for node in nodes {
+                    writeln!(dot, "  {}", node)?;
+                }
+            }
+        }
+    }
+    for edge in edges {
+        writeln!(dot, "  {}", edge)?;
+    }
+    dot.push_str("}\n");
+    Ok(dot)
+}
+
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
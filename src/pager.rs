@@ -0,0 +1,406 @@
+use crate::header::{DbHeader, HEADER_SIZE};
+use crate::wal::{Wal, FRAME_HEADER_SIZE};
+use crate::{Error, Result};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Pages kept in memory when the caller doesn't choose a cache size (8 MiB at 4 KiB pages)
+pub const DEFAULT_CACHE_PAGES: usize = 2000;
+
+/// Most pages read ahead at once when a scan reads the file in order, unless changed with
+/// [`Pager::set_readahead`]
+pub const DEFAULT_READAHEAD_PAGES: u32 = 32;
+
+/// Independent locks so concurrent readers rarely contend on the same one
+const CACHE_SHARDS: usize = 16;
+
+/// A raw database page as read from disk
+#[derive(Debug, Clone)]
+pub struct Page {
+    number: u32,
+    data: Arc<[u8]>,
+}
+
+impl Page {
+    pub fn number(&self) -> u32 {
+        self.number
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Page 1 starts with the database header, so its b-tree header is found 100 bytes in
+    pub fn btree_header_offset(&self) -> usize {
+        if self.number == 1 {
+            HEADER_SIZE
+        } else {
+            0
+        }
+    }
+}
+
+/// Reads fixed-size pages out of the database file through a shared page cache. In WAL mode
+/// pages are read as of the last transaction committed to the WAL when the pager was opened,
+/// as SQLite's own readers would see them.
+#[derive(Debug)]
+pub struct Pager {
+    file: File,
+    /// Where page 1 starts in the file: 0, unless the database is embedded in another file
+    base: u64,
+    page_size: u32,
+    page_count: u32,
+    /// Whole pages in the file or its WAL, which may be more than the database uses
+    file_pages: u32,
+    wal: Option<WalSnapshot>,
+    cache: PageCache,
+    readahead: Readahead,
+}
+
+/// Spots cache misses on consecutive pages, as when a scan walks leaves that were written in
+/// order, and reads ever larger runs of the pages that follow in a single call
+#[derive(Debug)]
+struct Readahead {
+    /// Most pages read at once; 0 or 1 turns readahead off
+    limit: AtomicU32,
+    /// The last page read from the file on a cache miss
+    last: AtomicU32,
+    /// Pages the next sequential miss reads
+    window: AtomicU32,
+}
+
+/// Where the newest committed image of each page in the WAL is
+#[derive(Debug)]
+struct WalSnapshot {
+    file: File,
+    /// Offset of each page's image in the WAL file
+    pages: HashMap<u32, u64>,
+    /// Database size in pages as of the last commit
+    db_size: u32,
+}
+
+impl WalSnapshot {
+    /// Reads the committed frames of the database's WAL, if it is in WAL mode and has one.
+    /// SQLite ignores a WAL it can't use, and so does this.
+    fn open(db_path: &Path, header: &DbHeader) -> Result<Option<WalSnapshot>> {
+        if header.read_version != 2 && header.write_version != 2 {
+            return Ok(None);
+        }
+        let path = Wal::path_for(db_path);
+        let wal = match Wal::open(&path) {
+            Ok(wal) => wal,
+            Err(Error::Io(e)) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(Error::NotAWal(_) | Error::Corrupt(_)) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let db_size = match wal.last_commit() {
+            Some(commit) if wal.header.page_size == header.page_size => commit.db_size,
+            _ => return Ok(None),
+        };
+        let pages = wal
+            .committed_frames()
+            .filter(|f| !f.superseded)
+            .map(|f| (f.page_number, f.offset + FRAME_HEADER_SIZE as u64))
+            .collect();
+        Ok(Some(WalSnapshot {
+            file: File::open(&path)?,
+            pages,
+            db_size,
+        }))
+    }
+}
+
+/// The trailer SQLite's append VFS writes after a database appended to another file: this
+/// mark, then the offset of the database as an 8-byte big-endian integer
+const APPEND_MARK: &[u8] = b"Start-Of-SQLite3-";
+const APPEND_TRAILER_SIZE: u64 = APPEND_MARK.len() as u64 + 8;
+
+/// Where a database appended by SQLite's append VFS starts and ends in `file`, or `None` when
+/// the file doesn't end with its trailer
+fn appended_database(file: &File, len: u64) -> io::Result<Option<(u64, u64)>> {
+    if len < APPEND_TRAILER_SIZE {
+        return Ok(None);
+    }
+    let mut trailer = [0u8; APPEND_TRAILER_SIZE as usize];
+    read_exact_at(file, &mut trailer, len - APPEND_TRAILER_SIZE)?;
+    let (mark, offset) = trailer.split_at(APPEND_MARK.len());
+    if mark != APPEND_MARK {
+        return Ok(None);
+    }
+    let offset = u64::from_be_bytes(offset.try_into().expect("the offset is 8 bytes"));
+    let end = len - APPEND_TRAILER_SIZE;
+    Ok((offset < end).then_some((offset, end)))
+}
+
+impl Pager {
+    /// Opens the file with a cache of [`DEFAULT_CACHE_PAGES`] pages. A file that doesn't start
+    /// with a database header but was written by SQLite's append VFS is read from where its
+    /// trailer says the database starts.
+    pub fn open(path: &Path) -> Result<(Pager, DbHeader)> {
+        Self::open_at(path, None)
+    }
+
+    /// Opens a database that starts `offset` bytes into the file, such as one embedded in an
+    /// executable or archive, or looks for it as [`open`](Self::open) does when `None`
+    pub fn open_at(path: &Path, offset: Option<u64>) -> Result<(Pager, DbHeader)> {
+        let file = File::open(path)?;
+        let len = file.metadata()?.len();
+        let mut raw = [0u8; HEADER_SIZE];
+        let (base, end) = match offset {
+            Some(offset) => (offset, len),
+            None => {
+                let starts_with_header = read_exact_at(&file, &mut raw, 0).is_ok()
+                    && raw.starts_with(b"SQLite format 3\0");
+                match appended_database(&file, len)? {
+                    Some(found) if !starts_with_header => found,
+                    _ => (0, len),
+                }
+            }
+        };
+        read_exact_at(&file, &mut raw, base).map_err(|_| Error::NotADatabase)?;
+        let mut header = DbHeader::parse(&raw)?;
+        let wal = WalSnapshot::open(path, &header)?;
+
+        let file_pages = (end.saturating_sub(base) / header.page_size as u64).min(u32::MAX as u64);
+        let mut file_pages = file_pages as u32;
+        let page_count = match &wal {
+            // a committed transaction records the database size, and may have rewritten the
+            // header along with the rest of page 1
+            Some(wal) => {
+                if let Some(&offset) = wal.pages.get(&1) {
+                    read_exact_at(&wal.file, &mut raw, offset)?;
+                    header = DbHeader::parse(&raw)?;
+                }
+                file_pages = file_pages.max(wal.db_size);
+                wal.db_size
+            }
+            // The in-header page count is only valid if written by a version that maintains
+            // it; otherwise fall back to the file size like SQLite does. Pages past the end of
+            // the file can't be read, so a larger count is never believed.
+            None => match header.valid_page_count() {
+                Some(count) => count.min(file_pages),
+                None => file_pages,
+            },
+        };
+
+        let pager = Pager {
+            file,
+            base,
+            page_size: header.page_size,
+            page_count,
+            file_pages,
+            wal,
+            cache: PageCache::new(DEFAULT_CACHE_PAGES),
+            readahead: Readahead {
+                limit: AtomicU32::new(DEFAULT_READAHEAD_PAGES),
+                last: AtomicU32::new(0),
+                window: AtomicU32::new(1),
+            },
+        };
+        Ok((pager, header))
+    }
+
+    pub fn page_size(&self) -> u32 {
+        self.page_size
+    }
+
+    /// Byte offset of the database within its file
+    pub fn base_offset(&self) -> u64 {
+        self.base
+    }
+
+    pub fn page_count(&self) -> u32 {
+        self.page_count
+    }
+
+    /// Whole pages in the file, or the database size recorded in the WAL if that is larger
+    pub fn file_pages(&self) -> u32 {
+        self.file_pages
+    }
+
+    /// Changes how many pages the cache may hold
+    pub fn resize_cache(&self, pages: usize) {
+        self.cache.resize(pages);
+    }
+
+    /// Changes how many pages at most are read ahead of a sequential scan; 0 turns it off
+    pub fn set_readahead(&self, pages: u32) {
+        self.readahead.limit.store(pages, Ordering::Relaxed);
+    }
+
+    pub fn read_page(&self, number: u32) -> Result<Page> {
+        if number == 0 || number > self.page_count {
+            return Err(Error::Corrupt(format!(
+                "page {} is out of range (database has {} pages)",
+                number, self.page_count
+            )));
+        }
+        if let Some(data) = self.cache.get(number) {
+            return Ok(Page { number, data });
+        }
+
+        if let Some(wal) = &self.wal {
+            if let Some(&offset) = wal.pages.get(&number) {
+                let mut data = vec![0u8; self.page_size as usize];
+                read_exact_at(&wal.file, &mut data, offset)?;
+                let data: Arc<[u8]> = data.into();
+                self.cache.insert(number, data.clone());
+                return Ok(Page { number, data });
+            }
+        }
+
+        // a run ends before the first page whose newest image is in the WAL
+        let run = self.readahead_run(number);
+        let run = (1..run)
+            .find(|&i| {
+                self.wal
+                    .as_ref()
+                    .is_some_and(|wal| wal.pages.contains_key(&(number + i)))
+            })
+            .unwrap_or(run);
+        let page_size = self.page_size as usize;
+        let mut buf = vec![0u8; page_size * run as usize];
+        let offset = self.base + (number as u64 - 1) * self.page_size as u64;
+        read_exact_at(&self.file, &mut buf, offset)?;
+        self.readahead
+            .last
+            .store(number + run - 1, Ordering::Relaxed);
+
+        let mut requested = None;
+        for (i, chunk) in buf.chunks_exact(page_size).enumerate() {
+            let data: Arc<[u8]> = chunk.into();
+            if i == 0 {
+                requested = Some(data.clone());
+            }
+            self.cache.insert(number + i as u32, data);
+        }
+        let data = requested.expect("a run has at least one page");
+        Ok(Page { number, data })
+    }
+
+    /// Pages to read from the file starting at `number`, which missed the cache: just the
+    /// one unless it continues the previous read, in which case the window doubles up to the
+    /// readahead limit. The run stays within the database and well within the cache.
+    fn readahead_run(&self, number: u32) -> u32 {
+        let limit = self
+            .readahead
+            .limit
+            .load(Ordering::Relaxed)
+            .min((self.cache.capacity() / 4).min(u32::MAX as usize) as u32);
+        let sequential = number > 1 && self.readahead.last.load(Ordering::Relaxed) == number - 1;
+        let window = if sequential && limit > 1 {
+            (self.readahead.window.load(Ordering::Relaxed) * 2).clamp(2, limit)
+        } else {
+            1
+        };
+        self.readahead.window.store(window, Ordering::Relaxed);
+        window
+            .min(self.file_pages.min(self.page_count) - number + 1)
+            .max(1)
+    }
+}
+
+/// A page cache split into independently locked shards, each evicting its least recently
+/// used page when full
+#[derive(Debug)]
+struct PageCache {
+    shards: Vec<Mutex<CacheShard>>,
+    shard_capacity: AtomicUsize,
+}
+
+#[derive(Debug, Default)]
+struct CacheShard {
+    pages: HashMap<u32, (Arc<[u8]>, u64)>,
+    clock: u64,
+}
+
+impl PageCache {
+    fn new(capacity: usize) -> PageCache {
+        PageCache {
+            shards: (0..CACHE_SHARDS).map(|_| Mutex::default()).collect(),
+            shard_capacity: AtomicUsize::new(capacity.div_ceil(CACHE_SHARDS)),
+        }
+    }
+
+    fn resize(&self, capacity: usize) {
+        self.shard_capacity
+            .store(capacity.div_ceil(CACHE_SHARDS), Ordering::Relaxed);
+    }
+
+    /// Pages the whole cache may hold
+    fn capacity(&self) -> usize {
+        self.shard_capacity() * CACHE_SHARDS
+    }
+
+    fn shard_capacity(&self) -> usize {
+        self.shard_capacity.load(Ordering::Relaxed)
+    }
+
+    fn shard(&self, number: u32) -> std::sync::MutexGuard<'_, CacheShard> {
+        // a panic while holding the lock can't leave the map half-updated, so keep going
+        self.shards[number as usize % CACHE_SHARDS]
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn get(&self, number: u32) -> Option<Arc<[u8]>> {
+        if self.shard_capacity() == 0 {
+            return None;
+        }
+        let mut shard = self.shard(number);
+        shard.clock += 1;
+        let clock = shard.clock;
+        shard.pages.get_mut(&number).map(|(data, used)| {
+            *used = clock;
+            data.clone()
+        })
+    }
+
+    fn insert(&self, number: u32, data: Arc<[u8]>) {
+        let capacity = self.shard_capacity();
+        let mut shard = self.shard(number);
+        if capacity == 0 {
+            shard.pages.clear();
+            return;
+        }
+        // a loop rather than a single eviction so a shrunken cache catches up
+        while shard.pages.len() >= capacity && !shard.pages.contains_key(&number) {
+            let oldest = shard
+                .pages
+                .iter()
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(n, _)| *n);
+            match oldest {
+                Some(oldest) => shard.pages.remove(&oldest),
+                None => break,
+            };
+        }
+        shard.clock += 1;
+        let clock = shard.clock;
+        shard.pages.insert(number, (data, clock));
+    }
+}
+
+#[cfg(unix)]
+fn read_exact_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<()> {
+    std::os::unix::fs::FileExt::read_exact_at(file, buf, offset)
+}
+
+#[cfg(windows)]
+fn read_exact_at(file: &File, mut buf: &mut [u8], mut offset: u64) -> io::Result<()> {
+    use std::os::windows::fs::FileExt;
+    while !buf.is_empty() {
+        match file.seek_read(buf, offset)? {
+            0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+            n => {
+                buf = &mut buf[n..];
+                offset += n as u64;
+            }
+        }
+    }
+    Ok(())
+}
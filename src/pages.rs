@@ -0,0 +1,431 @@
+use crate::btree::{BTreePage, Cell, PageType, Payload};
+use crate::db::{Database, SCHEMA_ROOT_PAGE};
+use crate::pager::Page;
+use crate::schema::ObjectType;
+use crate::Result;
+
+/// The lock-byte page covers the 512 bytes at offset 2^30 and never holds data
+pub const LOCK_BYTE_OFFSET: u64 = 1 << 30;
+
+/// What a page is used for, as discovered by walking the schema, the b-trees and the freelist
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageKind {
+    TableInterior,
+    TableLeaf,
+    IndexInterior,
+    IndexLeaf,
+    Overflow,
+    FreelistTrunk,
+    FreelistLeaf,
+    PointerMap,
+    LockByte,
+    /// Not reachable from the schema or the freelist
+    Orphan,
+}
+
+impl PageKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PageKind::TableInterior => "table interior",
+            PageKind::TableLeaf => "table leaf",
+            PageKind::IndexInterior => "index interior",
+            PageKind::IndexLeaf => "index leaf",
+            PageKind::Overflow => "overflow",
+            PageKind::FreelistTrunk => "freelist trunk",
+            PageKind::FreelistLeaf => "freelist leaf",
+            PageKind::PointerMap => "pointer map",
+            PageKind::LockByte => "lock byte",
+            PageKind::Orphan => "orphan",
+        }
+    }
+
+    fn from_page_type(page_type: PageType) -> PageKind {
+        match page_type {
+            PageType::InteriorTable => PageKind::TableInterior,
+            PageType::LeafTable => PageKind::TableLeaf,
+            PageType::InteriorIndex => PageKind::IndexInterior,
+            PageType::LeafIndex => PageKind::IndexLeaf,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PageInfo {
+    pub kind: PageKind,
+    /// Root page of the b-tree this page belongs to, including for overflow pages
+    pub root: Option<u32>,
+}
+
+/// Classification of every page in the file. Building it never fails: pages that can't be
+/// read or parsed are simply left as orphans, which is what forensic tools want to see.
+#[derive(Debug, Clone)]
+pub struct PageMap {
+    pages: Vec<PageInfo>,
+}
+
+impl PageMap {
+    pub fn build(db: &Database) -> PageMap {
+        let orphan = PageInfo {
+            kind: PageKind::Orphan,
+            root: None,
+        };
+        let mut map = PageMap {
+            pages: vec![orphan; db.page_count() as usize + 1],
+        };
+
+        let lock_byte_page = LOCK_BYTE_OFFSET / db.page_size() as u64 + 1;
+        if lock_byte_page <= db.page_count() as u64 {
+            map.pages[lock_byte_page as usize].kind = PageKind::LockByte;
+        }
+        // auto-vacuum databases interleave pointer-map pages starting at page 2
+        if db.header().largest_root_page != 0 {
+            let interval = db.usable_size() as usize / 5 + 1;
+            for number in (2..map.pages.len()).step_by(interval) {
+                map.pages[number].kind = PageKind::PointerMap;
+            }
+        }
+
+        map.walk_tree(db, SCHEMA_ROOT_PAGE);
+        if let Ok(schema) = db.schema() {
+            for entry in schema {
+                if matches!(entry.kind, ObjectType::Table | ObjectType::Index)
+                    && entry.root_page != 0
+                {
+                    map.walk_tree(db, entry.root_page);
+                }
+            }
+        }
+        map.walk_freelist(db);
+        map
+    }
+
+    pub fn get(&self, number: u32) -> Option<PageInfo> {
+        self.pages
+            .get(number as usize)
+            .copied()
+            .filter(|_| number != 0)
+    }
+
+    pub fn len(&self) -> usize {
+        self.pages.len() - 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Claims `number` for `info` unless it is out of range or already claimed
+    fn claim(&mut self, number: u32, info: PageInfo) -> bool {
+        match self.pages.get_mut(number as usize) {
+            Some(slot) if number != 0 && slot.kind == PageKind::Orphan => {
+                *slot = info;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn walk_tree(&mut self, db: &Database, root: u32) {
+        let mut stack = vec![root];
+        while let Some(number) = stack.pop() {
+            let Ok(page) = db.btree_page(number) else {
+                continue;
+            };
+            let info = PageInfo {
+                kind: PageKind::from_page_type(page.page_type()),
+                root: Some(root),
+            };
+            if !self.claim(number, info) {
+                continue;
+            }
+            for i in 0..page.cell_count() {
+                let Ok(cell) = page.cell(i) else {
+                    continue;
+                };
+                if let Some(payload) = cell.payload() {
+                    self.walk_overflow(db, root, payload);
+                }
+                stack.extend(cell.left_child());
+            }
+            stack.extend(page.header().right_child);
+        }
+    }
+
+    fn walk_overflow(&mut self, db: &Database, root: u32, payload: &Payload) {
+        let per_page = db.usable_size() as u64 - 4;
+        let mut remaining = payload.size.saturating_sub(payload.local.len() as u64);
+        let mut next = payload.first_overflow.unwrap_or(0);
+        let info = PageInfo {
+            kind: PageKind::Overflow,
+            root: Some(root),
+        };
+        while next != 0 && remaining > 0 && self.claim(next, info) {
+            let Ok(page) = db.read_page(next) else {
+                return;
+            };
+            next = OverflowPage::new(page, db.usable_size())
+                .next()
+                .unwrap_or(0);
+            remaining = remaining.saturating_sub(per_page);
+        }
+    }
+
+    fn walk_freelist(&mut self, db: &Database) {
+        let mut next = db.header().first_freelist_trunk;
+        let trunk = PageInfo {
+            kind: PageKind::FreelistTrunk,
+            root: None,
+        };
+        let leaf = PageInfo {
+            kind: PageKind::FreelistLeaf,
+            root: None,
+        };
+        while next != 0 && self.claim(next, trunk) {
+            let Ok(page) = db.read_page(next) else {
+                return;
+            };
+            let trunk_page = FreelistTrunkPage::new(page, db.usable_size());
+            for number in trunk_page.leaves() {
+                self.claim(number, leaf);
+            }
+            next = trunk_page.next_trunk().unwrap_or(0);
+        }
+    }
+}
+
+/// A page together with what it was found to be used for
+#[derive(Debug, Clone)]
+pub struct PageRef {
+    page: Page,
+    info: PageInfo,
+    usable_size: u32,
+}
+
+impl PageRef {
+    pub fn number(&self) -> u32 {
+        self.page.number()
+    }
+
+    pub fn data(&self) -> &[u8] {
+        self.page.data()
+    }
+
+    pub fn page(&self) -> &Page {
+        &self.page
+    }
+
+    pub fn kind(&self) -> PageKind {
+        self.info.kind
+    }
+
+    /// Root page of the b-tree owning this page, if any
+    pub fn root(&self) -> Option<u32> {
+        self.info.root
+    }
+
+    pub fn as_table_leaf(&self) -> Option<TableLeafPage> {
+        self.btree(PageKind::TableLeaf).map(TableLeafPage)
+    }
+
+    pub fn as_table_interior(&self) -> Option<TableInteriorPage> {
+        self.btree(PageKind::TableInterior).map(TableInteriorPage)
+    }
+
+    pub fn as_index_leaf(&self) -> Option<IndexLeafPage> {
+        self.btree(PageKind::IndexLeaf).map(IndexLeafPage)
+    }
+
+    pub fn as_index_interior(&self) -> Option<IndexInteriorPage> {
+        self.btree(PageKind::IndexInterior).map(IndexInteriorPage)
+    }
+
+    pub fn as_overflow(&self) -> Option<OverflowPage> {
+        (self.info.kind == PageKind::Overflow)
+            .then(|| OverflowPage::new(self.page.clone(), self.usable_size))
+    }
+
+    pub fn as_freelist_trunk(&self) -> Option<FreelistTrunkPage> {
+        (self.info.kind == PageKind::FreelistTrunk)
+            .then(|| FreelistTrunkPage::new(self.page.clone(), self.usable_size))
+    }
+
+    fn btree(&self, kind: PageKind) -> Option<BTreePage> {
+        if self.info.kind != kind {
+            return None;
+        }
+        BTreePage::parse(self.page.clone(), self.usable_size).ok()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TableLeafPage(BTreePage);
+
+impl TableLeafPage {
+    pub fn btree(&self) -> &BTreePage {
+        &self.0
+    }
+
+    /// Each cell's rowid and the start of its record
+    pub fn cells(&self) -> impl Iterator<Item = Result<(i64, Payload)>> + '_ {
+        (0..self.0.cell_count()).map(|i| match self.0.cell(i)? {
+            Cell::TableLeaf { rowid, payload } => Ok((rowid, payload)),
+            _ => unreachable!("table leaf pages only hold table leaf cells"),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TableInteriorPage(BTreePage);
+
+impl TableInteriorPage {
+    pub fn btree(&self) -> &BTreePage {
+        &self.0
+    }
+
+    /// Each cell's left child and the largest rowid found under it
+    pub fn cells(&self) -> impl Iterator<Item = Result<(u32, i64)>> + '_ {
+        (0..self.0.cell_count()).map(|i| match self.0.cell(i)? {
+            Cell::TableInterior { left_child, rowid } => Ok((left_child, rowid)),
+            _ => unreachable!("table interior pages only hold table interior cells"),
+        })
+    }
+
+    pub fn right_child(&self) -> u32 {
+        self.0.header().right_child.unwrap_or(0)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct IndexLeafPage(BTreePage);
+
+impl IndexLeafPage {
+    pub fn btree(&self) -> &BTreePage {
+        &self.0
+    }
+
+    pub fn cells(&self) -> impl Iterator<Item = Result<Payload>> + '_ {
+        (0..self.0.cell_count()).map(|i| match self.0.cell(i)? {
+            Cell::IndexLeaf { payload } => Ok(payload),
+            _ => unreachable!("index leaf pages only hold index leaf cells"),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct IndexInteriorPage(BTreePage);
+
+impl IndexInteriorPage {
+    pub fn btree(&self) -> &BTreePage {
+        &self.0
+    }
+
+    /// Each cell's left child and its divider key
+    pub fn cells(&self) -> impl Iterator<Item = Result<(u32, Payload)>> + '_ {
+        (0..self.0.cell_count()).map(|i| match self.0.cell(i)? {
+            Cell::IndexInterior {
+                left_child,
+                payload,
+            } => Ok((left_child, payload)),
+            _ => unreachable!("index interior pages only hold index interior cells"),
+        })
+    }
+
+    pub fn right_child(&self) -> u32 {
+        self.0.header().right_child.unwrap_or(0)
+    }
+}
+
+/// A link in an overflow chain: a 4-byte next pointer followed by payload bytes
+#[derive(Debug, Clone)]
+pub struct OverflowPage {
+    page: Page,
+    usable_size: u32,
+}
+
+impl OverflowPage {
+    pub fn new(page: Page, usable_size: u32) -> OverflowPage {
+        OverflowPage { page, usable_size }
+    }
+
+    /// The next page in the chain, or `None` on the last page
+    pub fn next(&self) -> Option<u32> {
+        let d = self.page.data();
+        Some(u32::from_be_bytes([d[0], d[1], d[2], d[3]])).filter(|&n| n != 0)
+    }
+
+    /// Payload bytes carried by this page; on the last page only a prefix is meaningful
+    pub fn content(&self) -> &[u8] {
+        &self.page.data()[4..self.usable_size as usize]
+    }
+}
+
+/// A freelist trunk page: a pointer to the next trunk, then a list of free leaf pages
+#[derive(Debug, Clone)]
+pub struct FreelistTrunkPage {
+    page: Page,
+    usable_size: u32,
+}
+
+impl FreelistTrunkPage {
+    pub fn new(page: Page, usable_size: u32) -> FreelistTrunkPage {
+        FreelistTrunkPage { page, usable_size }
+    }
+
+    pub fn next_trunk(&self) -> Option<u32> {
+        Some(self.u32_at(0)).filter(|&n| n != 0)
+    }
+
+    /// Number of leaf pages the trunk claims to list
+    pub fn leaf_count(&self) -> u32 {
+        self.u32_at(4)
+    }
+
+    /// The leaf page numbers, clamped to what physically fits on the page
+    pub fn leaves(&self) -> Vec<u32> {
+        let max = self.usable_size / 4 - 2;
+        (0..self.leaf_count().min(max) as usize)
+            .map(|i| self.u32_at(8 + 4 * i))
+            .collect()
+    }
+
+    fn u32_at(&self, offset: usize) -> u32 {
+        let d = self.page.data();
+        u32::from_be_bytes([d[offset], d[offset + 1], d[offset + 2], d[offset + 3]])
+    }
+}
+
+/// Iterator over every page in the file, in page-number order
+pub struct Pages<'a> {
+    db: &'a Database,
+    map: PageMap,
+    next: u32,
+}
+
+impl<'a> Pages<'a> {
+    pub fn new(db: &'a Database) -> Pages<'a> {
+        Pages {
+            db,
+            map: PageMap::build(db),
+            next: 1,
+        }
+    }
+
+    pub fn map(&self) -> &PageMap {
+        &self.map
+    }
+}
+
+impl Iterator for Pages<'_> {
+    type Item = Result<PageRef>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let number = self.next;
+        let info = self.map.get(number)?;
+        self.next += 1;
+        Some(self.db.read_page(number).map(|page| PageRef {
+            page,
+            info,
+            usable_size: self.db.usable_size(),
+        }))
+    }
+}
@@ -0,0 +1,52 @@
+//! Running an iterator on a thread of its own, ahead of the code consuming what it yields, so
+//! page reads and record decoding overlap with whatever is done with the rows
+
+use std::sync::mpsc;
+
+/// Items sent across at a time, so the channel's synchronization is paid per batch rather
+/// than per row
+const BATCH_SIZE: usize = 256;
+
+/// Batches the producing thread may get ahead of the consumer when the caller has no
+/// preference
+pub const DEFAULT_DEPTH: usize = 8;
+
+/// Runs `items` on another thread, at most `depth` batches ahead, and hands what it yields,
+/// in order, to `consume` on this one. Once `consume` returns, the producer stops at its next
+/// batch, so giving up early after an error reads no further. With a single CPU there is
+/// nothing to overlap, so the items are consumed on this thread as they are produced.
+pub fn pipelined<I, R>(
+    mut items: I,
+    depth: usize,
+    consume: impl FnOnce(&mut dyn Iterator<Item = I::Item>) -> R,
+) -> R
+where
+    I: Iterator + Send,
+    I::Item: Send,
+{
+    if std::thread::available_parallelism().map_or(1, |n| n.get()) < 2 {
+        return consume(&mut items);
+    }
+    let (sender, receiver) = mpsc::sync_channel(depth.max(1));
+    std::thread::scope(|scope| {
+        scope.spawn(move || {
+            let mut batch = Vec::with_capacity(BATCH_SIZE);
+            for item in items {
+                batch.push(item);
+                if batch.len() == BATCH_SIZE {
+                    let full = std::mem::replace(&mut batch, Vec::with_capacity(BATCH_SIZE));
+                    if sender.send(full).is_err() {
+                        return;
+                    }
+                }
+            }
+            if !batch.is_empty() {
+                let _ = sender.send(batch);
+            }
+        });
+        // the receiver is dropped on the way out, which is what stops a producer that is
+        // still running
+        let mut received = receiver.into_iter().flatten();
+        consume(&mut received)
+    })
+}
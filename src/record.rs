@@ -0,0 +1,351 @@
+use crate::varint::{read_varint, write_varint};
+use crate::{Error, Result, Value};
+
+/// What to do with TEXT whose bytes aren't valid UTF-8. SQLite itself never checks, so
+/// files written by other tools or through BLOB casts can hold anything.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Utf8Policy {
+    /// Replace invalid sequences with U+FFFD
+    #[default]
+    Lossy,
+    /// Fail with [`Error::InvalidUtf8`]
+    Strict,
+    /// Keep the stored bytes as [`Value::RawText`]
+    Raw,
+}
+
+/// Number of content bytes a column of the given serial type occupies, or `None` for the
+/// reserved types 10 and 11
+pub fn serial_type_len(serial_type: u64) -> Option<usize> {
+    match serial_type {
+        0 | 8 | 9 => Some(0),
+        1 => Some(1),
+        2 => Some(2),
+        3 => Some(3),
+        4 => Some(4),
+        5 => Some(6),
+        6 | 7 => Some(8),
+        10 | 11 => None,
+        // BLOBs are even, TEXT is odd
+        n => Some(((n - 12) / 2) as usize),
+    }
+}
+
+/// Reads the record header: the serial type of every column plus the offset where the
+/// column data begins
+pub fn read_header(record: &[u8]) -> Result<(Vec<u64>, usize)> {
+    read_header_prefix(record, usize::MAX)
+}
+
+/// Like [`read_header`], but stops parsing after the serial types of the first `count`
+/// columns
+pub fn read_header_prefix(record: &[u8], count: usize) -> Result<(Vec<u64>, usize)> {
+    let (header_size, mut p) = read_varint(record)
+        .ok_or_else(|| Error::Corrupt("record header size is truncated".to_string()))?;
+    let header_size = header_size as usize;
+    if header_size > record.len() || header_size < p {
+        return Err(Error::Corrupt(format!(
+            "record header size {} is out of bounds",
+            header_size
+        )));
+    }
+
+    let mut serial_types = Vec::new();
+    while p < header_size && serial_types.len() < count {
+        let (st, n) = read_varint(&record[p..header_size])
+            .ok_or_else(|| Error::Corrupt("record serial type is truncated".to_string()))?;
+        serial_types.push(st);
+        p += n;
+    }
+    Ok((serial_types, header_size))
+}
+
+/// Decodes every column of a record, replacing invalid UTF-8 in TEXT values
+pub fn decode_record(record: &[u8]) -> Result<Vec<Value>> {
+    decode_record_with(record, Utf8Policy::Lossy)
+}
+
+/// Decodes every column of a record, handling invalid UTF-8 in TEXT values per `policy`
+pub fn decode_record_with(record: &[u8], policy: Utf8Policy) -> Result<Vec<Value>> {
+    let (serial_types, mut q) = read_header(record)?;
+    let mut values = Vec::with_capacity(serial_types.len());
+    for st in serial_types {
+        let len = serial_type_len(st)
+            .ok_or_else(|| Error::Corrupt(format!("reserved serial type {}", st)))?;
+        let end = column_end(record, q, len)?;
+        values.push(decode_value_with(st, &record[q..end], policy)?);
+        q = end;
+    }
+    Ok(values)
+}
+
+/// Decodes only the columns at `columns`, in that order, handling invalid UTF-8 per
+/// `policy`. The header is parsed no further than the last of them and the content of the
+/// others is stepped over without being decoded. Columns past the end of the record are
+/// `None`.
+pub fn decode_columns_with(
+    record: &[u8],
+    columns: &[usize],
+    policy: Utf8Policy,
+) -> Result<Vec<Option<Value>>> {
+    let Some(&last) = columns.iter().max() else {
+        return Ok(Vec::new());
+    };
+    let (serial_types, mut q) = read_header_prefix(record, last + 1)?;
+    let mut found = vec![None; serial_types.len()];
+    for (i, st) in serial_types.into_iter().enumerate() {
+        let len = serial_type_len(st)
+            .ok_or_else(|| Error::Corrupt(format!("reserved serial type {}", st)))?;
+        // skipped columns are bounds-checked too, so a bogus length can't carry past the end
+        let end = column_end(record, q, len)?;
+        if columns.contains(&i) {
+            found[i] = Some(decode_value_with(st, &record[q..end], policy)?);
+        }
+        q = end;
+    }
+    Ok(columns
+        .iter()
+        .map(|&i| found.get(i).cloned().flatten())
+        .collect())
+}
+
+/// Where a column of `len` bytes starting at `start` ends, if that is within the record
+fn column_end(record: &[u8], start: usize, len: usize) -> Result<usize> {
+    start
+        .checked_add(len)
+        .filter(|&end| end <= record.len())
+        .ok_or_else(|| {
+            Error::Corrupt("record column extends past the end of the payload".to_string())
+        })
+}
+
+/// Decodes one column given its serial type and exactly `serial_type_len` bytes of content,
+/// replacing invalid UTF-8 in TEXT values
+pub fn decode_value(serial_type: u64, bytes: &[u8]) -> Value {
+    match serial_type {
+        n if n >= 13 && n % 2 == 1 => Value::Text(String::from_utf8_lossy(bytes).into_owned()),
+        n => decode_number_or_blob(n, bytes),
+    }
+}
+
+/// Like [`decode_value`], handling invalid UTF-8 per `policy`
+pub fn decode_value_with(serial_type: u64, bytes: &[u8], policy: Utf8Policy) -> Result<Value> {
+    if serial_type < 13 || serial_type % 2 == 0 {
+        return Ok(decode_number_or_blob(serial_type, bytes));
+    }
+    match (std::str::from_utf8(bytes), policy) {
+        (Ok(text), _) => Ok(Value::Text(text.to_string())),
+        (Err(_), Utf8Policy::Lossy) => Ok(Value::Text(String::from_utf8_lossy(bytes).into_owned())),
+        (Err(e), Utf8Policy::Strict) => Err(Error::InvalidUtf8(e.valid_up_to())),
+        (Err(_), Utf8Policy::Raw) => Ok(Value::RawText(bytes.to_vec())),
+    }
+}
+
+fn decode_number_or_blob(serial_type: u64, bytes: &[u8]) -> Value {
+    match serial_type {
+        0 => Value::Null,
+        1..=6 => {
+            // big-endian two's complement, sign-extended from the stored width
+            let mut v: i64 = if bytes[0] & 0x80 != 0 { -1 } else { 0 };
+            for &b in bytes {
+                v = (v << 8) | i64::from(b);
+            }
+            Value::Integer(v)
+        }
+        7 => {
+            let mut b = [0u8; 8];
+            b.copy_from_slice(bytes);
+            Value::Real(f64::from_be_bytes(b))
+        }
+        8 => Value::Integer(0),
+        9 => Value::Integer(1),
+        _ => Value::Blob(bytes.to_vec()),
+    }
+}
+
+/// Encodes values as a record the way SQLite would store them, each integer in the fewest
+/// bytes that hold it
+pub fn encode_record(values: &[Value]) -> Vec<u8> {
+    let mut types = Vec::new();
+    let mut body = Vec::new();
+    for value in values {
+        let serial_type = match value {
+            Value::Null => 0,
+            Value::Integer(0) => 8,
+            Value::Integer(1) => 9,
+            Value::Integer(i) => {
+                let (serial_type, len) = match *i {
+                    -0x80..=0x7F => (1, 1),
+                    -0x8000..=0x7FFF => (2, 2),
+                    -0x80_0000..=0x7F_FFFF => (3, 3),
+                    -0x8000_0000..=0x7FFF_FFFF => (4, 4),
+                    -0x8000_0000_0000..=0x7FFF_FFFF_FFFF => (5, 6),
+                    _ => (6, 8),
+                };
+                body.extend_from_slice(&i.to_be_bytes()[8 - len..]);
+                serial_type
+            }
+            Value::Real(f) => {
+                body.extend_from_slice(&f.to_be_bytes());
+                7
+            }
+            Value::Text(s) => {
+                body.extend_from_slice(s.as_bytes());
+                s.len() as u64 * 2 + 13
+            }
+            Value::RawText(b) => {
+                body.extend_from_slice(b);
+                b.len() as u64 * 2 + 13
+            }
+            Value::Blob(b) => {
+                body.extend_from_slice(b);
+                b.len() as u64 * 2 + 12
+            }
+        };
+        write_varint(serial_type, &mut types);
+    }
+
+    // the header size counts its own varint, which can grow a byte by counting itself
+    let mut header_size = types.len() as u64 + 1;
+    let mut record = Vec::new();
+    loop {
+        record.clear();
+        write_varint(header_size, &mut record);
+        if record.len() + types.len() == header_size as usize {
+            break;
+        }
+        header_size = (record.len() + types.len()) as u64;
+    }
+    record.extend(types);
+    record.extend(body);
+    record
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Vec<Value> {
+        vec![
+            Value::Null,
+            Value::Integer(0),
+            Value::Integer(1),
+            Value::Integer(-129),
+            Value::Integer(0x7F_FFFF),
+            Value::Integer(-0x8000_0001),
+            Value::Integer(0x7FFF_FFFF_FFFF),
+            Value::Integer(i64::MIN),
+            Value::Real(-0.5),
+            Value::Text("héllo".to_string()),
+            Value::Text(String::new()),
+            Value::Blob(vec![0, 0xff, b'\'']),
+        ]
+    }
+
+    #[test]
+    fn integers_take_the_fewest_bytes() {
+        let (serial_types, _) = read_header(&encode_record(&sample())).unwrap();
+        assert_eq!(serial_types, [0, 8, 9, 2, 3, 5, 5, 6, 7, 25, 13, 18]);
+    }
+
+    #[test]
+    fn round_trips() {
+        let values = sample();
+        assert_eq!(decode_record(&encode_record(&values)).unwrap(), values);
+    }
+
+    #[test]
+    fn decodes_every_serial_type() {
+        let mut record = vec![13, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 19, 18];
+        record.extend([0xff]);
+        record.extend([0x01, 0x00]);
+        record.extend([0xff, 0xff, 0x7f]);
+        record.extend([0x80, 0, 0, 0]);
+        record.extend([0, 0x01, 0, 0, 0, 0]);
+        record.extend(i64::MAX.to_be_bytes());
+        record.extend((-0.5f64).to_be_bytes());
+        record.extend(b"abc");
+        record.extend([0, 0xff, b'\'']);
+        assert_eq!(
+            decode_record(&record).unwrap(),
+            [
+                Value::Null,
+                Value::Integer(-1),
+                Value::Integer(256),
+                Value::Integer(-129),
+                Value::Integer(i64::from(i32::MIN)),
+                Value::Integer(1 << 32),
+                Value::Integer(i64::MAX),
+                Value::Real(-0.5),
+                Value::Integer(0),
+                Value::Integer(1),
+                Value::Text("abc".to_string()),
+                Value::Blob(vec![0, 0xff, b'\'']),
+            ]
+        );
+    }
+
+    #[test]
+    fn header_size_counts_itself() {
+        // 127 one-byte serial types and the size byte make 128, which no longer fits in one
+        let mut record = vec![0x81, 0x01];
+        record.extend([0; 127]);
+        assert_eq!(decode_record(&record).unwrap(), vec![Value::Null; 127]);
+    }
+
+    #[test]
+    fn decodes_only_the_columns_asked_for() {
+        let record = encode_record(&sample());
+        let columns = decode_columns_with(&record, &[9, 3, 40], Utf8Policy::Lossy).unwrap();
+        assert_eq!(
+            columns,
+            [
+                Some(Value::Text("héllo".to_string())),
+                Some(Value::Integer(-129)),
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_utf8_follows_the_policy() {
+        // one TEXT column of two bytes, "a" and a stray continuation byte
+        let record = [0x02, 0x11, b'a', 0xff];
+        assert_eq!(
+            decode_record_with(&record, Utf8Policy::Lossy).unwrap(),
+            [Value::Text("a\u{fffd}".to_string())]
+        );
+        assert!(matches!(
+            decode_record_with(&record, Utf8Policy::Strict),
+            Err(Error::InvalidUtf8(1))
+        ));
+        assert_eq!(
+            decode_record_with(&record, Utf8Policy::Raw).unwrap(),
+            [Value::RawText(vec![b'a', 0xff])]
+        );
+    }
+
+    #[test]
+    fn corrupt_records_are_errors() {
+        // header size past the end of the record
+        assert!(read_header(&[0x05, 0x01]).is_err());
+        // reserved serial type
+        assert!(decode_record(&[0x02, 0x0a]).is_err());
+        // a TEXT column longer than what follows the header
+        assert!(decode_record(&[0x02, 0x17, b'a']).is_err());
+    }
+
+    #[test]
+    fn skipped_columns_are_bounds_checked() {
+        // two BLOBs claiming the largest length a varint can give, whose lengths overflow
+        // when added up, then an INTEGER after them
+        let mut record = vec![0];
+        write_varint(u64::MAX - 1, &mut record);
+        write_varint(u64::MAX - 1, &mut record);
+        record.push(0x01);
+        record[0] = record.len() as u8;
+        record.push(0x2a);
+        assert!(decode_columns_with(&record, &[1], Utf8Policy::Lossy).is_err());
+        assert!(decode_record(&record).is_err());
+    }
+}
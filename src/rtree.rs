@@ -0,0 +1,273 @@
+//! Reading R*Tree virtual tables straight from their `_node` shadow table, without the rtree
+//! module
+
+use crate::db::Database;
+use crate::schema::ObjectType;
+use crate::sql::create::parse_create_virtual_table;
+use crate::sql::tokenizer::tokenize;
+use crate::table::TableInfo;
+use crate::{Error, Result, Value};
+use std::collections::HashSet;
+
+/// The node every tree starts from; its header also holds the height of the tree
+const ROOT_NODE: i64 = 1;
+
+/// The rtree module's limit on the height of a tree
+const MAX_TREE_DEPTH: u16 = 40;
+
+/// An rtree or rtree_i32 virtual table
+#[derive(Debug, Clone)]
+pub struct RTree {
+    pub name: String,
+    /// The id column, then the minimum and maximum of each dimension. Auxiliary (`+name`)
+    /// columns are kept in the `_rowid` table and left out.
+    pub columns: Vec<String>,
+    /// Coordinates are 32-bit integers (rtree_i32) rather than 32-bit floats
+    pub integer: bool,
+    node_table: TableInfo,
+}
+
+/// One node of the tree, as held in a row of the `_node` table
+#[derive(Debug, Clone)]
+pub struct RTreeNode {
+    pub number: i64,
+    /// Height above the leaves: 0 for leaves, whose cells are rows
+    pub level: u16,
+    pub cells: Vec<RTreeCell>,
+}
+
+/// A bounding box with the row it belongs to, or on interior nodes the child node that holds
+/// everything inside it
+#[derive(Debug, Clone, PartialEq)]
+pub struct RTreeCell {
+    pub id: i64,
+    /// The minimum then the maximum of each dimension
+    pub coords: Vec<f64>,
+}
+
+/// How a search box selects entries
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BoxMatch {
+    /// Entries that share any point with the box
+    Overlaps,
+    /// Entries entirely inside the box
+    Within,
+}
+
+impl RTreeCell {
+    fn overlaps(&self, query: &[f64]) -> bool {
+        self.coords
+            .chunks(2)
+            .zip(query.chunks(2))
+            .all(|(c, q)| c[0] <= q[1] && c[1] >= q[0])
+    }
+
+    fn within(&self, query: &[f64]) -> bool {
+        self.coords
+            .chunks(2)
+            .zip(query.chunks(2))
+            .all(|(c, q)| c[0] >= q[0] && c[1] <= q[1])
+    }
+}
+
+impl RTree {
+    /// Looks up an R*Tree table by name, returning `None` if there is no table of that name or
+    /// it isn't an rtree
+    pub fn find(db: &Database, name: &str) -> Result<Option<RTree>> {
+        let schema = db.schema()?;
+        let Some(entry) = schema
+            .iter()
+            .find(|e| e.kind == ObjectType::Table && e.name.eq_ignore_ascii_case(name))
+        else {
+            return Ok(None);
+        };
+        let Some(sql) = entry.sql.as_deref().filter(|_| entry.root_page == 0) else {
+            return Ok(None);
+        };
+        let Ok(table) = parse_create_virtual_table(sql) else {
+            return Ok(None);
+        };
+        let integer = match table.module.to_ascii_lowercase().as_str() {
+            "rtree" => false,
+            "rtree_i32" => true,
+            _ => return Ok(None),
+        };
+
+        // the first word of each argument names the column; any type after it is ignored
+        let columns: Vec<String> = table
+            .args
+            .iter()
+            .filter(|arg| !arg.starts_with('+'))
+            .map(|arg| {
+                let tokens = tokenize(arg)?;
+                Ok(tokens
+                    .first()
+                    .map(|t| t.value().into_owned())
+                    .unwrap_or_default())
+            })
+            .collect::<Result<_>>()?;
+        if columns.len() < 3 || columns.len() % 2 == 0 {
+            return Err(Error::Corrupt(format!(
+                "rtree {} has {} columns; it needs an id and a minimum and maximum per dimension",
+                entry.name,
+                columns.len()
+            )));
+        }
+        let node_name = format!("{}_node", entry.name);
+        let node_table = TableInfo::find(db, &node_name)?
+            .ok_or_else(|| Error::Corrupt(format!("rtree {} has no {}", entry.name, node_name)))?;
+        Ok(Some(RTree {
+            name: entry.name.clone(),
+            columns,
+            integer,
+            node_table,
+        }))
+    }
+
+    pub fn dimensions(&self) -> usize {
+        (self.columns.len() - 1) / 2
+    }
+
+    /// Reads one node. The root's level is the height of the tree; below it, `level` says
+    /// what the parent expects.
+    pub fn node(&self, db: &Database, number: i64, level: u16) -> Result<RTreeNode> {
+        let row = self
+            .node_table
+            .row(db, number)?
+            .ok_or_else(|| Error::Corrupt(format!("rtree {} has no node {}", self.name, number)))?;
+        let data = match row.values.get(1) {
+            Some(Value::Blob(data)) => data,
+            _ => {
+                return Err(Error::Corrupt(format!(
+                    "rtree {} node {} is not a blob",
+                    self.name, number
+                )))
+            }
+        };
+        let corrupt =
+            |what: &str| Error::Corrupt(format!("rtree {} node {} {}", self.name, number, what));
+
+        // a 4-byte header (the tree's height, on the root only, then the cell count), then
+        // cells of an 8-byte id and a 4-byte minimum and maximum per dimension
+        if data.len() < 4 {
+            return Err(corrupt("is shorter than its header"));
+        }
+        let level = if number == ROOT_NODE {
+            u16::from_be_bytes([data[0], data[1]])
+        } else {
+            level
+        };
+        if level > MAX_TREE_DEPTH {
+            return Err(corrupt("is too deep"));
+        }
+        let count = u16::from_be_bytes([data[2], data[3]]) as usize;
+        let cell_size = 8 + 8 * self.dimensions();
+        let cells_data = &data[4..];
+        if count * cell_size > cells_data.len() {
+            return Err(corrupt("has more cells than fit"));
+        }
+        let cells = cells_data
+            .chunks_exact(cell_size)
+            .take(count)
+            .map(|cell| {
+                let (id, coords) = cell.split_at(8);
+                let id = i64::from_be_bytes(id.try_into().unwrap());
+                let coords = coords
+                    .chunks_exact(4)
+                    .map(|c| {
+                        let bytes: [u8; 4] = c.try_into().unwrap();
+                        if self.integer {
+                            i32::from_be_bytes(bytes) as f64
+                        } else {
+                            f32::from_be_bytes(bytes) as f64
+                        }
+                    })
+                    .collect();
+                RTreeCell { id, coords }
+            })
+            .collect();
+        Ok(RTreeNode {
+            number,
+            level,
+            cells,
+        })
+    }
+
+    /// Every node reachable from the root, parents before their children
+    pub fn nodes(&self, db: &Database) -> Result<Vec<RTreeNode>> {
+        let mut nodes = Vec::new();
+        self.walk(db, &mut |node| {
+            nodes.push(node.clone());
+            None
+        })?;
+        Ok(nodes)
+    }
+
+    /// The rows whose boxes match `query`, given as the minimum and maximum of each
+    /// dimension. Only the subtrees whose boxes overlap the query are read.
+    pub fn search(&self, db: &Database, query: &[f64], mode: BoxMatch) -> Result<Vec<RTreeCell>> {
+        if query.len() != 2 * self.dimensions() {
+            return Err(Error::Syntax(format!(
+                "rtree {} has {} dimensions, so a box needs {} coordinates, not {}",
+                self.name,
+                self.dimensions(),
+                2 * self.dimensions(),
+                query.len()
+            )));
+        }
+        let mut found = Vec::new();
+        self.walk(db, &mut |node| {
+            if node.level == 0 {
+                found.extend(
+                    node.cells
+                        .iter()
+                        .filter(|cell| match mode {
+                            BoxMatch::Overlaps => cell.overlaps(query),
+                            BoxMatch::Within => cell.within(query),
+                        })
+                        .cloned(),
+                );
+                return None;
+            }
+            // an entry inside the query box must overlap it, and so must its parent's box
+            Some(
+                node.cells
+                    .iter()
+                    .filter(|cell| cell.overlaps(query))
+                    .map(|cell| cell.id)
+                    .collect(),
+            )
+        })?;
+        found.sort_by_key(|cell| cell.id);
+        Ok(found)
+    }
+
+    /// Visits nodes depth first from the root. For interior nodes, `visit` returns the children
+    /// to descend into, or `None` for all of them.
+    fn walk(
+        &self,
+        db: &Database,
+        visit: &mut dyn FnMut(&RTreeNode) -> Option<Vec<i64>>,
+    ) -> Result<()> {
+        let mut seen = HashSet::new();
+        let mut stack = vec![(ROOT_NODE, 0)];
+        while let Some((number, level)) = stack.pop() {
+            if !seen.insert(number) {
+                return Err(Error::Corrupt(format!(
+                    "rtree {} reaches node {} twice",
+                    self.name, number
+                )));
+            }
+            let node = self.node(db, number, level)?;
+            let children = visit(&node);
+            if node.level == 0 {
+                continue;
+            }
+            let children =
+                children.unwrap_or_else(|| node.cells.iter().map(|cell| cell.id).collect());
+            // pushed in reverse so the first child is visited first
+            stack.extend(children.into_iter().rev().map(|id| (id, node.level - 1)));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,155 @@
+use crate::{Error, Result, Value};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObjectType {
+    Table,
+    Index,
+    View,
+    Trigger,
+}
+
+impl ObjectType {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ObjectType::Table => "table",
+            ObjectType::Index => "index",
+            ObjectType::View => "view",
+            ObjectType::Trigger => "trigger",
+        }
+    }
+}
+
+/// One row of `sqlite_schema`
+#[derive(Debug, Clone)]
+pub struct SchemaEntry {
+    pub kind: ObjectType,
+    pub name: String,
+    pub tbl_name: String,
+    /// Root b-tree page; zero for views, triggers and virtual tables
+    pub root_page: u32,
+    /// The original CREATE statement; NULL for automatically created indexes
+    pub sql: Option<String>,
+}
+
+impl SchemaEntry {
+    pub fn from_record(values: &[Value]) -> Result<SchemaEntry> {
+        // names that aren't valid UTF-8, whether read as raw text or stored as BLOBs, are
+        // decoded lossily so that the rest of the schema can still be read
+        let lossy = |i: usize| match values.get(i) {
+            Some(Value::Text(s)) => Some(s.clone()),
+            Some(Value::RawText(bytes) | Value::Blob(bytes)) => {
+                Some(String::from_utf8_lossy(bytes).into_owned())
+            }
+            _ => None,
+        };
+        let text = |i: usize| {
+            lossy(i)
+                .ok_or_else(|| Error::Corrupt(format!("sqlite_schema column {} is not text", i)))
+        };
+
+        let kind = match text(0)?.as_str() {
+            "table" => ObjectType::Table,
+            "index" => ObjectType::Index,
+            "view" => ObjectType::View,
+            "trigger" => ObjectType::Trigger,
+            other => {
+                return Err(Error::Corrupt(format!(
+                    "unknown sqlite_schema object type {:?}",
+                    other
+                )))
+            }
+        };
+        let root_page = match values.get(3) {
+            Some(Value::Integer(n)) => u32::try_from(*n)
+                .map_err(|_| Error::Corrupt(format!("invalid root page {} in sqlite_schema", n)))?,
+            _ => 0,
+        };
+        let sql = lossy(4);
+
+        Ok(SchemaEntry {
+            kind,
+            name: text(1)?,
+            tbl_name: text(2)?,
+            root_page,
+            sql,
+        })
+    }
+
+    /// Tables whose names start with `sqlite_`, in any case, are reserved for SQLite's own
+    /// bookkeeping. Like the sqlite3 shell, listings leave them out unless asked for.
+    pub fn is_internal(&self) -> bool {
+        self.name
+            .get(..7)
+            .is_some_and(|prefix| prefix.eq_ignore_ascii_case("sqlite_"))
+    }
+
+    /// The internal tables whose rows a copy of the database needs, as SQLite won't remake
+    /// them: the AUTOINCREMENT counters of `sqlite_sequence` and the `sqlite_stat` tables of
+    /// ANALYZE. SQLite creates the tables themselves as they are needed.
+    pub fn carries_state(&self) -> bool {
+        self.is_internal()
+            && (self.name.eq_ignore_ascii_case("sqlite_sequence")
+                || self.name[7..]
+                    .get(..4)
+                    .is_some_and(|s| s.eq_ignore_ascii_case("stat")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(name: &str) -> SchemaEntry {
+        SchemaEntry {
+            kind: ObjectType::Table,
+            name: name.to_string(),
+            tbl_name: name.to_string(),
+            root_page: 2,
+            sql: None,
+        }
+    }
+
+    #[test]
+    fn internal_tables() {
+        for name in [
+            "sqlite_sequence",
+            "SQLITE_STAT1",
+            "sqlite_stat4",
+            "sqlite_autoindex_t_1",
+        ] {
+            assert!(table(name).is_internal(), "{}", name);
+        }
+        for name in ["sqlite", "sqlitex", "users", "my_sqlite_table", "é"] {
+            assert!(!table(name).is_internal(), "{}", name);
+        }
+        for name in [
+            "sqlite_sequence",
+            "Sqlite_Sequence",
+            "sqlite_stat1",
+            "sqlite_stat4",
+        ] {
+            assert!(table(name).carries_state(), "{}", name);
+        }
+        for name in ["sqlite_autoindex_t_1", "sqlite_sta", "stat1"] {
+            assert!(!table(name).carries_state(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn names_that_are_not_utf8_are_read_lossily() {
+        let entry = SchemaEntry::from_record(&[
+            Value::Text("table".to_string()),
+            Value::RawText(b"t\xff".to_vec()),
+            Value::Blob(b"t\xff".to_vec()),
+            Value::Integer(2),
+            Value::RawText(b"CREATE TABLE \"t\xff\"(a)".to_vec()),
+        ])
+        .unwrap();
+        assert_eq!(entry.name, "t\u{fffd}");
+        assert_eq!(entry.tbl_name, "t\u{fffd}");
+        assert_eq!(entry.sql.as_deref(), Some("CREATE TABLE \"t\u{fffd}\"(a)"));
+
+        let not_text = [Value::Text("table".to_string()), Value::Integer(1)];
+        assert!(SchemaEntry::from_record(&not_text).is_err());
+    }
+}
@@ -0,0 +1,256 @@
+use crate::schema::{ObjectType, SchemaEntry};
+use crate::sql::create::{parse_create_table, ColumnDef, CreateTable};
+use crate::sql::{normalize, quote_identifier};
+use crate::Result;
+
+/// One step of a migration between two schemas
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    /// A statement that performs part of the migration
+    Statement(String),
+    /// A table whose changes ALTER TABLE can't express; it has to be recreated and its rows
+    /// copied over
+    Rebuild { table: String, reasons: Vec<String> },
+}
+
+/// The changes that turn the `old` schema into the `new` one, in an order that can be run
+/// as-is: dependent objects are dropped before their tables and recreated after them
+pub fn diff_schemas(old: &[SchemaEntry], new: &[SchemaEntry]) -> Result<Vec<Change>> {
+    // automatic indexes have no SQL and follow their table's constraints
+    let user = |entries: &[SchemaEntry], kind| -> Vec<SchemaEntry> {
+        entries
+            .iter()
+            .filter(|e| e.kind == kind && !e.is_internal() && e.sql.is_some())
+            .cloned()
+            .collect()
+    };
+    let find = |entries: &[SchemaEntry], name: &str| -> Option<SchemaEntry> {
+        entries
+            .iter()
+            .find(|e| e.name.eq_ignore_ascii_case(name))
+            .cloned()
+    };
+    let same =
+        |a: &SchemaEntry, b: &SchemaEntry| -> Result<bool> {
+            Ok(normalize(a.sql.as_deref().unwrap_or(""))?
+                == normalize(b.sql.as_deref().unwrap_or(""))?)
+        };
+
+    let mut drops = Vec::new();
+    let mut tables = Vec::new();
+    let mut creates = Vec::new();
+
+    // triggers, views and indexes can't be altered, only replaced
+    for kind in [ObjectType::Trigger, ObjectType::View, ObjectType::Index] {
+        let new_entries = user(new, kind);
+        for entry in user(old, kind) {
+            let keep = match find(&new_entries, &entry.name) {
+                Some(replacement) => same(&entry, &replacement)?,
+                None => false,
+            };
+            if !keep {
+                drops.push(Change::Statement(format!(
+                    "DROP {} {}",
+                    kind.as_str().to_uppercase(),
+                    quote_identifier(&entry.name)
+                )));
+            }
+        }
+    }
+
+    let old_tables = user(old, ObjectType::Table);
+    let new_tables = user(new, ObjectType::Table);
+    for entry in &old_tables {
+        if find(&new_tables, &entry.name).is_none() {
+            drops.push(Change::Statement(format!(
+                "DROP TABLE {}",
+                quote_identifier(&entry.name)
+            )));
+        }
+    }
+    for entry in &new_tables {
+        match find(&old_tables, &entry.name) {
+            None => tables.push(Change::Statement(sql_of(entry))),
+            Some(previous) if !same(&previous, entry)? => {
+                tables.extend(alter_table(&previous, entry)?);
+            }
+            Some(_) => {}
+        }
+    }
+
+    for kind in [ObjectType::Index, ObjectType::View, ObjectType::Trigger] {
+        let old_entries = user(old, kind);
+        for entry in user(new, kind) {
+            let exists = match find(&old_entries, &entry.name) {
+                Some(previous) => same(&previous, &entry)?,
+                None => false,
+            };
+            if !exists {
+                creates.push(Change::Statement(sql_of(&entry)));
+            }
+        }
+    }
+
+    Ok(drops.into_iter().chain(tables).chain(creates).collect())
+}
+
+fn sql_of(entry: &SchemaEntry) -> String {
+    entry
+        .sql
+        .as_deref()
+        .unwrap_or_default()
+        .trim()
+        .trim_end_matches(';')
+        .to_string()
+}
+
+/// ALTER TABLE statements for a changed table, or a rebuild when they can't express the change
+fn alter_table(old: &SchemaEntry, new: &SchemaEntry) -> Result<Vec<Change>> {
+    let rebuild = |reasons: Vec<String>| {
+        Ok(vec![Change::Rebuild {
+            table: new.name.clone(),
+            reasons,
+        }])
+    };
+    let (before, after) = match (
+        parse_create_table(&sql_of(old)),
+        parse_create_table(&sql_of(new)),
+    ) {
+        (Ok(before), Ok(after)) => (before, after),
+        // virtual tables and the like can only be replaced wholesale
+        _ => return rebuild(vec!["the table definition changed".to_string()]),
+    };
+
+    let mut reasons = Vec::new();
+    if before.without_rowid != after.without_rowid {
+        reasons.push("WITHOUT ROWID changed".to_string());
+    }
+    if before.strict != after.strict {
+        reasons.push("STRICT changed".to_string());
+    }
+    let constraints = |t: &CreateTable| -> Result<Vec<String>> {
+        t.constraints.iter().map(|c| normalize(c)).collect()
+    };
+    if constraints(&before)? != constraints(&after)? {
+        reasons.push("table constraints changed".to_string());
+    }
+
+    let mut dropped: Vec<&ColumnDef> = before
+        .columns
+        .iter()
+        .filter(|c| after.column(&c.name).is_none())
+        .collect();
+    let mut added: Vec<&ColumnDef> = after
+        .columns
+        .iter()
+        .filter(|c| before.column(&c.name).is_none())
+        .collect();
+
+    // a single column swapped for another with the same definition in the same place is a rename
+    let mut statements = Vec::new();
+    let mut renamed = None;
+    let table = quote_identifier(&after.name);
+    if let ([gone], [arrived]) = (dropped.as_slice(), added.as_slice()) {
+        let position = |t: &CreateTable, c: &ColumnDef| t.columns.iter().position(|x| x == c);
+        if position(&before, gone) == position(&after, arrived)
+            && definition(gone)? == definition(arrived)?
+        {
+            statements.push(Change::Statement(format!(
+                "ALTER TABLE {} RENAME COLUMN {} TO {}",
+                table,
+                quote_identifier(&gone.name),
+                quote_identifier(&arrived.name)
+            )));
+            renamed = Some((gone.name.to_lowercase(), arrived.name.to_lowercase()));
+            dropped.clear();
+            added.clear();
+        }
+    }
+
+    for column in &after.columns {
+        if let Some(previous) = before.column(&column.name) {
+            if definition(previous)? != definition(column)? {
+                reasons.push(format!(
+                    "column {} changed from `{}` to `{}`",
+                    column.name, previous.sql, column.sql
+                ));
+            }
+        }
+    }
+    let kept = |t: &CreateTable| -> Vec<String> {
+        t.columns
+            .iter()
+            .filter(|c| !dropped.contains(c) && !added.contains(c))
+            .map(|c| match &renamed {
+                Some((from, to)) if c.name.eq_ignore_ascii_case(from) => to.clone(),
+                _ => c.name.to_lowercase(),
+            })
+            .collect()
+    };
+    if kept(&before) != kept(&after) {
+        reasons.push("columns were reordered".to_string());
+    }
+
+    for column in &dropped {
+        let in_key = before
+            .primary_key
+            .iter()
+            .any(|k| k.eq_ignore_ascii_case(&column.name));
+        if column.primary_key || column.unique || in_key {
+            reasons.push(format!(
+                "column {} is part of a PRIMARY KEY or UNIQUE constraint",
+                column.name
+            ));
+        }
+        statements.push(Change::Statement(format!(
+            "ALTER TABLE {} DROP COLUMN {}",
+            table,
+            quote_identifier(&column.name)
+        )));
+    }
+
+    // ADD COLUMN only appends, and only columns that every existing row can take on
+    let first_added = after.columns.len() - added.len();
+    for column in &added {
+        if after.columns[first_added..].iter().all(|c| c != *column) {
+            reasons.push(format!("new column {} is not at the end", column.name));
+        }
+        if column.primary_key || column.unique {
+            reasons.push(format!(
+                "new column {} has a PRIMARY KEY or UNIQUE constraint",
+                column.name
+            ));
+        }
+        let default = column.default.as_deref().map(str::to_ascii_uppercase);
+        if column.not_null && matches!(default.as_deref(), None | Some("NULL")) {
+            reasons.push(format!(
+                "new column {} is NOT NULL without a default",
+                column.name
+            ));
+        }
+        if default.is_some_and(|d| d.starts_with('(') || d.starts_with("CURRENT_")) {
+            reasons.push(format!(
+                "new column {} has a non-constant default",
+                column.name
+            ));
+        }
+        statements.push(Change::Statement(format!(
+            "ALTER TABLE {} ADD COLUMN {}",
+            table, column.sql
+        )));
+    }
+
+    if !reasons.is_empty() {
+        return rebuild(reasons);
+    }
+    if statements.is_empty() {
+        // only layout, comments or quoting differ
+        return Ok(Vec::new());
+    }
+    Ok(statements)
+}
+
+/// A column's type and constraints in canonical form, without its name
+fn definition(column: &ColumnDef) -> Result<String> {
+    normalize(&format!("{} {}", column.type_name, column.constraints))
+}
@@ -0,0 +1,109 @@
+//! Splitting a `.read` script into the dot-commands and SQL statements it holds
+
+use anyhow::{bail, Result};
+use sqliter::sql::tokenizer::tokenize;
+
+/// One command from a script, with the line it starts on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptCommand {
+    pub line: usize,
+    pub text: String,
+    pub kind: CommandKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandKind {
+    /// A dot-command split into its arguments, the command first
+    Dot(Vec<String>),
+    /// An SQL statement, which may span lines, up to and including its semicolon
+    Sql,
+}
+
+/// Splits a script as the sqlite3 shell reads it: a line starting with `.` is a dot-command
+/// on its own, `--` comments and blank lines between statements are skipped, and anything
+/// else is SQL that runs to the semicolon ending it
+pub fn parse_script(text: &str) -> Result<Vec<ScriptCommand>> {
+    let mut commands = Vec::new();
+    let mut sql: Option<(usize, String)> = None;
+    for (n, line) in text.lines().enumerate() {
+        let number = n + 1;
+        if let Some((start, pending)) = &mut sql {
+            pending.push('\n');
+            pending.push_str(line);
+            if statement_complete(pending) {
+                commands.push(ScriptCommand {
+                    line: *start,
+                    text: std::mem::take(pending),
+                    kind: CommandKind::Sql,
+                });
+                sql = None;
+            }
+            continue;
+        }
+        let trimmed = line.trim_start();
+        if trimmed.is_empty() || trimmed.starts_with("--") {
+            continue;
+        }
+        if trimmed.starts_with('.') {
+            commands.push(ScriptCommand {
+                line: number,
+                text: trimmed.trim_end().to_string(),
+                kind: CommandKind::Dot(split_arguments(trimmed)?),
+            });
+        } else if statement_complete(line) {
+            commands.push(ScriptCommand {
+                line: number,
+                text: line.to_string(),
+                kind: CommandKind::Sql,
+            });
+        } else {
+            sql = Some((number, line.to_string()));
+        }
+    }
+    if let Some((start, pending)) = sql {
+        // like the shell, a statement left open at the end of the script is still run
+        commands.push(ScriptCommand {
+            line: start,
+            text: pending,
+            kind: CommandKind::Sql,
+        });
+    }
+    Ok(commands)
+}
+
+/// Whether SQL text ends with a semicolon outside any string, identifier or comment
+fn statement_complete(sql: &str) -> bool {
+    tokenize(sql).is_ok_and(|tokens| tokens.last().is_some_and(|t| t.is_punct(";")))
+}
+
+/// Splits a dot-command line into arguments at whitespace, as the sqlite3 shell does.
+/// Single or double quotes group an argument containing spaces; inside double quotes, a
+/// backslash escapes the next character.
+pub fn split_arguments(line: &str) -> Result<Vec<String>> {
+    let mut args = Vec::new();
+    let mut chars = line.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        let Some(first) = chars.next() else {
+            return Ok(args);
+        };
+        let mut arg = String::new();
+        match first {
+            '\'' | '"' => loop {
+                match chars.next() {
+                    Some(c) if c == first => break,
+                    Some('\\') if first == '"' => arg.extend(chars.next()),
+                    Some(c) => arg.push(c),
+                    None => bail!("Unterminated quote in: {}", line),
+                }
+            },
+            c => {
+                arg.push(c);
+                while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                    arg.push(c);
+                }
+            }
+        }
+        args.push(arg);
+    }
+}
@@ -0,0 +1,107 @@
+use crate::pager::{DEFAULT_CACHE_PAGES, DEFAULT_READAHEAD_PAGES};
+use crate::record::Utf8Policy;
+use crate::sql::tokenizer::{tokenize, TokenKind};
+use crate::{Error, Result};
+
+/// Per-connection runtime settings, shared by every clone of a [`Database`](crate::Database)
+/// and changeable after opening with PRAGMA-style assignments
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Settings {
+    /// How TEXT values that aren't valid UTF-8 are decoded
+    pub utf8_policy: Utf8Policy,
+    /// Page cache size with SQLite's meaning: positive is a number of pages, negative is a
+    /// number of KiB
+    pub cache_size: i64,
+    /// Most pages read in one call when a scan reads the file in order; 0 turns readahead off
+    pub readahead: u32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            utf8_policy: Utf8Policy::default(),
+            cache_size: DEFAULT_CACHE_PAGES as i64,
+            readahead: DEFAULT_READAHEAD_PAGES,
+        }
+    }
+}
+
+impl Settings {
+    /// Pages the cache may hold at the given page size
+    pub fn cache_pages(&self, page_size: u32) -> usize {
+        if self.cache_size >= 0 {
+            self.cache_size as usize
+        } else {
+            (self.cache_size.unsigned_abs() * 1024 / page_size as u64) as usize
+        }
+    }
+
+    /// Applies an assignment such as `cache_size = -4096` or `PRAGMA utf8_policy=raw`
+    pub fn apply_pragma(&mut self, pragma: &str) -> Result<()> {
+        let tokens = tokenize(pragma)?;
+        let mut tokens = tokens.as_slice();
+        if let [first, rest @ ..] = tokens {
+            if first.is_keyword("PRAGMA") {
+                tokens = rest;
+            }
+        }
+        if let [rest @ .., last] = tokens {
+            if last.is_punct(";") {
+                tokens = rest;
+            }
+        }
+        let (name, value) = match tokens {
+            [name, eq, value @ ..] if name.is_name() && eq.is_punct("=") => (name, value),
+            [name, open, value @ .., close]
+                if name.is_name() && open.is_punct("(") && close.is_punct(")") =>
+            {
+                (name, value)
+            }
+            _ => return Err(Error::Syntax(format!("expected name=value: {}", pragma))),
+        };
+        // the value is a single word or string, or a signed number
+        let value: String = match value {
+            [token] if token.kind != TokenKind::Punct => token.value().into_owned(),
+            [sign, number] if sign.is_punct("-") && number.kind == TokenKind::Number => {
+                format!("-{}", number.text)
+            }
+            _ => {
+                return Err(Error::Syntax(format!(
+                    "expected a single value: {}",
+                    pragma
+                )))
+            }
+        };
+
+        match name.value().to_ascii_lowercase().as_str() {
+            "utf8_policy" => {
+                self.utf8_policy = match value.to_ascii_lowercase().as_str() {
+                    "lossy" => Utf8Policy::Lossy,
+                    "strict" => Utf8Policy::Strict,
+                    "raw" => Utf8Policy::Raw,
+                    _ => {
+                        return Err(Error::Syntax(format!(
+                            "utf8_policy must be lossy, strict or raw, not {}",
+                            value
+                        )))
+                    }
+                }
+            }
+            "cache_size" => {
+                self.cache_size = value.parse().map_err(|_| {
+                    Error::Syntax(format!("cache_size must be an integer, not {}", value))
+                })?
+            }
+            "readahead" => {
+                self.readahead = value.parse().map_err(|_| {
+                    Error::Syntax(format!(
+                        "readahead must be a number of pages, not {}",
+                        value
+                    ))
+                })?
+            }
+            other => return Err(Error::Syntax(format!("unknown setting: {}", other))),
+        }
+        Ok(())
+    }
+}
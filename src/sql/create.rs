@@ -0,0 +1,747 @@
+use super::tokenizer::{tokenize, Token};
+use crate::{Error, Result};
+
+/// One column of a CREATE TABLE statement
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnDef {
+    pub name: String,
+    /// The declared type as written, such as `VARCHAR(20)`; empty when none was given
+    pub type_name: String,
+    pub primary_key: bool,
+    pub not_null: bool,
+    pub unique: bool,
+    /// `GENERATED ALWAYS AS (...)` columns compute their value from other columns
+    pub generated: Option<Generated>,
+    /// Source text of the DEFAULT value
+    pub default: Option<String>,
+    pub collate: Option<String>,
+    /// Source text of the column constraints, after the type
+    pub constraints: String,
+    /// Source text of the whole definition
+    pub sql: String,
+}
+
+/// How a generated column's value is kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Generated {
+    /// Computed when read; the record has no slot for it
+    Virtual,
+    /// Computed when written and stored in the record like any other column
+    Stored,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreateTable {
+    pub name: String,
+    pub columns: Vec<ColumnDef>,
+    /// Source text of each table constraint
+    pub constraints: Vec<String>,
+    /// Columns named by a table-level PRIMARY KEY clause
+    pub primary_key: Vec<String>,
+    pub without_rowid: bool,
+    pub strict: bool,
+}
+
+impl CreateTable {
+    pub fn column(&self, name: &str) -> Option<&ColumnDef> {
+        self.columns
+            .iter()
+            .find(|c| c.name.eq_ignore_ascii_case(name))
+    }
+
+    /// The column that is an alias for the rowid: the sole PRIMARY KEY column of a rowid
+    /// table, declared with the type `INTEGER` exactly (in any case, but not `INT` or
+    /// `INTEGER(8)`). Its record slot holds NULL and its value is the cell's rowid. As in
+    /// SQLite, `PRIMARY KEY DESC` on the column itself makes an ordinary key instead, but a
+    /// descending table-level `PRIMARY KEY(id DESC)` still aliases the rowid.
+    pub fn rowid_alias(&self) -> Option<usize> {
+        if self.without_rowid {
+            return None;
+        }
+        let column = match self.columns.iter().position(|c| c.primary_key) {
+            Some(i) if column_key_descending(&self.columns[i]) => return None,
+            Some(i) => i,
+            None => match self.primary_key.as_slice() {
+                [name] => self
+                    .columns
+                    .iter()
+                    .position(|c| c.name.eq_ignore_ascii_case(name))?,
+                _ => return None,
+            },
+        };
+        let integer = tokenize(&self.columns[column].type_name).is_ok_and(|tokens| {
+            matches!(tokens.as_slice(), [t] if t.is_name() && t.value().eq_ignore_ascii_case("INTEGER"))
+        });
+        integer.then_some(column)
+    }
+
+    /// The terms of the PRIMARY KEY with their collations and sort orders, whether it was
+    /// declared on a column or for the whole table. A term without COLLATE uses its column's.
+    pub fn primary_key_terms(&self) -> Result<Vec<IndexedColumn>> {
+        if let Some(column) = self.columns.iter().find(|c| c.primary_key) {
+            return Ok(vec![IndexedColumn {
+                name: Some(column.name.clone()),
+                collate: None,
+                descending: column_key_descending(column),
+            }]);
+        }
+        for constraint in &self.constraints {
+            let mut p = Parser::new(constraint)?;
+            while p.peek().is_some_and(|t| !t.is_keyword("PRIMARY")) {
+                p.pos += 1;
+            }
+            if !p.eat_keyword("PRIMARY") {
+                continue;
+            }
+            p.expect_keyword("KEY")?;
+            p.expect_punct("(")?;
+            let mut terms = Vec::new();
+            loop {
+                let start = p.pos;
+                p.skip_to_separator()?;
+                terms.push(indexed_column(p.text(start, p.pos))?);
+                if !p.eat_punct(",") {
+                    break;
+                }
+            }
+            return Ok(terms);
+        }
+        Ok(Vec::new())
+    }
+
+    /// The table's foreign keys: those declared with REFERENCES on a column, then its
+    /// FOREIGN KEY constraints, each in the order written
+    pub fn foreign_keys(&self) -> Result<Vec<ForeignKey>> {
+        let mut keys = Vec::new();
+        for column in &self.columns {
+            let mut p = Parser::new(&column.constraints)?;
+            while p.peek().is_some_and(|t| !t.is_keyword("REFERENCES")) {
+                p.pos += 1;
+            }
+            if p.eat_keyword("REFERENCES") {
+                let (table, parent_columns) = p.references()?;
+                keys.push(ForeignKey {
+                    columns: vec![column.name.clone()],
+                    table,
+                    parent_columns,
+                });
+            }
+        }
+        for constraint in &self.constraints {
+            let mut p = Parser::new(constraint)?;
+            while p.peek().is_some_and(|t| !t.is_keyword("FOREIGN")) {
+                p.pos += 1;
+            }
+            if !p.eat_keyword("FOREIGN") {
+                continue;
+            }
+            p.expect_keyword("KEY")?;
+            let columns = p.name_list()?;
+            p.expect_keyword("REFERENCES")?;
+            let (table, parent_columns) = p.references()?;
+            keys.push(ForeignKey {
+                columns,
+                table,
+                parent_columns,
+            });
+        }
+        Ok(keys)
+    }
+}
+
+/// A foreign key: the columns of a table that refer to a row of the parent table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForeignKey {
+    pub columns: Vec<String>,
+    /// The parent table's name
+    pub table: String,
+    /// The parent's columns the key refers to; empty when the clause names none, which
+    /// means the parent's primary key
+    pub parent_columns: Vec<String>,
+}
+
+/// How values are coerced when stored in a column, decided by its declared type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Affinity {
+    Text,
+    Numeric,
+    Integer,
+    Real,
+    Blob,
+}
+
+impl Affinity {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Affinity::Text => "TEXT",
+            Affinity::Numeric => "NUMERIC",
+            Affinity::Integer => "INTEGER",
+            Affinity::Real => "REAL",
+            Affinity::Blob => "BLOB",
+        }
+    }
+}
+
+impl ColumnDef {
+    /// The column's affinity, by SQLite's rules: the first match of INT, then CHAR, CLOB or
+    /// TEXT, then BLOB or no type, then REAL, FLOA or DOUB, and NUMERIC otherwise
+    pub fn affinity(&self) -> Affinity {
+        let type_name = self.type_name.to_ascii_uppercase();
+        if type_name.contains("INT") {
+            Affinity::Integer
+        } else if ["CHAR", "CLOB", "TEXT"]
+            .iter()
+            .any(|t| type_name.contains(t))
+        {
+            Affinity::Text
+        } else if type_name.is_empty() || type_name.contains("BLOB") {
+            Affinity::Blob
+        } else if ["REAL", "FLOA", "DOUB"]
+            .iter()
+            .any(|t| type_name.contains(t))
+        {
+            Affinity::Real
+        } else {
+            Affinity::Numeric
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreateIndex {
+    pub name: String,
+    pub table: String,
+    pub unique: bool,
+    /// Source text of each indexed column or expression, including COLLATE and ASC/DESC
+    pub columns: Vec<String>,
+    /// Source text of the WHERE clause of a partial index
+    pub where_clause: Option<String>,
+}
+
+/// One term of an index key, split out of its source text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexedColumn {
+    /// The column name, or `None` when the term is an expression
+    pub name: Option<String>,
+    pub collate: Option<String>,
+    pub descending: bool,
+}
+
+impl CreateIndex {
+    pub fn indexed_columns(&self) -> Result<Vec<IndexedColumn>> {
+        self.columns
+            .iter()
+            .map(|text| indexed_column(text))
+            .collect()
+    }
+}
+
+/// Whether a column's own constraints declare it `PRIMARY KEY DESC`
+fn column_key_descending(column: &ColumnDef) -> bool {
+    tokenize(&column.constraints).is_ok_and(|tokens| {
+        tokens.windows(3).any(|w| {
+            w[0].is_keyword("PRIMARY") && w[1].is_keyword("KEY") && w[2].is_keyword("DESC")
+        })
+    })
+}
+
+/// Splits one `name COLLATE x DESC` term of an index or key into its parts
+fn indexed_column(text: &str) -> Result<IndexedColumn> {
+    let tokens = tokenize(text)?;
+    let mut tokens = tokens.as_slice();
+    let mut descending = false;
+    if let [rest @ .., last] = tokens {
+        if last.is_keyword("ASC") || last.is_keyword("DESC") {
+            descending = last.is_keyword("DESC");
+            tokens = rest;
+        }
+    }
+    let mut collate = None;
+    if let [rest @ .., keyword, name] = tokens {
+        if keyword.is_keyword("COLLATE") && name.is_name() {
+            collate = Some(name.value().into_owned());
+            tokens = rest;
+        }
+    }
+    let name = match tokens {
+        [name] if name.is_name() => Some(name.value().into_owned()),
+        _ => None,
+    };
+    Ok(IndexedColumn {
+        name,
+        collate,
+        descending,
+    })
+}
+
+/// Keywords that end a column's type and start its constraints
+const COLUMN_CONSTRAINTS: [&str; 11] = [
+    "CONSTRAINT",
+    "PRIMARY",
+    "NOT",
+    "NULL",
+    "UNIQUE",
+    "CHECK",
+    "DEFAULT",
+    "COLLATE",
+    "REFERENCES",
+    "GENERATED",
+    "AS",
+];
+
+/// Keywords that start a table constraint rather than a column definition
+const TABLE_CONSTRAINTS: [&str; 5] = ["CONSTRAINT", "PRIMARY", "UNIQUE", "CHECK", "FOREIGN"];
+
+pub fn parse_create_table(sql: &str) -> Result<CreateTable> {
+    let mut p = Parser::new(sql)?;
+    p.expect_keyword("CREATE")?;
+    if !p.eat_keyword("TEMP") {
+        p.eat_keyword("TEMPORARY");
+    }
+    p.expect_keyword("TABLE")?;
+    p.if_not_exists()?;
+    let name = p.qualified_name()?;
+    if p.peek().is_some_and(|t| t.is_keyword("AS")) {
+        return Err(p.error("CREATE TABLE ... AS SELECT has no column definitions"));
+    }
+    p.expect_punct("(")?;
+
+    let mut table = CreateTable {
+        name,
+        columns: Vec::new(),
+        constraints: Vec::new(),
+        primary_key: Vec::new(),
+        without_rowid: false,
+        strict: false,
+    };
+    loop {
+        let starts_constraint = p
+            .peek()
+            .is_some_and(|t| TABLE_CONSTRAINTS.iter().any(|k| t.is_keyword(k)));
+        if starts_constraint || !table.constraints.is_empty() {
+            let start = p.pos;
+            p.skip_to_separator()?;
+            let tokens = &p.tokens[start..p.pos];
+            if let Some(i) = (0..tokens.len().saturating_sub(1))
+                .find(|&i| tokens[i].is_keyword("PRIMARY") && tokens[i + 1].is_keyword("KEY"))
+            {
+                table.primary_key = key_columns(&tokens[i + 2..]);
+            }
+            table.constraints.push(p.text(start, p.pos).to_string());
+        } else {
+            table.columns.push(p.column_def()?);
+        }
+        if !p.eat_punct(",") {
+            break;
+        }
+    }
+    p.expect_punct(")")?;
+
+    loop {
+        if p.eat_keyword("WITHOUT") {
+            p.expect_keyword("ROWID")?;
+            table.without_rowid = true;
+        } else if p.eat_keyword("STRICT") {
+            table.strict = true;
+        } else {
+            break;
+        }
+        if !p.eat_punct(",") {
+            break;
+        }
+    }
+    p.finish()?;
+    Ok(table)
+}
+
+pub fn parse_create_index(sql: &str) -> Result<CreateIndex> {
+    let mut p = Parser::new(sql)?;
+    p.expect_keyword("CREATE")?;
+    let unique = p.eat_keyword("UNIQUE");
+    p.expect_keyword("INDEX")?;
+    p.if_not_exists()?;
+    let name = p.qualified_name()?;
+    p.expect_keyword("ON")?;
+    let table = p.name()?;
+    p.expect_punct("(")?;
+
+    let mut columns = Vec::new();
+    loop {
+        let start = p.pos;
+        p.skip_to_separator()?;
+        if start == p.pos {
+            return Err(p.error("expected an indexed column"));
+        }
+        columns.push(p.text(start, p.pos).to_string());
+        if !p.eat_punct(",") {
+            break;
+        }
+    }
+    p.expect_punct(")")?;
+
+    let where_clause = if p.eat_keyword("WHERE") {
+        let start = p.pos;
+        while p.peek().is_some_and(|t| !t.is_punct(";")) {
+            p.pos += 1;
+        }
+        Some(p.text(start, p.pos).to_string())
+    } else {
+        None
+    };
+    p.finish()?;
+    Ok(CreateIndex {
+        name,
+        table,
+        unique,
+        columns,
+        where_clause,
+    })
+}
+
+/// A `CREATE VIRTUAL TABLE ... USING module(args)` statement
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreateVirtualTable {
+    pub name: String,
+    pub module: String,
+    /// Source text of each module argument, which only the module interprets
+    pub args: Vec<String>,
+}
+
+pub fn parse_create_virtual_table(sql: &str) -> Result<CreateVirtualTable> {
+    let mut p = Parser::new(sql)?;
+    p.expect_keyword("CREATE")?;
+    p.expect_keyword("VIRTUAL")?;
+    p.expect_keyword("TABLE")?;
+    p.if_not_exists()?;
+    let name = p.qualified_name()?;
+    p.expect_keyword("USING")?;
+    let module = p.name()?;
+
+    let mut args = Vec::new();
+    if p.eat_punct("(") {
+        while !p.eat_punct(")") {
+            let start = p.pos;
+            p.skip_to_separator()?;
+            args.push(p.text(start, p.pos).to_string());
+            p.eat_punct(",");
+        }
+    }
+    p.finish()?;
+    Ok(CreateVirtualTable { name, module, args })
+}
+
+/// Column names in a `(a, b COLLATE nocase DESC)` list
+fn key_columns(tokens: &[Token]) -> Vec<String> {
+    let mut columns = Vec::new();
+    let mut expect_name = true;
+    let mut depth = 0;
+    for token in tokens {
+        if token.is_punct("(") {
+            depth += 1;
+        } else if token.is_punct(")") {
+            depth -= 1;
+            if depth == 0 {
+                break;
+            }
+        } else if depth == 1 && token.is_punct(",") {
+            expect_name = true;
+        } else if depth == 1 && expect_name && token.is_name() {
+            columns.push(token.value().into_owned());
+            expect_name = false;
+        }
+    }
+    columns
+}
+
+struct Parser<'a> {
+    sql: &'a str,
+    tokens: Vec<Token<'a>>,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(sql: &'a str) -> Result<Self> {
+        Ok(Parser {
+            sql,
+            tokens: tokenize(sql)?,
+            pos: 0,
+        })
+    }
+
+    fn error(&self, msg: &str) -> Error {
+        match self.peek() {
+            Some(t) => Error::Syntax(format!("{} near {:?}", msg, t.text)),
+            None => Error::Syntax(format!("{} at end of input", msg)),
+        }
+    }
+
+    fn peek(&self) -> Option<&Token<'a>> {
+        self.tokens.get(self.pos)
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let found = self.peek().is_some_and(|t| t.is_keyword(keyword));
+        self.pos += found as usize;
+        found
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<()> {
+        if self.eat_keyword(keyword) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected {}", keyword)))
+        }
+    }
+
+    fn eat_punct(&mut self, punct: &str) -> bool {
+        let found = self.peek().is_some_and(|t| t.is_punct(punct));
+        self.pos += found as usize;
+        found
+    }
+
+    fn expect_punct(&mut self, punct: &str) -> Result<()> {
+        if self.eat_punct(punct) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected {:?}", punct)))
+        }
+    }
+
+    fn name(&mut self) -> Result<String> {
+        match self.peek() {
+            Some(t) if t.is_name() => {
+                let name = t.value().into_owned();
+                self.pos += 1;
+                Ok(name)
+            }
+            _ => Err(self.error("expected a name")),
+        }
+    }
+
+    /// A name, dropping any `schema.` prefix
+    fn qualified_name(&mut self) -> Result<String> {
+        let name = self.name()?;
+        if self.eat_punct(".") {
+            return self.name();
+        }
+        Ok(name)
+    }
+
+    /// A parenthesized list of names
+    fn name_list(&mut self) -> Result<Vec<String>> {
+        self.expect_punct("(")?;
+        let mut names = vec![self.name()?];
+        while self.eat_punct(",") {
+            names.push(self.name()?);
+        }
+        self.expect_punct(")")?;
+        Ok(names)
+    }
+
+    /// The parent table and columns after REFERENCES; the ON and MATCH clauses that may
+    /// follow are left unread
+    fn references(&mut self) -> Result<(String, Vec<String>)> {
+        let table = self.name()?;
+        let columns = if self.peek().is_some_and(|t| t.is_punct("(")) {
+            self.name_list()?
+        } else {
+            Vec::new()
+        };
+        Ok((table, columns))
+    }
+
+    fn if_not_exists(&mut self) -> Result<()> {
+        if self.eat_keyword("IF") {
+            self.expect_keyword("NOT")?;
+            self.expect_keyword("EXISTS")?;
+        }
+        Ok(())
+    }
+
+    /// Source text covering tokens `start..end`
+    fn text(&self, start: usize, end: usize) -> &'a str {
+        if start >= end {
+            return "";
+        }
+        &self.sql[self.tokens[start].start..self.tokens[end - 1].end()]
+    }
+
+    /// Advances to the next `,` or `)` outside parentheses
+    fn skip_to_separator(&mut self) -> Result<()> {
+        let mut depth = 0usize;
+        while let Some(t) = self.peek() {
+            if t.is_punct("(") {
+                depth += 1;
+            } else if t.is_punct(")") || t.is_punct(",") {
+                if depth == 0 {
+                    return Ok(());
+                }
+                if t.is_punct(")") {
+                    depth -= 1;
+                }
+            }
+            self.pos += 1;
+        }
+        Err(self.error("unbalanced parentheses"))
+    }
+
+    fn column_def(&mut self) -> Result<ColumnDef> {
+        let start = self.pos;
+        let name = self.name()?;
+
+        let type_start = self.pos;
+        while self
+            .peek()
+            .is_some_and(|t| t.is_name() && !COLUMN_CONSTRAINTS.iter().any(|k| t.is_keyword(k)))
+        {
+            self.pos += 1;
+        }
+        if self.pos > type_start && self.eat_punct("(") {
+            self.skip_to_separator()?;
+            while self.eat_punct(",") {
+                self.skip_to_separator()?;
+            }
+            self.expect_punct(")")?;
+        }
+        let type_name = self.text(type_start, self.pos).to_string();
+
+        let mut column = ColumnDef {
+            name,
+            type_name,
+            primary_key: false,
+            not_null: false,
+            unique: false,
+            generated: None,
+            default: None,
+            collate: None,
+            constraints: String::new(),
+            sql: String::new(),
+        };
+        let constraints_start = self.pos;
+        let mut depth = 0usize;
+        while let Some(t) = self.peek().copied() {
+            if depth == 0 && (t.is_punct(",") || t.is_punct(")")) {
+                break;
+            }
+            self.pos += 1;
+            if t.is_punct("(") {
+                depth += 1;
+            } else if t.is_punct(")") {
+                depth -= 1;
+            } else if depth > 0 {
+                continue;
+            } else if t.is_keyword("CONSTRAINT") {
+                self.name()?;
+            } else if t.is_keyword("PRIMARY") {
+                column.primary_key = true;
+            } else if t.is_keyword("NOT") && self.eat_keyword("NULL") {
+                column.not_null = true;
+            } else if t.is_keyword("UNIQUE") {
+                column.unique = true;
+            } else if t.is_keyword("AS") {
+                column.generated.get_or_insert(Generated::Virtual);
+            } else if t.is_keyword("STORED") && column.generated.is_some() {
+                column.generated = Some(Generated::Stored);
+            } else if t.is_keyword("COLLATE") {
+                column.collate = Some(self.name()?);
+            } else if t.is_keyword("DEFAULT") {
+                let value_start = self.pos;
+                if self.eat_punct("(") {
+                    self.skip_to_separator()?;
+                    while self.eat_punct(",") {
+                        self.skip_to_separator()?;
+                    }
+                    self.expect_punct(")")?;
+                } else {
+                    if !self.eat_punct("-") {
+                        self.eat_punct("+");
+                    }
+                    if self.peek().is_none() {
+                        return Err(self.error("expected a DEFAULT value"));
+                    }
+                    self.pos += 1;
+                }
+                column.default = Some(self.text(value_start, self.pos).to_string());
+            }
+        }
+        column.constraints = self.text(constraints_start, self.pos).to_string();
+        column.sql = self.text(start, self.pos).to_string();
+        Ok(column)
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.eat_punct(";");
+        match self.peek() {
+            None => Ok(()),
+            Some(_) => Err(self.error("unexpected text after statement")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn alias(sql: &str) -> Option<usize> {
+        parse_create_table(sql).unwrap().rowid_alias()
+    }
+
+    #[test]
+    fn integer_primary_key_aliases_the_rowid() {
+        assert_eq!(alias("CREATE TABLE t(a, id INTEGER PRIMARY KEY)"), Some(1));
+        assert_eq!(alias("CREATE TABLE t(id integer primary key asc)"), Some(0));
+        assert_eq!(alias("CREATE TABLE t(id \"INTEGER\" PRIMARY KEY)"), Some(0));
+    }
+
+    #[test]
+    fn column_level_desc_is_an_ordinary_key() {
+        assert_eq!(alias("CREATE TABLE t(id INTEGER PRIMARY KEY DESC)"), None);
+    }
+
+    #[test]
+    fn table_level_key_aliases_even_descending() {
+        assert_eq!(
+            alias("CREATE TABLE t(x INTEGER, y, PRIMARY KEY(x))"),
+            Some(0)
+        );
+        assert_eq!(
+            alias("CREATE TABLE t(y, x INTEGER, PRIMARY KEY(x DESC))"),
+            Some(1)
+        );
+        assert_eq!(
+            alias("CREATE TABLE t(x INTEGER, PRIMARY KEY(\"X\" COLLATE NOCASE))"),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn only_the_type_integer_exactly() {
+        for sql in [
+            "CREATE TABLE t(id INT PRIMARY KEY)",
+            "CREATE TABLE t(id INTEGER UNSIGNED PRIMARY KEY)",
+            "CREATE TABLE t(id INTEGER(10) PRIMARY KEY)",
+            "CREATE TABLE t(id BIGINT PRIMARY KEY)",
+            "CREATE TABLE t(id PRIMARY KEY)",
+            "CREATE TABLE t(x INT, PRIMARY KEY(x))",
+        ] {
+            assert_eq!(alias(sql), None, "{}", sql);
+        }
+    }
+
+    #[test]
+    fn composite_keys_and_without_rowid_have_no_alias() {
+        assert_eq!(
+            alias("CREATE TABLE t(a INTEGER, b INTEGER, PRIMARY KEY(a, b))"),
+            None
+        );
+        assert_eq!(
+            alias("CREATE TABLE t(id INTEGER PRIMARY KEY, v) WITHOUT ROWID"),
+            None
+        );
+        assert_eq!(
+            alias("CREATE TABLE t(id INTEGER, v, PRIMARY KEY(id)) WITHOUT ROWID"),
+            None
+        );
+        assert_eq!(alias("CREATE TABLE t(a INTEGER, b)"), None);
+    }
+}
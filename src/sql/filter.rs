@@ -0,0 +1,917 @@
+//! Row filters: the WHERE clause of a query on one table, limited to comparing its columns
+//! with constants. Comparisons follow SQLite's rules for affinity, collation and NULL, so a
+//! filter keeps the same rows it would in sqlite3.
+
+use super::parse_literal;
+use super::tokenizer::{tokenize, Token, TokenKind};
+use crate::format::format_real;
+use crate::sql::create::Affinity;
+use crate::table::TableInfo;
+use crate::value::Collation;
+use crate::{Error, Result, Value};
+use std::cmp::Ordering;
+
+/// How deep a filter's expressions may nest, the same limit as SQLite's
+/// SQLITE_MAX_EXPR_DEPTH. Parsing and evaluating both recurse once per level, so this keeps
+/// a hostile filter from overflowing the stack.
+const MAX_DEPTH: usize = 1000;
+
+/// A parsed WHERE clause with its column names resolved against a table
+#[derive(Debug, Clone)]
+pub struct Filter {
+    expr: Expr,
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    /// A column by its position, or the rowid when there is none
+    Column {
+        index: Option<usize>,
+        affinity: Affinity,
+        collation: Option<Collation>,
+    },
+    Literal(Value),
+    Compare(Op, Box<Expr>, Box<Expr>),
+    /// `IS` and `IS NOT`, which treat NULLs as equal to each other
+    Is(Box<Expr>, Box<Expr>, bool),
+    In(Box<Expr>, Vec<Expr>, bool),
+    Between(Box<Expr>, Box<Expr>, Box<Expr>, bool),
+    Like(Box<Expr>, Box<Expr>, Option<char>, bool),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Op {
+    fn from_token(token: &Token) -> Option<Op> {
+        if token.kind != TokenKind::Punct {
+            return None;
+        }
+        match token.text {
+            "=" | "==" => Some(Op::Eq),
+            "!=" | "<>" => Some(Op::Ne),
+            "<" => Some(Op::Lt),
+            "<=" => Some(Op::Le),
+            ">" => Some(Op::Gt),
+            ">=" => Some(Op::Ge),
+            _ => None,
+        }
+    }
+
+    fn holds(self, ordering: Ordering) -> bool {
+        match self {
+            Op::Eq => ordering.is_eq(),
+            Op::Ne => ordering.is_ne(),
+            Op::Lt => ordering.is_lt(),
+            Op::Le => ordering.is_le(),
+            Op::Gt => ordering.is_gt(),
+            Op::Ge => ordering.is_ge(),
+        }
+    }
+}
+
+/// Parses a WHERE clause (without the `WHERE`) for rows of `table`. It may compare columns
+/// and constants with `=`, `<>`, `<` and the rest, `IS [NOT]`, `[NOT] IN (...)`,
+/// `[NOT] BETWEEN`, `[NOT] LIKE` and `IS [NOT] NULL`, combined with AND, OR, NOT and
+/// parentheses. Anything else, such as functions or arithmetic, is reported as unsupported
+/// rather than guessed at.
+pub fn parse_filter(sql: &str, table: &TableInfo) -> Result<Filter> {
+    let tokens = tokenize(sql)?;
+    let mut parser = Parser {
+        tokens: &tokens,
+        pos: 0,
+        depth: 0,
+        table,
+    };
+    let expr = parser.or()?;
+    if parser.pos < tokens.len() {
+        return Err(parser.unsupported());
+    }
+    Ok(Filter { expr })
+}
+
+impl Filter {
+    /// Whether the row passes: the clause is true for it, not false or NULL. `values` are the
+    /// row's columns in declaration order.
+    pub fn matches(&self, rowid: Option<i64>, values: &[Value]) -> bool {
+        truth(&self.expr.eval(rowid, values)) == Some(true)
+    }
+
+    /// The columns the filter reads, so a caller can load just those
+    pub fn columns(&self) -> Vec<usize> {
+        let mut columns = Vec::new();
+        self.expr.columns(&mut columns);
+        columns
+    }
+}
+
+struct Parser<'a, 'b> {
+    tokens: &'b [Token<'a>],
+    pos: usize,
+    /// How many expressions enclose the one being parsed, counting each operator of a chain
+    /// like `a OR b OR c` as one more level
+    depth: usize,
+    table: &'b TableInfo,
+}
+
+impl<'a, 'b> Parser<'a, 'b> {
+    fn peek(&self) -> Option<&'b Token<'a>> {
+        self.tokens.get(self.pos)
+    }
+
+    fn keyword(&mut self, keyword: &str) -> bool {
+        let found = self.peek().is_some_and(|t| t.is_keyword(keyword));
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn punct(&mut self, punct: &str) -> bool {
+        let found = self.peek().is_some_and(|t| t.is_punct(punct));
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn unsupported(&self) -> Error {
+        match self.peek() {
+            Some(t) => Error::Syntax(format!("unsupported in a row filter near {:?}", t.text)),
+            None => Error::Syntax("incomplete row filter".to_string()),
+        }
+    }
+
+    /// Goes one level deeper, failing past [`MAX_DEPTH`]. Callers put `depth` back once
+    /// they have parsed what is nested in them.
+    fn deeper(&mut self) -> Result<()> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(Error::Syntax(format!(
+                "row filter is nested more than {} deep",
+                MAX_DEPTH
+            )));
+        }
+        Ok(())
+    }
+
+    fn or(&mut self) -> Result<Expr> {
+        let depth = self.depth;
+        let mut expr = self.and()?;
+        while self.keyword("OR") {
+            self.deeper()?;
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        self.depth = depth;
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr> {
+        let depth = self.depth;
+        let mut expr = self.not()?;
+        while self.keyword("AND") {
+            self.deeper()?;
+            expr = Expr::And(Box::new(expr), Box::new(self.not()?));
+        }
+        self.depth = depth;
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> Result<Expr> {
+        if self.keyword("NOT") {
+            self.deeper()?;
+            let expr = Expr::Not(Box::new(self.not()?));
+            self.depth -= 1;
+            return Ok(expr);
+        }
+        self.equality()
+    }
+
+    /// `=`, `IS`, `IN`, `LIKE` and `BETWEEN`, which bind more loosely than `<` and the rest
+    fn equality(&mut self) -> Result<Expr> {
+        let depth = self.depth;
+        let mut expr = self.relational()?;
+        loop {
+            if let Some(op) = self
+                .peek()
+                .and_then(Op::from_token)
+                .filter(|op| matches!(op, Op::Eq | Op::Ne))
+            {
+                self.pos += 1;
+                self.deeper()?;
+                expr = Expr::Compare(op, Box::new(expr), Box::new(self.relational()?));
+            } else if self.keyword("ISNULL") {
+                self.deeper()?;
+                expr = Expr::Is(Box::new(expr), Box::new(Expr::Literal(Value::Null)), false);
+            } else if self.keyword("NOTNULL") {
+                self.deeper()?;
+                expr = Expr::Is(Box::new(expr), Box::new(Expr::Literal(Value::Null)), true);
+            } else if self.keyword("IS") {
+                let negated = self.keyword("NOT");
+                self.deeper()?;
+                expr = Expr::Is(Box::new(expr), Box::new(self.relational()?), negated);
+            } else {
+                let start = self.pos;
+                let negated = self.keyword("NOT");
+                if negated && self.keyword("NULL") {
+                    self.deeper()?;
+                    expr = Expr::Is(Box::new(expr), Box::new(Expr::Literal(Value::Null)), true);
+                } else if self.keyword("IN") {
+                    self.deeper()?;
+                    expr = Expr::In(Box::new(expr), self.list()?, negated);
+                } else if self.keyword("BETWEEN") {
+                    self.deeper()?;
+                    let low = self.relational()?;
+                    if !self.keyword("AND") {
+                        return Err(self.unsupported());
+                    }
+                    let high = self.relational()?;
+                    expr = Expr::Between(Box::new(expr), Box::new(low), Box::new(high), negated);
+                } else if self.keyword("LIKE") {
+                    self.deeper()?;
+                    let pattern = self.relational()?;
+                    let escape = if self.keyword("ESCAPE") {
+                        let escape = self.operand()?;
+                        let Expr::Literal(Value::Text(escape)) = escape else {
+                            return Err(Error::Syntax(
+                                "ESCAPE expects a single character".to_string(),
+                            ));
+                        };
+                        let mut chars = escape.chars();
+                        match (chars.next(), chars.next()) {
+                            (Some(c), None) => Some(c),
+                            _ => {
+                                return Err(Error::Syntax(
+                                    "ESCAPE expects a single character".to_string(),
+                                ))
+                            }
+                        }
+                    } else {
+                        None
+                    };
+                    expr = Expr::Like(Box::new(expr), Box::new(pattern), escape, negated);
+                } else {
+                    self.pos = start;
+                    self.depth = depth;
+                    return Ok(expr);
+                }
+            }
+        }
+    }
+
+    fn relational(&mut self) -> Result<Expr> {
+        let depth = self.depth;
+        let mut expr = self.operand()?;
+        while let Some(op) = self
+            .peek()
+            .and_then(Op::from_token)
+            .filter(|op| !matches!(op, Op::Eq | Op::Ne))
+        {
+            self.pos += 1;
+            self.deeper()?;
+            expr = Expr::Compare(op, Box::new(expr), Box::new(self.operand()?));
+        }
+        self.depth = depth;
+        Ok(expr)
+    }
+
+    /// The parenthesized list after IN
+    fn list(&mut self) -> Result<Vec<Expr>> {
+        if !self.punct("(") {
+            return Err(self.unsupported());
+        }
+        let mut list = Vec::new();
+        if self.punct(")") {
+            return Ok(list);
+        }
+        loop {
+            list.push(self.operand()?);
+            if self.punct(")") {
+                return Ok(list);
+            }
+            if !self.punct(",") {
+                return Err(self.unsupported());
+            }
+        }
+    }
+
+    /// A column, a constant or a parenthesized expression
+    fn operand(&mut self) -> Result<Expr> {
+        if self.punct("(") {
+            self.deeper()?;
+            let expr = self.or()?;
+            if !self.punct(")") {
+                return Err(self.unsupported());
+            }
+            self.depth -= 1;
+            return Ok(expr);
+        }
+        let token = *self.peek().ok_or_else(|| self.unsupported())?;
+        if token.is_punct("-") || token.is_punct("+") {
+            // only numbers can be signed, as there is no arithmetic on columns
+            let number = self
+                .tokens
+                .get(self.pos + 1)
+                .filter(|t| t.kind == TokenKind::Number)
+                .and_then(|t| parse_literal(&format!("{}{}", token.text, t.text)));
+            let Some(value) = number else {
+                return Err(self.unsupported());
+            };
+            self.pos += 2;
+            return Ok(Expr::Literal(value));
+        }
+        match token.kind {
+            TokenKind::Number | TokenKind::String | TokenKind::Blob => {
+                let value = parse_literal(token.text).ok_or_else(|| self.unsupported())?;
+                self.pos += 1;
+                Ok(Expr::Literal(value))
+            }
+            TokenKind::Word
+                if ["NULL", "TRUE", "FALSE"]
+                    .iter()
+                    .any(|k| token.is_keyword(k)) =>
+            {
+                self.pos += 1;
+                Ok(Expr::Literal(
+                    parse_literal(token.text).unwrap_or(Value::Null),
+                ))
+            }
+            TokenKind::Word | TokenKind::QuotedIdent => {
+                // followed by `(` it would be a function call
+                if self
+                    .tokens
+                    .get(self.pos + 1)
+                    .is_some_and(|t| t.is_punct("("))
+                {
+                    return Err(self.unsupported());
+                }
+                self.pos += 1;
+                let mut name = token.value().into_owned();
+                if self.peek().is_some_and(|t| t.is_punct(".")) {
+                    if !name.eq_ignore_ascii_case(&self.table.name) {
+                        return Err(Error::Syntax(format!(
+                            "a row filter of {} can't refer to {}",
+                            self.table.name, name
+                        )));
+                    }
+                    name = match self.tokens.get(self.pos + 1) {
+                        Some(t) if matches!(t.kind, TokenKind::Word | TokenKind::QuotedIdent) => {
+                            t.value().into_owned()
+                        }
+                        _ => {
+                            self.pos += 1;
+                            return Err(self.unsupported());
+                        }
+                    };
+                    self.pos += 2;
+                }
+                self.column(&name, token)
+            }
+            _ => Err(self.unsupported()),
+        }
+    }
+
+    fn column(&self, name: &str, token: Token) -> Result<Expr> {
+        let columns = self.table.columns();
+        if let Some(index) = columns
+            .iter()
+            .position(|c| c.name.eq_ignore_ascii_case(name))
+        {
+            let column = &columns[index];
+            let collation =
+                match &column.collate {
+                    Some(collate) => Some(Collation::from_name(collate).ok_or_else(|| {
+                        Error::Syntax(format!("unsupported collation {}", collate))
+                    })?),
+                    None => None,
+                };
+            return Ok(Expr::Column {
+                index: Some(index),
+                affinity: column.affinity(),
+                collation,
+            });
+        }
+        if !self.table.definition.without_rowid
+            && ["rowid", "oid", "_rowid_"]
+                .iter()
+                .any(|r| r.eq_ignore_ascii_case(name))
+        {
+            return Ok(Expr::Column {
+                index: None,
+                affinity: Affinity::Integer,
+                collation: None,
+            });
+        }
+        // like SQLite, a double-quoted name that isn't a column is taken for a string
+        if token.text.starts_with('"') {
+            return Ok(Expr::Literal(Value::Text(name.to_string())));
+        }
+        Err(Error::Syntax(format!(
+            "no such column in {}: {}",
+            self.table.name, name
+        )))
+    }
+}
+
+impl Expr {
+    fn eval(&self, rowid: Option<i64>, values: &[Value]) -> Value {
+        match self {
+            Expr::Column { index: Some(i), .. } => values.get(*i).cloned().unwrap_or(Value::Null),
+            Expr::Column { index: None, .. } => rowid.map_or(Value::Null, Value::Integer),
+            Expr::Literal(value) => value.clone(),
+            Expr::Compare(op, left, right) => {
+                boolean(compare(left, right, rowid, values).map(|o| op.holds(o)))
+            }
+            Expr::Is(left, right, negated) => {
+                let (a, b) = (left.eval(rowid, values), right.eval(rowid, values));
+                let same = match (&a, &b) {
+                    (Value::Null, Value::Null) => true,
+                    (Value::Null, _) | (_, Value::Null) => false,
+                    _ => compare_values(left, right, a, b) == Some(Ordering::Equal),
+                };
+                boolean(Some(same != *negated))
+            }
+            Expr::In(left, list, negated) => {
+                let mut found = Some(false);
+                for item in list {
+                    match compare(left, item, rowid, values) {
+                        Some(Ordering::Equal) => {
+                            found = Some(true);
+                            break;
+                        }
+                        Some(_) => {}
+                        None => found = None,
+                    }
+                }
+                boolean(found.map(|found| found != *negated))
+            }
+            Expr::Between(expr, low, high, negated) => {
+                let above = compare(expr, low, rowid, values).map(Ordering::is_ge);
+                let below = compare(expr, high, rowid, values).map(Ordering::is_le);
+                boolean(and(above, below).map(|within| within != *negated))
+            }
+            Expr::Like(expr, pattern, escape, negated) => {
+                let text = text(&expr.eval(rowid, values));
+                let pattern = text_pattern(&pattern.eval(rowid, values));
+                boolean(match (text, pattern) {
+                    (Some(text), Some(pattern)) => Some(like(&pattern, &text, *escape) != *negated),
+                    _ => None,
+                })
+            }
+            Expr::And(left, right) => boolean(and(
+                truth(&left.eval(rowid, values)),
+                truth(&right.eval(rowid, values)),
+            )),
+            Expr::Or(left, right) => {
+                let (a, b) = (
+                    truth(&left.eval(rowid, values)),
+                    truth(&right.eval(rowid, values)),
+                );
+                boolean(match (a, b) {
+                    (Some(true), _) | (_, Some(true)) => Some(true),
+                    (Some(false), Some(false)) => Some(false),
+                    _ => None,
+                })
+            }
+            Expr::Not(expr) => boolean(truth(&expr.eval(rowid, values)).map(|b| !b)),
+        }
+    }
+
+    fn columns(&self, out: &mut Vec<usize>) {
+        match self {
+            Expr::Column { index: Some(i), .. } => {
+                if !out.contains(i) {
+                    out.push(*i);
+                }
+            }
+            Expr::Column { index: None, .. } | Expr::Literal(_) => {}
+            Expr::Compare(_, a, b) | Expr::Is(a, b, _) | Expr::Like(a, b, _, _) => {
+                a.columns(out);
+                b.columns(out);
+            }
+            Expr::And(a, b) | Expr::Or(a, b) => {
+                a.columns(out);
+                b.columns(out);
+            }
+            Expr::In(a, list, _) => {
+                a.columns(out);
+                list.iter().for_each(|e| e.columns(out));
+            }
+            Expr::Between(a, b, c, _) => {
+                a.columns(out);
+                b.columns(out);
+                c.columns(out);
+            }
+            Expr::Not(a) => a.columns(out),
+        }
+    }
+
+    /// Columns have the affinity of their declared type; constants have none
+    fn affinity(&self) -> Option<Affinity> {
+        match self {
+            Expr::Column { affinity, .. } => Some(*affinity),
+            _ => None,
+        }
+    }
+
+    fn collation(&self) -> Option<Collation> {
+        match self {
+            Expr::Column { collation, .. } => *collation,
+            _ => None,
+        }
+    }
+}
+
+/// Orders two operands as SQLite compares them, or `None` when either is NULL
+fn compare(left: &Expr, right: &Expr, rowid: Option<i64>, values: &[Value]) -> Option<Ordering> {
+    compare_values(
+        left,
+        right,
+        left.eval(rowid, values),
+        right.eval(rowid, values),
+    )
+}
+
+fn compare_values(left: &Expr, right: &Expr, mut a: Value, mut b: Value) -> Option<Ordering> {
+    if a == Value::Null || b == Value::Null {
+        return None;
+    }
+    // a numeric column makes both sides numbers if they can be, and a TEXT column compared
+    // with a constant makes both text; a BLOB column converts nothing
+    let numeric = |affinity: Affinity| {
+        matches!(
+            affinity,
+            Affinity::Integer | Affinity::Real | Affinity::Numeric
+        )
+    };
+    let affinity = match (left.affinity(), right.affinity()) {
+        (Some(x), Some(y)) if numeric(x) || numeric(y) => Some(Affinity::Numeric),
+        (Some(_), Some(_)) => None,
+        (Some(x), None) | (None, Some(x)) => Some(x),
+        (None, None) => None,
+    };
+    match affinity {
+        Some(Affinity::Text) => {
+            a = to_text(a);
+            b = to_text(b);
+        }
+        Some(affinity) if numeric(affinity) => {
+            a = to_number(a);
+            b = to_number(b);
+        }
+        _ => {}
+    }
+    let collation = left
+        .collation()
+        .or_else(|| right.collation())
+        .unwrap_or_default();
+    Some(a.compare_with(&b, collation))
+}
+
+fn to_text(value: Value) -> Value {
+    match value {
+        Value::Integer(i) => Value::Text(i.to_string()),
+        Value::Real(f) => Value::Text(format_real(f)),
+        value => value,
+    }
+}
+
+/// TEXT that reads as a number in full, surrounding spaces aside, becomes that number
+fn to_number(value: Value) -> Value {
+    let Value::Text(text) = &value else {
+        return value;
+    };
+    let trimmed = text.trim_matches(|c: char| c.is_ascii_whitespace());
+    let well_formed = !trimmed.is_empty()
+        && trimmed
+            .bytes()
+            .all(|b| b.is_ascii_digit() || matches!(b, b'+' | b'-' | b'.' | b'e' | b'E'));
+    if !well_formed {
+        return value;
+    }
+    if let Ok(i) = trimmed.parse::<i64>() {
+        return Value::Integer(i);
+    }
+    match trimmed.parse::<f64>() {
+        Ok(f) => Value::Real(f),
+        Err(_) => value,
+    }
+}
+
+/// A value as the text LIKE matches, or `None` for NULL
+fn text(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => None,
+        Value::Integer(i) => Some(i.to_string()),
+        Value::Real(f) => Some(format_real(*f)),
+        Value::Text(s) => Some(s.clone()),
+        Value::RawText(b) | Value::Blob(b) => Some(String::from_utf8_lossy(b).into_owned()),
+    }
+}
+
+fn text_pattern(value: &Value) -> Option<Vec<char>> {
+    text(value).map(|s| s.chars().collect())
+}
+
+/// SQLite's LIKE: `%` matches any run of characters, `_` any one, and ASCII letters match
+/// either case. Like SQLite's patternCompare this only ever goes back to the latest `%`, so
+/// it takes time proportional to the pattern times the text however many there are.
+fn like(pattern: &[char], text: &str, escape: Option<char>) -> bool {
+    let Some(pattern) = like_pattern(pattern, escape) else {
+        return false;
+    };
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // where to resume after the latest `%`: the pattern after it and the text it has taken
+    let mut resume = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some(Like::Any) => {
+                p += 1;
+                resume = Some((p, t));
+                continue;
+            }
+            Some(Like::One) => {
+                p += 1;
+                t += 1;
+                continue;
+            }
+            Some(Like::Char(c)) if c.eq_ignore_ascii_case(&text[t]) => {
+                p += 1;
+                t += 1;
+                continue;
+            }
+            _ => {}
+        }
+        // a mismatch: let the latest `%` take one more character, or fail without one
+        let Some((after, taken)) = resume else {
+            return false;
+        };
+        p = after;
+        t = taken + 1;
+        resume = Some((after, t));
+    }
+    pattern[p..].iter().all(|piece| *piece == Like::Any)
+}
+
+/// One step of a LIKE pattern once escapes are resolved
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Like {
+    Any,
+    One,
+    Char(char),
+}
+
+/// Resolves escapes in a LIKE pattern, or `None` if it ends in the escape character, which
+/// matches nothing
+fn like_pattern(pattern: &[char], escape: Option<char>) -> Option<Vec<Like>> {
+    let mut pieces = Vec::with_capacity(pattern.len());
+    let mut chars = pattern.iter();
+    while let Some(&c) = chars.next() {
+        pieces.push(match c {
+            c if Some(c) == escape => Like::Char(*chars.next()?),
+            '%' => Like::Any,
+            '_' => Like::One,
+            c => Like::Char(c),
+        });
+    }
+    Some(pieces)
+}
+
+/// A value as a condition: NULL is unknown, numbers are true unless zero, and text and BLOBs
+/// by the number they start with
+fn truth(value: &Value) -> Option<bool> {
+    match value {
+        Value::Null => None,
+        Value::Integer(i) => Some(*i != 0),
+        Value::Real(f) => Some(*f != 0.0),
+        Value::Text(s) => Some(leading_number(s.as_bytes()) != 0.0),
+        Value::RawText(b) | Value::Blob(b) => Some(leading_number(b) != 0.0),
+    }
+}
+
+fn leading_number(bytes: &[u8]) -> f64 {
+    let start = bytes
+        .iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .unwrap_or(bytes.len());
+    let bytes = &bytes[start..];
+    let mut end = 0;
+    let mut best = 0.0;
+    // the longest prefix that parses, grown a byte at a time over the characters a number
+    // can hold
+    while end < bytes.len()
+        && (bytes[end].is_ascii_digit() || matches!(bytes[end], b'+' | b'-' | b'.' | b'e' | b'E'))
+    {
+        end += 1;
+        if let Some(f) = std::str::from_utf8(&bytes[..end])
+            .ok()
+            .and_then(|s| s.parse::<f64>().ok())
+        {
+            best = f;
+        }
+    }
+    best
+}
+
+fn and(a: Option<bool>, b: Option<bool>) -> Option<bool> {
+    match (a, b) {
+        (Some(false), _) | (_, Some(false)) => Some(false),
+        (Some(true), Some(true)) => Some(true),
+        _ => None,
+    }
+}
+
+fn boolean(value: Option<bool>) -> Value {
+    match value {
+        Some(b) => Value::Integer(i64::from(b)),
+        None => Value::Null,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::{ObjectType, SchemaEntry};
+
+    fn table() -> TableInfo {
+        TableInfo::from_schema(&SchemaEntry {
+            kind: ObjectType::Table,
+            name: "t".to_string(),
+            tbl_name: "t".to_string(),
+            root_page: 2,
+            sql: Some(
+                "CREATE TABLE t(id INTEGER PRIMARY KEY, n INT, s TEXT, c TEXT COLLATE NOCASE, b)"
+                    .to_string(),
+            ),
+        })
+        .unwrap()
+    }
+
+    fn row(n: Value, s: &str) -> Vec<Value> {
+        vec![
+            Value::Integer(7),
+            n,
+            Value::Text(s.to_string()),
+            Value::Text(s.to_string()),
+            Value::Blob(b"12".to_vec()),
+        ]
+    }
+
+    fn keeps(filter: &str, values: &[Value]) -> bool {
+        parse_filter(filter, &table())
+            .unwrap()
+            .matches(Some(7), values)
+    }
+
+    #[test]
+    fn comparisons() {
+        let r = row(Value::Integer(10), "abc");
+        assert!(keeps("n = 10", &r));
+        assert!(keeps("n == 10 AND id = 7 AND rowid = 7", &r));
+        assert!(keeps("n > 9.5 and n <= 10", &r));
+        assert!(keeps("10 >= n", &r));
+        assert!(!keeps("n <> 10", &r));
+        assert!(keeps("s = 'abc' OR n = 0", &r));
+        assert!(keeps("NOT (s = 'x')", &r));
+        assert!(keeps("t.s = \"abc\"", &r));
+    }
+
+    #[test]
+    fn precedence() {
+        // NOT binds more loosely than comparisons and AND more tightly than OR, so this is
+        // `((n = 1) AND (NOT (s = 'x'))) OR (id = 7)`
+        let r = row(Value::Integer(2), "x");
+        assert!(keeps("n = 1 AND NOT s = 'x' OR id = 7", &r));
+        assert!(!keeps("n = 1 AND NOT (s = 'x' OR id = 7)", &r));
+        assert!(!keeps("NOT n = 2 OR s <> 'x'", &r));
+        assert!(keeps("NOT NOT n = 2", &r));
+        // `<` binds more tightly than `=`, which compares the 0 or 1 it yields
+        assert!(keeps("n < 3 = 1", &r));
+        assert!(keeps("n = 2 = 1", &r));
+    }
+
+    #[test]
+    fn affinity_and_collation() {
+        let r = row(Value::Integer(10), "10");
+        // a numeric column compares with text that reads as a number as that number
+        assert!(keeps("n = '10'", &r));
+        assert!(keeps("n = ' 10.0 '", &r));
+        // a TEXT column compares with a number as text
+        assert!(keeps("s = 10", &r));
+        assert!(!keeps("s = 10.0", &r));
+        // a column without a type converts nothing
+        assert!(!keeps("b = 12", &r));
+        assert!(keeps("b = x'3132'", &r));
+        // the column's collation decides
+        let r = row(Value::Null, "Abc");
+        assert!(keeps("c = 'aBC'", &r));
+        assert!(!keeps("s = 'aBC'", &r));
+    }
+
+    #[test]
+    fn null_is_neither_true_nor_false() {
+        let r = row(Value::Null, "abc");
+        assert!(!keeps("n = 1", &r));
+        assert!(!keeps("NOT n = 1", &r));
+        assert!(keeps("n IS NULL AND n ISNULL", &r));
+        assert!(!keeps("n IS NOT NULL OR n NOTNULL OR n NOT NULL", &r));
+        assert!(keeps("n IS NULL OR n = 1", &r));
+        assert!(keeps("s IS 'abc' AND n IS NOT 1", &r));
+        assert!(!keeps("n IN (1, 2)", &r));
+        // no match, but a NULL in the list means the answer is unknown
+        let r = row(Value::Integer(3), "abc");
+        assert!(!keeps("n NOT IN (1, NULL)", &r));
+        assert!(keeps("n NOT IN (1, 2)", &r));
+        assert!(keeps("n IN (1, 3)", &r));
+    }
+
+    #[test]
+    fn between_and_like() {
+        let r = row(Value::Integer(5), "Hello_world");
+        assert!(keeps("n BETWEEN 1 AND 5 AND s = 'Hello_world'", &r));
+        assert!(keeps("n NOT BETWEEN 6 AND 9", &r));
+        assert!(keeps("s LIKE 'hello%'", &r));
+        assert!(keeps("s LIKE '_ELLO%D'", &r));
+        assert!(!keeps("s LIKE 'hello'", &r));
+        assert!(keeps("s NOT LIKE '%x%'", &r));
+        assert!(keeps("s LIKE 'hello!_%' ESCAPE '!'", &r));
+        assert!(keeps("n LIKE '5'", &r));
+        assert!(keeps("n = -5 OR n = +5", &r));
+        assert!(!keeps("s LIKE 'hello!' ESCAPE '!'", &r));
+    }
+
+    #[test]
+    fn like_with_many_wildcards() {
+        // each `%` used to try every split of the rest of the text, which for a pattern like
+        // this one would not finish
+        let text = "a".repeat(200);
+        let r = row(Value::Integer(0), &text);
+        let pattern = "%a".repeat(40);
+        assert!(keeps(&format!("s LIKE '{}'", pattern), &r));
+        assert!(!keeps(&format!("s LIKE '{}b'", pattern), &r));
+        assert!(keeps("s LIKE '%a%_%'", &r));
+        assert!(!keeps(&format!("s LIKE '{}'", "%_".repeat(201)), &r));
+        assert!(keeps(&format!("s LIKE '{}%'", "%_".repeat(200)), &r));
+    }
+
+    #[test]
+    fn unsupported_filters_are_errors() {
+        let table = table();
+        for filter in [
+            "",
+            "n = 1 +",
+            "length(s) > 3",
+            "n + 1 = 2",
+            "nope = 1",
+            "u.n = 1",
+            "n IN (SELECT 1)",
+            "s LIKE 'a' ESCAPE 'ab'",
+            "n = ?",
+        ] {
+            assert!(parse_filter(filter, &table).is_err(), "{}", filter);
+        }
+    }
+
+    #[test]
+    fn nesting_is_limited() {
+        // every level of parentheses goes through each precedence level of the parser, which
+        // in a debug build needs more stack than a test thread has at the limit
+        let parse = |filter: String| {
+            std::thread::Builder::new()
+                .stack_size(64 << 20)
+                .spawn(move || parse_filter(&filter, &table()).map(|_| ()))
+                .unwrap()
+                .join()
+                .unwrap()
+        };
+        let nested = |n| format!("{}n = 1{}", "(".repeat(n), ")".repeat(n));
+        // the comparison inside the parentheses is a level of its own
+        assert!(parse(nested(MAX_DEPTH - 1)).is_ok());
+        assert!(matches!(parse(nested(MAX_DEPTH)), Err(Error::Syntax(_))));
+        assert!(matches!(parse(nested(100_000)), Err(Error::Syntax(_))));
+        let nots = format!("{}n = 1", "NOT ".repeat(100_000));
+        assert!(matches!(parse(nots), Err(Error::Syntax(_))));
+        let chain = vec!["n = 1"; 100_000].join(" OR ");
+        assert!(matches!(parse(chain), Err(Error::Syntax(_))));
+        // levels that are closed again don't count against the ones that follow
+        let siblings = vec![nested(MAX_DEPTH / 2); 10].join(" AND ");
+        assert!(parse(siblings).is_ok());
+    }
+
+    #[test]
+    fn columns_read() {
+        let filter = parse_filter("s = 'a' OR (b IS NULL AND s <> rowid)", &table()).unwrap();
+        assert_eq!(filter.columns(), [2, 4]);
+    }
+}
@@ -0,0 +1,185 @@
+//! Just enough SQL to understand the statements stored in `sqlite_schema`, and the simple
+//! queries there is an execution path for
+
+pub mod create;
+pub mod filter;
+pub mod select;
+pub mod tokenizer;
+
+use crate::format::format_real_exact;
+use crate::{Result, Value};
+use std::io::Write;
+use tokenizer::{tokenize, TokenKind};
+
+/// A canonical spelling of a statement for comparing two definitions: comments and layout are
+/// dropped, and identifiers are unquoted and lowercased since SQLite matches them without regard
+/// to case or quoting
+pub fn normalize(sql: &str) -> Result<String> {
+    let tokens = tokenize(sql)?;
+    let words: Vec<String> = tokens
+        .iter()
+        .filter(|t| !t.is_punct(";"))
+        .map(|t| match t.kind {
+            TokenKind::Word | TokenKind::QuotedIdent => t.value().to_lowercase(),
+            _ => t.text.to_string(),
+        })
+        .collect();
+    Ok(words.join(" "))
+}
+
+/// Every keyword SQLite recognizes, sorted; any of them used as a name has to be quoted
+#[rustfmt::skip]
+const KEYWORDS: &[&str] = &[
+    "ABORT", "ACTION", "ADD", "AFTER", "ALL", "ALTER", "ALWAYS", "ANALYZE", "AND", "AS", "ASC",
+    "ATTACH", "AUTOINCREMENT", "BEFORE", "BEGIN", "BETWEEN", "BY", "CASCADE", "CASE", "CAST",
+    "CHECK", "COLLATE", "COLUMN", "COMMIT", "CONFLICT", "CONSTRAINT", "CREATE", "CROSS",
+    "CURRENT", "CURRENT_DATE", "CURRENT_TIME", "CURRENT_TIMESTAMP", "DATABASE", "DEFAULT",
+    "DEFERRABLE", "DEFERRED", "DELETE", "DESC", "DETACH", "DISTINCT", "DO", "DROP", "EACH",
+    "ELSE", "END", "ESCAPE", "EXCEPT", "EXCLUDE", "EXCLUSIVE", "EXISTS", "EXPLAIN", "FAIL",
+    "FILTER", "FIRST", "FOLLOWING", "FOR", "FOREIGN", "FROM", "FULL", "GENERATED", "GLOB",
+    "GROUP", "GROUPS", "HAVING", "IF", "IGNORE", "IMMEDIATE", "IN", "INDEX", "INDEXED",
+    "INITIALLY", "INNER", "INSERT", "INSTEAD", "INTERSECT", "INTO", "IS", "ISNULL", "JOIN",
+    "KEY", "LAST", "LEFT", "LIKE", "LIMIT", "MATCH", "MATERIALIZED", "NATURAL", "NO", "NOT",
+    "NOTHING", "NOTNULL", "NULL", "NULLS", "OF", "OFFSET", "ON", "OR", "ORDER", "OTHERS",
+    "OUTER", "OVER", "PARTITION", "PLAN", "PRAGMA", "PRECEDING", "PRIMARY", "QUERY", "RAISE",
+    "RANGE", "RECURSIVE", "REFERENCES", "REGEXP", "REINDEX", "RELEASE", "RENAME", "REPLACE",
+    "RESTRICT", "RETURNING", "RIGHT", "ROLLBACK", "ROW", "ROWS", "SAVEPOINT", "SELECT", "SET",
+    "TABLE", "TEMP", "TEMPORARY", "THEN", "TIES", "TO", "TRANSACTION", "TRIGGER", "UNBOUNDED",
+    "UNION", "UNIQUE", "UPDATE", "USING", "VACUUM", "VALUES", "VIEW", "VIRTUAL", "WHEN",
+    "WHERE", "WINDOW", "WITH", "WITHOUT",
+];
+
+/// Double-quotes an identifier unless it is a plain word that isn't a keyword
+pub fn quote_identifier(name: &str) -> String {
+    let plain = name
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && KEYWORDS
+            .binary_search(&name.to_ascii_uppercase().as_str())
+            .is_err();
+    if plain {
+        name.to_string()
+    } else {
+        format!("\"{}\"", name.replace('"', "\"\""))
+    }
+}
+
+/// The value as an SQL literal that reads back identically: TEXT with doubled quotes and
+/// line breaks spelled out with char(), BLOBs as X'..', and REALs always in a form that
+/// parses as REAL
+pub fn literal(value: &Value) -> String {
+    match value {
+        Value::Null => "NULL".to_string(),
+        Value::Integer(i) => i.to_string(),
+        Value::Real(f) if f.is_nan() => "NULL".to_string(),
+        Value::Real(f) if f.is_infinite() => if *f > 0.0 { "1e999" } else { "-1e999" }.to_string(),
+        Value::Real(f) => format_real_exact(*f),
+        Value::Text(s) => text_literal(s),
+        Value::RawText(b) => format!("CAST(X'{}' AS TEXT)", hex(b)),
+        Value::Blob(b) => format!("X'{}'", hex(b)),
+    }
+}
+
+/// A quoted string, with the characters a script can't carry safely inside quotes (NUL,
+/// CR and LF) concatenated in as char() calls
+fn text_literal(s: &str) -> String {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    for c in s.chars() {
+        if matches!(c, '\0' | '\r' | '\n') {
+            if !current.is_empty() {
+                parts.push(format!("'{}'", current.replace('\'', "''")));
+                current.clear();
+            }
+            parts.push(format!("char({})", c as u32));
+        } else {
+            current.push(c);
+        }
+    }
+    if !current.is_empty() || parts.is_empty() {
+        parts.push(format!("'{}'", current.replace('\'', "''")));
+    }
+    parts.join("||")
+}
+
+/// The value of a constant expression such as a column DEFAULT: a number, string, BLOB, NULL,
+/// TRUE or FALSE, optionally signed or parenthesized. Anything else yields `None`.
+pub fn parse_literal(sql: &str) -> Option<Value> {
+    let tokens = tokenize(sql).ok()?;
+    let mut tokens = tokens.as_slice();
+    while let [first, inner @ .., last] = tokens {
+        if !(first.is_punct("(") && last.is_punct(")")) {
+            break;
+        }
+        tokens = inner;
+    }
+    let (negative, tokens) = match tokens {
+        [sign, rest @ ..] if sign.is_punct("-") => (true, rest),
+        [sign, rest @ ..] if sign.is_punct("+") => (false, rest),
+        _ => (false, tokens),
+    };
+    let [token] = tokens else {
+        return None;
+    };
+    let value = match token.kind {
+        TokenKind::Number => {
+            let text = token.text.replace('_', "");
+            let int = match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+                Some(hex) => u64::from_str_radix(hex, 16).ok().and_then(|n| {
+                    if negative {
+                        (n as i64).checked_neg()
+                    } else {
+                        Some(n as i64)
+                    }
+                }),
+                // parse with the sign so i64::MIN fits
+                None if negative => format!("-{}", text).parse::<i64>().ok(),
+                None => text.parse::<i64>().ok(),
+            };
+            match int {
+                Some(n) => Value::Integer(n),
+                None => {
+                    let f: f64 = text.parse().ok()?;
+                    Value::Real(if negative { -f } else { f })
+                }
+            }
+        }
+        _ if negative => return None,
+        TokenKind::String => Value::Text(token.value().into_owned()),
+        TokenKind::Blob => {
+            let digits = &token.text[2..token.text.len() - 1];
+            let bytes = (0..digits.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(&digits[i..i + 2], 16))
+                .collect::<std::result::Result<Vec<u8>, _>>()
+                .ok()?;
+            Value::Blob(bytes)
+        }
+        _ if token.is_keyword("NULL") => Value::Null,
+        _ if token.is_keyword("TRUE") => Value::Integer(1),
+        _ if token.is_keyword("FALSE") => Value::Integer(0),
+        _ => return None,
+    };
+    Some(value)
+}
+
+/// Writes everything passed through it to the inner writer as uppercase hex digits, for
+/// spelling out X'..' literals too large to build as a string
+pub struct HexWriter<W: Write>(pub W);
+
+impl<W: Write> Write for HexWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.write_all(hex(buf).as_bytes())?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.flush()
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02X}", b)).collect()
+}
@@ -0,0 +1,89 @@
+//! The one query shape there is an execution path for: columns read straight out of a table
+
+use super::tokenizer::{tokenize, Token, TokenKind};
+use crate::{Error, Result};
+
+/// `SELECT <columns> FROM <table>`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Select {
+    pub columns: Vec<ResultColumn>,
+    pub table: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResultColumn {
+    /// `*`: every column of the table, in order
+    All,
+    /// A column by name, which may be `rowid` or one of its aliases
+    Column(String),
+}
+
+/// Parses `SELECT col, ... FROM table` or `SELECT * FROM table`, with a `main.` prefix on the
+/// table and a trailing semicolon allowed. Anything beyond that (expressions, WHERE, joins and
+/// the rest) is reported as unsupported rather than ignored.
+pub fn parse_select(sql: &str) -> Result<Select> {
+    let tokens = tokenize(sql)?;
+    let mut tokens = tokens.as_slice();
+    if let [rest @ .., last] = tokens {
+        if last.is_punct(";") {
+            tokens = rest;
+        }
+    }
+    let unsupported = |near: Option<&Token>| match near {
+        Some(t) => Error::Syntax(format!(
+            "only SELECT <columns> FROM <table> can be run; unsupported near {:?}",
+            t.text
+        )),
+        None => Error::Syntax(
+            "only SELECT <columns> FROM <table> can be run; incomplete statement".to_string(),
+        ),
+    };
+
+    let mut pos = 0;
+    if !tokens.first().is_some_and(|t| t.is_keyword("SELECT")) {
+        return Err(unsupported(tokens.first()));
+    }
+    pos += 1;
+    if tokens.get(pos).is_some_and(|t| t.is_keyword("ALL")) {
+        pos += 1;
+    }
+    let mut columns = Vec::new();
+    loop {
+        let column = match tokens.get(pos) {
+            Some(t) if t.is_punct("*") => ResultColumn::All,
+            // a string here is a literal value, not a column name
+            Some(t) if t.is_name() && t.kind != TokenKind::String && !t.is_keyword("FROM") => {
+                ResultColumn::Column(t.value().into_owned())
+            }
+            other => return Err(unsupported(other)),
+        };
+        columns.push(column);
+        pos += 1;
+        match tokens.get(pos) {
+            Some(t) if t.is_punct(",") => pos += 1,
+            Some(t) if t.is_keyword("FROM") => break,
+            other => return Err(unsupported(other)),
+        }
+    }
+    pos += 1;
+    let mut table = match tokens.get(pos) {
+        Some(t) if t.is_name() => t.value().into_owned(),
+        other => return Err(unsupported(other)),
+    };
+    pos += 1;
+    // there are no attached databases, so `main` is the only schema a table can be in
+    if tokens.get(pos).is_some_and(|t| t.is_punct(".")) {
+        if !table.eq_ignore_ascii_case("main") {
+            return Err(Error::Syntax(format!("unknown database {}", table)));
+        }
+        table = match tokens.get(pos + 1) {
+            Some(t) if t.is_name() => t.value().into_owned(),
+            other => return Err(unsupported(other)),
+        };
+        pos += 2;
+    }
+    if pos < tokens.len() {
+        return Err(unsupported(tokens.get(pos)));
+    }
+    Ok(Select { columns, table })
+}
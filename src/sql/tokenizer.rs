@@ -0,0 +1,263 @@
+use crate::{Error, Result};
+use std::borrow::Cow;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    /// A keyword or unquoted identifier
+    Word,
+    /// An identifier in `"double quotes"`, `[brackets]` or `` `backticks` ``
+    QuotedIdent,
+    /// A `'single-quoted'` string literal
+    String,
+    /// An `x'0a1b'` BLOB literal
+    Blob,
+    Number,
+    /// A bound parameter: `?`, `?3`, `:name`, `@name` or `$name`
+    Variable,
+    /// Operators and punctuation
+    Punct,
+}
+
+/// A token along with its position in the source, so callers can slice out original text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Token<'a> {
+    pub kind: TokenKind,
+    pub text: &'a str,
+    /// Byte offset of the token in the source
+    pub start: usize,
+}
+
+impl<'a> Token<'a> {
+    pub fn end(&self) -> usize {
+        self.start + self.text.len()
+    }
+
+    pub fn is_keyword(&self, keyword: &str) -> bool {
+        self.kind == TokenKind::Word && self.text.eq_ignore_ascii_case(keyword)
+    }
+
+    pub fn is_punct(&self, punct: &str) -> bool {
+        self.kind == TokenKind::Punct && self.text == punct
+    }
+
+    /// Whether the token can name something; SQLite also accepts string literals here
+    pub fn is_name(&self) -> bool {
+        matches!(
+            self.kind,
+            TokenKind::Word | TokenKind::QuotedIdent | TokenKind::String
+        )
+    }
+
+    /// The token's value with quotes removed and doubled quote characters collapsed
+    pub fn value(&self) -> Cow<'a, str> {
+        match self.kind {
+            TokenKind::QuotedIdent | TokenKind::String => {
+                let inner = &self.text[1..self.text.len() - 1];
+                match self.text.as_bytes()[0] {
+                    b'[' => Cow::Borrowed(inner),
+                    quote => {
+                        let quote = quote as char;
+                        let doubled: String = [quote, quote].iter().collect();
+                        if inner.contains(&doubled) {
+                            Cow::Owned(inner.replace(&doubled, &quote.to_string()))
+                        } else {
+                            Cow::Borrowed(inner)
+                        }
+                    }
+                }
+            }
+            _ => Cow::Borrowed(self.text),
+        }
+    }
+}
+
+// Longest operators first so `<=` isn't read as `<` then `=`
+const OPERATORS: [&str; 10] = ["->>", "||", "<=", ">=", "==", "!=", "<>", "<<", ">>", "->"];
+
+fn is_ident_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '$' || !c.is_ascii()
+}
+
+/// Splits SQL into tokens, skipping whitespace and `--` / `/* */` comments
+pub fn tokenize(sql: &str) -> Result<Vec<Token<'_>>> {
+    let bytes = sql.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    let syntax = |msg: &str, at: usize| Error::Syntax(format!("{} at offset {}", msg, at));
+
+    while i < sql.len() {
+        let c = sql[i..].chars().next().unwrap_or_default();
+        let start = i;
+        let kind = match c {
+            c if c.is_whitespace() => {
+                i += c.len_utf8();
+                continue;
+            }
+            '-' if bytes.get(i + 1) == Some(&b'-') => {
+                i = sql[i..].find('\n').map_or(sql.len(), |n| i + n + 1);
+                continue;
+            }
+            '/' if bytes.get(i + 1) == Some(&b'*') => {
+                // an unterminated comment runs to the end of the input, as in SQLite
+                i = sql[i + 2..].find("*/").map_or(sql.len(), |n| i + 2 + n + 2);
+                continue;
+            }
+            '\'' | '"' | '`' => {
+                i = closing_quote(sql, i, c).ok_or_else(|| syntax("unterminated quote", start))?;
+                if c == '\'' {
+                    TokenKind::String
+                } else {
+                    TokenKind::QuotedIdent
+                }
+            }
+            '[' => {
+                i = sql[i..]
+                    .find(']')
+                    .map(|n| i + n + 1)
+                    .ok_or_else(|| syntax("unterminated [identifier]", start))?;
+                TokenKind::QuotedIdent
+            }
+            'x' | 'X' if bytes.get(i + 1) == Some(&b'\'') => {
+                i = closing_quote(sql, i + 1, '\'')
+                    .ok_or_else(|| syntax("unterminated BLOB literal", start))?;
+                let digits = &sql[start + 2..i - 1];
+                if digits.len() % 2 != 0 || !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+                    return Err(syntax("malformed BLOB literal", start));
+                }
+                TokenKind::Blob
+            }
+            c if c.is_ascii_digit()
+                || (c == '.' && bytes.get(i + 1).is_some_and(u8::is_ascii_digit)) =>
+            {
+                i = number_end(bytes, i);
+                TokenKind::Number
+            }
+            '?' => {
+                i += 1;
+                while bytes.get(i).is_some_and(u8::is_ascii_digit) {
+                    i += 1;
+                }
+                TokenKind::Variable
+            }
+            ':' | '@' | '$' => {
+                i += 1;
+                while let Some(c) = sql[i..].chars().next().filter(|&c| is_ident_char(c)) {
+                    i += c.len_utf8();
+                }
+                if i == start + 1 {
+                    return Err(syntax("parameter without a name", start));
+                }
+                TokenKind::Variable
+            }
+            c if is_ident_char(c) => {
+                while let Some(c) = sql[i..].chars().next().filter(|&c| is_ident_char(c)) {
+                    i += c.len_utf8();
+                }
+                TokenKind::Word
+            }
+            _ => {
+                let op = OPERATORS.iter().find(|op| sql[i..].starts_with(**op));
+                match op {
+                    Some(op) => i += op.len(),
+                    None if "(),;.+-*/%=<>&|~".contains(c) => i += 1,
+                    None => return Err(syntax(&format!("unrecognized token {:?}", c), start)),
+                }
+                TokenKind::Punct
+            }
+        };
+        tokens.push(Token {
+            kind,
+            text: &sql[start..i],
+            start,
+        });
+    }
+    Ok(tokens)
+}
+
+/// Offset just past the quote closing the one at `open`; a doubled quote is an escaped one
+fn closing_quote(sql: &str, open: usize, quote: char) -> Option<usize> {
+    let mut i = open + 1;
+    loop {
+        i += sql[i..].find(quote)? + 1;
+        if !sql[i..].starts_with(quote) {
+            return Some(i);
+        }
+        i += 1;
+    }
+}
+
+fn number_end(bytes: &[u8], mut i: usize) -> usize {
+    let digits = |i: &mut usize, hex: bool| {
+        while bytes
+            .get(*i)
+            .is_some_and(|b| b.is_ascii_digit() || *b == b'_' || (hex && b.is_ascii_hexdigit()))
+        {
+            *i += 1;
+        }
+    };
+    if bytes[i] == b'0' && matches!(bytes.get(i + 1), Some(b'x' | b'X')) {
+        i += 2;
+        digits(&mut i, true);
+        return i;
+    }
+    digits(&mut i, false);
+    if bytes.get(i) == Some(&b'.') {
+        i += 1;
+        digits(&mut i, false);
+    }
+    if matches!(bytes.get(i), Some(b'e' | b'E')) {
+        let mut j = i + 1;
+        if matches!(bytes.get(j), Some(b'+' | b'-')) {
+            j += 1;
+        }
+        if bytes.get(j).is_some_and(u8::is_ascii_digit) {
+            i = j;
+            digits(&mut i, false);
+        }
+    }
+    i
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(sql: &str) -> Vec<&str> {
+        tokenize(sql).unwrap().iter().map(|t| t.text).collect()
+    }
+
+    #[test]
+    fn comments_are_skipped() {
+        let sql = "-- leading\nSELECT a, -- trailing, with (parens)\n\t/* block\n -- not a line comment */ b\nFROM/**/t -- no newline";
+        assert_eq!(texts(sql), ["SELECT", "a", ",", "b", "FROM", "t"]);
+        // an unterminated block comment runs to the end, as in SQLite
+        assert_eq!(texts("a /* b c"), ["a"]);
+        assert_eq!(texts("a/**/b"), ["a", "b"]);
+        // comment markers inside quotes are part of the token
+        assert_eq!(
+            texts("'--x' \"/*y*/\" [-- z]"),
+            ["'--x'", "\"/*y*/\"", "[-- z]"]
+        );
+        // a single `-` or `/` is an operator
+        assert_eq!(
+            texts("1-2/3 - -4"),
+            ["1", "-", "2", "/", "3", "-", "-", "4"]
+        );
+    }
+
+    #[test]
+    fn any_whitespace_separates_tokens() {
+        let sql = "\r\n  create\tTABLE\r\n\x0c t ( a int ,\n\n\nb )\r\n";
+        assert_eq!(
+            texts(sql),
+            ["create", "TABLE", "t", "(", "a", "int", ",", "b", ")"]
+        );
+        let tokens = tokenize(sql).unwrap();
+        assert!(tokens[0].is_keyword("CREATE") && tokens[1].is_keyword("table"));
+        // each offset locates its token in the source, past the skipped whitespace
+        assert!(tokens.iter().all(|t| &sql[t.start..t.end()] == t.text));
+        assert!(tokenize(" \t\r\n").unwrap().is_empty());
+        assert!(tokenize("-- only a comment").unwrap().is_empty());
+    }
+}
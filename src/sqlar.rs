@@ -0,0 +1,134 @@
+use crate::db::Database;
+use crate::inflate::zlib_decompress;
+use crate::schema::ObjectType;
+use crate::sql::create::parse_create_table;
+use crate::{Error, Result, Value};
+
+const S_IFMT: u32 = 0o170000;
+const S_IFDIR: u32 = 0o040000;
+const S_IFLNK: u32 = 0o120000;
+
+/// One file, directory or symlink stored in a SQLite Archive
+#[derive(Debug, Clone)]
+pub struct ArchiveEntry {
+    pub name: String,
+    /// Unix permission and file-type bits
+    pub mode: u32,
+    /// Modification time in seconds since the epoch
+    pub mtime: i64,
+    /// Uncompressed size; -1 for symlinks, whose data is the uncompressed link target
+    pub size: i64,
+    /// Stored content, zlib-compressed unless its length equals `size`
+    data: Option<Vec<u8>>,
+}
+
+impl ArchiveEntry {
+    pub fn is_dir(&self) -> bool {
+        self.mode & S_IFMT == S_IFDIR
+    }
+
+    pub fn is_symlink(&self) -> bool {
+        self.mode & S_IFMT == S_IFLNK
+    }
+
+    /// Bytes the entry occupies inside the archive
+    pub fn stored_size(&self) -> usize {
+        self.data.as_ref().map_or(0, Vec::len)
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        self.data.is_some() && !self.is_symlink() && self.stored_size() as i64 != self.size
+    }
+
+    /// The entry's content, decompressed; the link target for symlinks
+    pub fn contents(&self) -> Result<Vec<u8>> {
+        match &self.data {
+            None => Ok(Vec::new()),
+            Some(data) if !self.is_compressed() => Ok(data.clone()),
+            Some(data) => {
+                let out = zlib_decompress(data, self.size.max(0) as usize)?;
+                if out.len() as i64 != self.size {
+                    return Err(Error::Decompress(format!(
+                        "{} decompressed to {} bytes, expected {}",
+                        self.name,
+                        out.len(),
+                        self.size
+                    )));
+                }
+                Ok(out)
+            }
+        }
+    }
+}
+
+/// Reader for the `sqlar(name, mode, mtime, sz, data)` table used by `sqlite3 -A`
+pub struct Sqlar<'a> {
+    db: &'a Database,
+    root_page: u32,
+}
+
+impl<'a> Sqlar<'a> {
+    /// Finds the archive table, returning `None` if the database isn't a SQLite Archive
+    pub fn open(db: &'a Database) -> Result<Option<Sqlar<'a>>> {
+        let table = db
+            .schema()?
+            .into_iter()
+            .find(|e| e.kind == ObjectType::Table && e.name.eq_ignore_ascii_case("sqlar"));
+        let Some(table) = table else {
+            return Ok(None);
+        };
+
+        // the column list is fixed by the sqlar format; sqlite3 -A writes a comment on each line
+        let columns: Option<Vec<String>> = table
+            .sql
+            .as_deref()
+            .and_then(|sql| parse_create_table(sql).ok())
+            .map(|t| {
+                t.columns
+                    .into_iter()
+                    .map(|c| c.name.to_ascii_lowercase())
+                    .collect()
+            });
+        match columns {
+            Some(columns) if columns == ["name", "mode", "mtime", "sz", "data"] => {
+                Ok(Some(Sqlar {
+                    db,
+                    root_page: table.root_page,
+                }))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = Result<ArchiveEntry>> + '_ {
+        self.db.table_rows(self.root_page).map(|row| {
+            let (_, values) = row?;
+            entry_from_record(&values)
+        })
+    }
+}
+
+fn entry_from_record(values: &[Value]) -> Result<ArchiveEntry> {
+    let int = |i: usize| match values.get(i) {
+        Some(Value::Integer(n)) => Some(*n),
+        _ => None,
+    };
+    let name = match values.first() {
+        Some(Value::Text(name)) => name.clone(),
+        Some(Value::RawText(name)) => String::from_utf8_lossy(name).into_owned(),
+        _ => return Err(Error::Corrupt("sqlar entry without a name".to_string())),
+    };
+    let data = match values.get(4) {
+        Some(Value::Blob(b)) => Some(b.clone()),
+        Some(Value::Text(s)) => Some(s.clone().into_bytes()),
+        Some(Value::RawText(b)) => Some(b.clone()),
+        _ => None,
+    };
+    Ok(ArchiveEntry {
+        name,
+        mode: int(1).unwrap_or(0) as u32,
+        mtime: int(2).unwrap_or(0),
+        size: int(3).unwrap_or(-1),
+        data,
+    })
+}
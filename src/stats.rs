@@ -0,0 +1,277 @@
+use crate::db::Database;
+use crate::table::{Field, StoredValue, TableInfo};
+use crate::{Result, Value};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+pub const DEFAULT_BUCKETS: usize = 10;
+pub const DEFAULT_TOP: usize = 10;
+
+/// Profile of one column, gathered in at most two scans of its table
+#[derive(Debug, Clone)]
+pub struct ColumnStats {
+    pub rows: u64,
+    pub nulls: u64,
+    /// Smallest and largest non-NULL values in SQLite sort order. Values too large to load
+    /// (see [`STREAM_THRESHOLD`](crate::table::STREAM_THRESHOLD)) don't take part.
+    pub min: Option<Value>,
+    pub max: Option<Value>,
+    /// HyperLogLog estimate of the distinct non-NULL values, within about 1%
+    pub distinct: u64,
+    /// Equi-width histogram of the finite INTEGER and REAL values
+    pub buckets: Vec<Bucket>,
+    /// Most frequent TEXT values, most frequent first
+    pub top: Vec<TopValue>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Bucket {
+    pub low: f64,
+    pub high: f64,
+    pub count: u64,
+}
+
+/// A frequent value, counted with the Space-Saving algorithm: `count` never undercounts, and
+/// `count - error` never overcounts
+#[derive(Debug, Clone)]
+pub struct TopValue {
+    pub value: Value,
+    pub count: u64,
+    pub error: u64,
+}
+
+pub fn column_stats(
+    db: &Database,
+    table: &TableInfo,
+    column: usize,
+    buckets: usize,
+    top: usize,
+) -> Result<ColumnStats> {
+    let mut stats = ColumnStats {
+        rows: 0,
+        nulls: 0,
+        min: None,
+        max: None,
+        distinct: 0,
+        buckets: Vec::new(),
+        top: Vec::new(),
+    };
+    let mut distinct = HyperLogLog::new();
+    let mut frequent = SpaceSaving::new(top);
+    let mut numeric_range: Option<(f64, f64)> = None;
+
+    for row in table.project(db, &[column])? {
+        let row = row?;
+        stats.rows += 1;
+        let value = match &row.fields[0] {
+            Field::Stored(stored) => {
+                distinct.insert(hash_stored(db, stored)?);
+                continue;
+            }
+            Field::Value(Value::Null) => {
+                stats.nulls += 1;
+                continue;
+            }
+            Field::Value(value) => value,
+        };
+        distinct.insert(hash_value(value));
+        if stats
+            .min
+            .as_ref()
+            .map_or(true, |min| value.compare(min).is_lt())
+        {
+            stats.min = Some(value.clone());
+        }
+        if stats
+            .max
+            .as_ref()
+            .map_or(true, |max| value.compare(max).is_gt())
+        {
+            stats.max = Some(value.clone());
+        }
+        match value {
+            // infinities have no place on a finite axis
+            Value::Integer(_) | Value::Real(_) if as_f64(value).is_finite() => {
+                let x = as_f64(value);
+                numeric_range = Some(match numeric_range {
+                    Some((low, high)) => (low.min(x), high.max(x)),
+                    None => (x, x),
+                });
+            }
+            Value::Text(s) => frequent.insert(s.as_bytes()),
+            Value::RawText(b) => frequent.insert(b),
+            _ => {}
+        }
+    }
+    stats.distinct = distinct.estimate();
+    stats.top = frequent.into_top(top);
+
+    // bucket boundaries need the range, so the numbers are counted on a second pass
+    if let Some((low, high)) = numeric_range.filter(|_| buckets > 0) {
+        let count = if low == high { 1 } else { buckets };
+        let width = (high - low) / count as f64;
+        stats.buckets = (0..count)
+            .map(|i| Bucket {
+                low: low + width * i as f64,
+                high: if i + 1 == count {
+                    high
+                } else {
+                    low + width * (i + 1) as f64
+                },
+                count: 0,
+            })
+            .collect();
+        for row in table.project(db, &[column])? {
+            if let Field::Value(value @ (Value::Integer(_) | Value::Real(_))) = &row?.fields[0] {
+                if !as_f64(value).is_finite() {
+                    continue;
+                }
+                let offset = if width > 0.0 {
+                    ((as_f64(value) - low) / width) as usize
+                } else {
+                    0
+                };
+                stats.buckets[offset.min(count - 1)].count += 1;
+            }
+        }
+    }
+    Ok(stats)
+}
+
+fn as_f64(value: &Value) -> f64 {
+    match value {
+        Value::Integer(i) => *i as f64,
+        Value::Real(f) => *f,
+        _ => 0.0,
+    }
+}
+
+/// A hash under which values SQLite treats as equal collide: an integral REAL hashes like the
+/// INTEGER it equals, and TEXT hashes the same however it was decoded
+fn hash_value(value: &Value) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    match value {
+        Value::Null => 0u8.hash(&mut hasher),
+        Value::Integer(i) => (1u8, *i).hash(&mut hasher),
+        Value::Real(f) if f.fract() == 0.0 && f.abs() < 9.2e18 => {
+            (1u8, *f as i64).hash(&mut hasher)
+        }
+        Value::Real(f) => (2u8, f.to_bits()).hash(&mut hasher),
+        Value::Text(s) => (3u8, s.as_bytes()).hash(&mut hasher),
+        Value::RawText(b) => (3u8, b.as_slice()).hash(&mut hasher),
+        Value::Blob(b) => (4u8, b.as_slice()).hash(&mut hasher),
+    }
+    hasher.finish()
+}
+
+/// Hashes a value left in the file as [`hash_value`] would, a page at a time
+fn hash_stored(db: &Database, stored: &StoredValue) -> Result<u64> {
+    struct HashWriter(DefaultHasher);
+    impl std::io::Write for HashWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.write(buf);
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+    // slices hash as their length then their bytes
+    let mut out = HashWriter(DefaultHasher::new());
+    (if stored.is_text() { 3u8 } else { 4u8 }).hash(&mut out.0);
+    (stored.len as usize).hash(&mut out.0);
+    stored.copy_to(db, &mut out)?;
+    Ok(out.0.finish())
+}
+
+/// Registers of a HyperLogLog sketch: 2^14 of them gives a standard error of about 0.8%
+const HLL_BITS: u32 = 14;
+
+struct HyperLogLog {
+    registers: Vec<u8>,
+}
+
+impl HyperLogLog {
+    fn new() -> Self {
+        HyperLogLog {
+            registers: vec![0; 1 << HLL_BITS],
+        }
+    }
+
+    fn insert(&mut self, hash: u64) {
+        let index = (hash >> (64 - HLL_BITS)) as usize;
+        // position of the first set bit in the remaining bits, counting from 1
+        let rest = hash << HLL_BITS | 1 << (HLL_BITS - 1);
+        let rank = rest.leading_zeros() as u8 + 1;
+        self.registers[index] = self.registers[index].max(rank);
+    }
+
+    fn estimate(&self) -> u64 {
+        let m = self.registers.len() as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+        let sum: f64 = self.registers.iter().map(|&r| 2f64.powi(-(r as i32))).sum();
+        let raw = alpha * m * m / sum;
+        let zeros = self.registers.iter().filter(|&&r| r == 0).count();
+        // small cardinalities are counted more accurately from the empty registers
+        let estimate = if raw <= 2.5 * m && zeros > 0 {
+            m * (m / zeros as f64).ln()
+        } else {
+            raw
+        };
+        estimate.round() as u64
+    }
+}
+
+/// Space-Saving heavy hitters: a fixed number of counters, where a new value takes over the
+/// smallest counter and inherits its count as possible overcount
+struct SpaceSaving {
+    capacity: usize,
+    counters: HashMap<Vec<u8>, (u64, u64)>,
+}
+
+impl SpaceSaving {
+    fn new(top: usize) -> Self {
+        // spare counters keep the reported top values accurate on long-tailed data
+        SpaceSaving {
+            capacity: (top * 10).max(100),
+            counters: HashMap::new(),
+        }
+    }
+
+    fn insert(&mut self, key: &[u8]) {
+        if let Some((count, _)) = self.counters.get_mut(key) {
+            *count += 1;
+            return;
+        }
+        if self.counters.len() < self.capacity {
+            self.counters.insert(key.to_vec(), (1, 0));
+            return;
+        }
+        let (smallest, &(count, _)) = self
+            .counters
+            .iter()
+            .min_by_key(|(_, (count, _))| *count)
+            .expect("counters are full");
+        let smallest = smallest.clone();
+        self.counters.remove(&smallest);
+        self.counters.insert(key.to_vec(), (count + 1, count));
+    }
+
+    fn into_top(self, top: usize) -> Vec<TopValue> {
+        let mut counters: Vec<_> = self.counters.into_iter().collect();
+        counters.sort_by(|a, b| b.1 .0.cmp(&a.1 .0).then_with(|| a.0.cmp(&b.0)));
+        counters
+            .into_iter()
+            .take(top)
+            .map(|(bytes, (count, error))| TopValue {
+                value: match String::from_utf8(bytes) {
+                    Ok(s) => Value::Text(s),
+                    Err(e) => Value::RawText(e.into_bytes()),
+                },
+                count,
+                error,
+            })
+            .collect()
+    }
+}
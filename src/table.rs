@@ -0,0 +1,984 @@
+use crate::btree::{Cell, Payload};
+use crate::db::{Database, PayloadReader, TreeCells, SCHEMA_ROOT_PAGE};
+use crate::index::sort_order;
+use crate::record::{
+    decode_columns_with, decode_record_with, decode_value_with, encode_record, read_header_prefix,
+    serial_type_len,
+};
+use crate::schema::{ObjectType, SchemaEntry};
+use crate::sha256::Sha256;
+use crate::sql::create::{parse_create_table, Affinity, ColumnDef, CreateTable, Generated};
+use crate::sql::parse_literal;
+use crate::varint::read_varint;
+use crate::{Error, Result, Value};
+use std::fmt;
+use std::io::Write;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Values at least this many bytes long are left in the file by [`TableInfo::stream_rows`]
+pub const STREAM_THRESHOLD: u64 = 64 * 1024;
+
+/// How SQLite declares the schema table, which has no row of its own in `sqlite_schema`
+const SCHEMA_TABLE_SQL: &str =
+    "CREATE TABLE sqlite_schema(type text,name text,tbl_name text,rootpage int,sql text)";
+
+/// A table's definition together with how its stored records map onto its columns
+#[derive(Debug, Clone)]
+pub struct TableInfo {
+    pub name: String,
+    pub root_page: u32,
+    pub definition: CreateTable,
+    /// Column index held in each record slot, in record order
+    record_columns: Vec<usize>,
+    /// Value of each column for rows written before it was added with ALTER TABLE
+    defaults: Vec<Value>,
+    /// Columns with REAL affinity, whose whole numbers SQLite stores as integers
+    real_columns: Vec<bool>,
+    /// The columns were counted from the stored records because the CREATE statement is
+    /// missing or can't be parsed; see [`TableInfo::load`]
+    pub inferred: bool,
+}
+
+/// One row, with its columns in declaration order
+#[derive(Debug, Clone, PartialEq)]
+pub struct Row {
+    /// `None` for WITHOUT ROWID tables
+    pub rowid: Option<i64>,
+    pub values: Vec<Value>,
+}
+
+/// A row whose large values are left in the file, to be copied out by whoever consumes them
+#[derive(Debug, Clone)]
+pub struct StreamedRow {
+    pub rowid: Option<i64>,
+    pub fields: Vec<Field>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Field {
+    Value(Value),
+    Stored(StoredValue),
+}
+
+/// Where a paginated scan stopped, for carrying on after the last entry it returned without
+/// stepping over the ones before. It prints as `rowid:42`, or `key:` and the key as a
+/// record in hex, so it can be handed to a client and parsed back.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResumeToken {
+    /// After this rowid, in a rowid table
+    Rowid(i64),
+    /// After this key, in an index or WITHOUT ROWID table
+    Key(Vec<Value>),
+}
+
+impl fmt::Display for ResumeToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResumeToken::Rowid(rowid) => write!(f, "rowid:{}", rowid),
+            ResumeToken::Key(key) => {
+                write!(f, "key:")?;
+                encode_record(key)
+                    .iter()
+                    .try_for_each(|b| write!(f, "{:02x}", b))
+            }
+        }
+    }
+}
+
+impl FromStr for ResumeToken {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<ResumeToken> {
+        let invalid = || Error::Syntax(format!("invalid resume token {:?}", s));
+        if let Some(rowid) = s.strip_prefix("rowid:") {
+            return rowid.parse().map(ResumeToken::Rowid).map_err(|_| invalid());
+        }
+        let hex = s.strip_prefix("key:").ok_or_else(invalid)?;
+        if hex.len() % 2 != 0 || !hex.is_ascii() {
+            return Err(invalid());
+        }
+        let record = (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| invalid()))
+            .collect::<Result<Vec<u8>>>()?;
+        decode_record_with(&record, crate::record::Utf8Policy::Raw).map(ResumeToken::Key)
+    }
+}
+
+/// Digest of a whole table; see [`TableInfo::content_hash`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableHash {
+    pub rows: u64,
+    pub digest: [u8; 32],
+}
+
+impl Row {
+    /// SHA-256 of the row's values in declaration order, each type-tagged so that `1`, `1.0`
+    /// and `'1'` differ. The rowid only counts when a column holds it (an INTEGER PRIMARY
+    /// KEY), since VACUUM may renumber it otherwise.
+    pub fn content_hash(&self) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        for value in &self.values {
+            hash_value(&mut hasher, value);
+        }
+        hasher.finish()
+    }
+}
+
+impl StreamedRow {
+    /// The [`Row::content_hash`] of this row, streaming stored values through the hasher
+    pub fn content_hash(&self, db: &Database) -> Result<[u8; 32]> {
+        let mut hasher = Sha256::new();
+        for field in &self.fields {
+            match field {
+                Field::Value(value) => hash_value(&mut hasher, value),
+                Field::Stored(stored) => {
+                    hasher.update(&[if stored.is_text() { TAG_TEXT } else { TAG_BLOB }]);
+                    hasher.update(&stored.len.to_be_bytes());
+                    stored.copy_to(db, &mut hasher)?;
+                }
+            }
+        }
+        Ok(hasher.finish())
+    }
+}
+
+const TAG_NULL: u8 = 0;
+const TAG_INTEGER: u8 = 1;
+const TAG_REAL: u8 = 2;
+const TAG_TEXT: u8 = 3;
+const TAG_BLOB: u8 = 4;
+
+/// Feeds the canonical encoding of a value to `hasher`: its type tag, then 8 big-endian bytes
+/// for numbers, or the length and bytes for TEXT and BLOBs so adjacent values can't run into
+/// each other. TEXT is hashed as stored, whatever the UTF-8 policy.
+fn hash_value(hasher: &mut Sha256, value: &Value) {
+    match value {
+        Value::Null => hasher.update(&[TAG_NULL]),
+        Value::Integer(i) => {
+            hasher.update(&[TAG_INTEGER]);
+            hasher.update(&i.to_be_bytes());
+        }
+        Value::Real(f) => {
+            // SQLite doesn't distinguish negative zero
+            let f = if *f == 0.0 { 0.0f64 } else { *f };
+            hasher.update(&[TAG_REAL]);
+            hasher.update(&f.to_bits().to_be_bytes());
+        }
+        Value::Text(s) => {
+            hasher.update(&[TAG_TEXT]);
+            hasher.update(&(s.len() as u64).to_be_bytes());
+            hasher.update(s.as_bytes());
+        }
+        Value::RawText(b) | Value::Blob(b) => {
+            let tag = if matches!(value, Value::Blob(_)) {
+                TAG_BLOB
+            } else {
+                TAG_TEXT
+            };
+            hasher.update(&[tag]);
+            hasher.update(&(b.len() as u64).to_be_bytes());
+            hasher.update(b);
+        }
+    }
+}
+
+impl From<Value> for Field {
+    fn from(value: Value) -> Self {
+        Field::Value(value)
+    }
+}
+
+/// What `PRAGMA table_xinfo` reports about a column, and whether it is the rowid
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnInfo {
+    pub name: String,
+    /// The declared type, in capitals if it is one of STRICT's standard names; empty when
+    /// none was given
+    pub type_name: String,
+    /// Declared NOT NULL, or part of a WITHOUT ROWID table's primary key, which implies it
+    pub not_null: bool,
+    /// Source text of the DEFAULT value
+    pub default: Option<String>,
+    /// Position in the primary key counting from 1, or 0 for columns outside it
+    pub pk: usize,
+    pub generated: Option<Generated>,
+    /// The INTEGER PRIMARY KEY whose value is the rowid
+    pub rowid_alias: bool,
+}
+
+impl ColumnInfo {
+    /// `table_xinfo`'s hidden code: 0 for ordinary columns, 2 for VIRTUAL and 3 for STORED
+    /// generated columns. The hidden columns of virtual tables (1) never appear, since their
+    /// declarations come from the module rather than the schema.
+    pub fn hidden(&self) -> u8 {
+        match self.generated {
+            None => 0,
+            Some(Generated::Virtual) => 2,
+            Some(Generated::Stored) => 3,
+        }
+    }
+}
+
+/// A TEXT or BLOB value still in the database file
+#[derive(Debug, Clone)]
+pub struct StoredValue {
+    payload: Arc<Payload>,
+    /// Offset of the value within its record
+    offset: u64,
+    pub len: u64,
+    pub serial_type: u64,
+}
+
+impl StoredValue {
+    pub fn is_text(&self) -> bool {
+        self.serial_type % 2 == 1
+    }
+
+    /// Copies the stored bytes to `out` a page at a time. TEXT is copied as stored, whatever
+    /// the UTF-8 policy.
+    pub fn copy_to(&self, db: &Database, out: &mut dyn Write) -> Result<()> {
+        let mut reader = db.payload_reader(&self.payload)?;
+        reader.skip(self.offset)?;
+        let mut buf = vec![0u8; db.usable_size() as usize];
+        let mut left = self.len;
+        while left > 0 {
+            let want = left.min(buf.len() as u64) as usize;
+            let n = reader.read_chunk(&mut buf[..want])?;
+            if n == 0 {
+                return Err(Error::Corrupt(
+                    "stored value runs past its record".to_string(),
+                ));
+            }
+            out.write_all(&buf[..n])?;
+            left -= n as u64;
+        }
+        Ok(())
+    }
+}
+
+impl TableInfo {
+    pub fn from_schema(entry: &SchemaEntry) -> Result<TableInfo> {
+        if entry.kind != ObjectType::Table || entry.root_page == 0 {
+            return Err(Error::Syntax(format!(
+                "{} is not a table with stored rows",
+                entry.name
+            )));
+        }
+        let sql = entry.sql.as_deref().ok_or_else(|| {
+            Error::Syntax(format!("table {} has no CREATE statement", entry.name))
+        })?;
+        TableInfo::with_definition(entry, parse_create_table(sql)?)
+    }
+
+    /// Like [`from_schema`](TableInfo::from_schema), but a table whose CREATE statement is
+    /// missing or can't be parsed is still read, as columns `col0`, `col1`, ... for as many
+    /// values as its widest record holds. Without a definition, values keep the types they
+    /// are stored with, an INTEGER PRIMARY KEY reads as the NULL its record holds, and a
+    /// WITHOUT ROWID table's key is not known.
+    pub fn load(db: &Database, entry: &SchemaEntry) -> Result<TableInfo> {
+        match TableInfo::from_schema(entry) {
+            Err(Error::Syntax(_)) if entry.kind == ObjectType::Table && entry.root_page != 0 => {
+                TableInfo::inferred(db, entry)
+            }
+            result => result,
+        }
+    }
+
+    fn inferred(db: &Database, entry: &SchemaEntry) -> Result<TableInfo> {
+        let without_rowid = !db.btree_page(entry.root_page)?.page_type().is_table();
+        let mut width = 0;
+        for found in db.tree_cells(entry.root_page) {
+            let payload = match found?.cell {
+                Cell::TableLeaf { payload, .. }
+                | Cell::IndexLeaf { payload }
+                | Cell::IndexInterior { payload, .. } => payload,
+                Cell::TableInterior { .. } => continue,
+            };
+            let header = read_record_header(&mut db.payload_reader(&payload)?, usize::MAX)?;
+            width = width.max(header.len());
+        }
+        // a table needs a column even when it has no rows to count them from
+        let columns = (0..width.max(1))
+            .map(|i| ColumnDef {
+                name: format!("col{}", i),
+                type_name: String::new(),
+                primary_key: false,
+                not_null: false,
+                unique: false,
+                generated: None,
+                default: None,
+                collate: None,
+                constraints: String::new(),
+                sql: format!("col{}", i),
+            })
+            .collect();
+        let definition = CreateTable {
+            name: entry.name.clone(),
+            columns,
+            constraints: Vec::new(),
+            primary_key: Vec::new(),
+            without_rowid,
+            strict: false,
+        };
+        let mut table = TableInfo::with_definition(entry, definition)?;
+        table.inferred = true;
+        Ok(table)
+    }
+
+    fn with_definition(entry: &SchemaEntry, definition: CreateTable) -> Result<TableInfo> {
+        // virtual generated columns take no record slot. WITHOUT ROWID tables store the
+        // primary key columns first, then the rest in declaration order.
+        let stored: Vec<usize> = (0..definition.columns.len())
+            .filter(|&i| definition.columns[i].generated != Some(Generated::Virtual))
+            .collect();
+        let record_columns = if definition.without_rowid {
+            let key: Vec<usize> = primary_key(&definition);
+            key.iter()
+                .copied()
+                .chain(stored.iter().copied().filter(|i| !key.contains(i)))
+                .collect()
+        } else {
+            stored
+        };
+        let defaults = definition
+            .columns
+            .iter()
+            .map(|c| {
+                c.default
+                    .as_deref()
+                    .and_then(parse_literal)
+                    .unwrap_or(Value::Null)
+            })
+            .collect();
+
+        let real_columns = definition
+            .columns
+            .iter()
+            .map(|c| c.affinity() == Affinity::Real)
+            .collect();
+
+        Ok(TableInfo {
+            name: entry.name.clone(),
+            root_page: entry.root_page,
+            definition,
+            record_columns,
+            defaults,
+            real_columns,
+            inferred: false,
+        })
+    }
+
+    /// Looks a table up by name, ignoring case as SQLite does. Internal tables such as
+    /// `sqlite_stat1` are found like any other, and `sqlite_schema` (or its older name
+    /// `sqlite_master`) names the schema table itself.
+    pub fn find(db: &Database, name: &str) -> Result<Option<TableInfo>> {
+        if ["sqlite_schema", "sqlite_master"]
+            .iter()
+            .any(|alias| alias.eq_ignore_ascii_case(name))
+        {
+            return TableInfo::from_schema(&SchemaEntry {
+                kind: ObjectType::Table,
+                name: "sqlite_schema".to_string(),
+                tbl_name: "sqlite_schema".to_string(),
+                root_page: SCHEMA_ROOT_PAGE,
+                sql: Some(SCHEMA_TABLE_SQL.to_string()),
+            })
+            .map(Some);
+        }
+        db.schema()?
+            .iter()
+            .find(|e| e.kind == ObjectType::Table && e.name.eq_ignore_ascii_case(name))
+            .map(|entry| TableInfo::load(db, entry))
+            .transpose()
+    }
+
+    pub fn columns(&self) -> &[ColumnDef] {
+        &self.definition.columns
+    }
+
+    /// Every column, generated ones included, as `PRAGMA table_xinfo` describes them
+    pub fn column_info(&self) -> Vec<ColumnInfo> {
+        let key = primary_key(&self.definition);
+        let alias = self.definition.rowid_alias();
+        self.columns()
+            .iter()
+            .enumerate()
+            .map(|(i, column)| {
+                let pk = key.iter().position(|&k| k == i).map_or(0, |p| p + 1);
+                // SQLite reports the standard type names in capitals, others as written
+                let standard = ["INT", "INTEGER", "REAL", "TEXT", "BLOB", "ANY"]
+                    .iter()
+                    .any(|t| t.eq_ignore_ascii_case(&column.type_name));
+                let type_name = if standard {
+                    column.type_name.to_ascii_uppercase()
+                } else {
+                    column.type_name.clone()
+                };
+                ColumnInfo {
+                    name: column.name.clone(),
+                    type_name,
+                    not_null: column.not_null || (self.definition.without_rowid && pk > 0),
+                    default: column.default.clone(),
+                    pk,
+                    generated: column.generated,
+                    rowid_alias: alias == Some(i),
+                }
+            })
+            .collect()
+    }
+
+    /// Scans the table in key order
+    pub fn rows<'a>(&'a self, db: &'a Database) -> TableScan<'a> {
+        self.scan(db, db.tree_cells(self.root_page))
+    }
+
+    /// Scans the rows with a rowid greater than `rowid`, in rowid order, without reading the
+    /// pages of the rows before them
+    pub fn rows_after<'a>(&'a self, db: &'a Database, rowid: i64) -> TableScan<'a> {
+        self.scan(db, db.table_cells_after(self.root_page, rowid))
+    }
+
+    /// Scans the table in key order from just after the row of `token`, or from the start
+    /// without one: a rowid for rowid tables, a primary key for WITHOUT ROWID tables. Only the
+    /// pages on the path to the starting row are read, so every page of a paginated scan
+    /// costs about the same however far into the table it is.
+    pub fn scan_from<'a>(
+        &'a self,
+        db: &'a Database,
+        token: Option<&ResumeToken>,
+    ) -> Result<TableScan<'a>> {
+        let cells = match (token, self.definition.without_rowid) {
+            (None, _) => db.tree_cells(self.root_page),
+            (Some(ResumeToken::Rowid(rowid)), false) => {
+                db.table_cells_after(self.root_page, *rowid)
+            }
+            (Some(ResumeToken::Key(key)), true) => {
+                let order = self
+                    .definition
+                    .primary_key_terms()?
+                    .iter()
+                    .map(|term| sort_order(term, &self.definition))
+                    .collect::<Result<_>>()?;
+                db.index_cells_after(self.root_page, key.clone(), order)
+            }
+            (Some(_), without_rowid) => {
+                return Err(Error::Syntax(format!(
+                    "table {} resumes from {}",
+                    self.name,
+                    if without_rowid {
+                        "a key, not a rowid"
+                    } else {
+                        "a rowid, not a key"
+                    }
+                )))
+            }
+        };
+        Ok(self.scan(db, cells))
+    }
+
+    /// The row with this rowid, reading only the pages on the path to it. WITHOUT ROWID
+    /// tables have no rowids, so nothing is found in them.
+    pub fn row(&self, db: &Database, rowid: i64) -> Result<Option<Row>> {
+        let cells = match rowid.checked_sub(1) {
+            Some(before) => db.table_cells_after(self.root_page, before),
+            None => db.tree_cells(self.root_page),
+        };
+        let row = self.scan(db, cells).next().transpose()?;
+        Ok(row.filter(|row| row.rowid == Some(rowid)))
+    }
+
+    fn scan<'a>(&'a self, db: &'a Database, cells: TreeCells<'a>) -> TableScan<'a> {
+        let key_len = if self.definition.without_rowid {
+            primary_key(&self.definition).len()
+        } else {
+            0
+        };
+        TableScan {
+            table: self,
+            db,
+            cells,
+            key_len,
+            last: None,
+        }
+    }
+
+    /// Scans the table in key order without loading values of [`STREAM_THRESHOLD`] bytes or
+    /// more, so rows holding huge BLOBs take no more memory than small ones
+    pub fn stream_rows<'a>(&'a self, db: &'a Database) -> StreamScan<'a> {
+        StreamScan {
+            table: self,
+            db,
+            cells: db.tree_cells(self.root_page),
+        }
+    }
+
+    /// Scans the table in key order for just the columns at `columns`, declaration positions
+    /// given in the order the fields are wanted. Each record is parsed no further than the
+    /// last of them, and the values of the others are skipped over without being read, so
+    /// wide rows cost little when only a few narrow columns are needed. Large values are left
+    /// in the file as in [`stream_rows`](TableInfo::stream_rows).
+    pub fn project<'a>(&'a self, db: &'a Database, columns: &[usize]) -> Result<ProjectedScan<'a>> {
+        let alias = match self.definition.without_rowid {
+            true => None,
+            false => self.definition.rowid_alias(),
+        };
+        let sources = columns
+            .iter()
+            .map(|&column| {
+                if column >= self.columns().len() {
+                    return Err(Error::Syntax(format!(
+                        "table {} has no column {}",
+                        self.name, column
+                    )));
+                }
+                Ok(if alias == Some(column) {
+                    Source::Rowid
+                } else {
+                    match self.record_columns.iter().position(|&c| c == column) {
+                        Some(slot) => Source::Slot(slot, column),
+                        None => Source::Computed,
+                    }
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let mut slots: Vec<usize> = sources
+            .iter()
+            .filter_map(|source| match source {
+                Source::Slot(slot, _) => Some(*slot),
+                _ => None,
+            })
+            .collect();
+        slots.sort_unstable();
+        slots.dedup();
+        Ok(ProjectedScan {
+            table: self,
+            db,
+            cells: db.tree_cells(self.root_page),
+            sources,
+            slots,
+        })
+    }
+
+    /// SHA-256 over the [`content_hash`](Row::content_hash) of every row in key order, so two
+    /// copies of a table match exactly when they hold the same rows with the same keys
+    pub fn content_hash(&self, db: &Database) -> Result<TableHash> {
+        let mut hasher = Sha256::new();
+        let mut rows = 0;
+        for row in self.stream_rows(db) {
+            hasher.update(&row?.content_hash(db)?);
+            rows += 1;
+        }
+        Ok(TableHash {
+            rows,
+            digest: hasher.finish(),
+        })
+    }
+
+    fn row_from_record(&self, rowid: Option<i64>, mut record: Vec<Value>) -> Result<Row> {
+        for (slot, value) in record.iter_mut().enumerate() {
+            self.apply_affinity(slot, value);
+        }
+        let values = self.arrange(rowid, record)?;
+        Ok(Row { rowid, values })
+    }
+
+    /// Turns an INTEGER read from a REAL column back into the REAL it was stored as
+    fn apply_affinity(&self, slot: usize, value: &mut Value) {
+        let real = self
+            .record_columns
+            .get(slot)
+            .is_some_and(|&column| self.real_columns[column]);
+        if let (true, Value::Integer(i)) = (real, &*value) {
+            *value = Value::Real(*i as f64);
+        }
+    }
+
+    /// The value of a column that a row's record is too short to hold: its default if it was
+    /// added since the row was written, or NULL for a generated column
+    fn default_value(&self, column: usize) -> Value {
+        match self.columns()[column].generated {
+            Some(_) => Value::Null,
+            None => self.defaults[column].clone(),
+        }
+    }
+
+    /// Puts record values into declaration order, filling in the rowid alias and the defaults
+    /// of columns added since the row was written
+    fn arrange<T: From<Value>>(&self, rowid: Option<i64>, record: Vec<T>) -> Result<Vec<T>> {
+        if record.len() > self.record_columns.len() {
+            return Err(Error::Corrupt(format!(
+                "row of table {} has {} values but the table stores {} columns",
+                self.name,
+                record.len(),
+                self.record_columns.len()
+            )));
+        }
+        let mut values: Vec<T> = (0..self.columns().len())
+            .map(|column| self.default_value(column).into())
+            .collect();
+        for (slot, value) in record.into_iter().enumerate() {
+            values[self.record_columns[slot]] = value;
+        }
+        if let (Some(alias), Some(rowid)) = (self.definition.rowid_alias(), rowid) {
+            values[alias] = Value::Integer(rowid).into();
+        }
+        Ok(values)
+    }
+}
+
+/// Column indexes of the primary key, in key order
+fn primary_key(definition: &CreateTable) -> Vec<usize> {
+    let by_name = |name: &String| {
+        definition
+            .columns
+            .iter()
+            .position(|c| c.name.eq_ignore_ascii_case(name))
+    };
+    if definition.primary_key.is_empty() {
+        definition
+            .columns
+            .iter()
+            .position(|c| c.primary_key)
+            .into_iter()
+            .collect()
+    } else {
+        definition.primary_key.iter().filter_map(by_name).collect()
+    }
+}
+
+pub struct TableScan<'a> {
+    table: &'a TableInfo,
+    db: &'a Database,
+    cells: TreeCells<'a>,
+    /// Number of leading record values that make up a WITHOUT ROWID table's key
+    key_len: usize,
+    /// Where the row returned last is
+    last: Option<ResumeToken>,
+}
+
+impl TableScan<'_> {
+    /// A token for resuming after the row returned last, or `None` before the first
+    pub fn resume_token(&self) -> Option<ResumeToken> {
+        self.last.clone()
+    }
+}
+
+impl Iterator for TableScan<'_> {
+    type Item = Result<Row>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let found = match self.cells.next()? {
+            Ok(found) => found,
+            Err(e) => return Some(Err(e)),
+        };
+        let (rowid, payload) = match &found.cell {
+            Cell::TableLeaf { rowid, payload } => (Some(*rowid), payload),
+            Cell::IndexLeaf { payload } | Cell::IndexInterior { payload, .. } => (None, payload),
+            Cell::TableInterior { .. } => unreachable!("tree_cells skips table interior cells"),
+        };
+        let values = match self
+            .db
+            .read_payload(payload)
+            .and_then(|record| decode_record_with(&record, self.db.utf8_policy()))
+        {
+            Ok(values) => values,
+            Err(e) => return Some(Err(e)),
+        };
+        self.last = Some(match rowid {
+            Some(rowid) => ResumeToken::Rowid(rowid),
+            None => ResumeToken::Key(values[..self.key_len.min(values.len())].to_vec()),
+        });
+        Some(self.table.row_from_record(rowid, values))
+    }
+}
+
+pub struct StreamScan<'a> {
+    table: &'a TableInfo,
+    db: &'a Database,
+    cells: TreeCells<'a>,
+}
+
+impl StreamScan<'_> {
+    fn read_row(&self, rowid: Option<i64>, payload: Payload) -> Result<StreamedRow> {
+        let payload = Arc::new(payload);
+        let mut reader = self.db.payload_reader(&payload)?;
+        let serial_types = read_record_header(&mut reader, usize::MAX)?;
+
+        let mut fields = Vec::with_capacity(serial_types.len());
+        for (slot, st) in serial_types.into_iter().enumerate() {
+            let len = field_len(&reader, st)?;
+            fields.push(read_field(
+                self.table,
+                self.db,
+                &payload,
+                &mut reader,
+                slot,
+                st,
+                len,
+            )?);
+        }
+        let fields = self.table.arrange(rowid, fields)?;
+        Ok(StreamedRow { rowid, fields })
+    }
+}
+
+/// Where a projected column's value comes from
+#[derive(Debug, Clone, Copy)]
+enum Source {
+    /// This record slot, which holds this column
+    Slot(usize, usize),
+    /// The rowid, which an INTEGER PRIMARY KEY column stands for
+    Rowid,
+    /// Nowhere: a virtual generated column, read as NULL
+    Computed,
+}
+
+/// Rows holding only some of a table's columns; see [`TableInfo::project`]
+pub struct ProjectedScan<'a> {
+    table: &'a TableInfo,
+    db: &'a Database,
+    cells: TreeCells<'a>,
+    sources: Vec<Source>,
+    /// Record slots read, in record order
+    slots: Vec<usize>,
+}
+
+impl ProjectedScan<'_> {
+    fn read_row(&self, rowid: Option<i64>, payload: Payload) -> Result<StreamedRow> {
+        // one field per slot in `slots`, short when the record is
+        let found = if self.slots.is_empty() {
+            Vec::new()
+        } else if payload.first_overflow.is_none() {
+            // the record is all on the page, and smaller than STREAM_THRESHOLD
+            let values = decode_columns_with(&payload.local, &self.slots, self.db.utf8_policy())?;
+            values
+                .into_iter()
+                .zip(&self.slots)
+                .map_while(|(value, &slot)| {
+                    let mut value = value?;
+                    self.table.apply_affinity(slot, &mut value);
+                    Some(Field::Value(value))
+                })
+                .collect()
+        } else {
+            let last = self.slots[self.slots.len() - 1];
+            let payload = Arc::new(payload);
+            let mut reader = self.db.payload_reader(&payload)?;
+            let serial_types = read_record_header(&mut reader, last + 1)?;
+            let mut found = Vec::with_capacity(self.slots.len());
+            for (slot, st) in serial_types.into_iter().enumerate() {
+                let len = field_len(&reader, st)?;
+                if self.slots.binary_search(&slot).is_ok() {
+                    found.push(read_field(
+                        self.table,
+                        self.db,
+                        &payload,
+                        &mut reader,
+                        slot,
+                        st,
+                        len,
+                    )?);
+                } else {
+                    reader.skip(len)?;
+                }
+            }
+            found
+        };
+
+        let fields = self
+            .sources
+            .iter()
+            .map(|source| match *source {
+                Source::Slot(slot, column) => {
+                    let i = self.slots.binary_search(&slot).unwrap_or(usize::MAX);
+                    match found.get(i) {
+                        Some(field) => field.clone(),
+                        None => Field::Value(self.table.default_value(column)),
+                    }
+                }
+                Source::Rowid => Field::Value(rowid.map_or(Value::Null, Value::Integer)),
+                Source::Computed => Field::Value(Value::Null),
+            })
+            .collect();
+        Ok(StreamedRow { rowid, fields })
+    }
+}
+
+impl Iterator for ProjectedScan<'_> {
+    type Item = Result<StreamedRow>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let found = match self.cells.next()? {
+            Ok(found) => found,
+            Err(e) => return Some(Err(e)),
+        };
+        let (rowid, payload) = match found.cell {
+            Cell::TableLeaf { rowid, payload } => (Some(rowid), payload),
+            Cell::IndexLeaf { payload } | Cell::IndexInterior { payload, .. } => (None, payload),
+            Cell::TableInterior { .. } => unreachable!("tree_cells skips table interior cells"),
+        };
+        Some(self.read_row(rowid, payload))
+    }
+}
+
+/// Number of content bytes of a value of serial type `st`, checked against what is left of
+/// the payload
+fn field_len(reader: &PayloadReader, st: u64) -> Result<u64> {
+    let len = serial_type_len(st)
+        .ok_or_else(|| Error::Corrupt(format!("reserved serial type {}", st)))?
+        as u64;
+    if len > reader.remaining() {
+        return Err(Error::Corrupt(
+            "record column extends past the end of the payload".to_string(),
+        ));
+    }
+    Ok(len)
+}
+
+/// Reads the value at `reader` in record slot `slot`, or leaves it in the file and steps over
+/// it when it is [`STREAM_THRESHOLD`] bytes or more
+fn read_field(
+    table: &TableInfo,
+    db: &Database,
+    payload: &Arc<Payload>,
+    reader: &mut PayloadReader,
+    slot: usize,
+    st: u64,
+    len: u64,
+) -> Result<Field> {
+    if len < STREAM_THRESHOLD {
+        let bytes = read_bytes(reader, len)?;
+        let mut value = decode_value_with(st, &bytes, db.utf8_policy())?;
+        table.apply_affinity(slot, &mut value);
+        Ok(Field::Value(value))
+    } else {
+        let field = Field::Stored(StoredValue {
+            payload: payload.clone(),
+            offset: reader.size() - reader.remaining(),
+            len,
+            serial_type: st,
+        });
+        reader.skip(len)?;
+        Ok(field)
+    }
+}
+
+/// Reads a record's header from the start of its payload, returning the serial types of its
+/// first `count` values and leaving `reader` at the first value
+fn read_record_header(reader: &mut PayloadReader, count: usize) -> Result<Vec<u64>> {
+    // the header size is a varint of at most 9 bytes, and counts itself
+    let mut header = Vec::new();
+    while header.len() < 9 {
+        header.extend(read_bytes(reader, 1)?);
+        if header.last().is_some_and(|b| b & 0x80 == 0) {
+            break;
+        }
+    }
+    let (header_size, _) = read_varint(&header)
+        .ok_or_else(|| Error::Corrupt("record header size is truncated".to_string()))?;
+    let rest = header_size
+        .checked_sub(header.len() as u64)
+        .filter(|&rest| rest <= reader.remaining())
+        .ok_or_else(|| {
+            Error::Corrupt(format!(
+                "record header size {} is out of bounds",
+                header_size
+            ))
+        })?;
+    header.extend(read_bytes(reader, rest)?);
+    Ok(read_header_prefix(&header, count)?.0)
+}
+
+fn read_bytes(reader: &mut PayloadReader, n: u64) -> Result<Vec<u8>> {
+    let mut bytes = vec![0u8; n as usize];
+    let mut filled = 0;
+    while filled < bytes.len() {
+        match reader.read_chunk(&mut bytes[filled..])? {
+            0 => {
+                return Err(Error::Corrupt(
+                    "record is shorter than its header".to_string(),
+                ))
+            }
+            read => filled += read,
+        }
+    }
+    Ok(bytes)
+}
+
+impl Iterator for StreamScan<'_> {
+    type Item = Result<StreamedRow>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let found = match self.cells.next()? {
+            Ok(found) => found,
+            Err(e) => return Some(Err(e)),
+        };
+        let (rowid, payload) = match found.cell {
+            Cell::TableLeaf { rowid, payload } => (Some(rowid), payload),
+            Cell::IndexLeaf { payload } | Cell::IndexInterior { payload, .. } => (None, payload),
+            Cell::TableInterior { .. } => unreachable!("tree_cells skips table interior cells"),
+        };
+        Some(self.read_row(rowid, payload))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resume_tokens_round_trip() {
+        for token in [
+            ResumeToken::Rowid(0),
+            ResumeToken::Rowid(i64::MIN),
+            ResumeToken::Rowid(i64::MAX),
+            ResumeToken::Key(vec![]),
+            ResumeToken::Key(vec![
+                Value::Text("key00042".to_string()),
+                Value::Integer(-7),
+                Value::Real(0.5),
+                Value::Null,
+                Value::Blob(vec![0, 0xff]),
+                Value::RawText(vec![b'a', 0xff]),
+            ]),
+        ] {
+            let printed = token.to_string();
+            assert_eq!(
+                printed.parse::<ResumeToken>().unwrap(),
+                token,
+                "{}",
+                printed
+            );
+        }
+        assert_eq!(ResumeToken::Rowid(42).to_string(), "rowid:42");
+        assert_eq!(
+            ResumeToken::Key(vec![Value::Integer(1)]).to_string(),
+            "key:0209"
+        );
+    }
+
+    #[test]
+    fn tampered_tokens_are_rejected() {
+        for token in [
+            "",
+            "42",
+            "rowid:",
+            "rowid:4x",
+            "rowid:9223372036854775808",
+            "ROWID:1",
+            "key:020",
+            "key:zz09",
+            "key:02é9",
+            // a header that runs past the end of the record
+            "key:0509",
+            // a TEXT value longer than the bytes that follow
+            "key:021761",
+            // a reserved serial type
+            "key:020a",
+        ] {
+            assert!(token.parse::<ResumeToken>().is_err(), "{:?}", token);
+        }
+    }
+}
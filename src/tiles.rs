@@ -0,0 +1,356 @@
+//! Reading map tiles from MBTiles files and GeoPackage tile pyramids
+
+use crate::db::Database;
+use crate::index::IndexInfo;
+use crate::schema::{ObjectType, SchemaEntry};
+use crate::sql::create::parse_create_index;
+use crate::table::{Field, Row, TableInfo};
+use crate::value::compare_keys;
+use crate::{Error, Result, Value};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+
+/// The columns a tile is addressed by, in the order tile indexes list them
+const TILE_KEY: [&str; 3] = ["zoom_level", "tile_column", "tile_row"];
+
+/// The deepest zoom level whose rows fit in a tile address
+const MAX_ZOOM: i64 = 62;
+
+/// A set of tiles stored in one of the layouts below
+#[derive(Debug, Clone)]
+pub struct Tileset {
+    pub name: String,
+    layout: Layout,
+    /// Rows count from the bottom of the map, as in MBTiles (TMS), rather than from the top
+    /// as in GeoPackage and the XYZ scheme tile URLs use
+    tms: bool,
+}
+
+#[derive(Debug, Clone)]
+enum Layout {
+    /// `zoom_level, tile_column, tile_row, tile_data` in one table
+    Table(Lookup),
+    /// The deduplicated MBTiles layout behind a `tiles` view: `map` holds a `tile_id` per
+    /// address, at position `tile_id`, and `images` the data for each id
+    Split {
+        map: Lookup,
+        tile_id: usize,
+        images: Box<Lookup>,
+    },
+}
+
+/// Rows of a table found by the values of some of its columns, through an index whose key
+/// starts with those columns when the table has one
+#[derive(Debug, Clone)]
+struct Lookup {
+    table: TableInfo,
+    /// Positions of the columns looked up by
+    key: Vec<usize>,
+    index: Option<IndexInfo>,
+}
+
+/// The number of tiles at one zoom level
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ZoomLevel {
+    pub zoom: i64,
+    pub tiles: usize,
+}
+
+impl Tileset {
+    /// Opens the tiles of an MBTiles file: a `tiles` table, or a `tiles` view over `map` and
+    /// `images`. Returns `None` if the database has neither.
+    pub fn open(db: &Database) -> Result<Option<Tileset>> {
+        let schema = db.schema()?;
+        let Some(entry) = schema.iter().find(|e| {
+            matches!(e.kind, ObjectType::Table | ObjectType::View)
+                && e.name.eq_ignore_ascii_case("tiles")
+        }) else {
+            return Ok(None);
+        };
+        let layout = if entry.kind == ObjectType::Table {
+            Layout::Table(Lookup::new(db, &schema, required(db, "tiles")?, &TILE_KEY)?)
+        } else {
+            let map = required(db, "map")?;
+            let tile_id = column_position(&map, "tile_id")
+                .ok_or_else(|| Error::Syntax("table map has no tile_id column".to_string()))?;
+            Layout::Split {
+                map: Lookup::new(db, &schema, map, &TILE_KEY)?,
+                tile_id,
+                images: Box::new(Lookup::new(
+                    db,
+                    &schema,
+                    required(db, "images")?,
+                    &["tile_id"],
+                )?),
+            }
+        };
+        let tileset = Tileset {
+            name: entry.name.clone(),
+            layout,
+            tms: true,
+        };
+        tileset.data_column()?;
+        Ok(Some(tileset))
+    }
+
+    /// Opens a tile table by name, such as a GeoPackage tile pyramid user data table, whose
+    /// rows count from the top. Returns `None` if there is no table of that name.
+    pub fn open_table(db: &Database, name: &str) -> Result<Option<Tileset>> {
+        let Some(table) = TableInfo::find(db, name)? else {
+            return Ok(None);
+        };
+        let schema = db.schema()?;
+        let tileset = Tileset {
+            name: table.name.clone(),
+            layout: Layout::Table(Lookup::new(db, &schema, table, &TILE_KEY)?),
+            tms: false,
+        };
+        tileset.data_column()?;
+        Ok(Some(tileset))
+    }
+
+    /// The data of the tile at zoom `z`, column `x` and row `y`, with rows counted from the
+    /// top of the map as in tile URLs; `None` if the set has no such tile
+    pub fn get(&self, db: &Database, z: i64, x: i64, y: i64) -> Result<Option<Vec<u8>>> {
+        let (Some(_), Some(flipped)) = (flip_row(z, x), flip_row(z, y)) else {
+            return Ok(None);
+        };
+        let row = if self.tms { flipped } else { y };
+        let address = [Value::Integer(z), Value::Integer(x), Value::Integer(row)];
+        let data = self.data_column()?;
+        let found = match &self.layout {
+            Layout::Table(tiles) => tiles.get(db, &address)?,
+            Layout::Split {
+                map,
+                tile_id,
+                images,
+            } => match map.get(db, &address)? {
+                Some(tile) => {
+                    let id = tile.values.get(*tile_id).cloned().unwrap_or(Value::Null);
+                    images.get(db, &[id])?
+                }
+                None => None,
+            },
+        };
+        match found.and_then(|row| row.values.into_iter().nth(data)) {
+            None | Some(Value::Null) => Ok(None),
+            Some(Value::Blob(bytes)) => Ok(Some(bytes)),
+            Some(Value::Text(text)) => Ok(Some(text.into_bytes())),
+            Some(_) => Err(Error::Corrupt(format!(
+                "tile {}/{}/{} in {} holds a number rather than image data",
+                z, x, y, self.name
+            ))),
+        }
+    }
+
+    /// The zoom levels that hold tiles, lowest first. Every address is read, so this takes a
+    /// pass over the whole tile table.
+    pub fn zoom_levels(&self, db: &Database) -> Result<Vec<ZoomLevel>> {
+        let addresses = match &self.layout {
+            Layout::Table(tiles) => tiles,
+            Layout::Split { map, .. } => map,
+        };
+        let mut counts = BTreeMap::new();
+        for row in addresses.table.project(db, &addresses.key[..1])? {
+            if let Some(Field::Value(Value::Integer(z))) = row?.fields.first() {
+                *counts.entry(*z).or_insert(0) += 1;
+            }
+        }
+        Ok(counts
+            .into_iter()
+            .map(|(zoom, tiles)| ZoomLevel { zoom, tiles })
+            .collect())
+    }
+
+    /// Position of `tile_data` in the table that holds it
+    fn data_column(&self) -> Result<usize> {
+        let table = match &self.layout {
+            Layout::Table(tiles) => &tiles.table,
+            Layout::Split { images, .. } => &images.table,
+        };
+        column_position(table, "tile_data")
+            .ok_or_else(|| Error::Syntax(format!("table {} has no tile_data column", table.name)))
+    }
+}
+
+/// The `name, value` rows of the MBTiles `metadata` table, in table order, or nothing when
+/// there is no such table
+pub fn metadata(db: &Database) -> Result<Vec<(Value, Value)>> {
+    let Some(table) = TableInfo::find(db, "metadata")? else {
+        return Ok(Vec::new());
+    };
+    let (Some(name), Some(value)) = (
+        column_position(&table, "name"),
+        column_position(&table, "value"),
+    ) else {
+        return Ok(Vec::new());
+    };
+    let mut rows = Vec::new();
+    for row in table.rows(db) {
+        let values = row?.values;
+        let field = |i: usize| values.get(i).cloned().unwrap_or(Value::Null);
+        rows.push((field(name), field(value)));
+    }
+    Ok(rows)
+}
+
+/// The tile pyramid tables a GeoPackage lists in `gpkg_contents`, in table order, or
+/// nothing when there is no such table
+pub fn pyramids(db: &Database) -> Result<Vec<String>> {
+    let Some(table) = TableInfo::find(db, "gpkg_contents")? else {
+        return Ok(Vec::new());
+    };
+    let (Some(name), Some(data_type)) = (
+        column_position(&table, "table_name"),
+        column_position(&table, "data_type"),
+    ) else {
+        return Ok(Vec::new());
+    };
+    let mut names = Vec::new();
+    for row in table.rows(db) {
+        let values = row?.values;
+        if let (Some(Value::Text(name)), Some(Value::Text(data_type))) =
+            (values.get(name), values.get(data_type))
+        {
+            if data_type == "tiles" {
+                names.push(name.clone());
+            }
+        }
+    }
+    Ok(names)
+}
+
+impl Lookup {
+    fn new(
+        db: &Database,
+        schema: &[SchemaEntry],
+        table: TableInfo,
+        key: &[&str],
+    ) -> Result<Lookup> {
+        let positions = key
+            .iter()
+            .map(|name| {
+                column_position(&table, name).ok_or_else(|| {
+                    Error::Syntax(format!("table {} has no {} column", table.name, name))
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let mut lookup = Lookup {
+            table,
+            key: positions,
+            index: None,
+        };
+        // index entries end with a rowid, which WITHOUT ROWID tables don't have
+        if lookup.table.definition.without_rowid {
+            return Ok(lookup);
+        }
+        for entry in schema.iter().filter(|e| {
+            e.kind == ObjectType::Index
+                && e.root_page != 0
+                && e.tbl_name.eq_ignore_ascii_case(&lookup.table.name)
+        }) {
+            let leads = match &entry.sql {
+                Some(sql) => {
+                    let index = parse_create_index(sql)?;
+                    let columns = index.indexed_columns()?;
+                    index.where_clause.is_none()
+                        && columns.len() >= key.len()
+                        && key.iter().zip(&columns).all(|(name, column)| {
+                            column
+                                .name
+                                .as_deref()
+                                .is_some_and(|c| c.eq_ignore_ascii_case(name))
+                        })
+                }
+                // the columns of an index made for a UNIQUE or PRIMARY KEY constraint aren't
+                // recorded; tried below against the rows its entries point to
+                None => true,
+            };
+            if !leads {
+                continue;
+            }
+            let index = IndexInfo::from_schema(entry, &lookup.table.definition)?;
+            if entry.sql.is_some() || lookup.indexes_key(db, &index)? {
+                lookup.index = Some(index);
+                break;
+            }
+        }
+        Ok(lookup)
+    }
+
+    /// Whether the first entries of `index` begin with the key of the rows they point to
+    fn indexes_key(&self, db: &Database, index: &IndexInfo) -> Result<bool> {
+        const SAMPLE: usize = 16;
+        let mut sampled = 0;
+        for entry in index.entries(db).take(SAMPLE) {
+            let entry = entry?;
+            let Some(Value::Integer(rowid)) = entry.last() else {
+                return Ok(false);
+            };
+            let Some(row) = self.table.row(db, *rowid)? else {
+                return Ok(false);
+            };
+            if entry.len() <= self.key.len()
+                || self.key_of(&row).iter().zip(&entry).any(|(a, b)| a != b)
+            {
+                return Ok(false);
+            }
+            sampled += 1;
+        }
+        Ok(sampled > 0)
+    }
+
+    fn key_of(&self, row: &Row) -> Vec<Value> {
+        self.key
+            .iter()
+            .map(|&i| row.values.get(i).cloned().unwrap_or(Value::Null))
+            .collect()
+    }
+
+    /// The first row whose key equals `key`
+    fn get(&self, db: &Database, key: &[Value]) -> Result<Option<Row>> {
+        let Some(index) = &self.index else {
+            for row in self.table.rows(db) {
+                let row = row?;
+                if compare_keys(&self.key_of(&row), key, &[]) == Ordering::Equal {
+                    return Ok(Some(row));
+                }
+            }
+            return Ok(None);
+        };
+        for entry in index.entries_from_key(db, key) {
+            let entry = entry?;
+            if compare_keys(&entry, key, &index.order) != Ordering::Equal {
+                break;
+            }
+            if let Some(Value::Integer(rowid)) = entry.last() {
+                if let Some(row) = self.table.row(db, *rowid)? {
+                    return Ok(Some(row));
+                }
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Turns a row or column counted from one edge of the map at zoom `z` into one counted from
+/// the opposite edge, as between TMS and XYZ tile rows. `None` if the map has no such row.
+pub fn flip_row(z: i64, row: i64) -> Option<i64> {
+    if !(0..=MAX_ZOOM).contains(&z) {
+        return None;
+    }
+    let size = 1i64 << z;
+    (0..size).contains(&row).then(|| size - 1 - row)
+}
+
+fn column_position(table: &TableInfo, name: &str) -> Option<usize> {
+    table
+        .columns()
+        .iter()
+        .position(|c| c.name.eq_ignore_ascii_case(name))
+}
+
+fn required(db: &Database, name: &str) -> Result<TableInfo> {
+    TableInfo::find(db, name)?
+        .ok_or_else(|| Error::Corrupt(format!("tiles are stored in {}, which is missing", name)))
+}
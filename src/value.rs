@@ -0,0 +1,279 @@
+use std::cmp::Ordering;
+
+/// A single SQLite value, tagged with its storage class
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Integer(i64),
+    Real(f64),
+    Text(String),
+    /// TEXT whose bytes aren't valid UTF-8, kept exactly as stored. Only produced when
+    /// decoding with [`Utf8Policy::Raw`](crate::record::Utf8Policy::Raw).
+    RawText(Vec<u8>),
+    Blob(Vec<u8>),
+}
+
+/// The built-in rules for comparing TEXT
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Collation {
+    /// Byte by byte
+    #[default]
+    Binary,
+    /// Ignoring the case of ASCII letters
+    NoCase,
+    /// Ignoring trailing spaces
+    RTrim,
+}
+
+impl Collation {
+    /// The collation for a COLLATE name, if it is one of the built-in ones
+    pub fn from_name(name: &str) -> Option<Collation> {
+        match name.to_ascii_uppercase().as_str() {
+            "BINARY" => Some(Collation::Binary),
+            "NOCASE" => Some(Collation::NoCase),
+            "RTRIM" => Some(Collation::RTrim),
+            _ => None,
+        }
+    }
+
+    fn compare(self, a: &[u8], b: &[u8]) -> Ordering {
+        match self {
+            Collation::Binary => a.cmp(b),
+            Collation::NoCase => a
+                .iter()
+                .map(u8::to_ascii_lowercase)
+                .cmp(b.iter().map(u8::to_ascii_lowercase)),
+            Collation::RTrim => {
+                let trim = |s: &[u8]| s.len() - s.iter().rev().take_while(|&&c| c == b' ').count();
+                a[..trim(a)].cmp(&b[..trim(b)])
+            }
+        }
+    }
+}
+
+/// How one term of an index key sorts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SortOrder {
+    pub collation: Collation,
+    pub descending: bool,
+}
+
+/// Orders two index keys term by term, each term by its entry in `order` and any past the end
+/// of it (such as the trailing rowid) in BINARY ascending order. A key that is a prefix of the
+/// other compares equal to it.
+pub fn compare_keys(a: &[Value], b: &[Value], order: &[SortOrder]) -> Ordering {
+    for (i, (x, y)) in a.iter().zip(b).enumerate() {
+        let term = order.get(i).copied().unwrap_or_default();
+        let ordering = x.compare_with(y, term.collation);
+        let ordering = if term.descending {
+            ordering.reverse()
+        } else {
+            ordering
+        };
+        if ordering.is_ne() {
+            return ordering;
+        }
+    }
+    Ordering::Equal
+}
+
+impl Value {
+    pub fn is_numeric(&self) -> bool {
+        matches!(self, Value::Integer(_) | Value::Real(_))
+    }
+
+    /// Orders two values the way SQLite sorts them, comparing TEXT byte by byte
+    pub fn compare(&self, other: &Value) -> Ordering {
+        self.compare_with(other, Collation::Binary)
+    }
+
+    /// Orders two values the way SQLite sorts them: NULLs first, then INTEGER and REAL by
+    /// numeric value, then TEXT by `collation`, then BLOBs byte by byte. INTEGERs are compared
+    /// with REALs exactly, so values past 2^53 that a double can't hold still order correctly.
+    pub fn compare_with(&self, other: &Value, collation: Collation) -> Ordering {
+        match (self, other) {
+            (Value::Integer(a), Value::Integer(b)) => a.cmp(b),
+            (Value::Real(a), Value::Real(b)) => compare_reals(*a, *b),
+            (Value::Integer(a), Value::Real(b)) => compare_int_real(*a, *b),
+            (Value::Real(a), Value::Integer(b)) => compare_int_real(*b, *a).reverse(),
+            (Value::Blob(a), Value::Blob(b)) => a.cmp(b),
+            _ => match (self.text_bytes(), other.text_bytes()) {
+                (Some(a), Some(b)) => collation.compare(a, b),
+                _ => self.sort_class().cmp(&other.sort_class()),
+            },
+        }
+    }
+
+    fn text_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Value::Text(s) => Some(s.as_bytes()),
+            Value::RawText(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    /// Rank of the value's storage class in sort order, with INTEGER and REAL sharing one
+    fn sort_class(&self) -> u8 {
+        match self {
+            Value::Null => 0,
+            Value::Integer(_) | Value::Real(_) => 1,
+            Value::Text(_) | Value::RawText(_) => 2,
+            Value::Blob(_) => 3,
+        }
+    }
+}
+
+/// Stored REALs are never NaN (SQLite stores NULL instead), but a NaN sorts first like NULL
+/// rather than breaking the ordering
+fn compare_reals(a: f64, b: f64) -> Ordering {
+    a.partial_cmp(&b)
+        .unwrap_or_else(|| b.is_nan().cmp(&a.is_nan()))
+}
+
+/// Compares without converting the INTEGER to a double, which would round above 2^53
+fn compare_int_real(i: i64, r: f64) -> Ordering {
+    if r.is_nan() {
+        return Ordering::Greater;
+    }
+    // -2^63 and 2^63 are exact as doubles; anything outside that range is beyond every i64
+    if r < -9223372036854775808.0 {
+        return Ordering::Greater;
+    }
+    if r >= 9223372036854775808.0 {
+        return Ordering::Less;
+    }
+    // truncation toward zero is exact in this range, and settles it unless the integer parts
+    // match, in which case the fractional part decides
+    let whole = r as i64;
+    i.cmp(&whole).then_with(|| {
+        let fraction = r - whole as f64;
+        0.0.partial_cmp(&fraction).unwrap_or(Ordering::Equal)
+    })
+}
+
+impl From<i64> for Value {
+    fn from(v: i64) -> Self {
+        Value::Integer(v)
+    }
+}
+
+impl From<u32> for Value {
+    fn from(v: u32) -> Self {
+        Value::Integer(v as i64)
+    }
+}
+
+impl From<usize> for Value {
+    fn from(v: usize) -> Self {
+        Value::Integer(v as i64)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(v: &str) -> Self {
+        Value::Text(v.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(v: String) -> Self {
+        Value::Text(v)
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(v: Option<T>) -> Self {
+        v.map_or(Value::Null, Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Ordering::{Equal, Greater, Less};
+
+    fn text(s: &str) -> Value {
+        Value::Text(s.to_string())
+    }
+
+    #[test]
+    fn storage_classes_sort_null_numbers_text_blob() {
+        let ascending = [
+            Value::Null,
+            Value::Integer(i64::MIN),
+            Value::Real(-0.5),
+            Value::Integer(0),
+            Value::Real(1e300),
+            Value::RawText(vec![]),
+            text("1"),
+            Value::RawText(vec![b'a', 0xff]),
+            Value::Blob(vec![]),
+            Value::Blob(vec![0]),
+        ];
+        for (i, a) in ascending.iter().enumerate() {
+            for (j, b) in ascending.iter().enumerate() {
+                assert_eq!(a.compare(b), i.cmp(&j), "{:?} against {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn integers_and_reals_compare_exactly_near_2_pow_63() {
+        let two_63 = 9223372036854775808.0;
+        assert_eq!(Value::Integer(i64::MAX).compare(&Value::Real(two_63)), Less);
+        assert_eq!(
+            Value::Real(two_63).compare(&Value::Integer(i64::MAX)),
+            Greater
+        );
+        assert_eq!(
+            Value::Integer(i64::MIN).compare(&Value::Real(-two_63)),
+            Equal
+        );
+        assert_eq!(
+            Value::Integer(i64::MIN).compare(&Value::Real(-1e19)),
+            Greater
+        );
+        // 2^53 + 1 has no double; converting it would make it equal to 2^53
+        let above = (1i64 << 53) + 1;
+        assert_eq!(
+            Value::Integer(above).compare(&Value::Real((1i64 << 53) as f64)),
+            Greater
+        );
+        assert_eq!(Value::Integer(3).compare(&Value::Real(3.0)), Equal);
+        assert_eq!(Value::Integer(3).compare(&Value::Real(3.5)), Less);
+        assert_eq!(Value::Integer(-3).compare(&Value::Real(-3.5)), Greater);
+    }
+
+    #[test]
+    fn nan_sorts_before_every_number() {
+        let nan = Value::Real(f64::NAN);
+        assert_eq!(nan.compare(&Value::Real(f64::NEG_INFINITY)), Less);
+        assert_eq!(Value::Integer(i64::MIN).compare(&nan), Greater);
+        assert_eq!(nan.compare(&Value::Integer(i64::MIN)), Less);
+        assert_eq!(nan.compare(&Value::Real(f64::NAN)), Equal);
+        assert_eq!(Value::Null.compare(&nan), Less);
+    }
+
+    #[test]
+    fn nocase_and_rtrim_collations() {
+        let cmp = |a: &str, b: &str, collation| text(a).compare_with(&text(b), collation);
+        assert_eq!(cmp("ABC", "abc", Collation::Binary), Less);
+        assert_eq!(cmp("ABC", "abc", Collation::NoCase), Equal);
+        assert_eq!(cmp("abd", "ABC", Collation::NoCase), Greater);
+        // only ASCII letters fold
+        assert_eq!(cmp("É", "é", Collation::NoCase), Less);
+        assert_eq!(cmp("abc  ", "abc", Collation::RTrim), Equal);
+        assert_eq!(cmp("abc  ", "abc", Collation::Binary), Greater);
+        // leading spaces and other whitespace still count
+        assert_eq!(cmp(" abc", "abc", Collation::RTrim), Less);
+        assert_eq!(cmp("abc\t", "abc", Collation::RTrim), Greater);
+        // collations apply to TEXT only
+        let blob = |b: &[u8]| Value::Blob(b.to_vec());
+        assert_eq!(
+            blob(b"A").compare_with(&blob(b"a"), Collation::NoCase),
+            Less
+        );
+        assert_eq!(Collation::from_name("nocase"), Some(Collation::NoCase));
+        assert_eq!(Collation::from_name("unicode"), None);
+    }
+}
@@ -0,0 +1,89 @@
+/// Decodes a SQLite varint from the start of `buf`, returning the value and the number of
+/// bytes it occupied, or `None` if `buf` ends before the varint does
+pub fn read_varint(buf: &[u8]) -> Option<(u64, usize)> {
+    let mut value: u64 = 0;
+    for i in 0..9 {
+        let b = *buf.get(i)?;
+        // the 9th byte contributes all 8 bits
+        if i == 8 {
+            return Some(((value << 8) | u64::from(b), 9));
+        }
+        value = (value << 7) | u64::from(b & 0x7F);
+        if b & 0x80 == 0 {
+            return Some((value, i + 1));
+        }
+    }
+    unreachable!()
+}
+
+/// Appends `value` as a SQLite varint, the inverse of [`read_varint`]
+pub fn write_varint(value: u64, out: &mut Vec<u8>) {
+    // past 56 bits the ninth byte is needed, and it holds a full 8
+    if value >> 56 != 0 {
+        let high = value >> 8;
+        out.extend(
+            (0..8)
+                .rev()
+                .map(|i| ((high >> (7 * i)) & 0x7F) as u8 | 0x80),
+        );
+        out.push(value as u8);
+        return;
+    }
+    let groups = (1..9).find(|&n| value >> (7 * n) == 0).unwrap_or(8);
+    for i in (0..groups).rev() {
+        let more = if i > 0 { 0x80 } else { 0 };
+        out.push(((value >> (7 * i)) & 0x7F) as u8 | more);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(value: u64) -> Vec<u8> {
+        let mut out = Vec::new();
+        write_varint(value, &mut out);
+        out
+    }
+
+    #[test]
+    fn known_encodings() {
+        assert_eq!(encode(0), [0x00]);
+        assert_eq!(encode(0x7f), [0x7f]);
+        assert_eq!(encode(0x80), [0x81, 0x00]);
+        assert_eq!(encode(0x3fff), [0xff, 0x7f]);
+        assert_eq!(encode(0x4000), [0x81, 0x80, 0x00]);
+        // the ninth byte carries all eight of its bits
+        assert_eq!(encode(u64::MAX), [0xff; 9]);
+        assert_eq!(read_varint(&[0xff; 9]), Some((u64::MAX, 9)));
+    }
+
+    #[test]
+    fn round_trips_at_every_length() {
+        for bits in 0..64 {
+            for value in [1u64 << bits, (1u64 << bits) - 1, (1u64 << bits) + 1] {
+                let bytes = encode(value);
+                assert_eq!(
+                    read_varint(&bytes),
+                    Some((value, bytes.len())),
+                    "{:#x}",
+                    value
+                );
+            }
+        }
+        // negative integers, as rowids, take all nine bytes
+        assert_eq!(encode(-1i64 as u64).len(), 9);
+    }
+
+    #[test]
+    fn stops_at_the_end_of_the_varint() {
+        assert_eq!(read_varint(&[0x81, 0x00, 0xff]), Some((0x80, 2)));
+    }
+
+    #[test]
+    fn truncated() {
+        assert_eq!(read_varint(&[]), None);
+        assert_eq!(read_varint(&[0x81]), None);
+        assert_eq!(read_varint(&[0xff; 8]), None);
+    }
+}
@@ -0,0 +1,348 @@
+use crate::{Error, Result};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+
+pub const WAL_HEADER_SIZE: usize = 32;
+pub const FRAME_HEADER_SIZE: usize = 24;
+
+// The low bit of the magic number selects the byte order used for checksums
+const WAL_MAGIC_LE: u32 = 0x377f0682;
+const WAL_MAGIC_BE: u32 = 0x377f0683;
+
+#[derive(Debug, Clone)]
+pub struct WalHeader {
+    pub magic: u32,
+    pub format_version: u32,
+    pub page_size: u32,
+    pub checkpoint_seq: u32,
+    pub salt: (u32, u32),
+    pub checksum: (u32, u32),
+    pub checksum_valid: bool,
+}
+
+impl WalHeader {
+    pub fn big_endian_checksums(&self) -> bool {
+        self.magic == WAL_MAGIC_BE
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WalFrame {
+    /// 1-based position of the frame in the log
+    pub index: usize,
+    pub page_number: u32,
+    /// Database size in pages after this frame; non-zero only on commit frames
+    pub db_size: u32,
+    pub salt: (u32, u32),
+    pub checksum: (u32, u32),
+    /// Byte offset of the frame header within the WAL file
+    pub offset: u64,
+    pub salt_valid: bool,
+    pub checksum_valid: bool,
+    /// Part of a valid, committed transaction that readers would see
+    pub committed: bool,
+    /// A later committed frame holds a newer image of the same page
+    pub superseded: bool,
+    /// 1-based number of the transaction this frame belongs to, if committed
+    pub txn: Option<usize>,
+}
+
+impl WalFrame {
+    pub fn is_commit(&self) -> bool {
+        self.db_size != 0
+    }
+}
+
+#[derive(Debug)]
+pub struct Wal {
+    pub path: PathBuf,
+    pub header: WalHeader,
+    pub frames: Vec<WalFrame>,
+}
+
+impl Wal {
+    /// The WAL lives next to the database as `<db>-wal`
+    pub fn path_for(db_path: &Path) -> PathBuf {
+        let mut path = db_path.as_os_str().to_owned();
+        path.push("-wal");
+        PathBuf::from(path)
+    }
+
+    pub fn open(path: &Path) -> Result<Wal> {
+        let file = File::open(path)?;
+        let file_len = file.metadata()?.len();
+        let mut reader = BufReader::new(file);
+
+        let mut raw = [0u8; WAL_HEADER_SIZE];
+        reader.read_exact(&mut raw).map_err(|_| {
+            Error::NotAWal(format!("{} is shorter than a WAL header", path.display()))
+        })?;
+
+        let magic = be_u32(&raw[0..4]);
+        if magic != WAL_MAGIC_LE && magic != WAL_MAGIC_BE {
+            return Err(Error::NotAWal(format!("bad magic number 0x{:08x}", magic)));
+        }
+        let big_endian = magic == WAL_MAGIC_BE;
+
+        let page_size = be_u32(&raw[8..12]);
+        if !(512..=65536).contains(&page_size) || !page_size.is_power_of_two() {
+            return Err(Error::Corrupt(format!(
+                "invalid WAL page size {}",
+                page_size
+            )));
+        }
+
+        let checksum = (be_u32(&raw[24..28]), be_u32(&raw[28..32]));
+        let computed = wal_checksum((0, 0), &raw[..24], big_endian);
+        let header = WalHeader {
+            magic,
+            format_version: be_u32(&raw[4..8]),
+            page_size,
+            checkpoint_seq: be_u32(&raw[12..16]),
+            salt: (be_u32(&raw[16..20]), be_u32(&raw[20..24])),
+            checksum,
+            checksum_valid: computed == checksum,
+        };
+
+        let frame_size = FRAME_HEADER_SIZE as u64 + page_size as u64;
+        let frame_count = (file_len.saturating_sub(WAL_HEADER_SIZE as u64) / frame_size) as usize;
+
+        let mut frames = Vec::with_capacity(frame_count);
+        let mut frame_header = [0u8; FRAME_HEADER_SIZE];
+        let mut page = vec![0u8; page_size as usize];
+        // each frame checksum continues from the one stored in the previous frame (or the header)
+        let mut prev_checksum = header.checksum;
+        for i in 0..frame_count {
+            reader.read_exact(&mut frame_header)?;
+            reader.read_exact(&mut page)?;
+
+            let salt = (be_u32(&frame_header[8..12]), be_u32(&frame_header[12..16]));
+            let checksum = (be_u32(&frame_header[16..20]), be_u32(&frame_header[20..24]));
+            let computed = wal_checksum(prev_checksum, &frame_header[..8], big_endian);
+            let computed = wal_checksum(computed, &page, big_endian);
+            prev_checksum = checksum;
+
+            frames.push(WalFrame {
+                index: i + 1,
+                page_number: be_u32(&frame_header[0..4]),
+                db_size: be_u32(&frame_header[4..8]),
+                salt,
+                checksum,
+                offset: WAL_HEADER_SIZE as u64 + i as u64 * frame_size,
+                salt_valid: salt == header.salt,
+                checksum_valid: computed == checksum,
+                committed: false,
+                superseded: false,
+                txn: None,
+            });
+        }
+
+        // Readers only trust the unbroken run of valid frames from the start of the log, and of
+        // those only the ones up to and including the last commit frame. A header with a bad
+        // checksum means nothing in the log can be trusted.
+        let valid_prefix = if header.checksum_valid {
+            frames
+                .iter()
+                .take_while(|f| f.salt_valid && f.checksum_valid)
+                .count()
+        } else {
+            0
+        };
+        let committed_len = frames[..valid_prefix]
+            .iter()
+            .rposition(WalFrame::is_commit)
+            .map_or(0, |i| i + 1);
+
+        let mut txn = 1;
+        for frame in &mut frames[..committed_len] {
+            frame.committed = true;
+            frame.txn = Some(txn);
+            if frame.is_commit() {
+                txn += 1;
+            }
+        }
+
+        let mut latest: HashMap<u32, usize> = HashMap::new();
+        for (i, frame) in frames[..committed_len].iter().enumerate() {
+            latest.insert(frame.page_number, i);
+        }
+        for (i, frame) in frames[..committed_len].iter_mut().enumerate() {
+            frame.superseded = latest[&frame.page_number] != i;
+        }
+
+        Ok(Wal {
+            path: path.to_path_buf(),
+            header,
+            frames,
+        })
+    }
+
+    pub fn committed_frames(&self) -> impl Iterator<Item = &WalFrame> {
+        self.frames.iter().filter(|f| f.committed)
+    }
+
+    pub fn transaction_count(&self) -> usize {
+        self.committed_frames().filter(|f| f.is_commit()).count()
+    }
+
+    /// The last commit frame, whose `db_size` is the database size readers currently see
+    pub fn last_commit(&self) -> Option<&WalFrame> {
+        self.committed_frames().filter(|f| f.is_commit()).last()
+    }
+}
+
+/// SQLite's WAL checksum: a Fletcher-like sum over pairs of 32-bit words
+fn wal_checksum(seed: (u32, u32), data: &[u8], big_endian: bool) -> (u32, u32) {
+    let (mut s0, mut s1) = seed;
+    for chunk in data.chunks_exact(8) {
+        let (x0, x1) = if big_endian {
+            (be_u32(&chunk[0..4]), be_u32(&chunk[4..8]))
+        } else {
+            (le_u32(&chunk[0..4]), le_u32(&chunk[4..8]))
+        };
+        s0 = s0.wrapping_add(x0).wrapping_add(s1);
+        s1 = s1.wrapping_add(x1).wrapping_add(s0);
+    }
+    (s0, s1)
+}
+
+fn be_u32(b: &[u8]) -> u32 {
+    u32::from_be_bytes([b[0], b[1], b[2], b[3]])
+}
+
+fn le_u32(b: &[u8]) -> u32 {
+    u32::from_le_bytes([b[0], b[1], b[2], b[3]])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAGE_SIZE: u32 = 512;
+    const SALT: (u32, u32) = (0x1234, 0x5678);
+
+    /// A little-endian-checksummed log with one frame per `(page, db_size, salt)`, each
+    /// filled with its page number
+    fn build(frames: &[(u32, u32, (u32, u32))]) -> Vec<u8> {
+        let mut log = Vec::new();
+        log.extend(WAL_MAGIC_LE.to_be_bytes());
+        log.extend(3_007_000u32.to_be_bytes());
+        log.extend(PAGE_SIZE.to_be_bytes());
+        log.extend(0u32.to_be_bytes());
+        log.extend(SALT.0.to_be_bytes());
+        log.extend(SALT.1.to_be_bytes());
+        let mut checksum = wal_checksum((0, 0), &log, false);
+        log.extend(checksum.0.to_be_bytes());
+        log.extend(checksum.1.to_be_bytes());
+        for &(page_number, db_size, salt) in frames {
+            let mut header = Vec::new();
+            header.extend(page_number.to_be_bytes());
+            header.extend(db_size.to_be_bytes());
+            let page = vec![page_number as u8; PAGE_SIZE as usize];
+            checksum = wal_checksum(wal_checksum(checksum, &header, false), &page, false);
+            header.extend(salt.0.to_be_bytes());
+            header.extend(salt.1.to_be_bytes());
+            header.extend(checksum.0.to_be_bytes());
+            header.extend(checksum.1.to_be_bytes());
+            log.extend(header);
+            log.extend(page);
+        }
+        log
+    }
+
+    fn open(name: &str, log: &[u8]) -> Wal {
+        let path =
+            std::env::temp_dir().join(format!("sqliter-{}-{}-wal", name, std::process::id()));
+        std::fs::write(&path, log).unwrap();
+        let wal = Wal::open(&path);
+        std::fs::remove_file(&path).unwrap();
+        wal.unwrap()
+    }
+
+    #[test]
+    fn reads_a_log_written_by_sqlite() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/wal.db-wal");
+        let wal = Wal::open(&path).unwrap();
+        assert!(wal.header.checksum_valid);
+        assert_eq!(wal.header.page_size, 512);
+        // three single-row inserts, each rewriting the table's one leaf
+        assert_eq!(wal.frames.len(), 3);
+        assert!(wal.frames.iter().all(|f| f.checksum_valid && f.committed));
+        assert_eq!(wal.transaction_count(), 3);
+        assert_eq!(
+            wal.frames.iter().map(|f| f.superseded).collect::<Vec<_>>(),
+            [true, true, false]
+        );
+    }
+
+    #[test]
+    fn transactions_and_superseded_frames() {
+        let wal = open(
+            "txns",
+            &build(&[(2, 0, SALT), (3, 3, SALT), (2, 0, SALT), (4, 4, SALT)]),
+        );
+        assert_eq!(wal.transaction_count(), 2);
+        assert_eq!(
+            wal.frames.iter().map(|f| f.txn).collect::<Vec<_>>(),
+            [Some(1), Some(1), Some(2), Some(2)]
+        );
+        assert_eq!(
+            wal.frames.iter().map(|f| f.superseded).collect::<Vec<_>>(),
+            [true, false, false, false]
+        );
+        assert_eq!(wal.last_commit().map(|f| f.db_size), Some(4));
+    }
+
+    #[test]
+    fn frames_after_the_last_commit_are_not_committed() {
+        let wal = open("tail", &build(&[(2, 2, SALT), (3, 0, SALT)]));
+        assert_eq!(wal.committed_frames().count(), 1);
+        assert!(wal.frames[1].checksum_valid && !wal.frames[1].committed);
+    }
+
+    #[test]
+    fn a_stale_salt_ends_the_log() {
+        // frames left over from before the log was restarted carry the old salt
+        let wal = open(
+            "salt",
+            &build(&[(2, 2, SALT), (2, 2, (1, 1)), (3, 3, SALT)]),
+        );
+        assert!(!wal.frames[1].salt_valid);
+        assert_eq!(wal.transaction_count(), 1);
+        assert!(!wal.frames[2].committed);
+    }
+
+    #[test]
+    fn a_bad_checksum_ends_the_log() {
+        let mut log = build(&[(2, 2, SALT), (3, 3, SALT), (4, 4, SALT)]);
+        let second_page = WAL_HEADER_SIZE + 2 * FRAME_HEADER_SIZE + PAGE_SIZE as usize;
+        log[second_page] ^= 0xff;
+        let wal = open("checksum", &log);
+        assert!(!wal.frames[1].checksum_valid);
+        assert_eq!(wal.transaction_count(), 1);
+    }
+
+    #[test]
+    fn big_endian_checksums() {
+        let data: Vec<u8> = (0..16).collect();
+        assert_ne!(
+            wal_checksum((0, 0), &data, true),
+            wal_checksum((0, 0), &data, false)
+        );
+        assert_eq!(
+            wal_checksum((0, 0), &[0, 0, 0, 1, 0, 0, 0, 2], true),
+            (1, 3)
+        );
+    }
+
+    #[test]
+    fn not_a_wal() {
+        let path = std::env::temp_dir().join(format!("sqliter-bad-{}-wal", std::process::id()));
+        std::fs::write(&path, [0u8; 64]).unwrap();
+        let result = Wal::open(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(result, Err(Error::NotAWal(_))));
+    }
+}
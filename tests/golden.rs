@@ -0,0 +1,531 @@
+//! Each command's output on the fixtures, compared with a copy checked by hand. After a
+//! change that is meant to alter some output, `UPDATE_GOLDEN=1 cargo test` rewrites the
+//! copies, and the diff shows what changed.
+//!
+//! `shop.db` has 1024-byte pages and was made with:
+//!
+//! ```sql
+//! CREATE TABLE customers(id INTEGER PRIMARY KEY, name TEXT NOT NULL UNIQUE, email TEXT, note BLOB);
+//! CREATE TABLE orders(id INTEGER PRIMARY KEY AUTOINCREMENT,
+//!     customer_id INTEGER REFERENCES customers(id), total REAL,
+//!     placed TEXT DEFAULT CURRENT_TIMESTAMP);
+//! CREATE INDEX orders_customer ON orders(customer_id);
+//! CREATE VIEW order_totals AS
+//!     SELECT customer_id, sum(total) AS total FROM orders GROUP BY customer_id;
+//! CREATE TRIGGER orders_placed AFTER INSERT ON orders BEGIN SELECT 1; END;
+//! INSERT INTO customers VALUES(1, 'Ada', 'ada@example.com', NULL);
+//! INSERT INTO customers VALUES(2, 'Grace', NULL, x'00ff27');
+//! INSERT INTO customers VALUES(3, 'Linus',
+//!     'an address long enough to be cut short by the default writer@example.com', x'');
+//! INSERT INTO customers VALUES(4, 'Tab' || char(9) || 'and' || char(10) || 'newline', NULL, NULL);
+//! -- for i in 1..=300
+//! INSERT INTO orders(customer_id, total, placed)
+//!     VALUES(i % 4 + 1, i * 1.25, printf('2024-%02d-%02d', i % 12 + 1, i % 28 + 1));
+//! ```
+//!
+//! `damaged.db` is a copy of it with the right-most child pointer of the `orders` root page
+//! changed to page 2, the root of `customers`.
+//!
+//! `shop2.db` holds only a schema, the next version of `shop.db`'s:
+//!
+//! ```sql
+//! CREATE TABLE customers(id INTEGER PRIMARY KEY, name TEXT NOT NULL UNIQUE, email TEXT, note BLOB, phone TEXT);
+//! CREATE TABLE orders(id INTEGER PRIMARY KEY AUTOINCREMENT,
+//!     customer_id INTEGER NOT NULL REFERENCES customers(id), total REAL,
+//!     placed TEXT DEFAULT CURRENT_TIMESTAMP);
+//! CREATE INDEX orders_placed ON orders(placed);
+//! CREATE VIEW order_totals AS
+//!     SELECT customer_id, sum(total) AS total, count(*) AS n FROM orders GROUP BY customer_id;
+//! CREATE TABLE refunds(order_id INTEGER REFERENCES orders(id), amount REAL);
+//! ```
+//!
+//! `lint.db` has one of each problem `.lint` looks for, and two near misses (a UNIQUE and a
+//! partial index on a key another index covers):
+//!
+//! ```sql
+//! CREATE TABLE log(at TEXT, message TEXT);
+//! CREATE TABLE people(id INTEGER PRIMARY KEY, name TEXT COLLATE NOCASE, code TEXT UNIQUE, zip TEXT);
+//! CREATE INDEX people_name ON people(name);
+//! CREATE INDEX people_name_binary ON people(name COLLATE BINARY);
+//! CREATE INDEX people_id ON people(id);
+//! CREATE INDEX people_code ON people(code);
+//! CREATE INDEX people_zip ON people(zip);
+//! CREATE INDEX people_zip_name ON people(zip, name);
+//! CREATE UNIQUE INDEX people_zip_unique ON people(zip);
+//! CREATE INDEX people_active_zip ON people(zip) WHERE id > 0;
+//! CREATE TABLE wide(c0, c1, ..., c69, PRIMARY KEY(c0));
+//! -- zip holds '10001' to '10004' and 'N/A'
+//! ```
+//!
+//! `rtree.db` has 1024-byte pages, so that sixty boxes make a two-level tree:
+//!
+//! ```sql
+//! CREATE VIRTUAL TABLE boxes USING rtree(id, min_x, max_x, min_y, max_y);
+//! -- for i in 0..60
+//! INSERT INTO boxes VALUES(i + 1, i % 10, i % 10 + 0.5, i / 10, i / 10 + 0.5);
+//! CREATE VIRTUAL TABLE spans USING rtree_i32(id, lo, hi);
+//! INSERT INTO spans VALUES(1, 0, 10), (2, 5, 15), (3, 20, 30);
+//! ```
+//!
+//! `fts.db` has two FTS5 tables, one with a deleted and an updated row and one contentless:
+//!
+//! ```sql
+//! CREATE VIRTUAL TABLE notes USING fts5(title, body);
+//! INSERT INTO notes VALUES('Shopping', 'apples and pears');
+//! INSERT INTO notes VALUES('Garden', 'prune the pear tree before spring');
+//! INSERT INTO notes VALUES('Reading', 'The Apple and the Tree, a novel');
+//! INSERT INTO notes VALUES('Draft', 'to be deleted: apples');
+//! DELETE FROM notes WHERE title = 'Draft';
+//! UPDATE notes SET body = 'apples, pears and plums' WHERE title = 'Shopping';
+//! CREATE VIRTUAL TABLE tags USING fts5(tag, content='', detail=none);
+//! INSERT INTO tags(rowid, tag) VALUES(10, 'red apple'), (20, 'green pear'), (30, 'red pear');
+//! ```
+//!
+//! `tiles.mbtiles` holds zoom levels 0 to 2 in the MBTiles layout: a `metadata` table and a
+//! `tiles` table indexed on `(zoom_level, tile_column, tile_row)`, with rows counted from
+//! the bottom (TMS) and each tile's data the text `z/x/y` with `y` counted from the top. Its
+//! `pyramid` table is a GeoPackage-style tile table holding zoom 1, rows counted from the
+//! top, each tile `pyramid z/x/y`.
+//!
+//! `compressed.db` holds BLOBs compressed by an application:
+//!
+//! ```sql
+//! CREATE TABLE docs(id INTEGER PRIMARY KEY, codec TEXT, body BLOB);
+//! -- body is gzip (mtime 0) of {"name": "Ada", "langs": ["en", "fr"]}, zlib of
+//! -- 'hello, zlib', the uncompressed bytes 'plain bytes', and NULL
+//! CREATE TABLE snaps(id INTEGER PRIMARY KEY, body BLOB);
+//! -- body is raw snappy of 'hello hello hello hello': one literal and two copies
+//! ```
+//!
+//! `archive.db` is a SQLite Archive made with `sqlite3 -Ac` from a directory holding
+//! `hi.txt` (`hi\n`, stored as is) and `docs/fox.txt` (forty lines of
+//! `the quick brown fox <i % 7> jumps over the lazy dog`, stored compressed), with every
+//! mtime set to 1704164645.
+
+use std::path::Path;
+use std::process::Command;
+
+/// Runs sqliter from the crate root, so fixtures are named as `tests/fixtures/...` and any
+/// paths printed are the same on every machine
+fn run(args: &[&str]) -> Vec<u8> {
+    let output = Command::new(env!("CARGO_BIN_EXE_sqliter"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args(args)
+        .output()
+        .expect("sqliter runs");
+    assert!(
+        output.status.success(),
+        "{:?}: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    output.stdout
+}
+
+fn golden(name: &str, args: &[&str]) {
+    let output = run(args);
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(name);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(&path, &output).unwrap();
+        return;
+    }
+    let expected = std::fs::read(&path)
+        .unwrap_or_else(|e| panic!("{}: {} (UPDATE_GOLDEN=1 writes it)", path.display(), e));
+    assert!(
+        output == expected,
+        "{:?} no longer prints {}:\n{}",
+        args,
+        name,
+        String::from_utf8_lossy(&output)
+    );
+}
+
+#[test]
+fn tsv_and_markdown() {
+    for format in ["tsv", "markdown"] {
+        golden(
+            &format!("walframes.{}", format),
+            &["--format", format, "tests/fixtures/wal.db", ".walframes"],
+        );
+    }
+}
+
+#[test]
+fn quote_insert_and_line() {
+    for format in ["quote", "insert", "line"] {
+        golden(
+            &format!("ar.list.{}", format),
+            &[
+                "--format",
+                format,
+                "--insert-table",
+                "sqlar",
+                "tests/fixtures/archive.db",
+                ".ar",
+                "list",
+            ],
+        );
+    }
+}
+
+#[test]
+fn quickcheck() {
+    golden("quickcheck.ok", &["tests/fixtures/shop.db", ".quickcheck"]);
+    golden(
+        "quickcheck.damaged",
+        &["tests/fixtures/damaged.db", ".quickcheck"],
+    );
+}
+
+#[test]
+fn ar() {
+    golden("ar.list", &["tests/fixtures/archive.db", ".ar", "list"]);
+
+    let dir = std::env::temp_dir().join(format!("sqliter-golden-ar-{}", std::process::id()));
+    let dir_arg = dir.to_str().unwrap();
+    golden(
+        "ar.extract",
+        &[
+            "tests/fixtures/archive.db",
+            ".ar",
+            "extract",
+            "--dir",
+            dir_arg,
+        ],
+    );
+    let fox: String = (0..40)
+        .map(|i| format!("the quick brown fox {} jumps over the lazy dog\n", i % 7))
+        .collect();
+    assert_eq!(
+        std::fs::read_to_string(dir.join("docs/fox.txt")).unwrap(),
+        fox
+    );
+    assert_eq!(std::fs::read_to_string(dir.join("hi.txt")).unwrap(), "hi\n");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn dbstat() {
+    // checked against `SELECT * FROM dbstat` in sqlite3
+    golden("dbstat", &["tests/fixtures/shop.db", ".dbstat"]);
+    golden(
+        "dbstat.customers",
+        &["tests/fixtures/shop.db", ".dbstat", "customers"],
+    );
+}
+
+#[test]
+fn schemadiff() {
+    golden(
+        "schemadiff.up",
+        &[
+            "tests/fixtures/shop.db",
+            ".schemadiff",
+            "tests/fixtures/shop2.db",
+        ],
+    );
+    golden(
+        "schemadiff.down",
+        &[
+            "tests/fixtures/shop2.db",
+            ".schemadiff",
+            "tests/fixtures/shop.db",
+        ],
+    );
+}
+
+#[test]
+fn estimate_rows() {
+    // a fixed seed makes the sampled pages, and so the estimate, repeatable
+    golden(
+        "estimate-rows.orders",
+        &[
+            "tests/fixtures/shop.db",
+            ".estimate-rows",
+            "orders",
+            "--seed",
+            "1",
+        ],
+    );
+    golden(
+        "estimate-rows.orders.2",
+        &[
+            "tests/fixtures/shop.db",
+            ".estimate-rows",
+            "orders",
+            "--samples",
+            "2",
+            "--seed",
+            "7",
+        ],
+    );
+    // a single-leaf table is counted exactly
+    golden(
+        "estimate-rows.customers",
+        &["tests/fixtures/shop.db", ".estimate-rows", "customers"],
+    );
+}
+
+#[test]
+fn lint() {
+    golden("lint.shop", &["tests/fixtures/shop.db", ".lint"]);
+    golden(
+        "lint",
+        &["--format", "tsv", "tests/fixtures/lint.db", ".lint"],
+    );
+}
+
+#[test]
+fn stats_histogram() {
+    let stats = |column| {
+        [
+            "tests/fixtures/shop.db",
+            ".stats",
+            "histogram",
+            "orders",
+            column,
+        ]
+    };
+    golden("stats.total", &stats("total"));
+    golden(
+        "stats.placed",
+        &[&stats("placed")[..], &["--top", "3"]].concat(),
+    );
+    golden(
+        "stats.customer_id",
+        &[&stats("customer_id")[..], &["--buckets", "2"]].concat(),
+    );
+    golden(
+        "stats.note",
+        &[
+            "tests/fixtures/shop.db",
+            ".stats",
+            "histogram",
+            "customers",
+            "note",
+        ],
+    );
+}
+
+#[test]
+fn ndjson() {
+    golden(
+        "ar.list.ndjson",
+        &[
+            "--format",
+            "ndjson",
+            "tests/fixtures/archive.db",
+            ".ar",
+            "list",
+        ],
+    );
+    golden(
+        "tablehash.ndjson",
+        &[
+            "--format",
+            "ndjson",
+            "tests/fixtures/shop.db",
+            ".tablehash",
+            "customers",
+            "--rows",
+        ],
+    );
+}
+
+#[test]
+fn rtree() {
+    // searches checked against the same range queries in sqlite3
+    let db = "tests/fixtures/rtree.db";
+    golden("rtree-dump.boxes", &[db, ".rtree-dump", "boxes"]);
+    golden("rtree-dump.spans", &[db, ".rtree-dump", "spans"]);
+    golden(
+        "rtree-search.within",
+        &[db, ".rtree-search", "boxes", "--within", "1,3,1,3"],
+    );
+    golden(
+        "rtree-search.overlaps",
+        &[db, ".rtree-search", "boxes", "--overlaps", "2.5,3.2,4.2,5"],
+    );
+    golden(
+        "rtree-search.spans",
+        &[db, ".rtree-search", "spans", "--overlaps", "12,21"],
+    );
+}
+
+#[test]
+fn fts() {
+    // checked against fts5vocab and MATCH in sqlite3
+    let db = "tests/fixtures/fts.db";
+    golden("fts-terms.notes", &[db, ".fts-terms", "notes"]);
+    golden("fts-match.apples", &[db, ".fts-match", "notes", "apples"]);
+    golden("fts-match.pear", &[db, ".fts-match", "notes", "Pear"]);
+    golden("fts-terms.tags", &[db, ".fts-terms", "tags"]);
+    golden("fts-match.tags", &[db, ".fts-match", "tags", "red"]);
+}
+
+#[test]
+fn tiles() {
+    let db = "tests/fixtures/tiles.mbtiles";
+    golden("tiles.info", &[db, ".tiles", "info"]);
+    golden(
+        "tiles.info.pyramid",
+        &[db, ".tiles", "info", "--table", "pyramid"],
+    );
+    for (z, x, y, expected) in [
+        ("0", "0", "0", "0/0/0"),
+        ("1", "0", "0", "1/0/0"),
+        ("2", "3", "1", "2/3/1"),
+    ] {
+        assert_eq!(run(&[db, ".tiles", "get", z, x, y]), expected.as_bytes());
+    }
+    assert_eq!(
+        run(&[db, ".tiles", "get", "1", "0", "0", "--tms"]),
+        b"1/0/1"
+    );
+    let pyramid = [db, ".tiles", "get", "1", "1", "0", "--table", "pyramid"];
+    assert_eq!(run(&pyramid), b"pyramid 1/1/0");
+    assert_eq!(run(&[&pyramid[..], &["--tms"]].concat()), b"pyramid 1/1/1");
+}
+
+#[test]
+fn decode() {
+    let db = "tests/fixtures/compressed.db";
+    golden(
+        "dump.decode-auto",
+        &["--decode", "docs.body=auto", db, ".dump", "--table", "docs"],
+    );
+    golden(
+        "dump.decode-snappy",
+        &[
+            "--decode",
+            "snaps.body=snappy",
+            db,
+            ".dump",
+            "--table",
+            "snaps",
+        ],
+    );
+}
+
+#[test]
+fn codegen() {
+    golden(
+        "codegen.shop.rs",
+        &["tests/fixtures/shop.db", ".codegen", "--lang", "rust"],
+    );
+    // dump.db has mixed storage classes, a quoted name and a WITHOUT ROWID table
+    golden(
+        "codegen.dump.rs",
+        &["tests/fixtures/dump.db", ".codegen", "--lang", "rust"],
+    );
+    golden(
+        "codegen.dump.json",
+        &[
+            "tests/fixtures/dump.db",
+            ".codegen",
+            "--lang",
+            "json-schema",
+        ],
+    );
+}
+
+#[test]
+fn dump() {
+    // the same rows as sqlite3's .dump, which differs only in how it writes some literals
+    // and orders the schema statements at the end
+    golden("dump.shop", &["tests/fixtures/shop.db", ".dump"]);
+    golden(
+        "dump.shop.customers",
+        &["tests/fixtures/shop.db", ".dump", "--table", "customers"],
+    );
+}
+
+#[test]
+fn clone() {
+    let dir = std::env::temp_dir().join(format!("sqliter-golden-clone-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+
+    let copy = dir.join("shop.db");
+    golden(
+        "clone.shop",
+        &["tests/fixtures/shop.db", ".clone", copy.to_str().unwrap()],
+    );
+    assert!(std::fs::read(&copy).unwrap() == std::fs::read(fixtures.join("shop.db")).unwrap());
+
+    let copy = dir.join("seek.db");
+    golden(
+        "clone.seek",
+        &[
+            "tests/fixtures/seek.db",
+            ".clone",
+            copy.to_str().unwrap(),
+            "--incremental",
+        ],
+    );
+    assert!(std::fs::read(&copy).unwrap() == std::fs::read(fixtures.join("seek.db")).unwrap());
+    assert!(!dir.join("seek.db-clone").exists());
+
+    // the last commit in the log is copied, and the copy needs no log
+    let copy = dir.join("wal.db");
+    golden(
+        "clone.wal",
+        &["tests/fixtures/wal.db", ".clone", copy.to_str().unwrap()],
+    );
+    assert_eq!(&std::fs::read(&copy).unwrap()[18..20], [1, 1]);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn erd() {
+    golden("erd.shop.dot", &["tests/fixtures/shop.db", ".erd"]);
+    golden("erd.shop2.dot", &["tests/fixtures/shop2.db", ".erd"]);
+
+    let out = std::env::temp_dir().join(format!("sqliter-golden-erd-{}.dot", std::process::id()));
+    run(&[
+        "tests/fixtures/shop.db",
+        ".erd",
+        "--out",
+        out.to_str().unwrap(),
+    ]);
+    assert!(std::fs::read(&out).unwrap() == run(&["tests/fixtures/shop.db", ".erd"]));
+    std::fs::remove_file(&out).unwrap();
+}
+
+#[test]
+fn deps() {
+    for target in ["orders", "orders.total", "customers", "customers.id"] {
+        golden(
+            &format!("deps.{}", target),
+            &["tests/fixtures/shop.db", ".deps", target],
+        );
+    }
+}
+
+#[test]
+fn dbpage() {
+    // the page images are the file's own bytes, in the order asked for
+    let file = std::fs::read("tests/fixtures/shop.db").unwrap();
+    let page = |n: usize| -> String {
+        file[(n - 1) * 1024..n * 1024]
+            .iter()
+            .map(|b| format!("{:02X}", b))
+            .collect()
+    };
+    let output = run(&[
+        "--format",
+        "quote",
+        "tests/fixtures/shop.db",
+        ".dbpage",
+        "3",
+        "1-2",
+    ]);
+    let expected = format!("3,X'{}'\n1,X'{}'\n2,X'{}'\n", page(3), page(1), page(2));
+    assert!(String::from_utf8(output).unwrap() == expected);
+    let every = run(&["--format", "ndjson", "tests/fixtures/shop.db", ".dbpage"]);
+    let every = String::from_utf8(every).unwrap();
+    assert_eq!(every.lines().count(), 17 + 1);
+    assert!(every.ends_with("{\"summary\":{\"rows\":17,\"notes\":[]}}\n"));
+}
@@ -0,0 +1,32 @@
+/// A row of `kinds`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Kinds {
+    /// INTEGER
+    pub id: i64,
+    /// INT
+    pub i: Option<i64>,
+    /// REAL
+    pub r: Option<f64>,
+    /// TEXT
+    pub t: Option<String>,
+    /// BLOB
+    pub b: Option<Vec<u8>>,
+}
+
+/// A value of whichever storage class SQLite kept it in
+#[derive(Debug, Clone, PartialEq)]
+pub enum SqlValue {
+    Integer(i64),
+    Real(f64),
+    Text(String),
+    Blob(Vec<u8>),
+}
+
+/// A row of `odd name`
+#[derive(Debug, Clone, PartialEq)]
+pub struct OddName {
+    /// `a b`, TEXT
+    pub a_b: String,
+    /// no type
+    pub c: Option<SqlValue>,
+}
@@ -0,0 +1,25 @@
+/// A row of `customers`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Customers {
+    /// INTEGER
+    pub id: i64,
+    /// TEXT
+    pub name: String,
+    /// TEXT
+    pub email: Option<String>,
+    /// BLOB
+    pub note: Option<Vec<u8>>,
+}
+
+/// A row of `orders`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Orders {
+    /// INTEGER
+    pub id: i64,
+    /// INTEGER
+    pub customer_id: Option<i64>,
+    /// REAL
+    pub total: Option<f64>,
+    /// TEXT
+    pub placed: Option<String>,
+}
@@ -0,0 +1,128 @@
+//! Seeks into b-trees three levels deep land where a full scan says they should. The fixture
+//! has 512-byte pages and was made with:
+//!
+//! ```sql
+//! CREATE TABLE t(id INTEGER PRIMARY KEY, k TEXT, v INT);
+//! CREATE INDEX t_k ON t(k);
+//! CREATE TABLE w(k TEXT PRIMARY KEY, v INT) WITHOUT ROWID;
+//! -- for i in 1..=2000
+//! INSERT INTO t VALUES(2 * i, printf('key%05d', i), i * i);
+//! INSERT INTO w SELECT k, v FROM t;
+//! ```
+
+use sqliter::index::IndexInfo;
+use sqliter::table::{ResumeToken, TableInfo};
+use sqliter::{Database, Value};
+
+const ROWS: i64 = 2000;
+
+fn open() -> Database {
+    Database::open(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/seek.db"
+    ))
+    .unwrap()
+}
+
+fn key(i: i64) -> Value {
+    Value::Text(format!("key{:05}", i))
+}
+
+#[test]
+fn finds_every_rowid_and_nothing_between() {
+    let db = open();
+    let t = TableInfo::find(&db, "t").unwrap().unwrap();
+    for i in 1..=ROWS {
+        let row = t
+            .row(&db, 2 * i)
+            .unwrap()
+            .expect("every even rowid is there");
+        assert_eq!(row.rowid, Some(2 * i));
+        assert_eq!(row.values[1], key(i));
+        assert!(t.row(&db, 2 * i + 1).unwrap().is_none());
+    }
+    for missing in [i64::MIN, -1, 0, 2 * ROWS + 2, i64::MAX] {
+        assert!(t.row(&db, missing).unwrap().is_none(), "{}", missing);
+    }
+}
+
+#[test]
+fn rows_after_agree_with_a_full_scan() {
+    let db = open();
+    let t = TableInfo::find(&db, "t").unwrap().unwrap();
+    let all: Vec<i64> = t.rows(&db).map(|row| row.unwrap().rowid.unwrap()).collect();
+    assert_eq!(all.len() as i64, ROWS);
+    for after in [
+        i64::MIN,
+        0,
+        1,
+        2,
+        3,
+        97,
+        1000,
+        2 * ROWS - 1,
+        2 * ROWS,
+        i64::MAX,
+    ] {
+        let expected: Vec<i64> = all.iter().copied().filter(|&r| r > after).collect();
+        let found: Vec<i64> = t
+            .rows_after(&db, after)
+            .map(|row| row.unwrap().rowid.unwrap())
+            .collect();
+        assert_eq!(found, expected, "after {}", after);
+    }
+}
+
+#[test]
+fn index_seeks_land_on_the_first_key_not_before() {
+    let db = open();
+    let index = IndexInfo::find(&db, "t_k").unwrap().unwrap();
+    for i in [1, 2, 500, 1999, ROWS] {
+        let first = index
+            .entries_from_key(&db, &[key(i)])
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(first, [key(i), Value::Integer(2 * i)]);
+    }
+    // a key between two entries finds the later one
+    let between = Value::Text("key01000x".to_string());
+    let first = index
+        .entries_from_key(&db, &[between])
+        .next()
+        .unwrap()
+        .unwrap();
+    assert_eq!(first[0], key(1001));
+    let past = Value::Text("kez".to_string());
+    assert!(index.entries_from_key(&db, &[past]).next().is_none());
+}
+
+#[test]
+fn without_rowid_tables_resume_after_a_key() {
+    let db = open();
+    let w = TableInfo::find(&db, "w").unwrap().unwrap();
+    for i in [1, 777, ROWS - 1] {
+        let token = ResumeToken::Key(vec![key(i)]);
+        let next = w
+            .scan_from(&db, Some(&token))
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(next.values, [key(i + 1), Value::Integer((i + 1) * (i + 1))]);
+    }
+    let token = ResumeToken::Key(vec![key(ROWS)]);
+    assert!(w.scan_from(&db, Some(&token)).unwrap().next().is_none());
+}
+
+#[test]
+fn repeated_seeks_reuse_interior_pages() {
+    let db = open();
+    let t = TableInfo::find(&db, "t").unwrap().unwrap();
+    t.row(&db, 1000).unwrap();
+    let first = db.divider_cache_stats();
+    t.row(&db, 1002).unwrap();
+    let second = db.divider_cache_stats();
+    assert_eq!(second.misses, first.misses);
+    assert!(second.hits > first.hits);
+}